@@ -0,0 +1,88 @@
+/*! Helpers for comparing SDP answers against hand-written golden ones.
+ * Answers are compared line by line after normalizing parts whose form carries no meaning: format
+ * parameters are sorted and lowercased, codec names and fingerprints are lowercased and line endings
+ * are unified. The order of lines is left untouched, it stays part of the comparison.
+ */
+
+/** Normalized form of a raw SDP, with lines terminated by CRLF.
+ */
+pub fn normalize_sdp(raw_sdp: &str) -> String {
+    raw_sdp
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(normalize_line)
+        .map(|line| line + "\r\n")
+        .collect()
+}
+
+fn normalize_line(line: &str) -> String {
+    if let Some(value) = line.strip_prefix("a=fmtp:") {
+        let (payload_number, parameters) = value.split_once(' ').unwrap_or((value, ""));
+        let mut parameters = parameters
+            .split(';')
+            .map(|parameter| parameter.trim().to_lowercase())
+            .filter(|parameter| !parameter.is_empty())
+            .collect::<Vec<String>>();
+        parameters.sort();
+        return format!("a=fmtp:{} {}", payload_number, parameters.join(";"));
+    }
+    if line.starts_with("a=rtpmap:") || line.starts_with("a=fingerprint:") {
+        return line.to_lowercase();
+    }
+
+    line.to_string()
+}
+
+/** Asserts that both SDPs are equal once normalized.
+ */
+#[track_caller]
+pub fn assert_sdp_eq(actual: &str, expected: &str) {
+    assert_eq!(
+        normalize_sdp(actual),
+        normalize_sdp(expected),
+        "SDP answer should match expected answer"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    mod normalize_sdp {
+        use crate::golden::{assert_sdp_eq, normalize_sdp};
+
+        #[test]
+        fn equates_reordered_format_parameters() {
+            let answer = "m=video 52000 UDP/TLS/RTP/SAVPF 96\r\n\
+            a=fmtp:96 level-asymmetry-allowed=1;packetization-mode=1;profile-level-id=42e01f\r\n";
+            let reordered_answer = "m=video 52000 UDP/TLS/RTP/SAVPF 96\r\n\
+            a=fmtp:96 profile-level-id=42E01F; packetization-mode=1;level-asymmetry-allowed=1\r\n";
+
+            assert_sdp_eq(answer, reordered_answer);
+        }
+
+        #[test]
+        fn equates_codec_name_and_fingerprint_case() {
+            let answer = "a=fingerprint:sha-256 EF:53:C9:F2\r\na=rtpmap:96 H264/90000\r\n";
+            let lowercase_answer =
+                "a=fingerprint:sha-256 ef:53:c9:f2\r\na=rtpmap:96 h264/90000\r\n";
+
+            assert_sdp_eq(answer, lowercase_answer);
+        }
+
+        #[test]
+        fn unifies_line_endings() {
+            assert_eq!(
+                normalize_sdp("v=0\ns=SMID\r\n\r\nt=0 0"),
+                "v=0\r\ns=SMID\r\nt=0 0\r\n"
+            );
+        }
+
+        #[test]
+        fn keeps_line_order() {
+            assert_ne!(
+                normalize_sdp("a=rtcp-fb:96 nack\r\na=rtcp-fb:96 nack pli\r\n"),
+                normalize_sdp("a=rtcp-fb:96 nack pli\r\na=rtcp-fb:96 nack\r\n")
+            );
+        }
+    }
+}
@@ -1,7 +1,10 @@
 pub use crate::line_parsers::{AudioCodec, SDPParseError, VideoCodec};
 pub use crate::resolvers::{
-    AudioSession, ICECredentials, NegotiatedSession, SDP, SDPResolver, VideoSession,
+    AudioSession, ICECredentials, NegotiatedSession, NegotiatedSessionBuilder, OfferDiagnostics,
+    OfferRequirement, OfferSection, SDP, SDPResolver, SimulcastSession, VideoSession,
 };
 
+#[cfg(test)]
+mod golden;
 mod line_parsers;
 mod resolvers;
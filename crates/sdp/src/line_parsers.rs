@@ -1,5 +1,6 @@
-use std::collections::HashSet;
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::str::FromStr;
 
 use crate::SDPParseError::MalformedAttribute;
@@ -9,6 +10,7 @@ pub enum SDPParseError {
     SequenceError,
     InvalidDTLSRole,
     MissingICECredentials,
+    ICERestartRequired,
     MissingStreamSSRC,
     UnsupportedMediaCodecs,
     InvalidStreamDirection,
@@ -23,6 +25,36 @@ pub enum SDPParseError {
     MalformedMediaDescriptor,
     MalformedSDPLine,
 }
+
+impl Display for SDPParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let message = match self {
+            SDPParseError::SequenceError => "SDP lines are out of order",
+            SDPParseError::InvalidDTLSRole => "DTLS role should let the server be passive",
+            SDPParseError::MissingICECredentials => "ICE username and password are missing",
+            SDPParseError::ICERestartRequired => "ICE credentials changed without an ICE restart",
+            SDPParseError::MissingStreamSSRC => "stream SSRC is missing",
+            SDPParseError::UnsupportedMediaCodecs => "none of the offered codecs is supported",
+            SDPParseError::InvalidStreamDirection => "media direction isn't supported",
+            SDPParseError::InvalidMediaID => "media IDs are missing or don't match",
+            SDPParseError::BundleRequired => "audio and video should be bundled",
+            SDPParseError::MissingVideoCapabilities => "video format parameters are missing",
+            // Non-muxed offers ask for RTCP on a separate port, usually with an a=rtcp line
+            SDPParseError::DemuxRequired => {
+                "RTP and RTCP should be multiplexed on one port (a=rtcp-mux), \
+                separate RTCP ports aren't supported"
+            }
+            SDPParseError::UnsupportedMediaCount => "offer should have one audio and one video",
+            SDPParseError::UnsupportedMediaType => "media type isn't supported",
+            SDPParseError::UnsupportedMediaProtocol => "media protocol isn't supported",
+            SDPParseError::MalformedAttribute => "attribute is malformed",
+            SDPParseError::MalformedMediaDescriptor => "media description is malformed",
+            SDPParseError::MalformedSDPLine => "SDP line is malformed",
+        };
+        write!(f, "{}", message)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub(crate) enum SDPLine {
     ProtocolVersion(String),
@@ -48,17 +80,29 @@ pub(crate) enum Attribute {
     ICEOptions(ICEOptions),
     SendOnly,
     ReceiveOnly,
+    SendReceive,
+    Inactive,
     MediaID(MediaID),
     ICEUsername(ICEUsername),
     ICEPassword(ICEPassword),
     Fingerprint(Fingerprint),
     MediaGroup(MediaGroup),
     MediaSSRC(MediaSSRC),
+    SSRCGroup(SSRCGroup),
     RTCPMux,
     RTPMap(RTPMap),
     FMTP(FMTP),
     Setup(Setup),
     Candidate(Candidate),
+    RTCPFeedback(RTCPFeedback),
+    ExtMap(ExtMap),
+    /** Header extensions of one-byte and two-byte form may be mixed within a stream, see RFC 8285.
+     */
+    ExtMapAllowMixed,
+    Rid(Rid),
+    Simulcast(Simulcast),
+    MSID(MSID),
+    MSIDSemantic(MSIDSemantic),
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -73,13 +117,23 @@ pub(crate) struct MediaDescription {
 pub(crate) enum MediaType {
     Video,
     Audio,
+    Application,
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub(crate) enum MediaTransportProtocol {
     DtlsSrtp,
+    DtlsSctp,
 }
 
+/** Spellings of the DTLS-SRTP profile used by WebRTC clients. The first one is used in answers.
+ */
+const DTLS_SRTP_PROTOCOLS: [&str; 2] = ["UDP/TLS/RTP/SAVPF", "UDP/DTLS/RTP/SAVPF"];
+
+/** The only media format of an SCTP m-line carrying WebRTC data channels.
+ */
+const DATA_CHANNEL_MEDIA_FORMAT: &str = "webrtc-datachannel";
+
 #[derive(Clone, Debug, PartialEq)]
 pub(crate) enum ICEOption {
     ICE2,
@@ -90,15 +144,15 @@ pub(crate) enum ICEOption {
 #[derive(Clone, Debug, PartialEq)]
 pub(crate) struct Originator {
     pub(crate) username: String,
-    pub(crate) session_id: String,
-    pub(crate) session_version: String,
+    pub(crate) session_id: u64,
+    pub(crate) session_version: u64,
     pub(crate) ip_addr: IpAddr,
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub(crate) struct SessionTime {
-    pub(crate) start_time: usize,
-    pub(crate) end_time: usize,
+    pub(crate) start_time: u64,
+    pub(crate) end_time: u64,
 }
 #[derive(Clone, Debug, PartialEq)]
 pub(crate) struct ICEOptions {
@@ -139,6 +193,26 @@ pub enum AudioCodec {
     Opus,
 }
 
+impl VideoCodec {
+    /** RTP timestamp ticks per second.
+     */
+    pub fn clock_rate(&self) -> u32 {
+        match self {
+            VideoCodec::H264 => 90000,
+        }
+    }
+}
+
+impl AudioCodec {
+    /** RTP timestamp ticks per second.
+     */
+    pub fn clock_rate(&self) -> u32 {
+        match self {
+            AudioCodec::Opus => 48000,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub(crate) struct MediaSSRC {
     pub(crate) ssrc: u32,
@@ -148,6 +222,19 @@ pub(crate) struct MediaSSRC {
 #[derive(Debug, Clone, PartialEq)]
 pub(crate) enum SourceAttribute {
     CNAME(String),
+    MSID {
+        stream_id: String,
+        track_id: Option<String>,
+    },
+    Unsupported,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum SSRCGroup {
+    FlowIdentification {
+        primary_ssrc: u32,
+        retransmission_ssrc: u32,
+    },
     Unsupported,
 }
 
@@ -176,6 +263,94 @@ pub(crate) struct FMTP {
     pub(crate) format_capability: HashSet<String>,
 }
 
+/** Value of the H264 `profile-level-id` FMTP parameter, see RFC 6184 8.1.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct H264ProfileLevelID {
+    pub(crate) profile_idc: u8,
+    pub(crate) profile_iop: u8,
+    pub(crate) level_idc: u8,
+}
+
+/** RTCP feedback type supported for a payload, e.g. `nack` or `nack pli`.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct RTCPFeedback {
+    pub(crate) payload_number: usize,
+    pub(crate) feedback_type: String,
+}
+
+/** URI of the RTP header extension carrying the RID of a simulcast layer, see RFC 8852.
+ */
+pub(crate) const RTP_STREAM_ID_EXTENSION_URI: &str =
+    "urn:ietf:params:rtp-hdrext:sdes:rtp-stream-id";
+
+/** URI of the RTP header extension carrying the time a packet left the sender, used to estimate bandwidth.
+ */
+pub(crate) const ABS_SEND_TIME_EXTENSION_URI: &str =
+    "http://www.webrtc.org/experiments/rtp-hdrext/abs-send-time";
+
+/** URI of the RTP header extension carrying transport-wide sequence numbers, used by the receiver's
+ * congestion control.
+ */
+pub(crate) const TRANSPORT_CC_EXTENSION_URI: &str =
+    "http://www.ietf.org/id/draft-holmer-rmcat-transport-wide-cc-extensions-01";
+
+/** Mapping of an RTP header extension to the ID used in packets, see RFC 8285.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ExtMap {
+    pub(crate) id: u8,
+    pub(crate) uri: String,
+}
+
+impl ExtMap {
+    /** Whether the ID fits the one-byte header form, two-byte headers are needed for the rest.
+     */
+    pub(crate) fn is_one_byte_id(id: u8) -> bool {
+        (1..=14).contains(&id)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum RidDirection {
+    Send,
+    Receive,
+}
+
+/** RTP stream identifier declared for a media section, see RFC 8851. Restrictions are not tracked.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Rid {
+    pub(crate) id: String,
+    pub(crate) direction: RidDirection,
+}
+
+/** Simulcast streams of a media section in the offered order, see RFC 8853. Only the first alternative of
+ * each stream is kept and paused streams are listed like the others.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Simulcast {
+    pub(crate) direction: RidDirection,
+    pub(crate) rids: Vec<String>,
+}
+
+/** Media stream and track a media section belongs to, see RFC 8830.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct MSID {
+    pub(crate) stream_id: String,
+    pub(crate) track_id: Option<String>,
+}
+
+/** Session-level declaration of the media streams in use, e.g. `WMS *`, for clients predating RFC 8830.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct MSIDSemantic {
+    pub(crate) semantic: String,
+    pub(crate) stream_ids: Vec<String>,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub(crate) struct Candidate {
     pub(crate) foundation: String,
@@ -183,6 +358,44 @@ pub(crate) struct Candidate {
     pub(crate) priority: usize,
     pub(crate) connection_address: IpAddr,
     pub(crate) port: u16,
+    pub(crate) candidate_type: CandidateType,
+    pub(crate) related_address: Option<SocketAddr>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum CandidateType {
+    Host,
+    ServerReflexive,
+    PeerReflexive,
+    Relay,
+}
+
+impl Candidate {
+    /** Priority of a candidate, higher ones being preferred for connectivity checks.
+    https://datatracker.ietf.org/doc/html/rfc5245#section-4.1.2.1
+     */
+    pub(crate) fn get_priority(
+        candidate_type: &CandidateType,
+        local_preference: u16,
+        component_id: usize,
+    ) -> usize {
+        (candidate_type.get_type_preference() << 24)
+            + ((local_preference as usize) << 8)
+            + (256 - component_id)
+    }
+}
+
+impl CandidateType {
+    /** Type preferences recommended by RFC 5245, direct paths being preferred over relayed ones.
+     */
+    fn get_type_preference(&self) -> usize {
+        match self {
+            CandidateType::Host => 126,
+            CandidateType::PeerReflexive => 110,
+            CandidateType::ServerReflexive => 100,
+            CandidateType::Relay => 0,
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -228,6 +441,8 @@ impl From<Attribute> for String {
             }
             Attribute::SendOnly => "sendonly".to_string(),
             Attribute::ReceiveOnly => "recvonly".to_string(),
+            Attribute::SendReceive => "sendrecv".to_string(),
+            Attribute::Inactive => "inactive".to_string(),
             Attribute::RTCPMux => "rtcp-mux".to_string(),
             Attribute::MediaID(attr) => String::from(attr),
             Attribute::ICEUsername(attr) => String::from(attr),
@@ -235,6 +450,7 @@ impl From<Attribute> for String {
             Attribute::Fingerprint(attr) => String::from(attr),
             Attribute::MediaGroup(attr) => String::from(attr),
             Attribute::MediaSSRC(attr) => String::from(attr),
+            Attribute::SSRCGroup(attr) => String::from(attr),
             Attribute::RTPMap(attr) => String::from(attr),
             Attribute::FMTP(attr) => String::from(attr),
             Attribute::Candidate(attr) => String::from(attr),
@@ -242,6 +458,13 @@ impl From<Attribute> for String {
             Attribute::ICELite => "ice-lite".to_string(),
             Attribute::EndOfCandidates => "end-of-candidates".to_string(),
             Attribute::ICEOptions(ice_options) => String::from(ice_options),
+            Attribute::RTCPFeedback(attr) => String::from(attr),
+            Attribute::ExtMap(attr) => String::from(attr),
+            Attribute::ExtMapAllowMixed => "extmap-allow-mixed".to_string(),
+            Attribute::Rid(attr) => String::from(attr),
+            Attribute::Simulcast(attr) => String::from(attr),
+            Attribute::MSID(attr) => String::from(attr),
+            Attribute::MSIDSemantic(attr) => String::from(attr),
         };
         format!("a={attribute_name}")
     }
@@ -320,6 +543,7 @@ impl From<MediaType> for String {
             MediaType::Video => "video".to_string(),
 
             MediaType::Audio => "audio".to_string(),
+            MediaType::Application => "application".to_string(),
         }
     }
 }
@@ -327,19 +551,23 @@ impl From<MediaType> for String {
 impl From<MediaTransportProtocol> for String {
     fn from(value: MediaTransportProtocol) -> Self {
         match value {
-            MediaTransportProtocol::DtlsSrtp => "UDP/TLS/RTP/SAVPF".to_string(),
+            MediaTransportProtocol::DtlsSrtp => DTLS_SRTP_PROTOCOLS[0].to_string(),
+            MediaTransportProtocol::DtlsSctp => "UDP/DTLS/SCTP".to_string(),
         }
     }
 }
 
 impl From<MediaDescription> for String {
     fn from(value: MediaDescription) -> Self {
-        let media_payloads = value
-            .media_format_description
-            .into_iter()
-            .map(|item| item.to_string())
-            .collect::<Vec<_>>()
-            .join(" ");
+        let media_payloads = match value.transport_protocol {
+            MediaTransportProtocol::DtlsSrtp => value
+                .media_format_description
+                .into_iter()
+                .map(|item| item.to_string())
+                .collect::<Vec<_>>()
+                .join(" "),
+            MediaTransportProtocol::DtlsSctp => DATA_CHANNEL_MEDIA_FORMAT.to_string(),
+        };
         format!(
             "m={} {} {} {}",
             String::from(value.media_type),
@@ -415,7 +643,7 @@ impl From<MediaCodec> for String {
 impl From<VideoCodec> for String {
     fn from(value: VideoCodec) -> Self {
         match value {
-            VideoCodec::H264 => "h264/90000".to_string(),
+            VideoCodec::H264 => format!("h264/{}", value.clock_rate()),
         }
     }
 }
@@ -423,7 +651,7 @@ impl From<VideoCodec> for String {
 impl From<AudioCodec> for String {
     fn from(value: AudioCodec) -> Self {
         match value {
-            AudioCodec::Opus => "opus/48000/2".to_string(),
+            AudioCodec::Opus => format!("opus/{}/2", value.clock_rate()),
         }
     }
 }
@@ -442,6 +670,13 @@ impl From<SourceAttribute> for String {
     fn from(value: SourceAttribute) -> Self {
         match value {
             SourceAttribute::CNAME(cname) => format!("cname:{}", cname),
+            SourceAttribute::MSID {
+                stream_id,
+                track_id,
+            } => match track_id {
+                Some(track_id) => format!("msid:{} {}", stream_id, track_id),
+                None => format!("msid:{}", stream_id),
+            },
             SourceAttribute::Unsupported => {
                 panic!("Cannot cast unsupported SourceAttribute to String")
             }
@@ -449,27 +684,120 @@ impl From<SourceAttribute> for String {
     }
 }
 
+impl From<SSRCGroup> for String {
+    fn from(value: SSRCGroup) -> Self {
+        match value {
+            SSRCGroup::FlowIdentification {
+                primary_ssrc,
+                retransmission_ssrc,
+            } => format!("ssrc-group:FID {} {}", primary_ssrc, retransmission_ssrc),
+            SSRCGroup::Unsupported => panic!("Cannot cast unsupported SSRCGroup to String"),
+        }
+    }
+}
+
+impl From<ExtMap> for String {
+    fn from(value: ExtMap) -> Self {
+        format!("extmap:{} {}", value.id, value.uri)
+    }
+}
+
+impl From<RidDirection> for String {
+    fn from(value: RidDirection) -> Self {
+        match value {
+            RidDirection::Send => "send".to_string(),
+            RidDirection::Receive => "recv".to_string(),
+        }
+    }
+}
+
+impl From<Rid> for String {
+    fn from(value: Rid) -> Self {
+        format!("rid:{} {}", value.id, String::from(value.direction))
+    }
+}
+
+impl From<Simulcast> for String {
+    fn from(value: Simulcast) -> Self {
+        format!(
+            "simulcast:{} {}",
+            String::from(value.direction),
+            value.rids.join(";")
+        )
+    }
+}
+
 impl From<FMTP> for String {
     fn from(value: FMTP) -> Self {
-        let format_capabilities = value
+        // Sorted so that answers don't depend on the set's iteration order
+        let mut format_capabilities = value
             .format_capability
             .into_iter()
-            .collect::<Vec<String>>()
-            .join(";");
+            .collect::<Vec<String>>();
+        format_capabilities.sort();
+        let format_capabilities = format_capabilities.join(";");
         format!("fmtp:{} {}", value.payload_number, format_capabilities)
     }
 }
 
+impl From<MSID> for String {
+    fn from(value: MSID) -> Self {
+        match value.track_id {
+            Some(track_id) => format!("msid:{} {}", value.stream_id, track_id),
+            None => format!("msid:{}", value.stream_id),
+        }
+    }
+}
+
+impl From<MSIDSemantic> for String {
+    fn from(value: MSIDSemantic) -> Self {
+        let stream_ids = value
+            .stream_ids
+            .into_iter()
+            .map(|stream_id| format!(" {}", stream_id))
+            .collect::<String>();
+        format!("msid-semantic:{}{}", value.semantic, stream_ids)
+    }
+}
+
+impl From<RTCPFeedback> for String {
+    fn from(value: RTCPFeedback) -> Self {
+        format!("rtcp-fb:{} {}", value.payload_number, value.feedback_type)
+    }
+}
+
 impl From<Candidate> for String {
     fn from(value: Candidate) -> Self {
-        format!(
-            "candidate:{} {} UDP {} {} {} typ host", //todo Handle other candidate types
+        let candidate = format!(
+            "candidate:{} {} UDP {} {} {} typ {}",
             value.foundation,
             value.component_id,
             value.priority,
-            value.connection_address.to_string(),
-            value.port
-        )
+            value.connection_address,
+            value.port,
+            String::from(value.candidate_type)
+        );
+
+        match value.related_address {
+            Some(related_address) => format!(
+                "{} raddr {} rport {}",
+                candidate,
+                related_address.ip(),
+                related_address.port()
+            ),
+            None => candidate,
+        }
+    }
+}
+
+impl From<CandidateType> for String {
+    fn from(value: CandidateType) -> Self {
+        match value {
+            CandidateType::Host => "host".to_string(),
+            CandidateType::ServerReflexive => "srflx".to_string(),
+            CandidateType::PeerReflexive => "prflx".to_string(),
+            CandidateType::Relay => "relay".to_string(),
+        }
     }
 }
 
@@ -514,8 +842,11 @@ impl TryFrom<&str> for Attribute {
             "fingerprint" => Ok(Attribute::Fingerprint(Fingerprint::try_from(value)?)),
             "candidate" => Ok(Attribute::Candidate(Candidate::try_from(value)?)),
             "ssrc" => Ok(Attribute::MediaSSRC(MediaSSRC::try_from(value)?)),
+            "ssrc-group" => Ok(Attribute::SSRCGroup(SSRCGroup::try_from(value)?)),
             "sendonly" => Ok(Attribute::SendOnly),
             "recvonly" => Ok(Attribute::ReceiveOnly),
+            "sendrecv" => Ok(Attribute::SendReceive),
+            "inactive" => Ok(Attribute::Inactive),
             "mid" => Ok(Attribute::MediaID(MediaID::try_from(value)?)),
             "group" => Ok(Attribute::MediaGroup(MediaGroup::try_from(value)?)),
             "rtpmap" => Ok(Attribute::RTPMap(RTPMap::try_from(value)?)),
@@ -524,6 +855,16 @@ impl TryFrom<&str> for Attribute {
             "ice-options" => Ok(Attribute::ICEOptions(ICEOptions::try_from(value)?)),
             "end-of-candidates" => Ok(Attribute::EndOfCandidates),
             "setup" => Ok(Attribute::Setup(Setup::try_from(value)?)),
+            // Wildcard feedback applies to every payload and isn't tracked
+            "rtcp-fb" => Ok(RTCPFeedback::try_from(value)
+                .map(Attribute::RTCPFeedback)
+                .unwrap_or(Attribute::Unrecognized)),
+            "extmap" => Ok(Attribute::ExtMap(ExtMap::try_from(value)?)),
+            "extmap-allow-mixed" => Ok(Attribute::ExtMapAllowMixed),
+            "rid" => Ok(Attribute::Rid(Rid::try_from(value)?)),
+            "simulcast" => Ok(Attribute::Simulcast(Simulcast::try_from(value)?)),
+            "msid" => Ok(Attribute::MSID(MSID::try_from(value)?)),
+            "msid-semantic" => Ok(Attribute::MSIDSemantic(MSIDSemantic::try_from(value)?)),
             _ => Ok(Attribute::Unrecognized),
         }
     }
@@ -553,11 +894,18 @@ impl TryFrom<&str> for MediaDescription {
             .ok_or(SDPParseError::MalformedMediaDescriptor)
             .and_then(|transport_protocol| MediaTransportProtocol::try_from(transport_protocol))?;
 
-        let media_format_description = split
-            .take_while(|line| !line.is_empty())
-            .map(|line| line.parse::<usize>().ok())
-            .collect::<Option<Vec<usize>>>()
-            .ok_or(SDPParseError::MalformedAttribute)?;
+        let media_format_description = match transport_protocol {
+            MediaTransportProtocol::DtlsSrtp => split
+                .take_while(|line| !line.is_empty())
+                .map(|line| line.parse::<usize>().ok())
+                .collect::<Option<Vec<usize>>>()
+                .ok_or(SDPParseError::MalformedAttribute)?,
+            // SCTP formats aren't payload numbers, data channels have no payloads to list
+            MediaTransportProtocol::DtlsSctp => match split.next() {
+                Some(DATA_CHANNEL_MEDIA_FORMAT) => vec![],
+                _ => return Err(SDPParseError::MalformedMediaDescriptor),
+            },
+        };
 
         Ok(MediaDescription {
             transport_port,
@@ -577,9 +925,19 @@ impl TryFrom<&str> for Originator {
             .ok_or(Self::Error::MalformedSDPLine)?;
         let mut split = value.split(" ");
 
-        let username = split.next().ok_or(Self::Error::MalformedSDPLine)?;
-        let session_id = split.next().ok_or(Self::Error::MalformedSDPLine)?;
-        let session_version = split.next().ok_or(Self::Error::MalformedSDPLine)?;
+        let username = split
+            .next()
+            .filter(|username| !username.is_empty())
+            .ok_or(Self::Error::MalformedSDPLine)?;
+        // Session ID and version are numeric, the version is incremented on each renegotiation
+        let session_id = split
+            .next()
+            .and_then(|session_id| session_id.parse::<u64>().ok())
+            .ok_or(Self::Error::MalformedSDPLine)?;
+        let session_version = split
+            .next()
+            .and_then(|session_version| session_version.parse::<u64>().ok())
+            .ok_or(Self::Error::MalformedSDPLine)?;
         let network_type = split.next().ok_or(Self::Error::MalformedSDPLine)?;
 
         if network_type.ne("IN") {
@@ -587,32 +945,24 @@ impl TryFrom<&str> for Originator {
         }
 
         let ip_type = split.next().ok_or(Self::Error::MalformedSDPLine)?;
+        let unicast_address = split.next().ok_or(Self::Error::MalformedSDPLine)?;
+        if split.next().is_some() {
+            return Err(Self::Error::MalformedSDPLine);
+        }
 
-        match ip_type {
-            "IP4" => {
-                let unicast_address = split.next().ok_or(Self::Error::MalformedSDPLine)?;
-                let ip = Ipv4Addr::from_str(unicast_address)
-                    .map_err(|_| Self::Error::MalformedSDPLine)?;
-                Ok(Self {
-                    username: username.to_string(),
-                    session_id: session_id.to_string(),
-                    session_version: session_version.to_string(),
-                    ip_addr: IpAddr::V4(ip),
-                })
-            }
-            "IP6" => {
-                let unicast_address = split.next().ok_or(Self::Error::MalformedSDPLine)?;
-                let ip = Ipv6Addr::from_str(unicast_address)
-                    .map_err(|_| Self::Error::MalformedSDPLine)?;
-                Ok(Self {
-                    username: username.to_string(),
-                    session_id: session_id.to_string(),
-                    session_version: session_version.to_string(),
-                    ip_addr: IpAddr::V6(ip),
-                })
-            }
-            _ => Err(Self::Error::MalformedSDPLine),
+        let ip_addr = match ip_type {
+            "IP4" => Ipv4Addr::from_str(unicast_address).map(IpAddr::V4),
+            "IP6" => Ipv6Addr::from_str(unicast_address).map(IpAddr::V6),
+            _ => return Err(Self::Error::MalformedSDPLine),
         }
+        .map_err(|_| Self::Error::MalformedSDPLine)?;
+
+        Ok(Self {
+            username: username.to_string(),
+            session_id,
+            session_version,
+            ip_addr,
+        })
     }
 }
 
@@ -624,14 +974,22 @@ impl TryFrom<&str> for SessionTime {
             .split_once("t=")
             .ok_or(Self::Error::MalformedSDPLine)?;
         let (start_time, end_time) = value.split_once(" ").ok_or(Self::Error::MalformedSDPLine)?;
+        let start_time = start_time
+            .parse::<u64>()
+            .map_err(|_| Self::Error::MalformedSDPLine)?;
+        let end_time = end_time
+            .parse::<u64>()
+            .map_err(|_| Self::Error::MalformedSDPLine)?;
+
+        // Zero end time leaves the session unbounded, WebRTC always uses "t=0 0". A bounded session
+        // can't be permanent nor end before it starts, see RFC 8866 5.9
+        if end_time != 0 && (start_time == 0 || end_time < start_time) {
+            return Err(Self::Error::MalformedSDPLine);
+        }
 
         Ok(Self {
-            start_time: start_time
-                .parse::<usize>()
-                .map_err(|_| Self::Error::MalformedSDPLine)?,
-            end_time: end_time
-                .parse::<usize>()
-                .map_err(|_| Self::Error::MalformedSDPLine)?,
+            start_time,
+            end_time,
         })
     }
 }
@@ -713,6 +1071,7 @@ impl TryFrom<&str> for MediaType {
         match value {
             "video" => Ok(Self::Video),
             "audio" => Ok(Self::Audio),
+            "application" => Ok(Self::Application),
             _ => Err(Self::Error::UnsupportedMediaType),
         }
     }
@@ -723,7 +1082,9 @@ impl TryFrom<&str> for MediaTransportProtocol {
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
         match value {
-            "UDP/TLS/RTP/SAVPF" => Ok(Self::DtlsSrtp),
+            protocol if DTLS_SRTP_PROTOCOLS.contains(&protocol) => Ok(Self::DtlsSrtp),
+            "UDP/DTLS/SCTP" => Ok(Self::DtlsSctp),
+            // Plain RTP/AVP and friends carry unencrypted media
             _ => Err(Self::Error::UnsupportedMediaProtocol),
         }
     }
@@ -832,14 +1193,14 @@ impl TryFrom<&str> for MediaSSRC {
             .split_once("ssrc:")
             .ok_or(Self::Error::MalformedAttribute)?;
 
-        let mut split = value.split(" ");
+        // Attribute value may contain spaces, as in "msid:stream-id track-id"
+        let (ssrc, attribute) = value
+            .split_once(" ")
+            .ok_or(SDPParseError::MalformedAttribute)?;
 
-        let ssrc = split
-            .next()
-            .ok_or(SDPParseError::MalformedAttribute)?
+        let ssrc = ssrc
             .parse::<u32>()
             .map_err(|_| Self::Error::MalformedAttribute)?;
-        let attribute = split.next().ok_or(SDPParseError::MalformedAttribute)?;
 
         Ok(MediaSSRC {
             ssrc,
@@ -852,20 +1213,69 @@ impl TryFrom<&str> for SourceAttribute {
     type Error = SDPParseError;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        let mut split = value.split(":");
-
-        let key = split.next().ok_or(Self::Error::MalformedAttribute)?;
+        let (key, attribute_value) = match value.split_once(":") {
+            Some(attribute) => attribute,
+            None => return Ok(Self::Unsupported),
+        };
 
         match key {
             "cname" => {
-                let cname_value = split.next().ok_or(MalformedAttribute)?.to_string();
-                Ok(Self::CNAME(cname_value))
+                if attribute_value.is_empty() {
+                    return Err(MalformedAttribute);
+                }
+                Ok(Self::CNAME(attribute_value.to_string()))
+            }
+            "msid" => {
+                let mut split = attribute_value.split(" ");
+                let stream_id = split
+                    .next()
+                    .filter(|stream_id| !stream_id.is_empty())
+                    .ok_or(MalformedAttribute)?
+                    .to_string();
+                let track_id = split.next().map(String::from);
+
+                Ok(Self::MSID {
+                    stream_id,
+                    track_id,
+                })
             }
             _ => Ok(Self::Unsupported),
         }
     }
 }
 
+impl TryFrom<&str> for SSRCGroup {
+    type Error = SDPParseError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let (_, value) = value
+            .split_once("ssrc-group:")
+            .ok_or(Self::Error::MalformedAttribute)?;
+
+        let (semantics, ssrcs) = value
+            .split_once(" ")
+            .ok_or(Self::Error::MalformedAttribute)?;
+
+        let ssrcs = ssrcs
+            .split(" ")
+            .map(|ssrc| ssrc.parse::<u32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| Self::Error::MalformedAttribute)?;
+
+        match semantics {
+            // RFC 4588: primary SSRC followed by its retransmission SSRC
+            "FID" => match ssrcs.as_slice() {
+                [primary_ssrc, retransmission_ssrc] => Ok(SSRCGroup::FlowIdentification {
+                    primary_ssrc: *primary_ssrc,
+                    retransmission_ssrc: *retransmission_ssrc,
+                }),
+                _ => Err(Self::Error::MalformedAttribute),
+            },
+            _ => Ok(SSRCGroup::Unsupported),
+        }
+    }
+}
+
 impl TryFrom<&str> for Setup {
     type Error = SDPParseError;
 
@@ -910,6 +1320,194 @@ impl TryFrom<&str> for FMTP {
     }
 }
 
+impl TryFrom<&str> for H264ProfileLevelID {
+    type Error = SDPParseError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        if value.len() != 6 || !value.chars().all(|char| char.is_ascii_hexdigit()) {
+            return Err(MalformedAttribute);
+        }
+        let profile_level_id = u32::from_str_radix(value, 16).map_err(|_| MalformedAttribute)?;
+        let [_, profile_idc, profile_iop, level_idc] = profile_level_id.to_be_bytes();
+
+        Ok(H264ProfileLevelID {
+            profile_idc,
+            profile_iop,
+            level_idc,
+        })
+    }
+}
+
+/** Splits FMTP capabilities into parameter name/value pairs. Parameter names are case-insensitive,
+ * so they get lowercased. Capabilities without a value map to an empty value.
+ */
+pub(crate) fn get_format_parameters(format_capability: &HashSet<String>) -> HashMap<String, &str> {
+    format_capability
+        .iter()
+        .map(|capability| capability.split_once("=").unwrap_or((capability, "")))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim()))
+        .collect()
+}
+
+impl TryFrom<&str> for RTCPFeedback {
+    type Error = SDPParseError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let (_, value) = value
+            .split_once("rtcp-fb:")
+            .ok_or(Self::Error::MalformedAttribute)?;
+        let (payload_number, feedback_type) = value
+            .split_once(" ")
+            .ok_or(Self::Error::MalformedAttribute)?;
+
+        let payload_number = payload_number
+            .parse::<usize>()
+            .map_err(|_| Self::Error::MalformedAttribute)?;
+
+        Ok(RTCPFeedback {
+            payload_number,
+            feedback_type: feedback_type.trim().to_string(),
+        })
+    }
+}
+
+impl TryFrom<&str> for ExtMap {
+    type Error = SDPParseError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let (_, value) = value
+            .split_once("extmap:")
+            .ok_or(Self::Error::MalformedAttribute)?;
+        let mut split = value.split(" ");
+
+        // The ID may be followed by a direction, e.g. 3/sendonly
+        let id = split
+            .next()
+            .and_then(|id| id.split("/").next())
+            .and_then(|id| id.parse::<u8>().ok())
+            .ok_or(Self::Error::MalformedAttribute)?;
+        let uri = split
+            .next()
+            .filter(|uri| !uri.is_empty())
+            .ok_or(Self::Error::MalformedAttribute)?;
+
+        Ok(ExtMap {
+            id,
+            uri: uri.to_string(),
+        })
+    }
+}
+
+impl TryFrom<&str> for RidDirection {
+    type Error = SDPParseError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "send" => Ok(RidDirection::Send),
+            "recv" => Ok(RidDirection::Receive),
+            _ => Err(Self::Error::MalformedAttribute),
+        }
+    }
+}
+
+impl TryFrom<&str> for Rid {
+    type Error = SDPParseError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let (_, value) = value
+            .split_once("rid:")
+            .ok_or(Self::Error::MalformedAttribute)?;
+        let mut split = value.split(" ");
+
+        let id = split
+            .next()
+            .filter(|id| !id.is_empty())
+            .ok_or(Self::Error::MalformedAttribute)?;
+        let direction = split
+            .next()
+            .ok_or(Self::Error::MalformedAttribute)
+            .and_then(RidDirection::try_from)?;
+
+        Ok(Rid {
+            id: id.to_string(),
+            direction,
+        })
+    }
+}
+
+impl TryFrom<&str> for Simulcast {
+    type Error = SDPParseError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let (_, value) = value
+            .split_once("simulcast:")
+            .ok_or(Self::Error::MalformedAttribute)?;
+        let mut split = value.split(" ");
+
+        let direction = split
+            .next()
+            .ok_or(Self::Error::MalformedAttribute)
+            .and_then(RidDirection::try_from)?;
+        // Streams are separated with ';', alternatives of a stream with ',' and paused ones start with '~'
+        let rids = split
+            .next()
+            .ok_or(Self::Error::MalformedAttribute)?
+            .split(";")
+            .map(|stream| {
+                stream
+                    .split(",")
+                    .next()
+                    .map(|rid| rid.trim_start_matches("~"))
+                    .filter(|rid| !rid.is_empty())
+                    .map(|rid| rid.to_string())
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or(Self::Error::MalformedAttribute)?;
+
+        Ok(Simulcast { direction, rids })
+    }
+}
+
+impl TryFrom<&str> for MSID {
+    type Error = SDPParseError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let (_, value) = value
+            .split_once("msid:")
+            .ok_or(Self::Error::MalformedAttribute)?;
+        let mut split = value.split_whitespace();
+
+        let stream_id = split.next().ok_or(Self::Error::MalformedAttribute)?;
+        let track_id = split.next().map(|track_id| track_id.to_string());
+
+        Ok(MSID {
+            stream_id: stream_id.to_string(),
+            track_id,
+        })
+    }
+}
+
+impl TryFrom<&str> for MSIDSemantic {
+    type Error = SDPParseError;
+
+    /** Chromium puts a space after the colon, i.e. `msid-semantic: WMS`.
+     */
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let (_, value) = value
+            .split_once("msid-semantic:")
+            .ok_or(Self::Error::MalformedAttribute)?;
+        let mut split = value.split_whitespace();
+
+        let semantic = split.next().ok_or(Self::Error::MalformedAttribute)?;
+        let stream_ids = split.map(|stream_id| stream_id.to_string()).collect();
+
+        Ok(MSIDSemantic {
+            semantic: semantic.to_string(),
+            stream_ids,
+        })
+    }
+}
+
 impl TryFrom<&str> for Candidate {
     type Error = SDPParseError;
 
@@ -951,16 +1549,66 @@ impl TryFrom<&str> for Candidate {
             .parse::<u16>()
             .map_err(|_| SDPParseError::MalformedSDPLine)?;
 
+        if split.next() != Some("typ") {
+            return Err(SDPParseError::MalformedAttribute);
+        }
+        let candidate_type = split
+            .next()
+            .ok_or(SDPParseError::MalformedAttribute)
+            .and_then(CandidateType::try_from)?;
+
+        // Remaining extensions come in name-value pairs, only the related address is of interest
+        let mut related_ip = None;
+        let mut related_port = None;
+        while let (Some(name), Some(value)) = (split.next(), split.next()) {
+            match name {
+                "raddr" => {
+                    related_ip = Some(
+                        IpAddr::from_str(value).map_err(|_| SDPParseError::MalformedAttribute)?,
+                    )
+                }
+                "rport" => {
+                    related_port = Some(
+                        value
+                            .parse::<u16>()
+                            .map_err(|_| SDPParseError::MalformedAttribute)?,
+                    )
+                }
+                _ => {}
+            }
+        }
+        let related_address = match (related_ip, related_port) {
+            (Some(ip), Some(port)) => Some(SocketAddr::new(ip, port)),
+            (None, None) => None,
+            _ => return Err(SDPParseError::MalformedAttribute),
+        };
+
         Ok(Candidate {
             component_id,
             foundation,
             connection_address: ip,
             port,
             priority,
+            candidate_type,
+            related_address,
         })
     }
 }
 
+impl TryFrom<&str> for CandidateType {
+    type Error = SDPParseError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "host" => Ok(CandidateType::Host),
+            "srflx" => Ok(CandidateType::ServerReflexive),
+            "prflx" => Ok(CandidateType::PeerReflexive),
+            "relay" => Ok(CandidateType::Relay),
+            _ => Err(SDPParseError::MalformedAttribute),
+        }
+    }
+}
+
 impl TryFrom<&str> for ICEUsername {
     type Error = SDPParseError;
 
@@ -1193,3 +1841,515 @@ impl TryFrom<&str> for ICEPassword {
 //         }
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    mod media_ssrc {
+        use crate::line_parsers::{MediaSSRC, SourceAttribute};
+
+        #[test]
+        fn resolves_cname() {
+            let media_ssrc = MediaSSRC::try_from("ssrc:1349455989 cname:0X2NGAsK9XcmnsuZ")
+                .expect("Should resolve media SSRC");
+
+            assert_eq!(
+                media_ssrc,
+                MediaSSRC {
+                    ssrc: 1349455989,
+                    source_attribute: SourceAttribute::CNAME("0X2NGAsK9XcmnsuZ".to_string()),
+                }
+            );
+        }
+
+        #[test]
+        fn resolves_msid() {
+            let media_ssrc =
+                MediaSSRC::try_from("ssrc:1349455989 msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-audio")
+                    .expect("Should resolve media SSRC");
+
+            assert_eq!(
+                media_ssrc.source_attribute,
+                SourceAttribute::MSID {
+                    stream_id: "qUVEoh7TF9nLCrk4".to_string(),
+                    track_id: Some("qUVEoh7TF9nLCrk4-audio".to_string()),
+                }
+            );
+        }
+
+        #[test]
+        fn resolves_unknown_attribute_as_unsupported() {
+            let media_ssrc = MediaSSRC::try_from("ssrc:1349455989 mslabel:qUVEoh7TF9nLCrk4")
+                .expect("Should resolve media SSRC");
+
+            assert_eq!(media_ssrc.source_attribute, SourceAttribute::Unsupported);
+        }
+
+        #[test]
+        fn rejects_missing_source_attribute() {
+            assert!(MediaSSRC::try_from("ssrc:1349455989").is_err());
+        }
+    }
+
+    mod media_description {
+        use crate::line_parsers::{
+            MediaDescription, MediaTransportProtocol, MediaType, SDPParseError,
+        };
+
+        #[test]
+        fn resolves_data_channel_media() {
+            let media_description =
+                MediaDescription::try_from("m=application 9 UDP/DTLS/SCTP webrtc-datachannel")
+                    .expect("Should resolve media description");
+
+            assert_eq!(media_description.media_type, MediaType::Application);
+            assert_eq!(
+                media_description.transport_protocol,
+                MediaTransportProtocol::DtlsSctp
+            );
+            assert_eq!(
+                String::from(media_description),
+                "m=application 9 UDP/DTLS/SCTP webrtc-datachannel"
+            );
+        }
+
+        #[test]
+        fn resolves_dtls_srtp_protocol_alias() {
+            let media_description =
+                MediaDescription::try_from("m=video 9 UDP/DTLS/RTP/SAVPF 96 102")
+                    .expect("Should resolve media description");
+
+            assert_eq!(
+                media_description.transport_protocol,
+                MediaTransportProtocol::DtlsSrtp
+            );
+            assert_eq!(
+                String::from(media_description),
+                "m=video 9 UDP/TLS/RTP/SAVPF 96 102"
+            );
+        }
+
+        #[test]
+        fn rejects_plain_rtp_protocol() {
+            assert!(matches!(
+                MediaDescription::try_from("m=video 9 RTP/AVP 96"),
+                Err(SDPParseError::UnsupportedMediaProtocol)
+            ));
+        }
+
+        #[test]
+        fn rejects_unknown_sctp_media_format() {
+            assert!(MediaDescription::try_from("m=application 9 UDP/DTLS/SCTP 5000").is_err());
+        }
+    }
+
+    mod h264_profile_level_id {
+        use crate::line_parsers::H264ProfileLevelID;
+
+        #[test]
+        fn resolves_profile_level_id() {
+            let profile_level_id =
+                H264ProfileLevelID::try_from("42E01F").expect("Should resolve profile-level-id");
+
+            assert_eq!(
+                profile_level_id,
+                H264ProfileLevelID {
+                    profile_idc: 0x42,
+                    profile_iop: 0xe0,
+                    level_idc: 0x1f,
+                }
+            );
+        }
+
+        #[test]
+        fn rejects_malformed_profile_level_id() {
+            assert!(H264ProfileLevelID::try_from("42e01").is_err());
+            assert!(H264ProfileLevelID::try_from("+2e01f").is_err());
+        }
+    }
+
+    mod originator {
+        use std::net::{IpAddr, Ipv4Addr};
+
+        use crate::line_parsers::{Originator, SDPLine, SDPParseError};
+
+        #[test]
+        fn resolves_well_formed_originator() {
+            let line = SDPLine::try_from("o=- 9071898453171296627 2 IN IP4 127.0.0.1")
+                .expect("Should resolve originator");
+
+            assert_eq!(
+                line,
+                SDPLine::Originator(Originator {
+                    username: "-".to_string(),
+                    session_id: 9071898453171296627,
+                    session_version: 2,
+                    ip_addr: IpAddr::V4(Ipv4Addr::LOCALHOST),
+                })
+            );
+        }
+
+        #[test]
+        fn rejects_malformed_originator() {
+            for line in [
+                "o=- 3767197920 0 IN IP4",
+                "o=- session 0 IN IP4 127.0.0.1",
+                "o=- 3767197920 -1 IN IP4 127.0.0.1",
+                "o=- 3767197920 0 ATM IP4 127.0.0.1",
+                "o=- 3767197920 0 IN IPX 127.0.0.1",
+                "o=- 3767197920 0 IN IP6 127.0.0.1",
+                "o=- 3767197920 0 IN IP4 127.0.0.1 extra",
+                "o= 3767197920 0 IN IP4 127.0.0.1",
+            ] {
+                assert!(
+                    matches!(
+                        SDPLine::try_from(line),
+                        Err(SDPParseError::MalformedSDPLine)
+                    ),
+                    "{line} should be rejected"
+                );
+            }
+        }
+    }
+
+    mod session_time {
+        use crate::line_parsers::{SDPLine, SDPParseError, SessionTime};
+
+        #[test]
+        fn accepts_unbounded_session() {
+            let line = SDPLine::try_from("t=0 0").expect("Should resolve session time");
+
+            assert_eq!(
+                line,
+                SDPLine::SessionTime(SessionTime {
+                    start_time: 0,
+                    end_time: 0,
+                })
+            );
+        }
+
+        #[test]
+        fn accepts_bounded_session() {
+            let line =
+                SDPLine::try_from("t=3034423619 3042462419").expect("Should resolve session time");
+
+            assert_eq!(
+                line,
+                SDPLine::SessionTime(SessionTime {
+                    start_time: 3034423619,
+                    end_time: 3042462419,
+                })
+            );
+        }
+
+        #[test]
+        fn rejects_invalid_session_time() {
+            for line in [
+                "t=abc 0",
+                "t=0 abc",
+                "t=0",
+                "t=0 0 0",
+                "t=0 3042462419",
+                "t=3042462419 3034423619",
+            ] {
+                assert!(
+                    matches!(
+                        SDPLine::try_from(line),
+                        Err(SDPParseError::MalformedSDPLine)
+                    ),
+                    "{line} should be rejected"
+                );
+            }
+        }
+    }
+
+    mod rtcp_feedback {
+        use crate::line_parsers::{Attribute, RTCPFeedback};
+
+        #[test]
+        fn resolves_payload_feedback() {
+            let attribute =
+                Attribute::try_from("a=rtcp-fb:96 nack pli").expect("Should resolve feedback");
+
+            assert_eq!(
+                attribute,
+                Attribute::RTCPFeedback(RTCPFeedback {
+                    payload_number: 96,
+                    feedback_type: "nack pli".to_string(),
+                })
+            );
+        }
+
+        #[test]
+        fn resolves_full_intra_request_feedback() {
+            let attribute =
+                Attribute::try_from("a=rtcp-fb:96 ccm fir").expect("Should resolve feedback");
+
+            assert_eq!(
+                attribute,
+                Attribute::RTCPFeedback(RTCPFeedback {
+                    payload_number: 96,
+                    feedback_type: "ccm fir".to_string(),
+                })
+            );
+        }
+
+        #[test]
+        fn ignores_wildcard_feedback() {
+            let attribute =
+                Attribute::try_from("a=rtcp-fb:* transport-cc").expect("Should accept feedback");
+
+            assert_eq!(attribute, Attribute::Unrecognized);
+        }
+    }
+
+    mod ssrc_group {
+        use crate::line_parsers::{Attribute, SSRCGroup};
+
+        #[test]
+        fn resolves_flow_identification_group() {
+            let attribute = Attribute::try_from("a=ssrc-group:FID 1349455989 3735928559")
+                .expect("Should resolve SSRC group");
+
+            assert_eq!(
+                attribute,
+                Attribute::SSRCGroup(SSRCGroup::FlowIdentification {
+                    primary_ssrc: 1349455989,
+                    retransmission_ssrc: 3735928559,
+                })
+            );
+        }
+
+        #[test]
+        fn resolves_unknown_semantics_as_unsupported() {
+            let ssrc_group =
+                SSRCGroup::try_from("ssrc-group:SIM 1 2 3").expect("Should resolve SSRC group");
+
+            assert_eq!(ssrc_group, SSRCGroup::Unsupported);
+        }
+
+        #[test]
+        fn rejects_malformed_groups() {
+            assert!(SSRCGroup::try_from("ssrc-group:FID").is_err());
+            assert!(SSRCGroup::try_from("ssrc-group:FID 1349455989").is_err());
+            assert!(SSRCGroup::try_from("ssrc-group:FID 1349455989 2 3").is_err());
+            assert!(SSRCGroup::try_from("ssrc-group:FID 1349455989 rtx").is_err());
+        }
+    }
+
+    mod candidate {
+        use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+        use crate::line_parsers::{Candidate, CandidateType};
+
+        #[test]
+        fn resolves_server_reflexive_candidate() {
+            let candidate = Candidate::try_from(
+                "candidate:2 1 UDP 1694498815 203.0.113.7 52000 typ srflx raddr 192.168.0.10 rport 52000",
+            )
+            .expect("Should resolve candidate");
+
+            assert_eq!(
+                candidate,
+                Candidate {
+                    foundation: "2".to_string(),
+                    component_id: 1,
+                    priority: 1694498815,
+                    connection_address: IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7)),
+                    port: 52000,
+                    candidate_type: CandidateType::ServerReflexive,
+                    related_address: Some(SocketAddr::new(
+                        IpAddr::V4(Ipv4Addr::new(192, 168, 0, 10)),
+                        52000
+                    )),
+                }
+            );
+        }
+
+        #[test]
+        fn writes_related_address_of_server_reflexive_candidate() {
+            let line = "candidate:2 1 UDP 1694498815 203.0.113.7 52000 typ srflx raddr 192.168.0.10 rport 52000";
+            let candidate = Candidate::try_from(line).expect("Should resolve candidate");
+
+            assert_eq!(String::from(candidate), line);
+        }
+
+        #[test]
+        fn ignores_unknown_extensions() {
+            let candidate = Candidate::try_from(
+                "candidate:1 1 UDP 2015363327 192.168.0.10 52000 typ host generation 0",
+            )
+            .expect("Should resolve candidate");
+
+            assert_eq!(candidate.candidate_type, CandidateType::Host);
+            assert_eq!(candidate.related_address, None);
+        }
+
+        #[test]
+        fn rejects_partial_related_address() {
+            let candidate = Candidate::try_from(
+                "candidate:2 1 UDP 1694498815 203.0.113.7 52000 typ srflx raddr 192.168.0.10",
+            );
+
+            assert!(candidate.is_err());
+        }
+    }
+
+    mod get_priority {
+        use crate::line_parsers::{Candidate, CandidateType};
+
+        #[test]
+        fn computes_priority_from_preferences_and_component() {
+            assert_eq!(
+                Candidate::get_priority(&CandidateType::Host, u16::MAX, 1),
+                2130706431
+            );
+            assert_eq!(
+                Candidate::get_priority(&CandidateType::ServerReflexive, u16::MAX, 1),
+                1694498815
+            );
+            assert_eq!(Candidate::get_priority(&CandidateType::Relay, 0, 2), 254);
+        }
+
+        #[test]
+        fn prefers_host_over_server_reflexive() {
+            let host_priority = Candidate::get_priority(&CandidateType::Host, 0, 1);
+            let server_reflexive_priority =
+                Candidate::get_priority(&CandidateType::ServerReflexive, u16::MAX, 1);
+
+            assert!(host_priority > server_reflexive_priority);
+        }
+    }
+
+    mod codec {
+        use crate::line_parsers::{AudioCodec, VideoCodec};
+
+        #[test]
+        fn returns_clock_rate() {
+            assert_eq!(VideoCodec::H264.clock_rate(), 90000);
+            assert_eq!(AudioCodec::Opus.clock_rate(), 48000);
+        }
+
+        #[test]
+        fn writes_clock_rate_to_rtpmap() {
+            assert_eq!(String::from(VideoCodec::H264), "h264/90000");
+            assert_eq!(String::from(AudioCodec::Opus), "opus/48000/2");
+        }
+    }
+
+    mod simulcast {
+        use crate::line_parsers::{Attribute, ExtMap, Rid, RidDirection, Simulcast};
+
+        #[test]
+        fn resolves_simulcast_with_paused_and_alternative_streams() {
+            assert_eq!(
+                Simulcast::try_from("simulcast:send h;~m;l,l2").expect("Should resolve simulcast"),
+                Simulcast {
+                    direction: RidDirection::Send,
+                    rids: vec!["h".to_string(), "m".to_string(), "l".to_string()],
+                }
+            );
+        }
+
+        #[test]
+        fn resolves_rid_ignoring_restrictions() {
+            assert_eq!(
+                Rid::try_from("rid:h send max-width=1280;max-height=720")
+                    .expect("Should resolve rid"),
+                Rid {
+                    id: "h".to_string(),
+                    direction: RidDirection::Send,
+                }
+            );
+            assert!(Rid::try_from("rid:h").is_err());
+        }
+
+        #[test]
+        fn resolves_extmap_with_direction() {
+            assert_eq!(
+                ExtMap::try_from("extmap:10/sendonly urn:ietf:params:rtp-hdrext:sdes:rtp-stream-id")
+                    .expect("Should resolve extmap"),
+                ExtMap {
+                    id: 10,
+                    uri: "urn:ietf:params:rtp-hdrext:sdes:rtp-stream-id".to_string(),
+                }
+            );
+        }
+
+        #[test]
+        fn resolves_extmap_allow_mixed() {
+            let attribute =
+                Attribute::try_from("a=extmap-allow-mixed").expect("Should resolve attribute");
+
+            assert_eq!(attribute, Attribute::ExtMapAllowMixed);
+            assert_eq!(String::from(attribute), "a=extmap-allow-mixed");
+        }
+
+        #[test]
+        fn writes_receiving_simulcast() {
+            let simulcast = Attribute::Simulcast(Simulcast {
+                direction: RidDirection::Receive,
+                rids: vec!["h".to_string(), "l".to_string()],
+            });
+
+            assert_eq!(String::from(simulcast), "a=simulcast:recv h;l");
+        }
+    }
+
+    mod msid {
+        use crate::line_parsers::{Attribute, MSID, MSIDSemantic};
+
+        #[test]
+        fn resolves_msid_with_and_without_track() {
+            assert_eq!(
+                MSID::try_from("msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-audio")
+                    .expect("Should resolve msid"),
+                MSID {
+                    stream_id: "qUVEoh7TF9nLCrk4".to_string(),
+                    track_id: Some("qUVEoh7TF9nLCrk4-audio".to_string()),
+                }
+            );
+            assert_eq!(
+                MSID::try_from("msid:-").expect("Should resolve msid"),
+                MSID {
+                    stream_id: "-".to_string(),
+                    track_id: None,
+                }
+            );
+            assert!(MSID::try_from("msid:").is_err());
+        }
+
+        #[test]
+        fn resolves_msid_semantic_with_leading_space() {
+            assert_eq!(
+                MSIDSemantic::try_from("msid-semantic: WMS").expect("Should resolve msid-semantic"),
+                MSIDSemantic {
+                    semantic: "WMS".to_string(),
+                    stream_ids: vec![],
+                }
+            );
+            assert_eq!(
+                MSIDSemantic::try_from("msid-semantic:WMS *")
+                    .expect("Should resolve msid-semantic"),
+                MSIDSemantic {
+                    semantic: "WMS".to_string(),
+                    stream_ids: vec!["*".to_string()],
+                }
+            );
+        }
+
+        #[test]
+        fn writes_msid_lines() {
+            let msid = Attribute::MSID(MSID {
+                stream_id: "stream".to_string(),
+                track_id: Some("stream-video".to_string()),
+            });
+            let msid_semantic = Attribute::MSIDSemantic(MSIDSemantic {
+                semantic: "WMS".to_string(),
+                stream_ids: vec!["stream".to_string()],
+            });
+
+            assert_eq!(String::from(msid), "a=msid:stream stream-video");
+            assert_eq!(String::from(msid_semantic), "a=msid-semantic:WMS stream");
+        }
+    }
+}
@@ -1,21 +1,38 @@
 use std::collections::HashSet;
-use std::net::SocketAddr;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Mutex;
 
-use rand::{Rng, RngCore, thread_rng};
+use rand::{Rng, RngCore, SeedableRng, thread_rng};
 use rand::distr::Alphanumeric;
+use rand::rngs::StdRng;
 
 use crate::line_parsers::{
-    Attribute, AudioCodec, Candidate, ConnectionData, Fingerprint, FMTP, ICEOption,
-    ICEOptions, ICEPassword, ICEUsername, MediaCodec, MediaDescription, MediaGroup, MediaID,
-    MediaSSRC, MediaTransportProtocol, MediaType, Originator, RTPMap, SDPLine, SDPParseError,
-    SessionTime, Setup, SourceAttribute, VideoCodec,
+    ABS_SEND_TIME_EXTENSION_URI, Attribute, AudioCodec, Candidate, CandidateType, ConnectionData, ExtMap, Fingerprint, FMTP,
+    get_format_parameters, H264ProfileLevelID, HashFunction, ICEOption, ICEOptions, ICEPassword,
+    ICEUsername,
+    MediaCodec, MediaDescription, MediaGroup, MediaID, MediaSSRC, MediaTransportProtocol,
+    MediaType, MSID, MSIDSemantic, Originator, Rid, RidDirection, RTCPFeedback,
+    RTP_STREAM_ID_EXTENSION_URI, RTPMap,
+    SDPLine, SDPParseError, SessionTime, Setup, Simulcast, SourceAttribute, SSRCGroup,
+    TRANSPORT_CC_EXTENSION_URI, VideoCodec,
 };
 
+/** H264 FMTP parameters that decide whether a viewer can decode the streamer's video, with their RFC 6184 defaults.
+ * Browsers add or reorder other parameters freely.
+ */
+const H264_COMPATIBILITY_PARAMETERS: [(&str, &str); 3] = [
+    ("profile-level-id", "42000a"),
+    ("packetization-mode", "0"),
+    ("level-asymmetry-allowed", "0"),
+];
+
 #[derive(Debug, Clone)]
 pub struct SDP {
     session_section: Vec<SDPLine>,
     video_section: Vec<SDPLine>,
+    extra_video_sections: Vec<Vec<SDPLine>>,
     audio_section: Vec<SDPLine>,
+    application_section: Option<Vec<SDPLine>>,
 }
 
 #[derive(Debug, Clone)]
@@ -23,7 +40,14 @@ pub struct NegotiatedSession {
     pub sdp_answer: SDP,
     pub ice_credentials: ICECredentials,
     pub video_session: VideoSession,
+    /** Video tracks sent next to the primary one, e.g. a screen share, in the order of the offer.
+     */
+    pub extra_video_sessions: Vec<VideoSession>,
     pub audio_session: AudioSession,
+    /** ID of the media stream viewers group the audio and video tracks of a streamer into.
+     * Viewer sessions share the ID of their streamer's session.
+     */
+    pub stream_id: String,
 }
 #[derive(Debug, Clone)]
 pub struct ICECredentials {
@@ -38,7 +62,144 @@ pub struct VideoSession {
     pub payload_number: usize,
     pub host_ssrc: u32,
     pub remote_ssrc: Option<u32>,
+    pub remote_cname: Option<String>,
+    pub remote_rtx_ssrc: Option<u32>,
     pub capabilities: HashSet<String>,
+    pub simulcast: Option<SimulcastSession>,
+    /** ID of the abs-send-time header extension, present when the streamer's bandwidth can be estimated.
+     */
+    pub abs_send_time_extension_id: Option<u8>,
+    /** ID of the transport-wide-cc header extension, present when the viewer estimates bandwidth
+     * from the packets it receives.
+     */
+    pub transport_cc_extension_id: Option<u8>,
+    /** Whether the remote takes Full Intra Requests, negotiated with `ccm fir` feedback.
+     */
+    pub fir_supported: bool,
+}
+
+/** Simulcast layers sent by a streamer, in the order of the offer's simulcast attribute. Packets name their
+ * layer with the RID header extension.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulcastSession {
+    pub rids: Vec<String>,
+    pub rid_extension_id: u8,
+}
+
+impl NegotiatedSession {
+    /** Video sessions of every track, the primary one first.
+     */
+    pub fn get_video_sessions(&self) -> impl Iterator<Item = &VideoSession> {
+        std::iter::once(&self.video_session).chain(&self.extra_video_sessions)
+    }
+
+    /** Index of the extra video track whose packets come with the given SSRC.
+     */
+    pub fn get_extra_video_index(&self, remote_ssrc: u32) -> Option<usize> {
+        self.extra_video_sessions
+            .iter()
+            .position(|video_session| video_session.remote_ssrc == Some(remote_ssrc))
+    }
+
+    /** SSRCs the remote sends from, RTX ones included.
+     */
+    pub fn get_remote_ssrcs(&self) -> Vec<u32> {
+        std::iter::once(self.audio_session.remote_ssrc)
+            .chain(self.get_video_sessions().flat_map(|video_session| {
+                [video_session.remote_ssrc, video_session.remote_rtx_ssrc]
+            }))
+            .flatten()
+            .collect()
+    }
+
+    /** Forgets a source the remote stopped sending from, e.g. one announced leaving by an RTCP BYE.
+     */
+    pub fn forget_remote_ssrc(&mut self, ssrc: u32) {
+        let remote_ssrcs = std::iter::once(&mut self.video_session)
+            .chain(&mut self.extra_video_sessions)
+            .flat_map(|video_session| {
+                [
+                    &mut video_session.remote_ssrc,
+                    &mut video_session.remote_rtx_ssrc,
+                ]
+            })
+            .chain(std::iter::once(&mut self.audio_session.remote_ssrc));
+        for remote_ssrc in remote_ssrcs {
+            if *remote_ssrc == Some(ssrc) {
+                *remote_ssrc = None;
+            }
+        }
+    }
+}
+
+impl VideoSession {
+    /** SSRC of the remote video stream, required to address RTCP feedback such as NACKs.
+     */
+    pub fn require_remote_ssrc(&self) -> Result<u32, SDPParseError> {
+        self.remote_ssrc.ok_or(SDPParseError::MissingStreamSSRC)
+    }
+}
+
+/** Part of an offer a requirement applies to.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OfferSection {
+    Session,
+    Audio,
+    Video,
+}
+
+impl OfferSection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OfferSection::Session => "session",
+            OfferSection::Audio => "audio",
+            OfferSection::Video => "video",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OfferRequirement {
+    /** Audio and video bundled on a single transport.
+     */
+    Bundle,
+    ICECredentials,
+    /** The remote can't take the passive DTLS role, the server is always passive.
+     */
+    DTLSRole,
+    /** RTP and RTCP demuxed on a single port.
+     */
+    RTCPMux,
+    /** Streamers have to send media, viewers have to receive it.
+     */
+    Direction,
+    /** Opus audio and H264 video with its format parameters. Viewers have to accept the streamer's format.
+     */
+    SupportedCodec,
+}
+
+impl OfferRequirement {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OfferRequirement::Bundle => "bundle",
+            OfferRequirement::ICECredentials => "ice-credentials",
+            OfferRequirement::DTLSRole => "dtls-role",
+            OfferRequirement::RTCPMux => "rtcp-mux",
+            OfferRequirement::Direction => "direction",
+            OfferRequirement::SupportedCodec => "supported-codec",
+        }
+    }
+}
+
+/** Why an offer was rejected. All unmet requirements are listed, not only the one the error stems from.
+ * The list is empty when the offer couldn't be parsed at all.
+ */
+#[derive(Debug)]
+pub struct OfferDiagnostics {
+    pub error: SDPParseError,
+    pub failed_requirements: Vec<(OfferSection, OfferRequirement)>,
 }
 
 #[derive(Debug, Clone)]
@@ -47,31 +208,394 @@ pub struct AudioSession {
     pub payload_number: usize,
     pub host_ssrc: u32,
     pub remote_ssrc: Option<u32>,
+    pub remote_cname: Option<String>,
 }
 
 pub struct SDPResolver {
     fingerprint: Fingerprint,
-    candidate: Candidate,
+    candidates: Vec<Candidate>,
+    ice_username_length: usize,
+    ice_password_length: usize,
+    identifier_generator: IdentifierGenerator,
+    allowed_video_codecs: Vec<VideoCodec>,
 }
 
-fn get_random_string(size: usize) -> String {
-    thread_rng()
-        .sample_iter(Alphanumeric)
-        .take(size)
-        .map(char::from)
-        .collect()
+/** Source of the host identifiers of negotiated sessions, i.e. ICE credentials and SSRCs.
+ */
+enum IdentifierGenerator {
+    Random,
+    /** Yields the same identifiers on every run, so that answers can be compared in tests.
+     */
+    Seeded(Box<Mutex<StdRng>>),
 }
 
-fn get_random_ssrc() -> u32 {
-    thread_rng().next_u32()
+impl IdentifierGenerator {
+    fn get_random_string(&self, size: usize) -> String {
+        self.generate(|rng| {
+            rng.sample_iter(Alphanumeric)
+                .take(size)
+                .map(char::from)
+                .collect()
+        })
+    }
+
+    fn get_random_ssrc(&self) -> u32 {
+        self.generate(|rng| rng.next_u32())
+    }
+
+    fn generate<T>(&self, generator: impl FnOnce(&mut dyn RngCore) -> T) -> T {
+        match self {
+            IdentifierGenerator::Random => generator(&mut thread_rng()),
+            IdentifierGenerator::Seeded(rng) => generator(&mut *rng.lock().unwrap()),
+        }
+    }
+}
+
+fn has_attribute(media: &[SDPLine], predicate: impl Fn(&Attribute) -> bool) -> bool {
+    media.iter().any(|item| match item {
+        SDPLine::Attribute(attr) => predicate(attr),
+        _ => false,
+    })
+}
+
+fn is_rtcp_muxed(media: &[SDPLine]) -> bool {
+    has_attribute(media, |attr| matches!(attr, Attribute::RTCPMux))
+}
+
+/** Whether the remote sends media, either sendonly or sendrecv.
+ */
+fn is_sending(media: &[SDPLine]) -> bool {
+    has_attribute(media, |attr| {
+        matches!(attr, Attribute::SendOnly | Attribute::SendReceive)
+    })
+}
+
+/** Whether the remote receives media, either recvonly or sendrecv.
+ */
+fn is_receiving(media: &[SDPLine]) -> bool {
+    has_attribute(media, |attr| {
+        matches!(attr, Attribute::ReceiveOnly | Attribute::SendReceive)
+    })
+}
+
+/** None if the setup attribute is missing.
+ */
+fn is_passive_dtls_role(lines: &[SDPLine]) -> Option<bool> {
+    lines.iter().find_map(|item| match item {
+        SDPLine::Attribute(Attribute::Setup(setup)) => Some(matches!(setup, Setup::Passive)),
+        _ => None,
+    })
+}
+
+fn get_media_id(media: &[SDPLine]) -> Option<&MediaID> {
+    media.iter().find_map(|item| match item {
+        SDPLine::Attribute(Attribute::MediaID(media_id)) => Some(media_id),
+        _ => None,
+    })
+}
+
+fn get_codec_payload_number(media: &[SDPLine], codec: &MediaCodec) -> Option<usize> {
+    media.iter().find_map(|item| match item {
+        SDPLine::Attribute(Attribute::RTPMap(rtpmap)) if rtpmap.codec.eq(codec) => {
+            Some(rtpmap.payload_number)
+        }
+        _ => None,
+    })
+}
+
+fn has_feedback(media: &[SDPLine], payload_number: usize, feedback_type: &str) -> bool {
+    has_attribute(media, |attr| {
+        matches!(attr, Attribute::RTCPFeedback(feedback)
+            if feedback.payload_number == payload_number && feedback.feedback_type == feedback_type)
+    })
+}
+
+fn get_format_capability(media: &[SDPLine], payload_number: usize) -> Option<HashSet<String>> {
+    media.iter().find_map(|item| match item {
+        SDPLine::Attribute(Attribute::FMTP(fmtp)) if fmtp.payload_number.eq(&payload_number) => {
+            Some(fmtp.format_capability.clone())
+        }
+        _ => None,
+    })
+}
+
+fn is_compatible_fmtp(
+    streamer_capability: &HashSet<String>,
+    viewer_capability: &HashSet<String>,
+) -> bool {
+    let streamer_parameters = get_format_parameters(streamer_capability);
+    let viewer_parameters = get_format_parameters(viewer_capability);
+
+    H264_COMPATIBILITY_PARAMETERS
+        .iter()
+        .all(|(name, default_value)| {
+            let streamer_value = streamer_parameters.get(*name).unwrap_or(default_value);
+            let viewer_value = viewer_parameters.get(*name).unwrap_or(default_value);
+            match *name {
+                "profile-level-id" => is_compatible_profile_level_id(streamer_value, viewer_value),
+                _ => streamer_value.eq_ignore_ascii_case(viewer_value),
+            }
+        })
+}
+
+/** Profiles have to match, while the viewer may signal a level up to the streamer's one.
+ */
+fn is_compatible_profile_level_id(streamer_value: &str, viewer_value: &str) -> bool {
+    match (
+        H264ProfileLevelID::try_from(streamer_value),
+        H264ProfileLevelID::try_from(viewer_value),
+    ) {
+        (Ok(streamer_profile_level_id), Ok(viewer_profile_level_id)) => {
+            get_h264_profile_idc(&streamer_profile_level_id)
+                .eq(&get_h264_profile_idc(&viewer_profile_level_id))
+                && viewer_profile_level_id.level_idc <= streamer_profile_level_id.level_idc
+        }
+        _ => false,
+    }
+}
+
+/** Constrained Baseline variants differ only in constraint flags, so they resolve to the Baseline profile_idc.
+ * Main and Extended streams with constraint_set0_flag conform to Baseline as well (RFC 6184 8.1).
+ */
+fn get_h264_profile_idc(profile_level_id: &H264ProfileLevelID) -> u8 {
+    const BASELINE_PROFILE_IDC: u8 = 0x42;
+    const CONSTRAINT_SET0_FLAG: u8 = 0x80;
+
+    match profile_level_id.profile_idc {
+        0x4d | 0x58 if profile_level_id.profile_iop & CONSTRAINT_SET0_FLAG != 0 => {
+            BASELINE_PROFILE_IDC
+        }
+        profile_idc => profile_idc,
+    }
+}
+
+/** Builds a [`NegotiatedSession`] without an SDP offer, e.g. for tests of crates handling negotiated sessions.
+ * The SDP answer of a built session advertises neither candidates nor a fingerprint.
+ *
+ * ```
+ * use std::collections::HashSet;
+ *
+ * use sdp::{AudioCodec, AudioSession, ICECredentials, NegotiatedSessionBuilder, VideoCodec, VideoSession};
+ *
+ * let negotiated_session = NegotiatedSessionBuilder::new()
+ *     .set_ice_credentials(ICECredentials {
+ *         host_username: "host".to_string(),
+ *         host_password: "host-password".to_string(),
+ *         remote_username: "remote".to_string(),
+ *         remote_password: "remote-password".to_string(),
+ *     })
+ *     .set_audio_session(AudioSession {
+ *         codec: AudioCodec::Opus,
+ *         payload_number: 111,
+ *         host_ssrc: 1,
+ *         remote_ssrc: Some(2),
+ *         remote_cname: None,
+ *     })
+ *     .set_video_session(VideoSession {
+ *         codec: VideoCodec::H264,
+ *         payload_number: 96,
+ *         host_ssrc: 3,
+ *         remote_ssrc: Some(4),
+ *         remote_cname: None,
+ *         remote_rtx_ssrc: None,
+ *         capabilities: HashSet::from(["packetization-mode=1".to_string()]),
+ *         simulcast: None,
+ *         abs_send_time_extension_id: None,
+ *         transport_cc_extension_id: None,
+ *         fir_supported: false,
+ *     })
+ *     .build()
+ *     .expect("Should build negotiated session");
+ *
+ * assert_eq!(negotiated_session.video_session.payload_number, 96);
+ * assert!(String::from(negotiated_session.sdp_answer).contains("a=mid:1"));
+ * ```
+ */
+#[derive(Debug, Clone)]
+pub struct NegotiatedSessionBuilder {
+    ice_credentials: Option<ICECredentials>,
+    audio_session: Option<AudioSession>,
+    video_session: Option<VideoSession>,
+    audio_mid: String,
+    video_mid: String,
+}
+
+impl NegotiatedSessionBuilder {
+    pub fn new() -> Self {
+        NegotiatedSessionBuilder {
+            ice_credentials: None,
+            audio_session: None,
+            video_session: None,
+            audio_mid: "0".to_string(),
+            video_mid: "1".to_string(),
+        }
+    }
+
+    pub fn set_ice_credentials(mut self, ice_credentials: ICECredentials) -> Self {
+        self.ice_credentials = Some(ice_credentials);
+        self
+    }
+
+    pub fn set_audio_session(mut self, audio_session: AudioSession) -> Self {
+        self.audio_session = Some(audio_session);
+        self
+    }
+
+    pub fn set_video_session(mut self, video_session: VideoSession) -> Self {
+        self.video_session = Some(video_session);
+        self
+    }
+
+    /** Media IDs default to 0 for audio and 1 for video.
+     */
+    pub fn set_media_ids(mut self, audio_mid: &str, video_mid: &str) -> Self {
+        self.audio_mid = audio_mid.to_string();
+        self.video_mid = video_mid.to_string();
+        self
+    }
+
+    /** Fails unless both media sessions use the codecs accepted by [`SDPResolver`] and have distinct media IDs.
+     */
+    pub fn build(self) -> Result<NegotiatedSession, SDPParseError> {
+        let ice_credentials = self
+            .ice_credentials
+            .ok_or(SDPParseError::MissingICECredentials)?;
+        let audio_session = self
+            .audio_session
+            .ok_or(SDPParseError::UnsupportedMediaCount)?;
+        let video_session = self
+            .video_session
+            .ok_or(SDPParseError::UnsupportedMediaCount)?;
+
+        if audio_session.codec.ne(&SDPResolver::ACCEPTED_AUDIO_CODEC)
+            || video_session.codec.ne(&SDPResolver::ACCEPTED_VIDEO_CODEC)
+        {
+            return Err(SDPParseError::UnsupportedMediaCodecs);
+        }
+        if self.audio_mid.is_empty()
+            || self.video_mid.is_empty()
+            || self.audio_mid.eq(&self.video_mid)
+        {
+            return Err(SDPParseError::InvalidMediaID);
+        }
+
+        let session_section = vec![
+            SDPLine::ProtocolVersion("0".to_string()),
+            SDPLine::Originator(Originator {
+                username: HOST_CNAME.to_string(),
+                ip_addr: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                session_version: 0,
+                session_id: 3767197920,
+            }),
+            SDPLine::SessionName(HOST_CNAME.to_string()),
+            SDPLine::SessionTime(SessionTime {
+                start_time: 0,
+                end_time: 0,
+            }),
+            SDPLine::Attribute(Attribute::MediaGroup(MediaGroup::Bundle(vec![
+                self.audio_mid.clone(),
+                self.video_mid.clone(),
+            ]))),
+            SDPLine::Attribute(Attribute::ICEUsername(ICEUsername {
+                username: ice_credentials.host_username.clone(),
+            })),
+            SDPLine::Attribute(Attribute::ICEPassword(ICEPassword {
+                password: ice_credentials.host_password.clone(),
+            })),
+            SDPLine::Attribute(Attribute::ICELite),
+            SDPLine::Attribute(Attribute::Setup(Setup::Passive)),
+        ];
+
+        let audio_section = vec![
+            SDPLine::MediaDescription(MediaDescription {
+                transport_port: 9,
+                media_type: MediaType::Audio,
+                transport_protocol: MediaTransportProtocol::DtlsSrtp,
+                media_format_description: vec![audio_session.payload_number],
+            }),
+            SDPLine::Attribute(Attribute::ReceiveOnly),
+            SDPLine::Attribute(Attribute::RTCPMux),
+            SDPLine::Attribute(Attribute::MediaID(MediaID { id: self.audio_mid })),
+            SDPLine::Attribute(Attribute::RTPMap(RTPMap {
+                codec: MediaCodec::Audio(audio_session.codec.clone()),
+                payload_number: audio_session.payload_number,
+            })),
+            SDPLine::Attribute(Attribute::MediaSSRC(MediaSSRC {
+                ssrc: audio_session.host_ssrc,
+                source_attribute: SourceAttribute::CNAME(HOST_CNAME.to_string()),
+            })),
+        ];
+
+        let video_section = vec![
+            SDPLine::MediaDescription(MediaDescription {
+                transport_port: 9,
+                media_type: MediaType::Video,
+                transport_protocol: MediaTransportProtocol::DtlsSrtp,
+                media_format_description: vec![video_session.payload_number],
+            }),
+            SDPLine::Attribute(Attribute::ReceiveOnly),
+            SDPLine::Attribute(Attribute::RTCPMux),
+            SDPLine::Attribute(Attribute::MediaID(MediaID { id: self.video_mid })),
+            SDPLine::Attribute(Attribute::RTPMap(RTPMap {
+                codec: MediaCodec::Video(video_session.codec.clone()),
+                payload_number: video_session.payload_number,
+            })),
+            SDPLine::Attribute(Attribute::MediaSSRC(MediaSSRC {
+                ssrc: video_session.host_ssrc,
+                source_attribute: SourceAttribute::CNAME(HOST_CNAME.to_string()),
+            })),
+            SDPLine::Attribute(Attribute::FMTP(FMTP {
+                payload_number: video_session.payload_number,
+                format_capability: video_session.capabilities.clone(),
+            })),
+        ];
+
+        Ok(NegotiatedSession {
+            sdp_answer: SDP {
+                session_section,
+                audio_section,
+                video_section,
+                extra_video_sections: vec![],
+                application_section: None,
+            },
+            ice_credentials,
+            audio_session,
+            video_session,
+            extra_video_sessions: vec![],
+            stream_id: HOST_CNAME.to_string(),
+        })
+    }
+}
+
+impl Default for NegotiatedSessionBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SDP {
+    /** Version of the session from its originator line.
+     */
+    fn get_session_version(&self) -> Option<u64> {
+        self.session_section.iter().find_map(|line| match line {
+            SDPLine::Originator(originator) => Some(originator.session_version),
+            _ => None,
+        })
+    }
 }
 
 impl From<SDP> for String {
     fn from(value: SDP) -> Self {
-        let video = value
-            .video_section
+        let video = [vec![value.video_section], value.extra_video_sections]
+            .concat()
             .into_iter()
-            .map(String::from)
+            .map(|video_section| {
+                video_section
+                    .into_iter()
+                    .map(String::from)
+                    .collect::<Vec<_>>()
+                    .join("\r\n")
+            })
             .collect::<Vec<_>>()
             .join("\r\n");
         let audio = value
@@ -87,33 +611,200 @@ impl From<SDP> for String {
             .collect::<Vec<_>>()
             .join("\r\n");
 
-        format!("{}\r\n{}\r\n{}\r\n", session, audio, video)
+        let sdp = format!("{}\r\n{}\r\n{}\r\n", session, audio, video);
+
+        match value.application_section {
+            Some(application_section) => {
+                let application = application_section
+                    .into_iter()
+                    .map(String::from)
+                    .collect::<Vec<_>>()
+                    .join("\r\n");
+                format!("{}{}\r\n", sdp, application)
+            }
+            None => sdp,
+        }
     }
 }
 
 impl SDPResolver {
     const ACCEPTED_VIDEO_CODEC: VideoCodec = VideoCodec::H264;
     const ACCEPTED_AUDIO_CODEC: AudioCodec = AudioCodec::Opus;
-    pub fn new(fingerprint_hash: &str, udp_socket: SocketAddr) -> Self {
+    // Browser offers stay within a few KiB, the limits only keep hostile offers from allocating
+    const MAX_SDP_SIZE: usize = 64 * 1024;
+    const MAX_SDP_LINE_LENGTH: usize = 4 * 1024;
+    // Video tracks past the primary one, enough for a camera and a screen share
+    const MAX_EXTRA_VIDEO_TRACKS: usize = 1;
+    /** Length limits of ICE credentials.
+    https://datatracker.ietf.org/doc/html/rfc8839#section-5.4
+     */
+    pub const MIN_ICE_USERNAME_LENGTH: usize = 4;
+    pub const MAX_ICE_USERNAME_LENGTH: usize = 256;
+    pub const MIN_ICE_PASSWORD_LENGTH: usize = 22;
+    pub const MAX_ICE_PASSWORD_LENGTH: usize = 256;
+    const STREAM_ID_LENGTH: usize = 16;
+    /** Host candidates are advertised in the order of given addresses.
+     * The first one is also used in connection data.
+     */
+    pub fn new(fingerprint_hash: &str, udp_sockets: &[SocketAddr]) -> Self {
+        assert!(
+            !udp_sockets.is_empty(),
+            "Resolver should have at least one address"
+        );
         let fingerprint =
             Fingerprint::try_from(format!("fingerprint:{}", fingerprint_hash).as_str())
                 .expect("Fingerprint should be in form of \"hash-function hash\"");
-        let candidate = Candidate {
-            foundation: "1".to_string(),
-            component_id: 1,
-            priority: 2015363327,
-            connection_address: udp_socket.ip(),
-            port: udp_socket.port(),
-        };
+        let candidates = udp_sockets
+            .iter()
+            .enumerate()
+            .map(|(index, udp_socket)| Candidate {
+                foundation: (index + 1).to_string(),
+                component_id: 1,
+                priority: Candidate::get_priority(
+                    &CandidateType::Host,
+                    Self::get_local_preference(index),
+                    1,
+                ),
+                connection_address: udp_socket.ip(),
+                port: udp_socket.port(),
+                candidate_type: CandidateType::Host,
+                related_address: None,
+            })
+            .collect();
 
         SDPResolver {
             fingerprint,
-            candidate,
+            candidates,
+            ice_username_length: Self::MIN_ICE_USERNAME_LENGTH,
+            ice_password_length: Self::MIN_ICE_PASSWORD_LENGTH,
+            identifier_generator: IdentifierGenerator::Random,
+            allowed_video_codecs: vec![Self::ACCEPTED_VIDEO_CODEC],
         }
     }
+
+    /** Local preference of the address at given index, lower for each subsequent address, so
+     * IPv4 and IPv6 addresses are preferred in the order they're configured in.
+     */
+    fn get_local_preference(index: usize) -> u16 {
+        u16::MAX.saturating_sub(u16::try_from(index).unwrap_or(u16::MAX))
+    }
+
+    /** Whether a fingerprint is in form of "sha-256 hash", with 32 colon-separated hex bytes.
+     * Lets callers reject it up front, rather than have [`SDPResolver::new`] panic on it.
+     */
+    pub fn is_valid_fingerprint(fingerprint_hash: &str) -> bool {
+        Fingerprint::try_from(format!("fingerprint:{}", fingerprint_hash).as_str()).is_ok_and(
+            |fingerprint| {
+                let bytes = fingerprint.hash.split(':').collect::<Vec<_>>();
+                fingerprint.hash_function == HashFunction::SHA256
+                    && bytes.len() == 32
+                    && bytes.iter().all(|byte| {
+                        byte.len() == 2 && byte.chars().all(|char| char.is_ascii_hexdigit())
+                    })
+            },
+        )
+    }
+
+    /** Derives host ICE credentials and SSRCs of negotiated sessions from `seed`.
+     * Meant for tests comparing whole answers, as the identifiers are no longer unpredictable.
+     */
+    pub fn set_identifier_seed(&mut self, seed: u64) {
+        let rng = StdRng::seed_from_u64(seed);
+        self.identifier_generator = IdentifierGenerator::Seeded(Box::new(Mutex::new(rng)));
+    }
+
+    /** Sets the lengths of generated host ICE credentials, the shortest allowed ones by default.
+     * Some clients interoperate better with longer usernames.
+     */
+    pub fn set_ice_credential_lengths(&mut self, username_length: usize, password_length: usize) {
+        assert!(
+            (Self::MIN_ICE_USERNAME_LENGTH..=Self::MAX_ICE_USERNAME_LENGTH)
+                .contains(&username_length),
+            "ICE username length should be between {} and {}",
+            Self::MIN_ICE_USERNAME_LENGTH,
+            Self::MAX_ICE_USERNAME_LENGTH
+        );
+        assert!(
+            (Self::MIN_ICE_PASSWORD_LENGTH..=Self::MAX_ICE_PASSWORD_LENGTH)
+                .contains(&password_length),
+            "ICE password length should be between {} and {}",
+            Self::MIN_ICE_PASSWORD_LENGTH,
+            Self::MAX_ICE_PASSWORD_LENGTH
+        );
+        self.ice_username_length = username_length;
+        self.ice_password_length = password_length;
+    }
+
+    /** Restricts the video codecs offers are answered with, every supported one by default. Without
+     * transcoding, viewers can be held to the codec streamers send. Offers with none of the allowed
+     * codecs are rejected.
+     */
+    pub fn set_video_codec_policy(&mut self, allowed_codecs: &[VideoCodec]) {
+        self.allowed_video_codecs = allowed_codecs.to_vec();
+    }
+
+    fn is_allowed_video_codec(&self, codec: &VideoCodec) -> bool {
+        self.allowed_video_codecs.contains(codec)
+    }
     pub fn accept_stream_offer(&self, raw_data: &str) -> Result<NegotiatedSession, SDPParseError> {
         let sdp = Self::get_sdp(raw_data)?;
-        self.parse_stream_offer(sdp)
+        self.parse_stream_offer(sdp, None)
+    }
+
+    /** Same as [SDPResolver::accept_stream_offer], with the unmet requirements of rejected offers.
+     */
+    pub fn accept_stream_offer_with_diagnostics(
+        &self,
+        raw_data: &str,
+    ) -> Result<NegotiatedSession, OfferDiagnostics> {
+        self.accept_stream_offer(raw_data)
+            .map_err(|error| OfferDiagnostics {
+                failed_requirements: Self::get_sdp(raw_data)
+                    .map(|sdp| self.get_failed_stream_requirements(&sdp))
+                    .unwrap_or_default(),
+                error,
+            })
+    }
+
+    /** Renegotiates the stream of an existing session, e.g. after the streamer switched codecs.
+     * Host ICE credentials and SSRCs are kept as long as the remote ICE username doesn't change.
+     * Changing the remote ICE username is only allowed as part of an ICE restart.
+     */
+    pub fn update_stream_offer(
+        &self,
+        raw_data: &str,
+        session: &NegotiatedSession,
+        is_ice_restart: bool,
+    ) -> Result<NegotiatedSession, SDPParseError> {
+        let sdp = Self::get_sdp(raw_data)?;
+        let remote_username = self.get_ice_credentials(&sdp)
+            .ok_or(SDPParseError::MissingICECredentials)?
+            .remote_username;
+
+        let is_ice_username_changed = remote_username.ne(&session.ice_credentials.remote_username);
+        if is_ice_username_changed && !is_ice_restart {
+            return Err(SDPParseError::ICERestartRequired);
+        }
+
+        let previous_session = (!is_ice_username_changed).then_some(session);
+        self.parse_stream_offer(sdp, previous_session)
+    }
+
+    /** Same as [SDPResolver::update_stream_offer], with the unmet requirements of rejected offers.
+     */
+    pub fn update_stream_offer_with_diagnostics(
+        &self,
+        raw_data: &str,
+        session: &NegotiatedSession,
+        is_ice_restart: bool,
+    ) -> Result<NegotiatedSession, OfferDiagnostics> {
+        self.update_stream_offer(raw_data, session, is_ice_restart)
+            .map_err(|error| OfferDiagnostics {
+                failed_requirements: Self::get_sdp(raw_data)
+                    .map(|sdp| self.get_failed_stream_requirements(&sdp))
+                    .unwrap_or_default(),
+                error,
+            })
     }
 
     pub fn accept_viewer_offer(
@@ -125,10 +816,60 @@ impl SDPResolver {
         self.parse_viewer_offer(sdp, streamer_session)
     }
 
+    /** Same as [SDPResolver::accept_viewer_offer], with the unmet requirements of rejected offers.
+     */
+    pub fn accept_viewer_offer_with_diagnostics(
+        &self,
+        raw_data: &str,
+        streamer_session: &NegotiatedSession,
+    ) -> Result<NegotiatedSession, OfferDiagnostics> {
+        self.accept_viewer_offer(raw_data, streamer_session)
+            .map_err(|error| OfferDiagnostics {
+                failed_requirements: Self::get_sdp(raw_data)
+                    .map(|sdp| self.get_failed_viewer_requirements(&sdp, streamer_session))
+                    .unwrap_or_default(),
+                error,
+            })
+    }
+
+    /** Advertises the public address of the server, as seen from behind NAT, next to the host candidates.
+     */
+    pub fn add_server_reflexive_candidate(&mut self, public_address: SocketAddr) {
+        let host_candidate = &self.candidates[0];
+        let related_address =
+            SocketAddr::new(host_candidate.connection_address, host_candidate.port);
+
+        self.candidates.push(Candidate {
+            foundation: (self.candidates.len() + 1).to_string(),
+            component_id: 1,
+            // Shares local preference with the host candidate it's related to
+            priority: Candidate::get_priority(
+                &CandidateType::ServerReflexive,
+                Self::get_local_preference(0),
+                1,
+            ),
+            connection_address: public_address.ip(),
+            port: public_address.port(),
+            candidate_type: CandidateType::ServerReflexive,
+            related_address: Some(related_address),
+        });
+    }
+
+    /** Candidates of the bundled transport, followed by a single end-of-candidates once all of them are listed.
+     * Only the first media section carries them, the other sections share its transport.
+     */
+    fn get_candidate_lines(&self) -> Vec<SDPLine> {
+        self.candidates
+            .iter()
+            .map(|candidate| SDPLine::Attribute(Attribute::Candidate(candidate.clone())))
+            .chain([SDPLine::Attribute(Attribute::EndOfCandidates)])
+            .collect()
+    }
+
     /** Gets ICE credentials from the SDP. Uses session-level credentials if no media-level credentials were provided.
     If media-level credentials were provided, check if they match across media-streams and if so resolve to ICECredentials.
     */
-    fn get_ice_credentials(sdp: &SDP) -> Option<ICECredentials> {
+    fn get_ice_credentials(&self, sdp: &SDP) -> Option<ICECredentials> {
         let get_ice_username = |section: &Vec<SDPLine>| {
             section.iter().find_map(|line| match line {
                 SDPLine::Attribute(attr) => match attr {
@@ -174,53 +915,141 @@ impl SDPResolver {
             return Some(ICECredentials {
                 remote_username: audio_media_username.username.to_string(),
                 remote_password: audio_media_password.password.to_string(),
-                host_username: get_random_string(4),
-                host_password: get_random_string(22),
+                host_username: self
+                    .identifier_generator
+                    .get_random_string(self.ice_username_length),
+                host_password: self
+                    .identifier_generator
+                    .get_random_string(self.ice_password_length),
             });
         }
 
         return Some(ICECredentials {
             remote_username: default_username?.username.to_string(),
             remote_password: default_password?.password.to_string(),
-            host_username: get_random_string(4),
-            host_password: get_random_string(22),
+            host_username: self.identifier_generator.get_random_string(self.ice_username_length),
+            host_password: self.identifier_generator.get_random_string(self.ice_password_length),
         });
     }
 
+    /** Finds the CNAME of the remote media source, if the SDP declares one.
+     */
+    fn get_remote_cname(media: &[SDPLine], remote_ssrc: Option<u32>) -> Option<String> {
+        let remote_ssrc = remote_ssrc?;
+        media.iter().find_map(|item| match item {
+            SDPLine::Attribute(Attribute::MediaSSRC(MediaSSRC {
+                ssrc,
+                source_attribute: SourceAttribute::CNAME(cname),
+            })) if *ssrc == remote_ssrc => Some(cname.clone()),
+            _ => None,
+        })
+    }
+
+    /** Finds the retransmission SSRC paired with the remote media source by an FID SSRC group.
+     */
+    fn get_remote_rtx_ssrc(media: &[SDPLine], remote_ssrc: Option<u32>) -> Option<u32> {
+        let remote_ssrc = remote_ssrc?;
+        media.iter().find_map(|item| match item {
+            SDPLine::Attribute(Attribute::SSRCGroup(SSRCGroup::FlowIdentification {
+                primary_ssrc,
+                retransmission_ssrc,
+            })) if *primary_ssrc == remote_ssrc => Some(*retransmission_ssrc),
+            _ => None,
+        })
+    }
+
+    /** Whether the offer lets header extensions of both forms be mixed within a stream. The flag
+     * usually comes at session level, a media level one applies to the whole bundle as well.
+     */
+    fn is_extmap_mixing_allowed(sdp: &SDP) -> bool {
+        [&sdp.session_section, &sdp.audio_section, &sdp.video_section]
+            .into_iter()
+            .chain(&sdp.extra_video_sections)
+            .flatten()
+            .any(|line| line.eq(&SDPLine::Attribute(Attribute::ExtMapAllowMixed)))
+    }
+
+    /** Without extmap-allow-mixed, the header extensions of a stream have to share one form, see
+     * RFC 8285 6. Extensions with IDs only the two-byte form fits are left out next to one-byte
+     * ones.
+     */
+    fn use_single_extension_form(video_session: &mut VideoSession) {
+        let rid_extension_id = video_session
+            .simulcast
+            .as_ref()
+            .map(|simulcast| simulcast.rid_extension_id);
+        let has_one_byte_extension = [video_session.abs_send_time_extension_id, rid_extension_id]
+            .into_iter()
+            .flatten()
+            .any(ExtMap::is_one_byte_id);
+        if !has_one_byte_extension {
+            return;
+        }
+
+        video_session.abs_send_time_extension_id = video_session
+            .abs_send_time_extension_id
+            .filter(|id| ExtMap::is_one_byte_id(*id));
+        video_session.simulcast = video_session
+            .simulcast
+            .take()
+            .filter(|simulcast| ExtMap::is_one_byte_id(simulcast.rid_extension_id));
+    }
+
+    fn get_extension_id(media: &[SDPLine], uri: &str) -> Option<u8> {
+        media.iter().find_map(|item| match item {
+            SDPLine::Attribute(Attribute::ExtMap(ext_map)) if ext_map.uri == uri => Some(ext_map.id),
+            _ => None,
+        })
+    }
+
+    /** Finds the simulcast layers the streamer sends. Simulcast is only usable when packets carry the RID
+     * header extension, layers without a matching sending RID are left out.
+     */
+    fn get_simulcast_session(video_media: &[SDPLine]) -> Option<SimulcastSession> {
+        let simulcast = video_media.iter().find_map(|item| match item {
+            SDPLine::Attribute(Attribute::Simulcast(simulcast))
+                if simulcast.direction == RidDirection::Send =>
+            {
+                Some(simulcast)
+            }
+            _ => None,
+        })?;
+        let rid_extension_id = Self::get_extension_id(video_media, RTP_STREAM_ID_EXTENSION_URI)?;
+
+        let rids = simulcast
+            .rids
+            .iter()
+            .filter(|rid| {
+                video_media.contains(&SDPLine::Attribute(Attribute::Rid(Rid {
+                    id: rid.to_string(),
+                    direction: RidDirection::Send,
+                })))
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        if rids.is_empty() {
+            return None;
+        }
+
+        Some(SimulcastSession {
+            rids,
+            rid_extension_id,
+        })
+    }
+
     /** Get AudioSession based on audio-media-level SDPLines. Resolve codecs based on supported streamer codecs.
      */
     fn get_streamer_audio_session(
+        &self,
         audio_media_section: &Vec<SDPLine>,
     ) -> Result<AudioSession, SDPParseError> {
         // Check if audio stream is demuxed
-        let is_rtcp_demuxed = audio_media_section
-            .iter()
-            .find_map(|item| match item {
-                SDPLine::Attribute(attr) => match attr {
-                    Attribute::RTCPMux => Some(()),
-                    _ => None,
-                },
-                _ => None,
-            })
-            .is_some();
-
-        if !is_rtcp_demuxed {
+        if !is_rtcp_muxed(audio_media_section) {
             return Err(SDPParseError::DemuxRequired);
         }
 
-        // Check if stream is sendonly
-        let is_sendonly_direction = audio_media_section
-            .iter()
-            .find_map(|item| match item {
-                SDPLine::Attribute(attr) => match attr {
-                    Attribute::SendOnly => Some(()),
-                    _ => None,
-                },
-                _ => None,
-            })
-            .is_some();
-
-        if !is_sendonly_direction {
+        // Check if stream sends media, either sendonly or sendrecv
+        if !is_sending(audio_media_section) {
             return Err(SDPParseError::InvalidStreamDirection);
         }
 
@@ -232,65 +1061,31 @@ impl SDPResolver {
             _ => None,
         });
 
-        let accepted_codec_payload_number = audio_media_section
-            .iter()
-            .find_map(|item| match item {
-                SDPLine::Attribute(attr) => match attr {
-                    Attribute::RTPMap(rtpmap) => {
-                        if rtpmap
-                            .codec
-                            .eq(&MediaCodec::Audio(Self::ACCEPTED_AUDIO_CODEC))
-                        {
-                            return Some(rtpmap.payload_number);
-                        }
-                        None
-                    }
-                    _ => None,
-                },
-                _ => None,
-            })
-            .ok_or(SDPParseError::UnsupportedMediaCodecs)?;
+        let accepted_codec = MediaCodec::Audio(Self::ACCEPTED_AUDIO_CODEC);
+        let accepted_codec_payload_number =
+            get_codec_payload_number(audio_media_section, &accepted_codec)
+                .ok_or(SDPParseError::UnsupportedMediaCodecs)?;
 
         Ok(AudioSession {
             codec: Self::ACCEPTED_AUDIO_CODEC,
             payload_number: accepted_codec_payload_number,
             remote_ssrc: remote_audio_ssrc,
-            host_ssrc: get_random_ssrc(),
+            remote_cname: Self::get_remote_cname(audio_media_section, remote_audio_ssrc),
+            host_ssrc: self.identifier_generator.get_random_ssrc(),
         })
     }
 
     fn get_streamer_video_session(
+        &self,
         video_media: &Vec<SDPLine>,
     ) -> Result<VideoSession, SDPParseError> {
         // Check if stream is demuxed
-        let is_rtcp_demuxed = video_media
-            .iter()
-            .find_map(|item| match item {
-                SDPLine::Attribute(attr) => match attr {
-                    Attribute::RTCPMux => Some(()),
-                    _ => None,
-                },
-                _ => None,
-            })
-            .is_some();
-
-        if !is_rtcp_demuxed {
+        if !is_rtcp_muxed(video_media) {
             return Err(SDPParseError::DemuxRequired);
         }
 
-        // Check if stream is sendonly
-        let is_sendonly_direction = video_media
-            .iter()
-            .find_map(|item| match item {
-                SDPLine::Attribute(attr) => match attr {
-                    Attribute::SendOnly => Some(()),
-                    _ => None,
-                },
-                _ => None,
-            })
-            .is_some();
-
-        if !is_sendonly_direction {
+        // Check if stream sends media, either sendonly or sendrecv
+        if !is_sending(video_media) {
             return Err(SDPParseError::InvalidStreamDirection);
         }
 
@@ -305,48 +1100,49 @@ impl SDPResolver {
 
         // Check if supported codec is present
         // todo Pick highest available video capabilities
-        let accepted_codec_payload_number = video_media
-            .iter()
-            .find_map(|item| match item {
-                SDPLine::Attribute(attr) => match attr {
-                    Attribute::RTPMap(rtpmap) => {
-                        if rtpmap
-                            .codec
-                            .eq(&MediaCodec::Video(Self::ACCEPTED_VIDEO_CODEC))
-                        {
-                            return Some(rtpmap.payload_number);
-                        }
-                        None
-                    }
-                    _ => None,
-                },
-                _ => None,
-            })
-            .ok_or(SDPParseError::UnsupportedMediaCodecs)?;
+        let accepted_codec = MediaCodec::Video(Self::ACCEPTED_VIDEO_CODEC);
+        let accepted_codec_payload_number =
+            get_codec_payload_number(video_media, &accepted_codec)
+                .filter(|_| self.is_allowed_video_codec(&Self::ACCEPTED_VIDEO_CODEC))
+                .ok_or(SDPParseError::UnsupportedMediaCodecs)?;
 
         // Get FMTP value
-        let video_capabilities = video_media
-            .iter()
-            .find_map(|item| match item {
-                SDPLine::Attribute(attr) => match attr {
-                    Attribute::FMTP(fmtp) => {
-                        if fmtp.payload_number.eq(&accepted_codec_payload_number) {
-                            return Some(fmtp.format_capability.clone());
-                        }
-                        None
-                    }
-                    _ => None,
-                },
-                _ => None,
-            })
-            .ok_or(SDPParseError::MissingVideoCapabilities)?;
+        let video_capabilities =
+            get_format_capability(video_media, accepted_codec_payload_number)
+                .ok_or(SDPParseError::MissingVideoCapabilities)?;
 
         Ok(VideoSession {
             codec: Self::ACCEPTED_VIDEO_CODEC,
             capabilities: video_capabilities,
             payload_number: accepted_codec_payload_number,
             remote_ssrc: remote_video_ssrc,
-            host_ssrc: get_random_ssrc(),
+            remote_cname: Self::get_remote_cname(video_media, remote_video_ssrc),
+            remote_rtx_ssrc: Self::get_remote_rtx_ssrc(video_media, remote_video_ssrc),
+            host_ssrc: self.identifier_generator.get_random_ssrc(),
+            simulcast: Self::get_simulcast_session(video_media),
+            abs_send_time_extension_id: Self::get_extension_id(
+                video_media,
+                ABS_SEND_TIME_EXTENSION_URI,
+            ),
+            transport_cc_extension_id: None,
+            fir_supported: has_feedback(video_media, accepted_codec_payload_number, "ccm fir"),
+        })
+    }
+
+    /** Video session of a track sent next to the primary one. Its packets are told apart by SSRC, so
+     * the offer has to announce it. Simulcast and bandwidth estimation apply to the primary track only.
+     */
+    fn get_streamer_extra_video_session(
+        &self,
+        video_media: &Vec<SDPLine>,
+    ) -> Result<VideoSession, SDPParseError> {
+        let video_session = self.get_streamer_video_session(video_media)?;
+        video_session.require_remote_ssrc()?;
+
+        Ok(VideoSession {
+            simulcast: None,
+            abs_send_time_extension_id: None,
+            ..video_session
         })
     }
 
@@ -366,102 +1162,270 @@ impl SDPResolver {
             })
             .ok_or(SDPParseError::BundleRequired)?;
 
-        let expected_audio_mid = MediaID {
-            id: bundle_group
-                .iter()
-                .nth(0)
-                .ok_or(SDPParseError::MalformedSDPLine)?
-                .to_string(),
-        };
+        let audio_mid = get_media_id(&sdp.audio_section).ok_or(SDPParseError::InvalidMediaID)?;
+        let video_mid = get_media_id(&sdp.video_section).ok_or(SDPParseError::InvalidMediaID)?;
+        let extra_video_mids = sdp
+            .extra_video_sections
+            .iter()
+            .map(|video_media| get_media_id(video_media))
+            .collect::<Option<Vec<_>>>()
+            .ok_or(SDPParseError::InvalidMediaID)?;
+        let application_mid = sdp
+            .application_section
+            .as_ref()
+            .and_then(|application_media| get_media_id(application_media));
+        let section_mids = [Some(audio_mid), Some(video_mid), application_mid]
+            .into_iter()
+            .flatten()
+            .chain(extra_video_mids.iter().copied())
+            .collect::<Vec<_>>();
 
-        let expected_video_mid = MediaID {
-            id: bundle_group
+        // Bundle entries may come in any order, as long as each of them names a media section
+        let is_bundle_resolved = bundle_group.iter().all(|bundled_mid| {
+            section_mids
                 .iter()
-                .nth(1)
-                .ok_or(SDPParseError::MalformedSDPLine)?
-                .to_string(),
-        };
+                .any(|section_mid| section_mid.id.eq(bundled_mid))
+        });
 
-        let actual_audio_id = sdp
-            .audio_section
+        // Audio and every video track have to share the bundled transport
+        let is_media_bundled = [audio_mid, video_mid]
             .iter()
-            .find_map(|item| match item {
-                SDPLine::Attribute(attr) => match attr {
-                    Attribute::MediaID(media_id) => Some(media_id),
-                    _ => None,
-                },
-                _ => None,
-            })
-            .ok_or(SDPParseError::InvalidMediaID)?;
+            .chain(&extra_video_mids)
+            .all(|media_mid| bundle_group.contains(&media_mid.id));
 
-        if expected_audio_mid.ne(actual_audio_id) {
+        if !is_bundle_resolved || !is_media_bundled {
             return Err(SDPParseError::InvalidMediaID);
         }
 
-        let actual_video_id = sdp
-            .video_section
+        Ok((audio_mid.clone(), video_mid.clone()))
+    }
+
+    /** Answers a data channel m-line with port 0, declining it without breaking BUNDLE negotiation.
+     * The declined media is left out of the answer's BUNDLE group.
+     */
+    fn get_declined_application_section(
+        &self,
+        application_media: &[SDPLine],
+    ) -> Result<Vec<SDPLine>, SDPParseError> {
+        let media_id = get_media_id(application_media)
+            .cloned()
+            .ok_or(SDPParseError::InvalidMediaID)?;
+
+        Ok(vec![
+            SDPLine::MediaDescription(MediaDescription {
+                transport_port: 0,
+                media_type: MediaType::Application,
+                transport_protocol: MediaTransportProtocol::DtlsSctp,
+                media_format_description: vec![],
+            }),
+            SDPLine::ConnectionData(ConnectionData {
+                ip: self.candidates[0].connection_address,
+            }),
+            SDPLine::Attribute(Attribute::MediaID(media_id)),
+        ])
+    }
+
+    /** Answers a video m-line with port 0, for viewers offering more video tracks than the streamer sends.
+     * The declined media is left out of the answer's BUNDLE group.
+     */
+    fn get_declined_video_section(
+        &self,
+        video_media: &[SDPLine],
+        media_id: MediaID,
+    ) -> Vec<SDPLine> {
+        let media_format_description = video_media
             .iter()
             .find_map(|item| match item {
-                SDPLine::Attribute(attr) => match attr {
-                    Attribute::MediaID(media_id) => Some(media_id),
-                    _ => None,
-                },
+                SDPLine::MediaDescription(media) => Some(media.media_format_description.clone()),
                 _ => None,
             })
-            .ok_or(SDPParseError::InvalidMediaID)?;
+            .unwrap_or_default();
 
-        if expected_video_mid.ne(actual_video_id) {
-            return Err(SDPParseError::InvalidMediaID);
+        vec![
+            SDPLine::MediaDescription(MediaDescription {
+                transport_port: 0,
+                media_type: MediaType::Video,
+                transport_protocol: MediaTransportProtocol::DtlsSrtp,
+                media_format_description,
+            }),
+            SDPLine::ConnectionData(ConnectionData {
+                ip: self.candidates[0].connection_address,
+            }),
+            SDPLine::Attribute(Attribute::Inactive),
+            SDPLine::Attribute(Attribute::MediaID(media_id)),
+        ]
+    }
+
+    fn get_failed_session_requirements(&self, sdp: &SDP) -> Vec<(OfferSection, OfferRequirement)> {
+        let mut failed_requirements = vec![];
+        if matches!(
+            Self::get_media_ids(sdp),
+            Err(SDPParseError::BundleRequired)
+        ) {
+            failed_requirements.push((OfferSection::Session, OfferRequirement::Bundle));
+        }
+        if self.get_ice_credentials(sdp).is_none() {
+            failed_requirements.push((OfferSection::Session, OfferRequirement::ICECredentials));
+        }
+        failed_requirements
+    }
+
+    fn get_failed_stream_requirements(&self, sdp: &SDP) -> Vec<(OfferSection, OfferRequirement)> {
+        let mut failed_requirements = self.get_failed_session_requirements(sdp);
+        if is_passive_dtls_role(&sdp.session_section) != Some(false) {
+            failed_requirements.push((OfferSection::Session, OfferRequirement::DTLSRole));
         }
 
-        return Ok((expected_audio_mid, expected_video_mid));
+        let audio_codec = MediaCodec::Audio(Self::ACCEPTED_AUDIO_CODEC);
+        let video_codec = MediaCodec::Video(Self::ACCEPTED_VIDEO_CODEC);
+        for (section, media, is_supported_codec) in [
+            (
+                OfferSection::Audio,
+                &sdp.audio_section,
+                get_codec_payload_number(&sdp.audio_section, &audio_codec).is_some(),
+            ),
+            (
+                OfferSection::Video,
+                &sdp.video_section,
+                get_codec_payload_number(&sdp.video_section, &video_codec)
+                    .filter(|_| self.is_allowed_video_codec(&Self::ACCEPTED_VIDEO_CODEC))
+                    .and_then(|payload_number| {
+                        get_format_capability(&sdp.video_section, payload_number)
+                    })
+                    .is_some(),
+            ),
+        ] {
+            if !is_rtcp_muxed(media) {
+                failed_requirements.push((section, OfferRequirement::RTCPMux));
+            }
+            if !is_sending(media) {
+                failed_requirements.push((section, OfferRequirement::Direction));
+            }
+            if !is_supported_codec {
+                failed_requirements.push((section, OfferRequirement::SupportedCodec));
+            }
+        }
+        failed_requirements
+    }
+
+    fn get_failed_viewer_requirements(
+        &self,
+        sdp: &SDP,
+        streamer_session: &NegotiatedSession,
+    ) -> Vec<(OfferSection, OfferRequirement)> {
+        let mut failed_requirements = self.get_failed_session_requirements(sdp);
+
+        let audio_codec = MediaCodec::Audio(streamer_session.audio_session.codec.clone());
+        for (section, media, is_supported_codec) in [
+            (
+                OfferSection::Audio,
+                &sdp.audio_section,
+                get_codec_payload_number(&sdp.audio_section, &audio_codec).is_some(),
+            ),
+            (
+                OfferSection::Video,
+                &sdp.video_section,
+                self.resolve_viewer_video_payload_number(
+                    &sdp.video_section,
+                    &streamer_session.video_session,
+                )
+                .is_some(),
+            ),
+        ] {
+            if !is_rtcp_muxed(media) {
+                failed_requirements.push((section, OfferRequirement::RTCPMux));
+            }
+            if is_passive_dtls_role(media) != Some(false) {
+                failed_requirements.push((section, OfferRequirement::DTLSRole));
+            }
+            if !is_receiving(media) {
+                failed_requirements.push((section, OfferRequirement::Direction));
+            }
+            if !is_supported_codec {
+                failed_requirements.push((section, OfferRequirement::SupportedCodec));
+            }
+        }
+        failed_requirements
     }
 
-    fn parse_stream_offer(&self, sdp_offer: SDP) -> Result<NegotiatedSession, SDPParseError> {
+    /** Host ICE credentials, SSRCs and stream ID of the previous session, if given, are kept.
+     */
+    fn parse_stream_offer(
+        &self,
+        sdp_offer: SDP,
+        previous_session: Option<&NegotiatedSession>,
+    ) -> Result<NegotiatedSession, SDPParseError> {
         // Check if stream is bundled and get media stream ids
         let (audio_mid, video_mid) = Self::get_media_ids(&sdp_offer)?;
+        let extra_video_mids = sdp_offer
+            .extra_video_sections
+            .iter()
+            .filter_map(|video_media| get_media_id(video_media).cloned())
+            .collect::<Vec<_>>();
 
-        let ice_credentials =
-            Self::get_ice_credentials(&sdp_offer).ok_or(SDPParseError::MissingICECredentials)?;
-        let audio_session = Self::get_streamer_audio_session(&sdp_offer.audio_section)?;
-        let video_session = Self::get_streamer_video_session(&sdp_offer.video_section)?;
-
-        let is_passive_dtls_role = sdp_offer
-            .session_section
+        let mut ice_credentials =
+            self.get_ice_credentials(&sdp_offer).ok_or(SDPParseError::MissingICECredentials)?;
+        let mut audio_session = self.get_streamer_audio_session(&sdp_offer.audio_section)?;
+        let mut video_session = self.get_streamer_video_session(&sdp_offer.video_section)?;
+        let is_extmap_mixing_allowed = Self::is_extmap_mixing_allowed(&sdp_offer);
+        if !is_extmap_mixing_allowed {
+            Self::use_single_extension_form(&mut video_session);
+        }
+        let mut extra_video_sessions = sdp_offer
+            .extra_video_sections
             .iter()
-            .find_map(|item| match item {
-                SDPLine::Attribute(attr) => match attr {
-                    Attribute::Setup(setup) => match setup {
-                        Setup::Passive => Some(true),
-                        _ => Some(false),
-                    },
-                    _ => None,
-                },
-                _ => None,
-            })
+            .map(|video_media| self.get_streamer_extra_video_session(video_media))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut stream_id = self
+            .identifier_generator
+            .get_random_string(Self::STREAM_ID_LENGTH);
+
+        if let Some(previous_session) = previous_session {
+            ice_credentials.host_username = previous_session.ice_credentials.host_username.clone();
+            ice_credentials.host_password = previous_session.ice_credentials.host_password.clone();
+            audio_session.host_ssrc = previous_session.audio_session.host_ssrc;
+            video_session.host_ssrc = previous_session.video_session.host_ssrc;
+            for (video_session, previous_video_session) in extra_video_sessions
+                .iter_mut()
+                .zip(&previous_session.extra_video_sessions)
+            {
+                video_session.host_ssrc = previous_video_session.host_ssrc;
+            }
+            stream_id = previous_session.stream_id.clone();
+        }
+
+        let is_passive_dtls_role = is_passive_dtls_role(&sdp_offer.session_section)
             .ok_or(SDPParseError::MalformedSDPLine)?;
 
         if is_passive_dtls_role {
             return Err(SDPParseError::InvalidDTLSRole);
         }
 
-        let session_section = vec![
+        // Each renegotiated answer is a new version of the session, see RFC 3264 8
+        let session_version = previous_session
+            .and_then(|previous_session| previous_session.sdp_answer.get_session_version())
+            .map_or(0, |session_version| session_version + 1);
+
+        let mut session_section = vec![
             SDPLine::ProtocolVersion("0".to_string()),
             SDPLine::Originator(Originator {
                 username: HOST_CNAME.to_string(),
-                ip_addr: self.candidate.connection_address.clone(),
-                session_version: "0".to_string(),
-                session_id: "3767197920".to_string(), // todo Handle unique NTP-like timestamps
+                ip_addr: self.candidates[0].connection_address.clone(),
+                session_version,
+                session_id: 3767197920, // todo Handle unique NTP-like timestamps
             }),
             SDPLine::SessionName(HOST_CNAME.to_string()),
             SDPLine::SessionTime(SessionTime {
                 start_time: 0,
                 end_time: 0,
             }),
-            SDPLine::Attribute(Attribute::MediaGroup(MediaGroup::Bundle(vec![
-                audio_mid.id.clone(),
-                video_mid.id.clone(),
-            ]))),
+            SDPLine::Attribute(Attribute::MediaGroup(MediaGroup::Bundle(
+                [&audio_mid, &video_mid]
+                    .into_iter()
+                    .chain(&extra_video_mids)
+                    .map(|media_id| media_id.id.clone())
+                    .collect(),
+            ))),
             SDPLine::Attribute(Attribute::ICEUsername(ICEUsername {
                 username: ice_credentials.host_username.clone(),
             })),
@@ -475,45 +1439,95 @@ impl SDPResolver {
             SDPLine::Attribute(Attribute::Fingerprint(self.fingerprint.clone())),
             SDPLine::Attribute(Attribute::Setup(Setup::Passive)),
         ];
+        if is_extmap_mixing_allowed {
+            session_section.push(SDPLine::Attribute(Attribute::ExtMapAllowMixed));
+        }
 
-        let audio_section = vec![
-            SDPLine::MediaDescription(MediaDescription {
-                transport_port: self.candidate.port as usize,
-                media_type: MediaType::Audio,
-                transport_protocol: MediaTransportProtocol::DtlsSrtp,
-                media_format_description: vec![audio_session.payload_number],
-            }),
-            SDPLine::ConnectionData(ConnectionData {
-                ip: self.candidate.connection_address,
-            }),
-            SDPLine::Attribute(Attribute::ReceiveOnly),
-            SDPLine::Attribute(Attribute::RTCPMux),
-            SDPLine::Attribute(Attribute::MediaID(audio_mid)),
-            SDPLine::Attribute(Attribute::Candidate(self.candidate.clone())),
-            SDPLine::Attribute(Attribute::EndOfCandidates),
-            SDPLine::Attribute(Attribute::RTPMap(RTPMap {
-                codec: MediaCodec::Audio(audio_session.codec.clone()),
-                payload_number: audio_session.payload_number,
-            })),
-            SDPLine::Attribute(Attribute::MediaSSRC(MediaSSRC {
-                ssrc: audio_session.host_ssrc,
-                source_attribute: SourceAttribute::CNAME(HOST_CNAME.to_string()),
-            })),
-        ];
+        let audio_section = [
+            vec![
+                SDPLine::MediaDescription(MediaDescription {
+                    transport_port: self.candidates[0].port as usize,
+                    media_type: MediaType::Audio,
+                    transport_protocol: MediaTransportProtocol::DtlsSrtp,
+                    media_format_description: vec![audio_session.payload_number],
+                }),
+                SDPLine::ConnectionData(ConnectionData {
+                    ip: self.candidates[0].connection_address,
+                }),
+                SDPLine::Attribute(Attribute::ReceiveOnly),
+                SDPLine::Attribute(Attribute::RTCPMux),
+                SDPLine::Attribute(Attribute::MediaID(audio_mid)),
+            ],
+            self.get_candidate_lines(),
+            vec![
+                SDPLine::Attribute(Attribute::RTPMap(RTPMap {
+                    codec: MediaCodec::Audio(audio_session.codec.clone()),
+                    payload_number: audio_session.payload_number,
+                })),
+                SDPLine::Attribute(Attribute::MediaSSRC(MediaSSRC {
+                    ssrc: audio_session.host_ssrc,
+                    source_attribute: SourceAttribute::CNAME(HOST_CNAME.to_string()),
+                })),
+            ],
+        ]
+        .concat();
+
+        let video_section =
+            self.get_streamer_video_section(&video_session, video_mid, &sdp_offer.video_section);
+        let extra_video_sections = extra_video_sessions
+            .iter()
+            .zip(extra_video_mids)
+            .zip(&sdp_offer.extra_video_sections)
+            .map(|((video_session, video_mid), video_media)| {
+                self.get_streamer_video_section(video_session, video_mid, video_media)
+            })
+            .collect::<Vec<_>>();
 
-        let video_section = vec![
+        let application_section = sdp_offer
+            .application_section
+            .as_ref()
+            .map(|application_media| self.get_declined_application_section(application_media))
+            .transpose()?;
+
+        let sdp_answer = SDP {
+            session_section,
+            audio_section,
+            video_section,
+            extra_video_sections,
+            application_section,
+        };
+
+        Ok(NegotiatedSession {
+            ice_credentials,
+            audio_session,
+            video_session,
+            extra_video_sessions,
+            sdp_answer,
+            stream_id,
+        })
+    }
+
+    /** Answer to a streamer's video media, receiving the track of the given session.
+     */
+    fn get_streamer_video_section(
+        &self,
+        video_session: &VideoSession,
+        media_id: MediaID,
+        offer_media: &[SDPLine],
+    ) -> Vec<SDPLine> {
+        let mut video_section = vec![
             SDPLine::MediaDescription(MediaDescription {
-                transport_port: self.candidate.port as usize,
+                transport_port: self.candidates[0].port as usize,
                 media_type: MediaType::Video,
                 transport_protocol: MediaTransportProtocol::DtlsSrtp,
                 media_format_description: vec![video_session.payload_number],
             }),
             SDPLine::ConnectionData(ConnectionData {
-                ip: self.candidate.connection_address,
+                ip: self.candidates[0].connection_address,
             }),
             SDPLine::Attribute(Attribute::ReceiveOnly),
             SDPLine::Attribute(Attribute::RTCPMux),
-            SDPLine::Attribute(Attribute::MediaID(video_mid)),
+            SDPLine::Attribute(Attribute::MediaID(media_id)),
             SDPLine::Attribute(Attribute::RTPMap(RTPMap {
                 codec: MediaCodec::Video(video_session.codec.clone()),
                 payload_number: video_session.payload_number,
@@ -527,94 +1541,66 @@ impl SDPResolver {
                 format_capability: video_session.capabilities.clone(),
             })),
         ];
-
-        let sdp_answer = SDP {
-            session_section,
-            audio_section,
-            video_section,
-        };
-
-        Ok(NegotiatedSession {
-            ice_credentials,
-            audio_session,
-            video_session,
-            sdp_answer,
-        })
+        // Lost video packets, keyframes and lower bitrates are requested only from streamers able to respond to them
+        for feedback_type in ["nack", "nack pli", "ccm fir", "goog-remb"] {
+            let feedback = SDPLine::Attribute(Attribute::RTCPFeedback(RTCPFeedback {
+                payload_number: video_session.payload_number,
+                feedback_type: feedback_type.to_string(),
+            }));
+            if offer_media.contains(&feedback) {
+                video_section.push(feedback);
+            }
+        }
+        if let Some(id) = video_session.abs_send_time_extension_id {
+            video_section.push(SDPLine::Attribute(Attribute::ExtMap(ExtMap {
+                id,
+                uri: ABS_SEND_TIME_EXTENSION_URI.to_string(),
+            })));
+        }
+        if let Some(simulcast) = &video_session.simulcast {
+            video_section.push(SDPLine::Attribute(Attribute::ExtMap(ExtMap {
+                id: simulcast.rid_extension_id,
+                uri: RTP_STREAM_ID_EXTENSION_URI.to_string(),
+            })));
+            video_section.extend(simulcast.rids.iter().map(|rid| {
+                SDPLine::Attribute(Attribute::Rid(Rid {
+                    id: rid.clone(),
+                    direction: RidDirection::Receive,
+                }))
+            }));
+            video_section.push(SDPLine::Attribute(Attribute::Simulcast(Simulcast {
+                direction: RidDirection::Receive,
+                rids: simulcast.rids.clone(),
+            })));
+        }
+        video_section
     }
 
     fn get_viewer_audio_session(
+        &self,
         audio_media: &Vec<SDPLine>,
         streamer_session: &AudioSession,
     ) -> Result<AudioSession, SDPParseError> {
         // Check if audio stream is demuxed
-        let is_rtcp_demuxed = audio_media
-            .iter()
-            .find_map(|item| match item {
-                SDPLine::Attribute(attr) => match attr {
-                    Attribute::RTCPMux => Some(()),
-                    _ => None,
-                },
-                _ => None,
-            })
-            .is_some();
-
-        if !is_rtcp_demuxed {
+        if !is_rtcp_muxed(audio_media) {
             return Err(SDPParseError::DemuxRequired);
         }
 
-        let is_passive_dtls_role = audio_media
-            .iter()
-            .find_map(|item| match item {
-                SDPLine::Attribute(attr) => match attr {
-                    Attribute::Setup(setup) => match setup {
-                        Setup::Passive => Some(true),
-                        _ => Some(false),
-                    },
-                    _ => None,
-                },
-                _ => None,
-            })
-            .ok_or(SDPParseError::MalformedSDPLine)?;
+        let is_passive_dtls_role =
+            is_passive_dtls_role(audio_media).ok_or(SDPParseError::MalformedSDPLine)?;
 
         if is_passive_dtls_role {
             return Err(SDPParseError::InvalidDTLSRole);
         }
 
-        // Check if stream is recvonly
-        let is_recvonly_direction = audio_media
-            .iter()
-            .find_map(|item| match item {
-                SDPLine::Attribute(attr) => match attr {
-                    Attribute::ReceiveOnly => Some(()),
-                    _ => None,
-                },
-                _ => None,
-            })
-            .is_some();
-
-        if !is_recvonly_direction {
+        // Check if stream receives media, either recvonly or sendrecv
+        if !is_receiving(audio_media) {
             return Err(SDPParseError::InvalidStreamDirection);
         }
 
-        let legal_audio_codec = &streamer_session.codec;
+        let legal_audio_codec = MediaCodec::Audio(streamer_session.codec.clone());
 
-        let resolved_payload_number = audio_media
-            .iter()
-            .find_map(|item| match item {
-                SDPLine::Attribute(attr) => match attr {
-                    Attribute::RTPMap(rtpmap) => {
-                        if rtpmap
-                            .codec
-                            .eq(&MediaCodec::Audio(legal_audio_codec.clone()))
-                        {
-                            return Some(rtpmap.payload_number);
-                        }
-                        None
-                    }
-                    _ => None,
-                },
-                _ => None,
-            })
+        let resolved_payload_number = get_codec_payload_number(audio_media, &legal_audio_codec)
             .ok_or(SDPParseError::UnsupportedMediaCodecs)?;
 
         let remote_ssrc = audio_media.iter().find_map(|item| match item {
@@ -626,72 +1612,29 @@ impl SDPResolver {
         });
 
         Ok(AudioSession {
-            codec: legal_audio_codec.clone(),
+            codec: streamer_session.codec.clone(),
             payload_number: resolved_payload_number,
-            host_ssrc: get_random_ssrc(),
+            host_ssrc: self.identifier_generator.get_random_ssrc(),
+            remote_cname: Self::get_remote_cname(audio_media, remote_ssrc),
             remote_ssrc,
         })
     }
 
-    fn get_viewer_video_session(
-        video_media: &Vec<SDPLine>,
+    /** Payload number of the viewer video format matching both the streamer's codec and its capabilities.
+     */
+    fn resolve_viewer_video_payload_number(
+        &self,
+        video_media: &[SDPLine],
         streamer_session: &VideoSession,
-    ) -> Result<VideoSession, SDPParseError> {
-        // Check if stream is demuxed
-        let is_rtcp_demuxed = video_media
-            .iter()
-            .find_map(|item| match item {
-                SDPLine::Attribute(attr) => match attr {
-                    Attribute::RTCPMux => Some(()),
-                    _ => None,
-                },
-                _ => None,
-            })
-            .is_some();
-
-        if !is_rtcp_demuxed {
-            return Err(SDPParseError::DemuxRequired);
-        }
-
-        let is_passive_dtls_role = video_media
-            .iter()
-            .find_map(|item| match item {
-                SDPLine::Attribute(attr) => match attr {
-                    Attribute::Setup(setup) => match setup {
-                        Setup::Passive => Some(true),
-                        _ => Some(false),
-                    },
-                    _ => None,
-                },
-                _ => None,
-            })
-            .ok_or(SDPParseError::MalformedSDPLine)?;
-
-        if is_passive_dtls_role {
-            return Err(SDPParseError::InvalidDTLSRole);
-        }
-
-        // Check if stream is recvonly
-        let is_recvonly_direction = video_media
-            .iter()
-            .find_map(|item| match item {
-                SDPLine::Attribute(attr) => match attr {
-                    Attribute::ReceiveOnly => Some(()),
-                    _ => None,
-                },
-                _ => None,
-            })
-            .is_some();
-
-        if !is_recvonly_direction {
-            return Err(SDPParseError::InvalidStreamDirection);
-        }
-
+    ) -> Option<usize> {
         /*
         Here we start to look for a payload number that matches both streamer video codec and streamer video capabilities
          */
-        // Only the negotiated streamer video codec is considered a legal option
+        // Only the negotiated streamer video codec is considered a legal option, as long as the policy allows it
         let legal_video_codec = &streamer_session.codec;
+        if !self.is_allowed_video_codec(legal_video_codec) {
+            return None;
+        }
 
         // Get all payload numbers matching legal Video codec
         let available_payload_numbers = video_media
@@ -718,7 +1661,7 @@ impl SDPResolver {
 
         // Filter out all FMTPs not matching the available payload numbers and then look for one matching the legal FMTP
         // The filter could be skipped, but then we have no guarantee that this FMTP actually points to the proper codec
-        let resolved_payload_number = video_media
+        video_media
             .iter()
             .filter_map(|item| match item {
                 SDPLine::Attribute(attr) => match attr {
@@ -733,12 +1676,39 @@ impl SDPResolver {
                 _ => None,
             })
             .find_map(|fmtp| {
-                if fmtp.format_capability.eq(legal_video_fmtp) {
+                if is_compatible_fmtp(legal_video_fmtp, &fmtp.format_capability) {
                     return Some(fmtp.payload_number);
                 }
                 None
             })
-            .ok_or(SDPParseError::UnsupportedMediaCodecs)?;
+    }
+
+    fn get_viewer_video_session(
+        &self,
+        video_media: &Vec<SDPLine>,
+        streamer_session: &VideoSession,
+    ) -> Result<VideoSession, SDPParseError> {
+        // Check if stream is demuxed
+        if !is_rtcp_muxed(video_media) {
+            return Err(SDPParseError::DemuxRequired);
+        }
+
+        let is_passive_dtls_role =
+            is_passive_dtls_role(video_media).ok_or(SDPParseError::MalformedSDPLine)?;
+
+        if is_passive_dtls_role {
+            return Err(SDPParseError::InvalidDTLSRole);
+        }
+
+        // Check if stream receives media, either recvonly or sendrecv
+        if !is_receiving(video_media) {
+            return Err(SDPParseError::InvalidStreamDirection);
+        }
+
+        let legal_video_fmtp = &streamer_session.capabilities;
+        let resolved_payload_number =
+            self.resolve_viewer_video_payload_number(video_media, streamer_session)
+                .ok_or(SDPParseError::UnsupportedMediaCodecs)?;
 
         let remote_ssrc = video_media.iter().find_map(|item| match item {
             SDPLine::Attribute(attr) => match attr {
@@ -750,10 +1720,21 @@ impl SDPResolver {
 
         Ok(VideoSession {
             capabilities: legal_video_fmtp.clone(),
-            host_ssrc: get_random_ssrc(),
+            host_ssrc: self.identifier_generator.get_random_ssrc(),
+            remote_cname: Self::get_remote_cname(video_media, remote_ssrc),
+            remote_rtx_ssrc: Self::get_remote_rtx_ssrc(video_media, remote_ssrc),
             remote_ssrc,
             payload_number: resolved_payload_number,
-            codec: legal_video_codec.clone(),
+            codec: streamer_session.codec.clone(),
+            simulcast: None,
+            abs_send_time_extension_id: None,
+            // Sequence numbers are written in the one-byte header form only
+            transport_cc_extension_id: Self::get_extension_id(
+                video_media,
+                TRANSPORT_CC_EXTENSION_URI,
+            )
+            .filter(|id| ExtMap::is_one_byte_id(*id)),
+            fir_supported: false,
         })
     }
 
@@ -763,34 +1744,57 @@ impl SDPResolver {
         streamer_session: &NegotiatedSession,
     ) -> Result<NegotiatedSession, SDPParseError> {
         let ice_credentials =
-            Self::get_ice_credentials(&viewer_sdp).ok_or(SDPParseError::MissingICECredentials)?;
+            self.get_ice_credentials(&viewer_sdp).ok_or(SDPParseError::MissingICECredentials)?;
         let (audio_mid, video_mid) = Self::get_media_ids(&viewer_sdp)?;
-        let audio_session = Self::get_viewer_audio_session(
+        let audio_session = self.get_viewer_audio_session(
             &viewer_sdp.audio_section,
             &streamer_session.audio_session,
         )?;
-        let video_session = Self::get_viewer_video_session(
+        let video_session = self.get_viewer_video_session(
             &viewer_sdp.video_section,
             &streamer_session.video_session,
         )?;
+        // Video media past the streamer's tracks is declined
+        let extra_video_sessions = viewer_sdp
+            .extra_video_sections
+            .iter()
+            .zip(&streamer_session.extra_video_sessions)
+            .map(|(video_media, streamer_video_session)| {
+                self.get_viewer_video_session(video_media, streamer_video_session)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let extra_video_mids = viewer_sdp
+            .extra_video_sections
+            .iter()
+            .take(extra_video_sessions.len())
+            .filter_map(|video_media| get_media_id(video_media).cloned())
+            .collect::<Vec<_>>();
+        let stream_id = streamer_session.stream_id.clone();
 
         let session_section = vec![
             SDPLine::ProtocolVersion("0".to_string()),
             SDPLine::Originator(Originator {
                 username: HOST_CNAME.to_string(),
-                ip_addr: self.candidate.connection_address.clone(),
-                session_version: "0".to_string(),
-                session_id: "3767197920".to_string(), // todo Handle unique NTP-like timestamps
+                ip_addr: self.candidates[0].connection_address.clone(),
+                session_version: 0,
+                session_id: 3767197920, // todo Handle unique NTP-like timestamps
             }),
             SDPLine::SessionName(HOST_CNAME.to_string()),
             SDPLine::SessionTime(SessionTime {
                 start_time: 0,
                 end_time: 0,
             }),
-            SDPLine::Attribute(Attribute::MediaGroup(MediaGroup::Bundle(vec![
-                audio_mid.id.clone(),
-                video_mid.id.clone(),
-            ]))),
+            SDPLine::Attribute(Attribute::MediaGroup(MediaGroup::Bundle(
+                [&audio_mid, &video_mid]
+                    .into_iter()
+                    .chain(&extra_video_mids)
+                    .map(|media_id| media_id.id.clone())
+                    .collect(),
+            ))),
+            SDPLine::Attribute(Attribute::MSIDSemantic(MSIDSemantic {
+                semantic: "WMS".to_string(),
+                stream_ids: vec![stream_id.clone()],
+            })),
             SDPLine::Attribute(Attribute::ICEUsername(ICEUsername {
                 username: ice_credentials.host_username.clone(),
             })),
@@ -805,44 +1809,118 @@ impl SDPResolver {
             SDPLine::Attribute(Attribute::Setup(Setup::Passive)),
         ];
 
-        let audio_section = vec![
-            SDPLine::MediaDescription(MediaDescription {
-                transport_port: self.candidate.port as usize,
-                media_type: MediaType::Audio,
-                transport_protocol: MediaTransportProtocol::DtlsSrtp,
-                media_format_description: vec![audio_session.payload_number],
-            }),
-            SDPLine::ConnectionData(ConnectionData {
-                ip: self.candidate.connection_address,
-            }),
-            SDPLine::Attribute(Attribute::SendOnly),
-            SDPLine::Attribute(Attribute::RTCPMux),
-            SDPLine::Attribute(Attribute::MediaID(audio_mid)),
-            SDPLine::Attribute(Attribute::Candidate(self.candidate.clone())),
-            SDPLine::Attribute(Attribute::EndOfCandidates),
-            SDPLine::Attribute(Attribute::RTPMap(RTPMap {
-                codec: MediaCodec::Audio(audio_session.codec.clone()),
-                payload_number: audio_session.payload_number,
-            })),
-            SDPLine::Attribute(Attribute::MediaSSRC(MediaSSRC {
-                ssrc: audio_session.host_ssrc,
-                source_attribute: SourceAttribute::CNAME(HOST_CNAME.to_string()),
-            })),
-        ];
+        let audio_section = [
+            vec![
+                SDPLine::MediaDescription(MediaDescription {
+                    transport_port: self.candidates[0].port as usize,
+                    media_type: MediaType::Audio,
+                    transport_protocol: MediaTransportProtocol::DtlsSrtp,
+                    media_format_description: vec![audio_session.payload_number],
+                }),
+                SDPLine::ConnectionData(ConnectionData {
+                    ip: self.candidates[0].connection_address,
+                }),
+                SDPLine::Attribute(Attribute::SendOnly),
+                SDPLine::Attribute(Attribute::RTCPMux),
+                SDPLine::Attribute(Attribute::MediaID(audio_mid)),
+                SDPLine::Attribute(Attribute::MSID(MSID {
+                    stream_id: stream_id.clone(),
+                    track_id: Some(format!("{}-audio", stream_id)),
+                })),
+            ],
+            self.get_candidate_lines(),
+            vec![
+                SDPLine::Attribute(Attribute::RTPMap(RTPMap {
+                    codec: MediaCodec::Audio(audio_session.codec.clone()),
+                    payload_number: audio_session.payload_number,
+                })),
+                SDPLine::Attribute(Attribute::MediaSSRC(MediaSSRC {
+                    ssrc: audio_session.host_ssrc,
+                    source_attribute: SourceAttribute::CNAME(HOST_CNAME.to_string()),
+                })),
+            ],
+        ]
+        .concat();
+
+        let video_section = self.get_viewer_video_section(
+            &video_session,
+            video_mid,
+            &stream_id,
+            format!("{}-video", stream_id),
+            &viewer_sdp.video_section,
+        );
+        let extra_video_sections = viewer_sdp
+            .extra_video_sections
+            .iter()
+            .enumerate()
+            .map(|(index, video_media)| {
+                let video_mid = get_media_id(video_media)
+                    .cloned()
+                    .ok_or(SDPParseError::InvalidMediaID)?;
+                Ok(match extra_video_sessions.get(index) {
+                    Some(video_session) => self.get_viewer_video_section(
+                        video_session,
+                        video_mid,
+                        &stream_id,
+                        format!("{}-video-{}", stream_id, index + 1),
+                        video_media,
+                    ),
+                    None => self.get_declined_video_section(video_media, video_mid),
+                })
+            })
+            .collect::<Result<Vec<_>, SDPParseError>>()?;
 
-        let video_section = vec![
+        let application_section = viewer_sdp
+            .application_section
+            .as_ref()
+            .map(|application_media| self.get_declined_application_section(application_media))
+            .transpose()?;
+
+        let sdp_answer = SDP {
+            session_section,
+            audio_section,
+            video_section,
+            extra_video_sections,
+            application_section,
+        };
+
+        Ok(NegotiatedSession {
+            ice_credentials,
+            audio_session,
+            video_session,
+            extra_video_sessions,
+            sdp_answer,
+            stream_id,
+        })
+    }
+
+    /** Answer to a viewer's video media, sending the track of the given session.
+     */
+    fn get_viewer_video_section(
+        &self,
+        video_session: &VideoSession,
+        media_id: MediaID,
+        stream_id: &str,
+        track_id: String,
+        offer_media: &[SDPLine],
+    ) -> Vec<SDPLine> {
+        let mut video_section = vec![
             SDPLine::MediaDescription(MediaDescription {
-                transport_port: self.candidate.port as usize,
+                transport_port: self.candidates[0].port as usize,
                 media_type: MediaType::Video,
                 transport_protocol: MediaTransportProtocol::DtlsSrtp,
                 media_format_description: vec![video_session.payload_number],
             }),
             SDPLine::ConnectionData(ConnectionData {
-                ip: self.candidate.connection_address,
+                ip: self.candidates[0].connection_address,
             }),
             SDPLine::Attribute(Attribute::SendOnly),
             SDPLine::Attribute(Attribute::RTCPMux),
-            SDPLine::Attribute(Attribute::MediaID(video_mid)),
+            SDPLine::Attribute(Attribute::MediaID(media_id)),
+            SDPLine::Attribute(Attribute::MSID(MSID {
+                stream_id: stream_id.to_string(),
+                track_id: Some(track_id),
+            })),
             SDPLine::Attribute(Attribute::RTPMap(RTPMap {
                 codec: MediaCodec::Video(video_session.codec.clone()),
                 payload_number: video_session.payload_number,
@@ -856,32 +1934,42 @@ impl SDPResolver {
                 format_capability: video_session.capabilities.clone(),
             })),
         ];
-
-        let sdp_answer = SDP {
-            session_section,
-            audio_section,
-            video_section,
-        };
-
-        Ok(NegotiatedSession {
-            ice_credentials,
-            audio_session,
-            video_session,
-            sdp_answer,
-        })
+        // Only video packets are stamped with transport-wide sequence numbers
+        if let Some(id) = video_session.transport_cc_extension_id {
+            video_section.push(SDPLine::Attribute(Attribute::ExtMap(ExtMap {
+                id,
+                uri: TRANSPORT_CC_EXTENSION_URI.to_string(),
+            })));
+            let feedback = SDPLine::Attribute(Attribute::RTCPFeedback(RTCPFeedback {
+                payload_number: video_session.payload_number,
+                feedback_type: "transport-cc".to_string(),
+            }));
+            if offer_media.contains(&feedback) {
+                video_section.push(feedback);
+            }
+        }
+        video_section
     }
 
     /**
     Parse raw string data to SDP struct. SDP struct is split into session, audio and video section, with each section having ownership over corresponding SDPLine elements.
     Check if session section is properly formatted.
     Only two media sections are legal and the first one needs to be audio. This is a completely arbitrary decision
-    that serves to ease parser implementations.
+    that serves to ease parser implementations. Another video section may follow, e.g. for a screen share.
+    A last, data channel media section is allowed so that it can be declined.
         */
     fn get_sdp(raw_data: &str) -> Result<SDP, SDPParseError> {
+        if raw_data.len() > Self::MAX_SDP_SIZE {
+            return Err(SDPParseError::MalformedSDPLine);
+        }
+
         let sdp_lines = raw_data
             .lines()
             .filter(|line| !line.is_empty())
-            .map(SDPLine::try_from)
+            .map(|line| match line.len() > Self::MAX_SDP_LINE_LENGTH {
+                true => Err(SDPParseError::MalformedSDPLine),
+                false => SDPLine::try_from(line),
+            })
             .collect::<Result<Vec<SDPLine>, SDPParseError>>()?;
 
         let next_line = sdp_lines
@@ -924,8 +2012,9 @@ impl SDPResolver {
             })
             .collect::<Vec<_>>();
 
-        let has_two_media_descriptors = media_descriptors.iter().count().eq(&2);
-        if !has_two_media_descriptors {
+        let has_supported_media_count =
+            (2..=3 + Self::MAX_EXTRA_VIDEO_TRACKS).contains(&media_descriptors.len());
+        if !has_supported_media_count {
             return Err(SDPParseError::UnsupportedMediaCount);
         }
 
@@ -949,46 +2038,72 @@ impl SDPResolver {
             return Err(SDPParseError::SequenceError);
         }
 
-        let session_section = sdp_lines
+        // Further video tracks may follow, a data channel has to come last
+        let has_application_media = media_descriptors.len() > 2
+            && media_descriptors.last().is_some_and(|media| {
+                media.media_type.eq(&MediaType::Application)
+                    && media
+                        .transport_protocol
+                        .eq(&MediaTransportProtocol::DtlsSctp)
+            });
+        let extra_video_count = media_descriptors.len() - 2 - usize::from(has_application_media);
+        let is_extra_media_video = media_descriptors[2..2 + extra_video_count]
             .iter()
-            .take_while(|item| match item {
-                SDPLine::MediaDescription(media) => media.ne(first_media),
-                _ => true,
-            })
-            .map(Clone::clone)
-            .collect::<Vec<_>>();
+            .all(|media| media.media_type.eq(&MediaType::Video));
 
-        let audio_section = sdp_lines
-            .iter()
-            .skip_while(|item| match item {
-                SDPLine::MediaDescription(media) => media.ne(first_media),
-                _ => true,
-            })
-            .take_while(|item| match item {
-                SDPLine::MediaDescription(media) => media.ne(second_media),
-                _ => true,
-            })
-            .map(Clone::clone)
-            .collect::<Vec<_>>();
+        if !is_extra_media_video {
+            return Err(SDPParseError::SequenceError);
+        }
+        if extra_video_count > Self::MAX_EXTRA_VIDEO_TRACKS {
+            return Err(SDPParseError::UnsupportedMediaCount);
+        }
 
-        let video_section = sdp_lines
-            .iter()
-            .skip_while(|&item| match item {
-                SDPLine::MediaDescription(media) => media.ne(second_media),
-                _ => true,
-            })
-            .map(Clone::clone)
-            .collect::<Vec<_>>();
+        // Sections are split at media descriptions, as identical m-lines are legal
+        let mut sections = vec![vec![]];
+        for sdp_line in sdp_lines {
+            if matches!(sdp_line, SDPLine::MediaDescription(_)) {
+                sections.push(vec![]);
+            }
+            sections
+                .last_mut()
+                .expect("Sections should start with the session section")
+                .push(sdp_line);
+        }
+        let application_section = has_application_media.then(|| {
+            sections
+                .pop()
+                .expect("Sections should include the application section")
+        });
+        let mut sections = sections.into_iter();
+        let session_section = sections.next().expect("Sections should include the session section");
+        let audio_section = sections.next().expect("Sections should include the audio section");
+        let video_section = sections.next().expect("Sections should include the video section");
+        let extra_video_sections = sections.collect::<Vec<_>>();
 
         Ok(SDP {
             session_section,
             audio_section,
             video_section,
+            extra_video_sections,
+            application_section,
         })
     }
 }
 
+#[cfg(test)]
 mod tests {
+    use std::net::SocketAddr;
+
+    use crate::resolvers::SDPResolver;
+
+    const FINGERPRINT: &str = "sha-256 EF:53:C9:F2:E0:A0:4F:1D:5E:99:4C:20:B8:D7:DE:21:3B:58:15:C4:E5:88:87:46:65:27:F7:3B:C6:DC:EF:3B";
+    const VALID_SDP: &str = "v=0\r\no=rtc 3767197920 0 IN IP4 127.0.0.1\r\ns=-\r\nt=0 0\r\na=group:BUNDLE 0 1\r\na=group:LS 0 1\r\na=msid-semantic:WMS *\r\na=setup:actpass\r\na=ice-ufrag:E2Fr\r\na=ice-pwd:OpQzg1PAwUdeOB244chlgd\r\na=ice-options:trickle\r\na=fingerprint:sha-256 EF:53:C9:F2:E0:A0:4F:1D:5E:99:4C:20:B8:D7:DE:21:3B:58:15:C4:E5:88:87:46:65:27:F7:3B:C6:DC:EF:3B\r\nm=audio 4557 UDP/TLS/RTP/SAVPF 111\r\nc=IN IP4 192.168.0.198\r\na=mid:0\r\na=sendonly\r\na=ssrc:1349455989 cname:0X2NGAsK9XcmnsuZ\r\na=ssrc:1349455989 msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-audio\r\na=msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-audio\r\na=rtcp-mux\r\na=rtpmap:111 opus/48000/2\r\na=fmtp:111 minptime=10;maxaveragebitrate=96000;stereo=1;sprop-stereo=1;useinbandfec=1\r\na=candidate:1 1 UDP 2015363327 192.168.0.198 4557 typ host\r\na=candidate:2 1 UDP 2015363583 fe80::6c3d:5b42:1532:2f9a 10007 typ host\r\na=end-of-candidates\r\nm=video 4557 UDP/TLS/RTP/SAVPF 96\r\nc=IN IP4 192.168.0.198\r\na=mid:1\r\na=sendonly\r\na=ssrc:1349455990 cname:0X2NGAsK9XcmnsuZ\r\na=ssrc:1349455990 msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-video\r\na=msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-video\r\na=rtcp-mux\r\na=rtpmap:96 H264/90000\r\na=rtcp-fb:96 nack\r\na=rtcp-fb:96 nack pli\r\na=rtcp-fb:96 goog-remb\r\na=fmtp:96 profile-level-id=42e01f;packetization-mode=1;level-asymmetry-allowed=1\r\n";
+
+    fn get_resolver() -> SDPResolver {
+        let socket: SocketAddr = "192.168.0.10:52000".parse().unwrap();
+        SDPResolver::new(FINGERPRINT, &[socket])
+    }
+
     mod sdp_resolver {
         mod get_sdp {
             use std::collections::HashSet;
@@ -996,15 +2111,15 @@ mod tests {
             use std::str::FromStr;
 
             use crate::line_parsers::{
-                Attribute, AudioCodec, Candidate, ConnectionData, Fingerprint, FMTP,
-                HashFunction, ICEOption, ICEOptions, ICEPassword, ICEUsername, MediaCodec,
+                Attribute, AudioCodec, Candidate, CandidateType, ConnectionData, Fingerprint,
+                FMTP, HashFunction, ICEOption, ICEOptions, ICEPassword, ICEUsername, MediaCodec,
                 MediaDescription, MediaGroup, MediaID, MediaSSRC, MediaTransportProtocol, MediaType,
-                Originator, RTPMap, SDPLine, SessionTime, Setup, SourceAttribute, VideoCodec,
+                MSID, MSIDSemantic, Originator, RTCPFeedback, RTPMap, SDPLine, SDPParseError,
+                SessionTime, Setup, SourceAttribute, VideoCodec,
             };
+            use crate::resolvers::tests::VALID_SDP;
             use crate::resolvers::SDPResolver;
 
-            const VALID_SDP: &str = "v=0\r\no=rtc 3767197920 0 IN IP4 127.0.0.1\r\ns=-\r\nt=0 0\r\na=group:BUNDLE 0 1\r\na=group:LS 0 1\r\na=msid-semantic:WMS *\r\na=setup:actpass\r\na=ice-ufrag:E2Fr\r\na=ice-pwd:OpQzg1PAwUdeOB244chlgd\r\na=ice-options:trickle\r\na=fingerprint:sha-256 EF:53:C9:F2:E0:A0:4F:1D:5E:99:4C:20:B8:D7:DE:21:3B:58:15:C4:E5:88:87:46:65:27:F7:3B:C6:DC:EF:3B\r\nm=audio 4557 UDP/TLS/RTP/SAVPF 111\r\nc=IN IP4 192.168.0.198\r\na=mid:0\r\na=sendonly\r\na=ssrc:1349455989 cname:0X2NGAsK9XcmnsuZ\r\na=ssrc:1349455989 msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-audio\r\na=msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-audio\r\na=rtcp-mux\r\na=rtpmap:111 opus/48000/2\r\na=fmtp:111 minptime=10;maxaveragebitrate=96000;stereo=1;sprop-stereo=1;useinbandfec=1\r\na=candidate:1 1 UDP 2015363327 192.168.0.198 4557 typ host\r\na=candidate:2 1 UDP 2015363583 fe80::6c3d:5b42:1532:2f9a 10007 typ host\r\na=end-of-candidates\r\nm=video 4557 UDP/TLS/RTP/SAVPF 96\r\nc=IN IP4 192.168.0.198\r\na=mid:1\r\na=sendonly\r\na=ssrc:1349455990 cname:0X2NGAsK9XcmnsuZ\r\na=ssrc:1349455990 msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-video\r\na=msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-video\r\na=rtcp-mux\r\na=rtpmap:96 H264/90000\r\na=rtcp-fb:96 nack\r\na=rtcp-fb:96 nack pli\r\na=rtcp-fb:96 goog-remb\r\na=fmtp:96 profile-level-id=42e01f;packetization-mode=1;level-asymmetry-allowed=1\r\n";
-
             #[test]
             fn resolves_valid_sdp() {
                 let result = SDPResolver::get_sdp(VALID_SDP).expect("Should resolve to OK");
@@ -1013,8 +2128,8 @@ mod tests {
                     SDPLine::ProtocolVersion("0".to_string()),
                     SDPLine::Originator(Originator {
                         username: "rtc".to_string(),
-                        session_id: "3767197920".to_string(),
-                        session_version: "0".to_string(),
+                        session_id: 3767197920,
+                        session_version: 0,
                         ip_addr: IpAddr::V4(Ipv4Addr::from([127, 0, 0, 1])),
                     }),
                     SDPLine::SessionName("-".to_string()),
@@ -1030,7 +2145,10 @@ mod tests {
                         "0".to_string(),
                         "1".to_string(),
                     ]))),
-                    SDPLine::Attribute(Attribute::Unrecognized),
+                    SDPLine::Attribute(Attribute::MSIDSemantic(MSIDSemantic {
+                        semantic: "WMS".to_string(),
+                        stream_ids: vec!["*".to_string()],
+                    })),
                     SDPLine::Attribute(Attribute::Setup(Setup::ActivePassive)),
                     SDPLine::Attribute(Attribute::ICEUsername(ICEUsername {
                         username: "E2Fr".to_string(),
@@ -1067,9 +2185,15 @@ mod tests {
                     })),
                     SDPLine::Attribute(Attribute::MediaSSRC(MediaSSRC {
                         ssrc: 1349455989,
-                        source_attribute: SourceAttribute::Unsupported,
+                        source_attribute: SourceAttribute::MSID {
+                            stream_id: "qUVEoh7TF9nLCrk4".to_string(),
+                            track_id: Some("qUVEoh7TF9nLCrk4-audio".to_string()),
+                        },
+                    })),
+                    SDPLine::Attribute(Attribute::MSID(MSID {
+                        stream_id: "qUVEoh7TF9nLCrk4".to_string(),
+                        track_id: Some("qUVEoh7TF9nLCrk4-audio".to_string()),
                     })),
-                    SDPLine::Attribute(Attribute::Unrecognized),
                     SDPLine::Attribute(Attribute::RTCPMux),
                     SDPLine::Attribute(Attribute::RTPMap(RTPMap {
                         codec: MediaCodec::Audio(AudioCodec::Opus),
@@ -1091,6 +2215,8 @@ mod tests {
                         priority: 2015363327,
                         component_id: 1,
                         foundation: "1".to_string(),
+                        candidate_type: CandidateType::Host,
+                        related_address: None,
                     })),
                     SDPLine::Attribute(Attribute::Candidate(Candidate {
                         connection_address: IpAddr::V6(
@@ -1101,6 +2227,8 @@ mod tests {
                         priority: 2015363583,
                         component_id: 1,
                         foundation: "2".to_string(),
+                        candidate_type: CandidateType::Host,
+                        related_address: None,
                     })),
                     SDPLine::Attribute(Attribute::EndOfCandidates),
                 ];
@@ -1125,17 +2253,32 @@ mod tests {
                     })),
                     SDPLine::Attribute(Attribute::MediaSSRC(MediaSSRC {
                         ssrc: 1349455990,
-                        source_attribute: SourceAttribute::Unsupported,
+                        source_attribute: SourceAttribute::MSID {
+                            stream_id: "qUVEoh7TF9nLCrk4".to_string(),
+                            track_id: Some("qUVEoh7TF9nLCrk4-video".to_string()),
+                        },
+                    })),
+                    SDPLine::Attribute(Attribute::MSID(MSID {
+                        stream_id: "qUVEoh7TF9nLCrk4".to_string(),
+                        track_id: Some("qUVEoh7TF9nLCrk4-video".to_string()),
                     })),
-                    SDPLine::Attribute(Attribute::Unrecognized),
                     SDPLine::Attribute(Attribute::RTCPMux),
                     SDPLine::Attribute(Attribute::RTPMap(RTPMap {
                         codec: MediaCodec::Video(VideoCodec::H264),
                         payload_number: 96,
                     })),
-                    SDPLine::Attribute(Attribute::Unrecognized),
-                    SDPLine::Attribute(Attribute::Unrecognized),
-                    SDPLine::Attribute(Attribute::Unrecognized),
+                    SDPLine::Attribute(Attribute::RTCPFeedback(RTCPFeedback {
+                        payload_number: 96,
+                        feedback_type: "nack".to_string(),
+                    })),
+                    SDPLine::Attribute(Attribute::RTCPFeedback(RTCPFeedback {
+                        payload_number: 96,
+                        feedback_type: "nack pli".to_string(),
+                    })),
+                    SDPLine::Attribute(Attribute::RTCPFeedback(RTCPFeedback {
+                        payload_number: 96,
+                        feedback_type: "goog-remb".to_string(),
+                    })),
                     SDPLine::Attribute(Attribute::FMTP(FMTP {
                         payload_number: 96,
                         format_capability: HashSet::from([
@@ -1162,7 +2305,7 @@ mod tests {
 
             #[test]
             fn rejects_sdp_with_extra_media() {
-                let invalid_sdp = "v=0\r\no=rtc 3767197920 0 IN IP4 127.0.0.1\r\ns=-\r\nt=0 0\r\na=group:BUNDLE 0 1\r\na=group:LS 0 1\r\na=msid-semantic:WMS *\r\na=setup:actpass\r\na=ice-ufrag:E2Fr\r\na=ice-pwd:OpQzg1PAwUdeOB244chlgd\r\na=ice-options:trickle\r\na=fingerprint:sha-256 EF:53:C9:F2:E0:A0:4F:1D:5E:99:4C:20:B8:D7:DE:21:3B:58:15:C4:E5:88:87:46:65:27:F7:3B:C6:DC:EF:3B\r\nm=audio 4557 UDP/TLS/RTP/SAVPF 111\r\nc=IN IP4 192.168.0.198\r\na=mid:0\r\na=sendonly\r\na=ssrc:1349455989 cname:0X2NGAsK9XcmnsuZ\r\na=ssrc:1349455989 msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-audio\r\na=msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-audio\r\na=rtcp-mux\r\na=rtpmap:111 opus/48000/2\r\na=fmtp:111 minptime=10;maxaveragebitrate=96000;stereo=1;sprop-stereo=1;useinbandfec=1\r\na=candidate:1 1 UDP 2015363327 192.168.0.198 4557 typ host\r\na=candidate:2 1 UDP 2015363583 fe80::6c3d:5b42:1532:2f9a 10007 typ host\r\na=end-of-candidates\r\nm=video 4557 UDP/TLS/RTP/SAVPF 96\r\nc=IN IP4 192.168.0.198\r\na=mid:1\r\na=sendonly\r\na=ssrc:1349455990 cname:0X2NGAsK9XcmnsuZ\r\na=ssrc:1349455990 msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-video\r\na=msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-video\r\na=rtcp-mux\r\na=rtpmap:96 H264/90000\r\na=rtcp-fb:96 nack\r\na=rtcp-fb:96 nack pli\r\na=rtcp-fb:96 goog-remb\r\na=fmtp:96 profile-level-id=42e01f;packetization-mode=1;level-asymmetry-allowed=1\r\nm=video 4557 UDP/TLS/RTP/SAVPF 96\r\nc=IN IP4 192.168.0.198\r\na=mid:1\r\na=sendonly\r\na=ssrc:1349455990 cname:0X2NGAsK9XcmnsuZ\r\na=ssrc:1349455990 msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-video\r\na=msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-video\r\na=rtcp-mux\r\na=rtpmap:96 H264/90000\r\na=rtcp-fb:96 nack\r\na=rtcp-fb:96 nack pli\r\na=rtcp-fb:96 goog-remb\r\na=fmtp:96 profile-level-id=42e01f;packetization-mode=1;level-asymmetry-allowed=1\r\n";
+                let invalid_sdp = "v=0\r\no=rtc 3767197920 0 IN IP4 127.0.0.1\r\ns=-\r\nt=0 0\r\na=group:BUNDLE 0 1\r\na=group:LS 0 1\r\na=msid-semantic:WMS *\r\na=setup:actpass\r\na=ice-ufrag:E2Fr\r\na=ice-pwd:OpQzg1PAwUdeOB244chlgd\r\na=ice-options:trickle\r\na=fingerprint:sha-256 EF:53:C9:F2:E0:A0:4F:1D:5E:99:4C:20:B8:D7:DE:21:3B:58:15:C4:E5:88:87:46:65:27:F7:3B:C6:DC:EF:3B\r\nm=audio 4557 UDP/TLS/RTP/SAVPF 111\r\nc=IN IP4 192.168.0.198\r\na=mid:0\r\na=sendonly\r\na=ssrc:1349455989 cname:0X2NGAsK9XcmnsuZ\r\na=ssrc:1349455989 msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-audio\r\na=msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-audio\r\na=rtcp-mux\r\na=rtpmap:111 opus/48000/2\r\na=fmtp:111 minptime=10;maxaveragebitrate=96000;stereo=1;sprop-stereo=1;useinbandfec=1\r\na=candidate:1 1 UDP 2015363327 192.168.0.198 4557 typ host\r\na=candidate:2 1 UDP 2015363583 fe80::6c3d:5b42:1532:2f9a 10007 typ host\r\na=end-of-candidates\r\nm=video 4557 UDP/TLS/RTP/SAVPF 96\r\nc=IN IP4 192.168.0.198\r\na=mid:1\r\na=sendonly\r\na=ssrc:1349455990 cname:0X2NGAsK9XcmnsuZ\r\na=ssrc:1349455990 msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-video\r\na=msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-video\r\na=rtcp-mux\r\na=rtpmap:96 H264/90000\r\na=rtcp-fb:96 nack\r\na=rtcp-fb:96 nack pli\r\na=rtcp-fb:96 goog-remb\r\na=fmtp:96 profile-level-id=42e01f;packetization-mode=1;level-asymmetry-allowed=1\r\nm=video 4557 UDP/TLS/RTP/SAVPF 96\r\nc=IN IP4 192.168.0.198\r\na=mid:1\r\na=sendonly\r\na=ssrc:1349455990 cname:0X2NGAsK9XcmnsuZ\r\na=ssrc:1349455990 msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-video\r\na=msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-video\r\na=rtcp-mux\r\na=rtpmap:96 H264/90000\r\na=rtcp-fb:96 nack\r\na=rtcp-fb:96 nack pli\r\na=rtcp-fb:96 goog-remb\r\na=fmtp:96 profile-level-id=42e01f;packetization-mode=1;level-asymmetry-allowed=1\r\nm=video 4557 UDP/TLS/RTP/SAVPF 96\r\nc=IN IP4 192.168.0.198\r\na=mid:1\r\na=sendonly\r\na=ssrc:1349455990 cname:0X2NGAsK9XcmnsuZ\r\na=ssrc:1349455990 msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-video\r\na=msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-video\r\na=rtcp-mux\r\na=rtpmap:96 H264/90000\r\na=rtcp-fb:96 nack\r\na=rtcp-fb:96 nack pli\r\na=rtcp-fb:96 goog-remb\r\na=fmtp:96 profile-level-id=42e01f;packetization-mode=1;level-asymmetry-allowed=1\r\n";
 
                 SDPResolver::get_sdp(invalid_sdp).expect_err("Should reject SDP");
             }
@@ -1185,6 +2328,27 @@ mod tests {
                 SDPResolver::get_sdp(invalid_sdp).expect_err("Should reject SDP");
             }
 
+            #[test]
+            fn rejects_sdp_with_over_limit_line() {
+                let oversized_fmtp = format!("a=fmtp:96 {}\r\n", "a".repeat(8 * 1024));
+                let invalid_sdp =
+                    VALID_SDP.replacen("a=ice-options:trickle\r\n", &oversized_fmtp, 1);
+
+                let result = SDPResolver::get_sdp(&invalid_sdp);
+
+                assert!(matches!(result, Err(SDPParseError::MalformedSDPLine)));
+            }
+
+            #[test]
+            fn rejects_over_limit_sdp() {
+                let invalid_sdp =
+                    format!("{}{}", VALID_SDP, "a=end-of-candidates\r\n".repeat(4096));
+
+                let result = SDPResolver::get_sdp(&invalid_sdp);
+
+                assert!(matches!(result, Err(SDPParseError::MalformedSDPLine)));
+            }
+
             #[test]
             fn rejects_sdp_with_missing_required_session_media_items() {
                 let invalid_sdp = "v=0\r\no=rtc 3767197920 0 IN IP4 127.0.0.1\r\ns=-\r\na=group:BUNDLE 0 1\r\na=group:LS 0 1\r\na=msid-semantic:WMS *\r\na=setup:actpass\r\na=ice-ufrag:E2Fr\r\na=ice-pwd:OpQzg1PAwUdeOB244chlgd\r\na=ice-options:trickle\r\na=fingerprint:sha-256 EF:53:C9:F2:E0:A0:4F:1D:5E:99:4C:20:B8:D7:DE:21:3B:58:15:C4:E5:88:87:46:65:27:F7:3B:C6:DC:EF:3B\r\nm=audio 4557 UDP/TLS/RTP/SAVPF 111\r\nc=IN IP4 192.168.0.198\r\na=mid:0\r\na=sendonly\r\na=ssrc:1349455989 cname:0X2NGAsK9XcmnsuZ\r\na=ssrc:1349455989 msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-audio\r\na=msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-audio\r\na=rtcp-mux\r\na=rtpmap:111 opus/48000/2\r\na=fmtp:111 minptime=10;maxaveragebitrate=96000;stereo=1;sprop-stereo=1;useinbandfec=1\r\na=candidate:1 1 UDP 2015363327 192.168.0.198 4557 typ host\r\na=candidate:2 1 UDP 2015363583 fe80::6c3d:5b42:1532:2f9a 10007 typ host\r\na=end-of-candidates\r\nm=video 4557 UDP/TLS/RTP/SAVPF 96\r\nc=IN IP4 192.168.0.198\r\na=mid:1\r\na=sendonly\r\na=ssrc:1349455990 cname:0X2NGAsK9XcmnsuZ\r\na=ssrc:1349455990 msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-video\r\na=msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-video\r\na=rtcp-mux\r\na=rtpmap:96 H264/90000\r\na=rtcp-fb:96 nack\r\na=rtcp-fb:96 nack pli\r\na=rtcp-fb:96 goog-remb\r\na=fmtp:96 profile-level-id=42e01f;packetization-mode=1;level-asymmetry-allowed=1\r\n";
@@ -1192,9 +2356,503 @@ mod tests {
             }
         }
 
+        mod accept_stream_offer {
+            use std::net::SocketAddr;
+
+            use crate::line_parsers::SDPParseError;
+            use crate::resolvers::tests::{get_resolver, FINGERPRINT, VALID_SDP};
+            use crate::resolvers::{SDPResolver, SimulcastSession};
+
+            const SCREEN_SHARE_MEDIA: &str = "m=video 4557 UDP/TLS/RTP/SAVPF 96\r\nc=IN IP4 192.168.0.198\r\na=mid:2\r\na=sendonly\r\na=ssrc:1349455991 cname:0X2NGAsK9XcmnsuZ\r\na=msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-screen\r\na=rtcp-mux\r\na=rtpmap:96 H264/90000\r\na=rtcp-fb:96 nack pli\r\na=fmtp:96 profile-level-id=42e01f;packetization-mode=1;level-asymmetry-allowed=1\r\n";
+
+            #[test]
+            fn emits_candidate_per_address() {
+                let ipv4_socket: SocketAddr = "192.168.0.10:52000".parse().unwrap();
+                let ipv6_socket: SocketAddr = "[2001:db8::10]:52000".parse().unwrap();
+                let resolver = SDPResolver::new(FINGERPRINT, &[ipv4_socket, ipv6_socket]);
+
+                let negotiated_session = resolver
+                    .accept_stream_offer(VALID_SDP)
+                    .expect("Should resolve offer");
+                let sdp_answer = String::from(negotiated_session.sdp_answer);
+                let candidate_lines = sdp_answer
+                    .split("\r\n")
+                    .skip_while(|line| !line.starts_with("a=candidate"))
+                    .take(3)
+                    .collect::<Vec<_>>();
+
+                assert_eq!(
+                    candidate_lines,
+                    vec![
+                        "a=candidate:1 1 UDP 2130706431 192.168.0.10 52000 typ host",
+                        "a=candidate:2 1 UDP 2130706175 2001:db8::10 52000 typ host",
+                        "a=end-of-candidates"
+                    ]
+                );
+            }
+
+            #[test]
+            fn prefers_addresses_in_configured_order() {
+                let ipv4_socket: SocketAddr = "192.168.0.10:52000".parse().unwrap();
+                let ipv6_socket: SocketAddr = "[2001:db8::10]:52000".parse().unwrap();
+
+                for (addresses, preferred_socket) in [
+                    ([ipv4_socket, ipv6_socket], ipv4_socket),
+                    ([ipv6_socket, ipv4_socket], ipv6_socket),
+                ] {
+                    let resolver = SDPResolver::new(FINGERPRINT, &addresses);
+
+                    let preferred_candidate = resolver
+                        .candidates
+                        .iter()
+                        .max_by_key(|candidate| candidate.priority)
+                        .unwrap();
+                    assert_eq!(
+                        preferred_candidate.connection_address,
+                        preferred_socket.ip()
+                    );
+                }
+            }
+
+            #[test]
+            fn ends_candidates_once_after_last_candidate() {
+                let mut resolver = get_resolver();
+                resolver.add_server_reflexive_candidate("203.0.113.7:52000".parse().unwrap());
+
+                let negotiated_session = resolver
+                    .accept_stream_offer(VALID_SDP)
+                    .expect("Should resolve offer");
+                let sdp_answer = String::from(negotiated_session.sdp_answer);
+                let candidate_lines = sdp_answer
+                    .split("\r\n")
+                    .filter(|line| line.starts_with("a=candidate") || *line == "a=end-of-candidates")
+                    .collect::<Vec<_>>();
+
+                assert_eq!(
+                    candidate_lines,
+                    vec![
+                        "a=candidate:1 1 UDP 2130706431 192.168.0.10 52000 typ host",
+                        "a=candidate:2 1 UDP 1694498815 203.0.113.7 52000 typ srflx raddr 192.168.0.10 rport 52000",
+                        "a=end-of-candidates"
+                    ]
+                );
+                assert!(sdp_answer.contains("rport 52000\r\na=end-of-candidates\r\n"));
+            }
+
+            #[test]
+            fn resolves_two_layer_simulcast() {
+                let resolver = get_resolver();
+                let offer = VALID_SDP.to_string()
+                    + "a=extmap:4 urn:ietf:params:rtp-hdrext:sdes:rtp-stream-id\r\na=rid:h send\r\na=rid:l send\r\na=simulcast:send h;l\r\n";
+
+                let negotiated_session = resolver
+                    .accept_stream_offer(&offer)
+                    .expect("Should resolve simulcast offer");
+
+                assert_eq!(
+                    negotiated_session.video_session.simulcast,
+                    Some(SimulcastSession {
+                        rids: vec!["h".to_string(), "l".to_string()],
+                        rid_extension_id: 4,
+                    })
+                );
+                assert!(String::from(negotiated_session.sdp_answer).ends_with("a=extmap:4 urn:ietf:params:rtp-hdrext:sdes:rtp-stream-id\r\na=rid:h recv\r\na=rid:l recv\r\na=simulcast:recv h;l\r\n"));
+            }
+
+            #[test]
+            fn ignores_simulcast_without_rid_extension() {
+                let resolver = get_resolver();
+                let offer = VALID_SDP.to_string()
+                    + "a=rid:h send\r\na=rid:l send\r\na=simulcast:send h;l\r\n";
+
+                let negotiated_session = resolver
+                    .accept_stream_offer(&offer)
+                    .expect("Should resolve simulcast offer");
+
+                assert_eq!(negotiated_session.video_session.simulcast, None);
+                assert!(!String::from(negotiated_session.sdp_answer).contains("a=simulcast"));
+            }
+
+            #[test]
+            fn resolves_abs_send_time_extension() {
+                let resolver = get_resolver();
+                let offer = VALID_SDP.to_string()
+                    + "a=extmap:3 http://www.webrtc.org/experiments/rtp-hdrext/abs-send-time\r\n";
+
+                let negotiated_session = resolver
+                    .accept_stream_offer(&offer)
+                    .expect("Should resolve offer with abs-send-time");
+
+                assert_eq!(
+                    negotiated_session.video_session.abs_send_time_extension_id,
+                    Some(3)
+                );
+                let sdp_answer = String::from(negotiated_session.sdp_answer);
+                assert!(sdp_answer.contains("a=rtcp-fb:96 goog-remb\r\n"));
+                assert!(sdp_answer.ends_with(
+                    "a=extmap:3 http://www.webrtc.org/experiments/rtp-hdrext/abs-send-time\r\n"
+                ));
+            }
+
+            #[test]
+            fn keeps_single_extension_form_without_allow_mixed() {
+                let resolver = get_resolver();
+                let offer = VALID_SDP.to_string()
+                    + "a=extmap:3 http://www.webrtc.org/experiments/rtp-hdrext/abs-send-time\r\na=extmap:15 urn:ietf:params:rtp-hdrext:sdes:rtp-stream-id\r\na=rid:h send\r\na=rid:l send\r\na=simulcast:send h;l\r\n";
+
+                let negotiated_session = resolver
+                    .accept_stream_offer(&offer)
+                    .expect("Should resolve offer with mixed extensions");
+
+                let sdp_answer = String::from(negotiated_session.sdp_answer);
+                let extension_ids = sdp_answer
+                    .split("\r\n")
+                    .filter_map(|line| line.strip_prefix("a=extmap:"))
+                    .filter_map(|extmap| extmap.split_once(' '))
+                    .map(|(id, _)| id.parse::<u8>().unwrap())
+                    .collect::<Vec<_>>();
+                assert!(!extension_ids.is_empty());
+                let is_one_byte = |id: &u8| *id <= 14;
+                assert!(
+                    extension_ids.iter().all(is_one_byte) || !extension_ids.iter().any(is_one_byte)
+                );
+                assert_eq!(extension_ids, vec![3]);
+                assert_eq!(negotiated_session.video_session.simulcast, None);
+                assert!(!sdp_answer.contains("a=extmap-allow-mixed"));
+            }
+
+            #[test]
+            fn mixes_extension_forms_with_allow_mixed() {
+                let resolver = get_resolver();
+                let offer = VALID_SDP.replace("t=0 0\r\n", "t=0 0\r\na=extmap-allow-mixed\r\n")
+                    + "a=extmap:3 http://www.webrtc.org/experiments/rtp-hdrext/abs-send-time\r\na=extmap:15 urn:ietf:params:rtp-hdrext:sdes:rtp-stream-id\r\na=rid:h send\r\na=rid:l send\r\na=simulcast:send h;l\r\n";
+
+                let negotiated_session = resolver
+                    .accept_stream_offer(&offer)
+                    .expect("Should resolve offer with mixed extensions");
+
+                assert_eq!(
+                    negotiated_session.video_session.abs_send_time_extension_id,
+                    Some(3)
+                );
+                assert_eq!(
+                    negotiated_session.video_session.simulcast,
+                    Some(SimulcastSession {
+                        rids: vec!["h".to_string(), "l".to_string()],
+                        rid_extension_id: 15,
+                    })
+                );
+                let sdp_answer = String::from(negotiated_session.sdp_answer);
+                assert!(sdp_answer.contains("a=extmap-allow-mixed\r\n"));
+                assert!(sdp_answer
+                    .contains("a=extmap:15 urn:ietf:params:rtp-hdrext:sdes:rtp-stream-id\r\n"));
+            }
+
+            #[test]
+            fn declines_data_channel_media() {
+                let resolver = get_resolver();
+                let offer = VALID_SDP.replace("a=group:BUNDLE 0 1", "a=group:BUNDLE 0 1 2")
+                    + "m=application 9 UDP/DTLS/SCTP webrtc-datachannel\r\nc=IN IP4 0.0.0.0\r\na=mid:2\r\na=sctp-port:5000\r\n";
+
+                let negotiated_session = resolver
+                    .accept_stream_offer(&offer)
+                    .expect("Should resolve offer with data channel");
+                let sdp_answer = String::from(negotiated_session.sdp_answer);
+                let media_lines = sdp_answer
+                    .split("\r\n")
+                    .filter(|line| line.starts_with("m="))
+                    .collect::<Vec<_>>();
+
+                assert_eq!(
+                    media_lines,
+                    vec![
+                        "m=audio 52000 UDP/TLS/RTP/SAVPF 111",
+                        "m=video 52000 UDP/TLS/RTP/SAVPF 96",
+                        "m=application 0 UDP/DTLS/SCTP webrtc-datachannel"
+                    ]
+                );
+                assert!(sdp_answer.contains("a=group:BUNDLE 0 1\r\n"));
+                assert!(sdp_answer.ends_with("m=application 0 UDP/DTLS/SCTP webrtc-datachannel\r\nc=IN IP4 192.168.0.10\r\na=mid:2\r\n"));
+            }
+
+            #[test]
+            fn resolves_camera_and_screen_share_tracks() {
+                let resolver = get_resolver();
+                let offer = VALID_SDP.replace("a=group:BUNDLE 0 1", "a=group:BUNDLE 0 1 2")
+                    + SCREEN_SHARE_MEDIA;
+
+                let negotiated_session = resolver
+                    .accept_stream_offer(&offer)
+                    .expect("Should resolve offer with screen share");
+                let screen_share_session = &negotiated_session.extra_video_sessions[0];
+
+                assert_eq!(negotiated_session.extra_video_sessions.len(), 1);
+                assert_eq!(negotiated_session.video_session.remote_ssrc, Some(1349455990));
+                assert_eq!(screen_share_session.remote_ssrc, Some(1349455991));
+                assert_ne!(
+                    screen_share_session.host_ssrc,
+                    negotiated_session.video_session.host_ssrc
+                );
+                let sdp_answer = String::from(negotiated_session.sdp_answer);
+                let media_lines = sdp_answer
+                    .split("\r\n")
+                    .filter(|line| line.starts_with("m="))
+                    .collect::<Vec<_>>();
+                assert_eq!(
+                    media_lines,
+                    vec![
+                        "m=audio 52000 UDP/TLS/RTP/SAVPF 111",
+                        "m=video 52000 UDP/TLS/RTP/SAVPF 96",
+                        "m=video 52000 UDP/TLS/RTP/SAVPF 96"
+                    ]
+                );
+                assert!(sdp_answer.contains("a=group:BUNDLE 0 1 2\r\n"));
+                assert!(sdp_answer.contains(&format!(
+                    "a=mid:2\r\na=rtpmap:96 h264/90000\r\na=ssrc:{} cname:SMID\r\n",
+                    screen_share_session.host_ssrc
+                )));
+                assert!(sdp_answer.ends_with("a=rtcp-fb:96 nack pli\r\n"));
+            }
+
+            #[test]
+            fn rejects_screen_share_track_without_ssrc() {
+                let resolver = get_resolver();
+                let offer = VALID_SDP.replace("a=group:BUNDLE 0 1", "a=group:BUNDLE 0 1 2")
+                    + &SCREEN_SHARE_MEDIA.replace("a=ssrc:1349455991 cname:0X2NGAsK9XcmnsuZ\r\n", "");
+
+                let result = resolver.accept_stream_offer(&offer);
+
+                assert!(matches!(result, Err(SDPParseError::MissingStreamSSRC)));
+            }
+        }
+
+        mod update_stream_offer {
+            use crate::line_parsers::SDPParseError;
+            use crate::resolvers::tests::{get_resolver, VALID_SDP};
+
+            #[test]
+            fn renegotiates_codec_change_keeping_ice_credentials() {
+                let resolver = get_resolver();
+                let session = resolver
+                    .accept_stream_offer(VALID_SDP)
+                    .expect("Should resolve offer");
+                let updated_offer = VALID_SDP
+                    .replace("SAVPF 96", "SAVPF 102")
+                    .replace(":96 ", ":102 ");
+
+                let updated_session = resolver
+                    .update_stream_offer(&updated_offer, &session, false)
+                    .expect("Should renegotiate offer");
+
+                assert_eq!(updated_session.video_session.payload_number, 102);
+                assert_eq!(
+                    updated_session.ice_credentials.host_username,
+                    session.ice_credentials.host_username
+                );
+                assert_eq!(
+                    updated_session.ice_credentials.host_password,
+                    session.ice_credentials.host_password
+                );
+                assert_eq!(
+                    updated_session.video_session.host_ssrc,
+                    session.video_session.host_ssrc
+                );
+            }
+
+            #[test]
+            fn increments_answer_session_version() {
+                let resolver = get_resolver();
+                let session = resolver
+                    .accept_stream_offer(VALID_SDP)
+                    .expect("Should resolve offer");
+
+                let updated_session = resolver
+                    .update_stream_offer(VALID_SDP, &session, false)
+                    .expect("Should renegotiate offer");
+                let renegotiated_session = resolver
+                    .update_stream_offer(VALID_SDP, &updated_session, false)
+                    .expect("Should renegotiate offer");
+
+                assert_eq!(session.sdp_answer.get_session_version(), Some(0));
+                assert_eq!(updated_session.sdp_answer.get_session_version(), Some(1));
+                assert!(String::from(renegotiated_session.sdp_answer)
+                    .starts_with("v=0\r\no=SMID 3767197920 2 IN IP4 192.168.0.10\r\n"));
+            }
+
+            #[test]
+            fn rejects_ice_username_change_without_ice_restart() {
+                let resolver = get_resolver();
+                let session = resolver
+                    .accept_stream_offer(VALID_SDP)
+                    .expect("Should resolve offer");
+                let updated_offer = VALID_SDP.replace("ice-ufrag:E2Fr", "ice-ufrag:R3st");
+
+                let result = resolver.update_stream_offer(&updated_offer, &session, false);
+
+                assert!(matches!(result, Err(SDPParseError::ICERestartRequired)));
+            }
+
+            #[test]
+            fn renews_ice_credentials_on_ice_restart() {
+                let resolver = get_resolver();
+                let session = resolver
+                    .accept_stream_offer(VALID_SDP)
+                    .expect("Should resolve offer");
+                let updated_offer = VALID_SDP.replace("ice-ufrag:E2Fr", "ice-ufrag:R3st");
+
+                let updated_session = resolver
+                    .update_stream_offer(&updated_offer, &session, true)
+                    .expect("Should renegotiate offer");
+
+                assert_eq!(updated_session.ice_credentials.remote_username, "R3st");
+                assert_ne!(
+                    updated_session.ice_credentials.host_password,
+                    session.ice_credentials.host_password
+                );
+            }
+        }
+
+        mod is_valid_fingerprint {
+            use crate::resolvers::tests::FINGERPRINT;
+            use crate::resolvers::SDPResolver;
+
+            #[test]
+            fn accepts_sha256_fingerprint() {
+                assert!(SDPResolver::is_valid_fingerprint(FINGERPRINT));
+            }
+
+            #[test]
+            fn rejects_malformed_fingerprints() {
+                let fingerprints = [
+                    "".to_string(),
+                    FINGERPRINT.replace("sha-256 ", ""),
+                    FINGERPRINT.replace("sha-256", "sha-1"),
+                    FINGERPRINT.replace("EF:53:", ""),
+                    FINGERPRINT.replace("EF:53", "EF53"),
+                    FINGERPRINT.replace("EF", "XY"),
+                ];
+
+                for fingerprint in fingerprints {
+                    assert!(
+                        !SDPResolver::is_valid_fingerprint(&fingerprint),
+                        "{}",
+                        fingerprint
+                    );
+                }
+            }
+        }
+
+        mod accept_stream_offer_with_diagnostics {
+            use crate::line_parsers::SDPParseError;
+            use crate::resolvers::tests::{get_resolver, VALID_SDP};
+            use crate::resolvers::{OfferRequirement, OfferSection};
+
+            #[test]
+            fn reports_missing_video_demux() {
+                let resolver = get_resolver();
+                let (audio, video) = VALID_SDP.split_once("m=video").unwrap();
+                let offer = format!("{}m=video{}", audio, video.replace("a=rtcp-mux\r\n", ""));
+
+                let diagnostics = resolver
+                    .accept_stream_offer_with_diagnostics(&offer)
+                    .expect_err("Should reject offer");
+
+                assert!(matches!(diagnostics.error, SDPParseError::DemuxRequired));
+                assert_eq!(
+                    diagnostics.failed_requirements,
+                    vec![(OfferSection::Video, OfferRequirement::RTCPMux)]
+                );
+            }
+
+            #[test]
+            fn reports_demux_for_non_muxed_offer() {
+                let resolver = get_resolver();
+                let offer = VALID_SDP
+                    .replace("a=rtcp-mux\r\n", "a=rtcp:4558 IN IP4 192.168.0.198\r\n");
+
+                let diagnostics = resolver
+                    .accept_stream_offer_with_diagnostics(&offer)
+                    .expect_err("Should reject offer");
+
+                assert!(matches!(diagnostics.error, SDPParseError::DemuxRequired));
+                assert!(diagnostics.error.to_string().contains("a=rtcp-mux"));
+                assert_eq!(
+                    diagnostics.failed_requirements,
+                    vec![
+                        (OfferSection::Audio, OfferRequirement::RTCPMux),
+                        (OfferSection::Video, OfferRequirement::RTCPMux)
+                    ]
+                );
+            }
+
+            #[test]
+            fn reports_every_failed_requirement() {
+                let resolver = get_resolver();
+                let offer = VALID_SDP
+                    .replace("a=sendonly", "a=recvonly")
+                    .replace("H264/90000", "VP8/90000");
+
+                let diagnostics = resolver
+                    .accept_stream_offer_with_diagnostics(&offer)
+                    .expect_err("Should reject offer");
+
+                assert_eq!(
+                    diagnostics.failed_requirements,
+                    vec![
+                        (OfferSection::Audio, OfferRequirement::Direction),
+                        (OfferSection::Video, OfferRequirement::Direction),
+                        (OfferSection::Video, OfferRequirement::SupportedCodec)
+                    ]
+                );
+            }
+        }
+
         mod get_ice_credentials {
             use crate::line_parsers::{Attribute, ICEPassword, ICEUsername, SDPLine};
-            use crate::resolvers::{SDP, SDPResolver};
+            use crate::resolvers::tests::get_resolver;
+            use crate::resolvers::SDP;
+
+            #[test]
+            fn generates_host_credentials_of_configured_lengths() {
+                let sdp = SDP {
+                    session_section: vec![
+                        SDPLine::Attribute(Attribute::ICEUsername(ICEUsername {
+                            username: "tests".to_string(),
+                        })),
+                        SDPLine::Attribute(Attribute::ICEPassword(ICEPassword {
+                            password: "tests".to_string(),
+                        })),
+                    ],
+                    video_section: vec![],
+                    audio_section: vec![],
+                    extra_video_sections: vec![],
+
+                    application_section: None,
+                };
+                let mut resolver = get_resolver();
+
+                let ice_credentials = resolver.get_ice_credentials(&sdp).unwrap();
+                assert_eq!(ice_credentials.host_username.len(), 4);
+                assert_eq!(ice_credentials.host_password.len(), 22);
+
+                resolver.set_ice_credential_lengths(16, 32);
+
+                let ice_credentials = resolver.get_ice_credentials(&sdp).unwrap();
+                assert_eq!(ice_credentials.host_username.len(), 16);
+                assert_eq!(ice_credentials.host_password.len(), 32);
+            }
+
+            #[test]
+            #[should_panic(expected = "ICE username length should be between 4 and 256")]
+            fn rejects_too_short_username_length() {
+                get_resolver().set_ice_credential_lengths(3, 22);
+            }
+
+            #[test]
+            #[should_panic(expected = "ICE password length should be between 22 and 256")]
+            fn rejects_too_short_password_length() {
+                get_resolver().set_ice_credential_lengths(4, 21);
+            }
 
             #[test]
             fn resolves_sdp_with_default_credentials() {
@@ -1213,10 +2871,14 @@ mod tests {
                     ],
                     video_section: vec![],
                     audio_section: vec![],
+                    extra_video_sections: vec![],
+
+                    application_section: None,
                 };
 
-                let ice_credentials =
-                    SDPResolver::get_ice_credentials(&sdp).expect("Should resolve ICE credentials");
+                let ice_credentials = get_resolver()
+                    .get_ice_credentials(&sdp)
+                    .expect("Should resolve ICE credentials");
 
                 assert_eq!(
                     ice_credentials.remote_username, expected_ice_username.username,
@@ -1248,10 +2910,14 @@ mod tests {
                         SDPLine::Attribute(Attribute::ICEUsername(expected_ice_username.clone())),
                         SDPLine::Attribute(Attribute::ICEPassword(expected_ice_password.clone())),
                     ],
+                    extra_video_sections: vec![],
+
+                    application_section: None,
                 };
 
-                let ice_credentials =
-                    SDPResolver::get_ice_credentials(&sdp).expect("Should resolve ICE credentials");
+                let ice_credentials = get_resolver()
+                    .get_ice_credentials(&sdp)
+                    .expect("Should resolve ICE credentials");
 
                 assert_eq!(
                     ice_credentials.remote_username, expected_ice_username.username,
@@ -1290,10 +2956,14 @@ mod tests {
                         SDPLine::Attribute(Attribute::ICEUsername(expected_ice_username.clone())),
                         SDPLine::Attribute(Attribute::ICEPassword(expected_ice_password.clone())),
                     ],
+                    extra_video_sections: vec![],
+
+                    application_section: None,
                 };
 
-                let ice_credentials =
-                    SDPResolver::get_ice_credentials(&sdp).expect("Should resolve ICE credentials");
+                let ice_credentials = get_resolver()
+                    .get_ice_credentials(&sdp)
+                    .expect("Should resolve ICE credentials");
 
                 assert_eq!(
                     ice_credentials.remote_username, expected_ice_username.username,
@@ -1321,9 +2991,12 @@ mod tests {
                         SDPLine::Attribute(Attribute::ICEPassword(expected_ice_password.clone())),
                     ],
                     audio_section: vec![],
+                    extra_video_sections: vec![],
+
+                    application_section: None,
                 };
 
-                let ice_credentials = SDPResolver::get_ice_credentials(&sdp);
+                let ice_credentials = get_resolver().get_ice_credentials(&sdp);
 
                 assert!(ice_credentials.is_none(), "Should reject SDP")
             }
@@ -1351,9 +3024,12 @@ mod tests {
                         SDPLine::Attribute(Attribute::ICEPassword(expected_ice_password.clone())),
                     ],
                     audio_section: vec![],
+                    extra_video_sections: vec![],
+
+                    application_section: None,
                 };
 
-                let ice_credentials = SDPResolver::get_ice_credentials(&sdp);
+                let ice_credentials = get_resolver().get_ice_credentials(&sdp);
 
                 assert!(ice_credentials.is_none(), "Should reject SDP")
             }
@@ -1364,9 +3040,12 @@ mod tests {
                     session_section: vec![],
                     video_section: vec![],
                     audio_section: vec![],
+                    extra_video_sections: vec![],
+
+                    application_section: None,
                 };
 
-                let ice_credentials = SDPResolver::get_ice_credentials(&sdp);
+                let ice_credentials = get_resolver().get_ice_credentials(&sdp);
 
                 assert!(ice_credentials.is_none(), "Should reject SDP")
             }
@@ -1387,45 +3066,124 @@ mod tests {
 
                 let sdp = SDP {
                     session_section: vec![SDPLine::Attribute(Attribute::MediaGroup(
-                        MediaGroup::Bundle(vec!["0".to_string(), "1".to_string()]),
-                    ))],
-                    audio_section: vec![SDPLine::Attribute(Attribute::MediaID(
-                        expected_audio_id.clone(),
-                    ))],
-                    video_section: vec![SDPLine::Attribute(Attribute::MediaID(
-                        expected_video_id.clone(),
+                        MediaGroup::Bundle(vec!["0".to_string(), "1".to_string()]),
+                    ))],
+                    audio_section: vec![SDPLine::Attribute(Attribute::MediaID(
+                        expected_audio_id.clone(),
+                    ))],
+                    video_section: vec![SDPLine::Attribute(Attribute::MediaID(
+                        expected_video_id.clone(),
+                    ))],
+                    extra_video_sections: vec![],
+
+                    application_section: None,
+                };
+
+                let (actual_audio_id, actual_video_id) =
+                    SDPResolver::get_media_ids(&sdp).expect("Should resolve media ids");
+
+                assert_eq!(
+                    actual_audio_id, expected_audio_id,
+                    "Audio media ids should match"
+                );
+                assert_eq!(
+                    actual_video_id, expected_video_id,
+                    "Video media ids should match"
+                )
+            }
+
+            #[test]
+            fn rejects_if_mediaid_doesnt_match_bundle() {
+                let sdp = SDP {
+                    session_section: vec![SDPLine::Attribute(Attribute::MediaGroup(
+                        MediaGroup::Bundle(vec!["0".to_string(), "1".to_string()]),
+                    ))],
+                    audio_section: vec![SDPLine::Attribute(Attribute::MediaID(MediaID {
+                        id: "0".to_string(),
+                    }))],
+                    video_section: vec![SDPLine::Attribute(Attribute::MediaID(MediaID {
+                        id: "2".to_string(),
+                    }))],
+                    extra_video_sections: vec![],
+
+                    application_section: None,
+                };
+
+                SDPResolver::get_media_ids(&sdp).expect_err("Should reject SDP");
+            }
+
+            #[test]
+            fn gets_media_ids_of_reordered_bundle() {
+                let sdp = SDP {
+                    session_section: vec![SDPLine::Attribute(Attribute::MediaGroup(
+                        MediaGroup::Bundle(vec!["1".to_string(), "0".to_string()]),
+                    ))],
+                    audio_section: vec![SDPLine::Attribute(Attribute::MediaID(MediaID {
+                        id: "0".to_string(),
+                    }))],
+                    video_section: vec![SDPLine::Attribute(Attribute::MediaID(MediaID {
+                        id: "1".to_string(),
+                    }))],
+                    extra_video_sections: vec![],
+
+                    application_section: None,
+                };
+
+                let (audio_id, video_id) =
+                    SDPResolver::get_media_ids(&sdp).expect("Should resolve media ids");
+
+                assert_eq!(audio_id.id, "0");
+                assert_eq!(video_id.id, "1");
+            }
+
+            #[test]
+            fn gets_media_ids_of_bundle_with_application() {
+                let sdp = SDP {
+                    session_section: vec![SDPLine::Attribute(Attribute::MediaGroup(
+                        MediaGroup::Bundle(vec!["2".to_string(), "1".to_string(), "0".to_string()]),
                     ))],
+                    audio_section: vec![SDPLine::Attribute(Attribute::MediaID(MediaID {
+                        id: "1".to_string(),
+                    }))],
+                    video_section: vec![SDPLine::Attribute(Attribute::MediaID(MediaID {
+                        id: "0".to_string(),
+                    }))],
+                    extra_video_sections: vec![],
+
+                    application_section: Some(vec![SDPLine::Attribute(Attribute::MediaID(
+                        MediaID {
+                            id: "2".to_string(),
+                        },
+                    ))]),
                 };
 
-                let (actual_audio_id, actual_video_id) =
+                let (audio_id, video_id) =
                     SDPResolver::get_media_ids(&sdp).expect("Should resolve media ids");
 
-                assert_eq!(
-                    actual_audio_id, expected_audio_id,
-                    "Audio media ids should match"
-                );
-                assert_eq!(
-                    actual_video_id, expected_video_id,
-                    "Video media ids should match"
-                )
+                assert_eq!(audio_id.id, "1");
+                assert_eq!(video_id.id, "0");
             }
 
             #[test]
-            fn rejects_if_mediaid_doesnt_match_bundle() {
+            fn rejects_if_bundled_mid_is_missing() {
                 let sdp = SDP {
                     session_section: vec![SDPLine::Attribute(Attribute::MediaGroup(
-                        MediaGroup::Bundle(vec!["0".to_string(), "1".to_string()]),
+                        MediaGroup::Bundle(vec!["0".to_string(), "1".to_string(), "2".to_string()]),
                     ))],
                     audio_section: vec![SDPLine::Attribute(Attribute::MediaID(MediaID {
                         id: "0".to_string(),
                     }))],
                     video_section: vec![SDPLine::Attribute(Attribute::MediaID(MediaID {
-                        id: "2".to_string(),
+                        id: "1".to_string(),
                     }))],
+                    extra_video_sections: vec![],
+
+                    application_section: None,
                 };
 
                 SDPResolver::get_media_ids(&sdp).expect_err("Should reject SDP");
             }
+
             #[test]
             fn rejects_if_missing_bundle() {
                 let sdp = SDP {
@@ -1436,6 +3194,9 @@ mod tests {
                     video_section: vec![SDPLine::Attribute(Attribute::MediaID(MediaID {
                         id: "1".to_string(),
                     }))],
+                    extra_video_sections: vec![],
+
+                    application_section: None,
                 };
 
                 SDPResolver::get_media_ids(&sdp).expect_err("Should reject SDP");
@@ -1448,7 +3209,7 @@ mod tests {
                 Attribute, AudioCodec, FMTP, MediaCodec, MediaSSRC, RTPMap, SDPLine,
                 SourceAttribute,
             };
-            use crate::resolvers::SDPResolver;
+            use crate::resolvers::tests::get_resolver;
 
             #[test]
             fn resolves_valid_sdp() {
@@ -1470,7 +3231,7 @@ mod tests {
                         source_attribute: SourceAttribute::CNAME("smid".to_string()),
                     })),
                 ];
-                let audio_session = SDPResolver::get_streamer_audio_session(&audio_media)
+                let audio_session = get_resolver().get_streamer_audio_session(&audio_media)
                     .expect("Should resolve to OK");
 
                 assert_eq!(audio_session.codec, AudioCodec::Opus);
@@ -1494,7 +3255,7 @@ mod tests {
                     })),
                 ];
 
-                let audio_session = SDPResolver::get_streamer_audio_session(&audio_media)
+                let audio_session = get_resolver().get_streamer_audio_session(&audio_media)
                     .expect("Should resolve audio media");
 
                 assert_eq!(audio_session.remote_ssrc, None)
@@ -1516,7 +3277,37 @@ mod tests {
                     })),
                 ];
 
-                SDPResolver::get_streamer_audio_session(&audio_media)
+                get_resolver().get_streamer_audio_session(&audio_media)
+                    .expect_err("Should reject audio media");
+            }
+
+            #[test]
+            fn resolves_media_with_sendrecv_direction() {
+                let audio_media = vec![
+                    SDPLine::Attribute(Attribute::SendReceive),
+                    SDPLine::Attribute(Attribute::RTCPMux),
+                    SDPLine::Attribute(Attribute::RTPMap(RTPMap {
+                        payload_number: 96,
+                        codec: MediaCodec::Audio(AudioCodec::Opus),
+                    })),
+                ];
+
+                get_resolver().get_streamer_audio_session(&audio_media)
+                    .expect("Should resolve audio media");
+            }
+
+            #[test]
+            fn rejects_media_with_inactive_direction() {
+                let audio_media = vec![
+                    SDPLine::Attribute(Attribute::Inactive),
+                    SDPLine::Attribute(Attribute::RTCPMux),
+                    SDPLine::Attribute(Attribute::RTPMap(RTPMap {
+                        payload_number: 96,
+                        codec: MediaCodec::Audio(AudioCodec::Opus),
+                    })),
+                ];
+
+                get_resolver().get_streamer_audio_session(&audio_media)
                     .expect_err("Should reject audio media");
             }
 
@@ -1536,7 +3327,7 @@ mod tests {
                     })),
                 ];
 
-                SDPResolver::get_streamer_audio_session(&audio_media)
+                get_resolver().get_streamer_audio_session(&audio_media)
                     .expect_err("Should reject audio media");
             }
 
@@ -1555,7 +3346,7 @@ mod tests {
                     })),
                 ];
 
-                SDPResolver::get_streamer_audio_session(&audio_media)
+                get_resolver().get_streamer_audio_session(&audio_media)
                     .expect_err("Should reject audio media");
             }
 
@@ -1575,7 +3366,7 @@ mod tests {
                     })),
                 ];
 
-                SDPResolver::get_streamer_audio_session(&audio_media)
+                get_resolver().get_streamer_audio_session(&audio_media)
                     .expect_err("Should reject audio media");
             }
         }
@@ -1585,9 +3376,10 @@ mod tests {
 
             use crate::line_parsers::{
                 Attribute, FMTP, MediaCodec, MediaSSRC, RTPMap, SDPLine, Setup,
-                SourceAttribute, VideoCodec,
+                SourceAttribute, SSRCGroup, VideoCodec,
             };
-            use crate::resolvers::{HOST_CNAME, SDPResolver};
+            use crate::resolvers::tests::get_resolver;
+            use crate::resolvers::HOST_CNAME;
 
             #[test]
             fn resolves_valid_media() {
@@ -1612,15 +3404,93 @@ mod tests {
                     })),
                 ];
 
-                let video_session = SDPResolver::get_streamer_video_session(&video_media)
+                let video_session = get_resolver().get_streamer_video_session(&video_media)
                     .expect("Should resolve video media");
 
                 assert_eq!(video_session.codec, VideoCodec::H264);
                 assert_eq!(video_session.payload_number, expected_payload_number);
                 assert_eq!(video_session.remote_ssrc, Some(expected_ssrc));
+                assert_eq!(video_session.remote_cname, Some(HOST_CNAME.to_string()));
                 assert_eq!(video_session.capabilities, expected_capabilities);
             }
 
+            #[test]
+            fn resolves_cname_among_other_source_attributes() {
+                let expected_ssrc: u32 = 1;
+                let video_media = vec![
+                    SDPLine::Attribute(Attribute::SendOnly),
+                    SDPLine::Attribute(Attribute::RTCPMux),
+                    SDPLine::Attribute(Attribute::FMTP(FMTP {
+                        payload_number: 96,
+                        format_capability: HashSet::from(["profile-tests".to_string()]),
+                    })),
+                    SDPLine::Attribute(Attribute::RTPMap(RTPMap {
+                        payload_number: 96,
+                        codec: MediaCodec::Video(VideoCodec::H264),
+                    })),
+                    SDPLine::Attribute(Attribute::MediaSSRC(MediaSSRC {
+                        ssrc: expected_ssrc,
+                        source_attribute: SourceAttribute::Unsupported,
+                    })),
+                    SDPLine::Attribute(Attribute::MediaSSRC(MediaSSRC {
+                        ssrc: expected_ssrc,
+                        source_attribute: SourceAttribute::MSID {
+                            stream_id: "stream".to_string(),
+                            track_id: Some("video".to_string()),
+                        },
+                    })),
+                    SDPLine::Attribute(Attribute::MediaSSRC(MediaSSRC {
+                        ssrc: expected_ssrc,
+                        source_attribute: SourceAttribute::CNAME("streamer-cname".to_string()),
+                    })),
+                ];
+
+                let video_session = get_resolver().get_streamer_video_session(&video_media)
+                    .expect("Should resolve video media");
+
+                assert_eq!(video_session.remote_ssrc, Some(expected_ssrc));
+                assert_eq!(
+                    video_session.remote_cname,
+                    Some("streamer-cname".to_string())
+                );
+            }
+
+            #[test]
+            fn links_retransmission_ssrc_to_primary() {
+                let primary_ssrc: u32 = 1;
+                let retransmission_ssrc: u32 = 2;
+                let video_media = vec![
+                    SDPLine::Attribute(Attribute::SendOnly),
+                    SDPLine::Attribute(Attribute::RTCPMux),
+                    SDPLine::Attribute(Attribute::FMTP(FMTP {
+                        payload_number: 96,
+                        format_capability: HashSet::from(["profile-tests".to_string()]),
+                    })),
+                    SDPLine::Attribute(Attribute::RTPMap(RTPMap {
+                        payload_number: 96,
+                        codec: MediaCodec::Video(VideoCodec::H264),
+                    })),
+                    SDPLine::Attribute(Attribute::SSRCGroup(SSRCGroup::FlowIdentification {
+                        primary_ssrc,
+                        retransmission_ssrc,
+                    })),
+                    SDPLine::Attribute(Attribute::MediaSSRC(MediaSSRC {
+                        ssrc: primary_ssrc,
+                        source_attribute: SourceAttribute::CNAME(HOST_CNAME.to_string()),
+                    })),
+                    SDPLine::Attribute(Attribute::MediaSSRC(MediaSSRC {
+                        ssrc: retransmission_ssrc,
+                        source_attribute: SourceAttribute::CNAME(HOST_CNAME.to_string()),
+                    })),
+                ];
+
+                let video_session = get_resolver().get_streamer_video_session(&video_media)
+                    .expect("Should resolve video media");
+
+                assert_eq!(video_session.remote_ssrc, Some(primary_ssrc));
+                assert_eq!(video_session.remote_rtx_ssrc, Some(retransmission_ssrc));
+            }
+
             #[test]
             fn resolves_media_with_missing_ssrc() {
                 let expected_payload_number: usize = 96;
@@ -1639,7 +3509,7 @@ mod tests {
                     })),
                 ];
 
-                let video_session = SDPResolver::get_streamer_video_session(&video_media)
+                let video_session = get_resolver().get_streamer_video_session(&video_media)
                     .expect("Should resolve media");
                 assert_eq!(video_session.remote_ssrc, None)
             }
@@ -1666,7 +3536,7 @@ mod tests {
                     })),
                 ];
 
-                SDPResolver::get_streamer_video_session(&video_media)
+                get_resolver().get_streamer_video_session(&video_media)
                     .expect_err("Should reject media");
             }
 
@@ -1688,7 +3558,7 @@ mod tests {
                     })),
                 ];
 
-                SDPResolver::get_streamer_video_session(&video_media)
+                get_resolver().get_streamer_video_session(&video_media)
                     .expect_err("Should reject media");
             }
 
@@ -1715,7 +3585,7 @@ mod tests {
                     })),
                 ];
 
-                SDPResolver::get_streamer_video_session(&video_media)
+                get_resolver().get_streamer_video_session(&video_media)
                     .expect_err("Should reject media");
             }
 
@@ -1743,7 +3613,7 @@ mod tests {
                     })),
                 ];
 
-                SDPResolver::get_streamer_video_session(&video_media)
+                get_resolver().get_streamer_video_session(&video_media)
                     .expect_err("Should reject media");
             }
         }
@@ -1753,12 +3623,14 @@ mod tests {
                 Attribute, AudioCodec, MediaCodec, MediaSSRC, RTPMap, SDPLine, Setup,
                 SourceAttribute,
             };
-            use crate::resolvers::{AudioSession, HOST_CNAME, SDPResolver};
+            use crate::resolvers::tests::get_resolver;
+            use crate::resolvers::{AudioSession, HOST_CNAME};
 
             fn init_streamer_session() -> AudioSession {
                 let audio_session = AudioSession {
                     codec: AudioCodec::Opus,
                     remote_ssrc: Some(2),
+                    remote_cname: None,
                     host_ssrc: 1,
                     payload_number: 111,
                 };
@@ -1788,7 +3660,7 @@ mod tests {
                 ];
 
                 let audio_session =
-                    SDPResolver::get_viewer_audio_session(&audio_media, &streamer_session)
+                    get_resolver().get_viewer_audio_session(&audio_media, &streamer_session)
                         .expect("Should resolve media");
 
                 assert_eq!(audio_session.codec, streamer_session.codec);
@@ -1816,7 +3688,7 @@ mod tests {
                     })),
                 ];
 
-                SDPResolver::get_viewer_audio_session(&audio_media, &streamer_session)
+                get_resolver().get_viewer_audio_session(&audio_media, &streamer_session)
                     .expect_err("Should reject media");
             }
 
@@ -1835,7 +3707,43 @@ mod tests {
                     })),
                 ];
 
-                SDPResolver::get_viewer_audio_session(&audio_media, &streamer_session)
+                get_resolver().get_viewer_audio_session(&audio_media, &streamer_session)
+                    .expect_err("Should reject media");
+            }
+
+            #[test]
+            fn resolves_media_with_sendrecv_direction() {
+                let streamer_session = init_streamer_session();
+
+                let audio_media = vec![
+                    SDPLine::Attribute(Attribute::SendReceive),
+                    SDPLine::Attribute(Attribute::RTCPMux),
+                    SDPLine::Attribute(Attribute::Setup(Setup::ActivePassive)),
+                    SDPLine::Attribute(Attribute::RTPMap(RTPMap {
+                        codec: MediaCodec::Audio(streamer_session.codec.clone()),
+                        payload_number: 96,
+                    })),
+                ];
+
+                get_resolver().get_viewer_audio_session(&audio_media, &streamer_session)
+                    .expect("Should resolve media");
+            }
+
+            #[test]
+            fn rejects_media_with_inactive_direction() {
+                let streamer_session = init_streamer_session();
+
+                let audio_media = vec![
+                    SDPLine::Attribute(Attribute::Inactive),
+                    SDPLine::Attribute(Attribute::RTCPMux),
+                    SDPLine::Attribute(Attribute::Setup(Setup::ActivePassive)),
+                    SDPLine::Attribute(Attribute::RTPMap(RTPMap {
+                        codec: MediaCodec::Audio(streamer_session.codec.clone()),
+                        payload_number: 96,
+                    })),
+                ];
+
+                get_resolver().get_viewer_audio_session(&audio_media, &streamer_session)
                     .expect_err("Should reject media");
             }
 
@@ -1859,7 +3767,7 @@ mod tests {
                     })),
                 ];
 
-                SDPResolver::get_viewer_audio_session(&audio_media, &streamer_session)
+                get_resolver().get_viewer_audio_session(&audio_media, &streamer_session)
                     .expect_err("Should reject media");
             }
 
@@ -1883,7 +3791,7 @@ mod tests {
                     })),
                 ];
 
-                SDPResolver::get_viewer_audio_session(&audio_media, &streamer_session)
+                get_resolver().get_viewer_audio_session(&audio_media, &streamer_session)
                     .expect_err("Should reject media");
             }
         }
@@ -1895,15 +3803,26 @@ mod tests {
                 Attribute, FMTP, MediaCodec, MediaSSRC, RTPMap, SDPLine, Setup,
                 SourceAttribute, VideoCodec,
             };
-            use crate::resolvers::{HOST_CNAME, SDPResolver, VideoSession};
+            use crate::resolvers::tests::get_resolver;
+            use crate::resolvers::{HOST_CNAME, VideoSession};
 
             fn init_streamer_session() -> VideoSession {
                 let video_session = VideoSession {
                     codec: VideoCodec::H264,
-                    capabilities: HashSet::from(["profile-tests".to_string()]),
+                    capabilities: HashSet::from([
+                        "profile-level-id=42e01f".to_string(),
+                        "packetization-mode=1".to_string(),
+                        "level-asymmetry-allowed=1".to_string(),
+                    ]),
                     remote_ssrc: Some(2),
+                    remote_cname: None,
+                    remote_rtx_ssrc: None,
                     host_ssrc: 1,
                     payload_number: 111,
+                    simulcast: None,
+                    abs_send_time_extension_id: None,
+                    transport_cc_extension_id: None,
+                    fir_supported: false,
                 };
 
                 video_session
@@ -1935,7 +3854,7 @@ mod tests {
                 ];
 
                 let video_session =
-                    SDPResolver::get_viewer_video_session(&video_media, &streamer_session)
+                    get_resolver().get_viewer_video_session(&video_media, &streamer_session)
                         .expect("Should resolve media");
 
                 assert_eq!(video_session.codec, streamer_session.codec);
@@ -1970,7 +3889,7 @@ mod tests {
                     })),
                 ];
 
-                SDPResolver::get_viewer_video_session(&video_media, &streamer_session)
+                get_resolver().get_viewer_video_session(&video_media, &streamer_session)
                     .expect_err("Should reject media");
             }
 
@@ -1995,11 +3914,122 @@ mod tests {
                     SDPLine::Attribute(Attribute::Setup(Setup::ActivePassive)),
                     SDPLine::Attribute(Attribute::FMTP(FMTP {
                         payload_number: expected_payload_number,
-                        format_capability: HashSet::from(["unsupported-fmtp".to_string()]),
+                        format_capability: HashSet::from([
+                            "profile-level-id=640032".to_string(),
+                            "packetization-mode=1".to_string(),
+                            "level-asymmetry-allowed=1".to_string(),
+                        ]),
+                    })),
+                ];
+
+                get_resolver().get_viewer_video_session(&video_media, &streamer_session)
+                    .expect_err("Should reject media");
+            }
+
+            #[test]
+            fn resolves_media_with_reordered_fmtp() {
+                let streamer_session = init_streamer_session();
+
+                let expected_payload_number = 96;
+                let viewer_fmtp = FMTP::try_from(
+                    "a=fmtp:96 packetization-mode=1;x-google-start-bitrate=1000;level-asymmetry-allowed=1;profile-level-id=42E01F",
+                )
+                .expect("Should resolve FMTP");
+
+                let video_media = vec![
+                    SDPLine::Attribute(Attribute::ReceiveOnly),
+                    SDPLine::Attribute(Attribute::RTCPMux),
+                    SDPLine::Attribute(Attribute::RTPMap(RTPMap {
+                        codec: MediaCodec::Video(streamer_session.codec.clone()),
+                        payload_number: expected_payload_number,
+                    })),
+                    SDPLine::Attribute(Attribute::Setup(Setup::ActivePassive)),
+                    SDPLine::Attribute(Attribute::FMTP(viewer_fmtp)),
+                ];
+
+                let video_session =
+                    get_resolver().get_viewer_video_session(&video_media, &streamer_session)
+                        .expect("Should resolve media");
+
+                assert_eq!(video_session.payload_number, expected_payload_number);
+                assert_eq!(video_session.capabilities, streamer_session.capabilities)
+            }
+
+            #[test]
+            fn resolves_media_with_compatible_profile_level_id() {
+                let streamer_session = init_streamer_session();
+
+                let expected_payload_number = 96;
+                let viewer_fmtp = FMTP::try_from(
+                    "a=fmtp:96 level-asymmetry-allowed=1;packetization-mode=1;profile-level-id=42001f",
+                )
+                .expect("Should resolve FMTP");
+
+                let video_media = vec![
+                    SDPLine::Attribute(Attribute::ReceiveOnly),
+                    SDPLine::Attribute(Attribute::RTCPMux),
+                    SDPLine::Attribute(Attribute::RTPMap(RTPMap {
+                        codec: MediaCodec::Video(streamer_session.codec.clone()),
+                        payload_number: expected_payload_number,
+                    })),
+                    SDPLine::Attribute(Attribute::Setup(Setup::ActivePassive)),
+                    SDPLine::Attribute(Attribute::FMTP(viewer_fmtp)),
+                ];
+
+                let video_session =
+                    get_resolver().get_viewer_video_session(&video_media, &streamer_session)
+                        .expect("Should resolve media");
+
+                assert_eq!(video_session.payload_number, expected_payload_number);
+            }
+
+            #[test]
+            fn rejects_media_with_main_profile() {
+                let streamer_session = init_streamer_session();
+
+                let expected_payload_number = 96;
+                let viewer_fmtp = FMTP::try_from(
+                    "a=fmtp:96 level-asymmetry-allowed=1;packetization-mode=1;profile-level-id=4d001f",
+                )
+                .expect("Should resolve FMTP");
+
+                let video_media = vec![
+                    SDPLine::Attribute(Attribute::ReceiveOnly),
+                    SDPLine::Attribute(Attribute::RTCPMux),
+                    SDPLine::Attribute(Attribute::RTPMap(RTPMap {
+                        codec: MediaCodec::Video(streamer_session.codec.clone()),
+                        payload_number: expected_payload_number,
+                    })),
+                    SDPLine::Attribute(Attribute::Setup(Setup::ActivePassive)),
+                    SDPLine::Attribute(Attribute::FMTP(viewer_fmtp)),
+                ];
+
+                get_resolver().get_viewer_video_session(&video_media, &streamer_session)
+                    .expect_err("Should reject media");
+            }
+
+            #[test]
+            fn rejects_media_with_different_packetization_mode() {
+                let streamer_session = init_streamer_session();
+
+                let expected_payload_number = 96;
+                let viewer_fmtp = FMTP::try_from(
+                    "a=fmtp:96 level-asymmetry-allowed=1;packetization-mode=0;profile-level-id=42e01f",
+                )
+                .expect("Should resolve FMTP");
+
+                let video_media = vec![
+                    SDPLine::Attribute(Attribute::ReceiveOnly),
+                    SDPLine::Attribute(Attribute::RTCPMux),
+                    SDPLine::Attribute(Attribute::RTPMap(RTPMap {
+                        codec: MediaCodec::Video(streamer_session.codec.clone()),
+                        payload_number: expected_payload_number,
                     })),
+                    SDPLine::Attribute(Attribute::Setup(Setup::ActivePassive)),
+                    SDPLine::Attribute(Attribute::FMTP(viewer_fmtp)),
                 ];
 
-                SDPResolver::get_viewer_video_session(&video_media, &streamer_session)
+                get_resolver().get_viewer_video_session(&video_media, &streamer_session)
                     .expect_err("Should reject media");
             }
 
@@ -2024,7 +4054,7 @@ mod tests {
                 ];
 
                 let video_session =
-                    SDPResolver::get_viewer_video_session(&video_media, &streamer_session)
+                    get_resolver().get_viewer_video_session(&video_media, &streamer_session)
                         .expect("Should resolve media");
 
                 assert_eq!(video_session.remote_ssrc, None)
@@ -2055,7 +4085,7 @@ mod tests {
                     })),
                 ];
 
-                SDPResolver::get_viewer_video_session(&video_media, &streamer_session)
+                get_resolver().get_viewer_video_session(&video_media, &streamer_session)
                     .expect_err("Should reject media");
             }
 
@@ -2083,10 +4113,115 @@ mod tests {
                     })),
                 ];
 
-                SDPResolver::get_viewer_video_session(&video_media, &streamer_session)
+                get_resolver().get_viewer_video_session(&video_media, &streamer_session)
                     .expect_err("Should reject media");
             }
         }
     }
+
+    mod negotiated_session_builder {
+        use std::collections::HashSet;
+
+        use crate::line_parsers::{AudioCodec, SDPParseError, VideoCodec};
+        use crate::resolvers::{
+            AudioSession, ICECredentials, NegotiatedSessionBuilder, VideoSession,
+        };
+
+        fn init_builder() -> NegotiatedSessionBuilder {
+            NegotiatedSessionBuilder::new()
+                .set_ice_credentials(ICECredentials {
+                    host_username: "host".to_string(),
+                    host_password: "host-password".to_string(),
+                    remote_username: "remote".to_string(),
+                    remote_password: "remote-password".to_string(),
+                })
+                .set_audio_session(AudioSession {
+                    codec: AudioCodec::Opus,
+                    payload_number: 111,
+                    host_ssrc: 1,
+                    remote_ssrc: Some(2),
+                    remote_cname: None,
+                })
+                .set_video_session(VideoSession {
+                    codec: VideoCodec::H264,
+                    payload_number: 96,
+                    host_ssrc: 3,
+                    remote_ssrc: Some(4),
+                    remote_cname: None,
+                    remote_rtx_ssrc: None,
+                    capabilities: HashSet::new(),
+                    simulcast: None,
+                    abs_send_time_extension_id: None,
+                    transport_cc_extension_id: None,
+                    fir_supported: false,
+                })
+        }
+
+        #[test]
+        fn rejects_matching_media_ids() {
+            let result = init_builder().set_media_ids("0", "0").build();
+
+            assert!(matches!(result, Err(SDPParseError::InvalidMediaID)));
+        }
+
+        #[test]
+        fn rejects_missing_video_session() {
+            let mut builder = init_builder();
+            builder.video_session = None;
+
+            assert!(matches!(
+                builder.build(),
+                Err(SDPParseError::UnsupportedMediaCount)
+            ));
+        }
+    }
+
+    mod forget_remote_ssrc {
+        use std::collections::HashSet;
+
+        use crate::line_parsers::{AudioCodec, VideoCodec};
+        use crate::resolvers::{
+            AudioSession, ICECredentials, NegotiatedSessionBuilder, VideoSession,
+        };
+
+        #[test]
+        fn keeps_sources_still_sending() {
+            let mut session = NegotiatedSessionBuilder::new()
+                .set_ice_credentials(ICECredentials {
+                    host_username: "host".to_string(),
+                    host_password: "host-password".to_string(),
+                    remote_username: "remote".to_string(),
+                    remote_password: "remote-password".to_string(),
+                })
+                .set_audio_session(AudioSession {
+                    codec: AudioCodec::Opus,
+                    payload_number: 111,
+                    host_ssrc: 1,
+                    remote_ssrc: Some(2),
+                    remote_cname: None,
+                })
+                .set_video_session(VideoSession {
+                    codec: VideoCodec::H264,
+                    payload_number: 96,
+                    host_ssrc: 3,
+                    remote_ssrc: Some(4),
+                    remote_cname: None,
+                    remote_rtx_ssrc: Some(5),
+                    capabilities: HashSet::new(),
+                    simulcast: None,
+                    abs_send_time_extension_id: None,
+                    transport_cc_extension_id: None,
+                    fir_supported: false,
+                })
+                .build()
+                .unwrap();
+            assert_eq!(session.get_remote_ssrcs(), vec![2, 4, 5]);
+
+            session.forget_remote_ssrc(4);
+
+            assert_eq!(session.video_session.remote_ssrc, None);
+            assert_eq!(session.get_remote_ssrcs(), vec![2, 5]);
+        }
+    }
 }
 pub(crate) static HOST_CNAME: &str = "SMID";
@@ -1,14 +1,19 @@
+#[path = "../src/golden.rs"]
+mod golden;
+
 mod streamer_offer {
     use std::collections::HashSet;
     use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 
     use sdp::{AudioCodec, SDPResolver, VideoCodec};
 
+    use crate::golden::assert_sdp_eq;
+
     const EXPECTED_FINGERPRINT: &str = "sha-256 EF:53:C9:F2:E0:A0:4F:1D:5E:99:4C:20:B8:D7:DE:21:3B:58:15:C4:E5:88:87:46:65:27:F7:3B:C6:DC:EF:3B";
     fn init_sdp_resolver() -> SDPResolver {
         let ip = IpAddr::V4(Ipv4Addr::LOCALHOST);
         let socket_addr = SocketAddr::new(ip, 52000);
-        SDPResolver::new(EXPECTED_FINGERPRINT, socket_addr)
+        SDPResolver::new(EXPECTED_FINGERPRINT, &[socket_addr])
     }
     const VALID_SDP_OFFER: &str = "v=0\r\no=rtc 3767197920 0 IN IP4 127.0.0.1\r\ns=-\r\nt=0 0\r\na=group:BUNDLE 0 1\r\na=group:LS 0 1\r\na=msid-semantic:WMS *\r\na=setup:actpass\r\na=ice-ufrag:E2Fr\r\na=ice-pwd:OpQzg1PAwUdeOB244chlgd\r\na=ice-options:trickle\r\na=fingerprint:sha-256 EF:53:C9:F2:E0:A0:4F:1D:5E:99:4C:20:B8:D7:DE:21:3B:58:15:C4:E5:88:87:46:65:27:F7:3B:C6:DC:EF:3B\r\nm=audio 4557 UDP/TLS/RTP/SAVPF 111\r\nc=IN IP4 192.168.0.198\r\na=mid:0\r\na=sendonly\r\na=ssrc:1349455989 cname:0X2NGAsK9XcmnsuZ\r\na=ssrc:1349455989 msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-audio\r\na=msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-audio\r\na=rtcp-mux\r\na=rtpmap:111 opus/48000/2\r\na=fmtp:111 minptime=10;maxaveragebitrate=96000;stereo=1;sprop-stereo=1;useinbandfec=1\r\na=candidate:1 1 UDP 2015363327 192.168.0.198 4557 typ host\r\na=candidate:2 1 UDP 2015363583 fe80::6c3d:5b42:1532:2f9a 10007 typ host\r\na=end-of-candidates\r\nm=video 4557 UDP/TLS/RTP/SAVPF 96\r\nc=IN IP4 192.168.0.198\r\na=mid:1\r\na=sendonly\r\na=ssrc:1349455990 cname:0X2NGAsK9XcmnsuZ\r\na=ssrc:1349455990 msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-video\r\na=msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-video\r\na=rtcp-mux\r\na=rtpmap:96 H264/90000\r\na=rtcp-fb:96 nack\r\na=rtcp-fb:96 nack pli\r\na=rtcp-fb:96 goog-remb\r\na=fmtp:96 profile-level-id=42e01f;packetization-mode=1;level-asymmetry-allowed=1\r\n";
 
@@ -71,7 +76,7 @@ mod streamer_offer {
     a=recvonly\r\n\
     a=rtcp-mux\r\n\
     a=mid:0\r\n\
-    a=candidate:1 1 UDP 2015363327 127.0.0.1 52000 typ host\r\n\
+    a=candidate:1 1 UDP 2130706431 127.0.0.1 52000 typ host\r\n\
     a=end-of-candidates\r\n\
     a=rtpmap:111 opus/48000/2\r\n\
     a=ssrc:{audio_ssrc} cname:SMID\r\n\
@@ -82,24 +87,43 @@ mod streamer_offer {
     a=mid:1\r\n\
     a=rtpmap:96 h264/90000\r\n\
     a=ssrc:{video_ssrc} cname:SMID\r\n\
-    a=fmtp:96 {video_fmtp}\r\n",
+    a=fmtp:96 {video_fmtp}\r\n\
+    a=rtcp-fb:96 nack\r\n\
+    a=rtcp-fb:96 nack pli\r\n\
+    a=rtcp-fb:96 goog-remb\r\n",
             ice_username = negotiated_session.ice_credentials.host_username,
             ice_password = negotiated_session.ice_credentials.host_password,
             fingerprint = EXPECTED_FINGERPRINT,
             audio_ssrc = negotiated_session.audio_session.host_ssrc,
             video_ssrc = negotiated_session.video_session.host_ssrc,
-            video_fmtp = negotiated_session
-                .video_session
-                .capabilities
-                .into_iter()
-                .collect::<Vec<_>>()
-                .join(";") //todo Figure out a better way to compare FMTP
+            video_fmtp = "profile-level-id=42e01f;level-asymmetry-allowed=1;packetization-mode=1"
         );
 
-        assert_eq!(
-            expected_answer, actual_answer,
-            "SDP answer should match excepted answer"
+        assert_sdp_eq(&actual_answer, &expected_answer);
+    }
+
+    #[test]
+    fn negotiates_full_intra_request() {
+        let sdp_resolver = init_sdp_resolver();
+        let sdp_offer = VALID_SDP_OFFER.replace(
+            "a=rtcp-fb:96 goog-remb\r\n",
+            "a=rtcp-fb:96 ccm fir\r\na=rtcp-fb:96 goog-remb\r\n",
         );
+
+        let negotiated_session = sdp_resolver
+            .accept_stream_offer(&sdp_offer)
+            .expect("Should resolve offer");
+
+        assert!(negotiated_session.video_session.fir_supported);
+        assert!(String::from(negotiated_session.sdp_answer).contains("a=rtcp-fb:96 ccm fir\r\n"));
+
+        // Offers without it are never sent FIRs
+        let negotiated_session = sdp_resolver
+            .accept_stream_offer(VALID_SDP_OFFER)
+            .expect("Should resolve offer");
+
+        assert!(!negotiated_session.video_session.fir_supported);
+        assert!(!String::from(negotiated_session.sdp_answer).contains("ccm fir"));
     }
 
     #[test]
@@ -118,7 +142,7 @@ mod streamer_offer {
     a=sendonly\r\n\
     a=rtcp-mux\r\n\
     a=mid:0\r\n\
-    a=candidate:1 1 UDP 2015363327 127.0.0.1 52000 typ host\r\n\
+    a=candidate:1 1 UDP 2130706431 127.0.0.1 52000 typ host\r\n\
     a=end-of-candidates\r\n\
     a=rtpmap:111 opus/48000/2\r\n\
     a=ssrc:2\r\n\
@@ -154,7 +178,7 @@ mod streamer_offer {
     a=sendonly\r\n\
     a=rtcp-mux\r\n\
     a=mid:0\r\n\
-    a=candidate:1 1 UDP 2015363327 127.0.0.1 52000 typ host\r\n\
+    a=candidate:1 1 UDP 2130706431 127.0.0.1 52000 typ host\r\n\
     a=end-of-candidates\r\n\
     a=rtpmap:111 opus/48000/2\r\n\
     a=ssrc:2 cname:my-cname\r\n\
@@ -193,7 +217,7 @@ mod streamer_offer {
     a=recvonly\r\n\
     a=rtcp-mux\r\n\
     a=mid:0\r\n\
-    a=candidate:1 1 UDP 2015363327 127.0.0.1 52000 typ host\r\n\
+    a=candidate:1 1 UDP 2130706431 127.0.0.1 52000 typ host\r\n\
     a=end-of-candidates\r\n\
     a=rtpmap:111 opus/48000/2\r\n\
     a=ssrc:2\r\n\
@@ -230,7 +254,7 @@ mod streamer_offer {
     a=sendonly\r\n\
     a=rtcp-mux\r\n\
     a=mid:0\r\n\
-    a=candidate:1 1 UDP 2015363327 127.0.0.1 52000 typ host\r\n\
+    a=candidate:1 1 UDP 2130706431 127.0.0.1 52000 typ host\r\n\
     a=end-of-candidates\r\n\
     a=rtpmap:111 opus/48000/2\r\n\
     m=video 52000 UDP/TLS/RTP/SAVPF 96 97\r\n\
@@ -267,7 +291,7 @@ mod streamer_offer {
     c=IN IP4 127.0.0.1\r\n\
     a=sendonly\r\n\
     a=mid:0\r\n\
-    a=candidate:1 1 UDP 2015363327 127.0.0.1 52000 typ host\r\n\
+    a=candidate:1 1 UDP 2130706431 127.0.0.1 52000 typ host\r\n\
     a=end-of-candidates\r\n\
     a=rtpmap:111 opus/48000/2\r\n\
     a=ssrc:2\r\n\
@@ -301,7 +325,7 @@ mod streamer_offer {
     c=IN IP4 127.0.0.1\r\n\
     a=sendonly\r\n\
     a=rtcp-mux\r\n\
-    a=candidate:1 1 UDP 2015363327 127.0.0.1 52000 typ host\r\n\
+    a=candidate:1 1 UDP 2130706431 127.0.0.1 52000 typ host\r\n\
     a=end-of-candidates\r\n\
     a=rtpmap:111 opus/48000/2\r\n\
     a=ssrc:2\r\n\
@@ -337,7 +361,7 @@ mod streamer_offer {
     a=mid:0\r\n\
     a=sendonly\r\n\
     a=rtcp-mux\r\n\
-    a=candidate:1 1 UDP 2015363327 127.0.0.1 52000 typ host\r\n\
+    a=candidate:1 1 UDP 2130706431 127.0.0.1 52000 typ host\r\n\
     a=end-of-candidates\r\n\
     a=rtpmap:111 opus/48000/2\r\n\
     a=ssrc:2\r\n\
@@ -375,7 +399,7 @@ mod streamer_offer {
     a=sendonly\r\n\
     a=rtcp-mux\r\n\
     a=mid:0\r\n\
-    a=candidate:1 1 UDP 2015363327 127.0.0.1 52000 typ host\r\n\
+    a=candidate:1 1 UDP 2130706431 127.0.0.1 52000 typ host\r\n\
     a=end-of-candidates\r\n\
     a=rtpmap:111 opus/48000/2\r\n\
     a=ssrc:2\r\n\
@@ -395,6 +419,16 @@ mod streamer_offer {
             .expect_err("Should reject SDP");
     }
 
+    #[test]
+    fn rejects_offer_with_invalid_session_time() {
+        let sdp_resolver = init_sdp_resolver();
+        let sdp_offer = VALID_SDP_OFFER.replace("t=0 0", "t=abc 0");
+
+        sdp_resolver
+            .accept_stream_offer(&sdp_offer)
+            .expect_err("Should reject SDP");
+    }
+
     #[test]
     fn rejects_offer_with_missing_video_fmtp() {
         let sdp_offer = "v=0\r\n\
@@ -412,7 +446,7 @@ mod streamer_offer {
     a=sendonly\r\n\
     a=rtcp-mux\r\n\
     a=mid:0\r\n\
-    a=candidate:1 1 UDP 2015363327 127.0.0.1 52000 typ host\r\n\
+    a=candidate:1 1 UDP 2130706431 127.0.0.1 52000 typ host\r\n\
     a=end-of-candidates\r\n\
     a=rtpmap:111 opus/48000/2\r\n\
     a=ssrc:2\r\n\
@@ -446,7 +480,7 @@ mod streamer_offer {
     a=sendonly\r\n\
     a=rtcp-mux\r\n\
     a=mid:0\r\n\
-    a=candidate:1 1 UDP 2015363327 127.0.0.1 52000 typ host\r\n\
+    a=candidate:1 1 UDP 2130706431 127.0.0.1 52000 typ host\r\n\
     a=end-of-candidates\r\n\
     a=rtpmap:111 opus/48000/2\r\n\
     a=ssrc:2\r\n\
@@ -1,14 +1,19 @@
+#[path = "../src/golden.rs"]
+mod golden;
+
 mod viewer_offer {
     use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 
-    use sdp::{NegotiatedSession, SDPResolver};
+    use sdp::{NegotiatedSession, SDPResolver, VideoCodec};
+
+    use crate::golden::assert_sdp_eq;
 
     const VALID_SDP_STREAMER_OFFER: &str = "v=0\r\no=rtc 3767197920 0 IN IP4 127.0.0.1\r\ns=-\r\nt=0 0\r\na=group:BUNDLE 0 1\r\na=group:LS 0 1\r\na=msid-semantic:WMS *\r\na=setup:actpass\r\na=ice-ufrag:E2Fr\r\na=ice-pwd:OpQzg1PAwUdeOB244chlgd\r\na=ice-options:trickle\r\na=fingerprint:sha-256 EF:53:C9:F2:E0:A0:4F:1D:5E:99:4C:20:B8:D7:DE:21:3B:58:15:C4:E5:88:87:46:65:27:F7:3B:C6:DC:EF:3B\r\nm=audio 4557 UDP/TLS/RTP/SAVPF 111\r\nc=IN IP4 192.168.0.198\r\na=mid:0\r\na=sendonly\r\na=ssrc:1349455989 cname:0X2NGAsK9XcmnsuZ\r\na=ssrc:1349455989 msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-audio\r\na=msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-audio\r\na=rtcp-mux\r\na=rtpmap:111 opus/48000/2\r\na=fmtp:111 minptime=10;maxaveragebitrate=96000;stereo=1;sprop-stereo=1;useinbandfec=1\r\na=candidate:1 1 UDP 2015363327 192.168.0.198 4557 typ host\r\na=candidate:2 1 UDP 2015363583 fe80::6c3d:5b42:1532:2f9a 10007 typ host\r\na=end-of-candidates\r\nm=video 4557 UDP/TLS/RTP/SAVPF 96\r\nc=IN IP4 192.168.0.198\r\na=mid:1\r\na=sendonly\r\na=ssrc:1349455990 cname:0X2NGAsK9XcmnsuZ\r\na=ssrc:1349455990 msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-video\r\na=msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-video\r\na=rtcp-mux\r\na=rtpmap:96 H264/90000\r\na=rtcp-fb:96 nack\r\na=rtcp-fb:96 nack pli\r\na=rtcp-fb:96 goog-remb\r\na=fmtp:96 profile-level-id=42e01f;packetization-mode=1;level-asymmetry-allowed=1\r\n";
     const EXPECTED_FINGERPRINT: &str = "sha-256 EF:53:C9:F2:E0:A0:4F:1D:5E:99:4C:20:B8:D7:DE:21:3B:58:15:C4:E5:88:87:46:65:27:F7:3B:C6:DC:EF:3B";
     fn init_tests() -> (SDPResolver, NegotiatedSession) {
         let ip = IpAddr::V4(Ipv4Addr::LOCALHOST);
         let socket_addr = SocketAddr::new(ip, 52000);
-        let sdp_resolver = SDPResolver::new(EXPECTED_FINGERPRINT, socket_addr);
+        let sdp_resolver = SDPResolver::new(EXPECTED_FINGERPRINT, &[socket_addr]);
 
         let streamer_session = sdp_resolver
             .accept_stream_offer(VALID_SDP_STREAMER_OFFER)
@@ -163,6 +168,7 @@ mod viewer_offer {
             viewer_session.video_session.payload_number,
             expected_video_codec_payload_number
         );
+        assert_eq!(viewer_session.video_session.transport_cc_extension_id, Some(7));
 
         // The SDP answer structure & order should remain deterministic
         let expected_answer = format!(
@@ -171,6 +177,7 @@ mod viewer_offer {
     s=SMID\r\n\
     t=0 0\r\n\
     a=group:BUNDLE 0 1\r\n\
+    a=msid-semantic:WMS {stream_id}\r\n\
     a=ice-ufrag:{ice_username}\r\n\
     a=ice-pwd:{ice_password}\r\n\
     a=ice-options:ice2\r\n\
@@ -182,7 +189,8 @@ mod viewer_offer {
     a=sendonly\r\n\
     a=rtcp-mux\r\n\
     a=mid:0\r\n\
-    a=candidate:1 1 UDP 2015363327 127.0.0.1 52000 typ host\r\n\
+    a=msid:{stream_id} {stream_id}-audio\r\n\
+    a=candidate:1 1 UDP 2130706431 127.0.0.1 52000 typ host\r\n\
     a=end-of-candidates\r\n\
     a=rtpmap:{audio_codec_number} opus/48000/2\r\n\
     a=ssrc:{audio_ssrc} cname:SMID\r\n\
@@ -191,25 +199,24 @@ mod viewer_offer {
     a=sendonly\r\n\
     a=rtcp-mux\r\n\
     a=mid:1\r\n\
+    a=msid:{stream_id} {stream_id}-video\r\n\
     a=rtpmap:{video_codec_number} h264/90000\r\n\
     a=ssrc:{video_ssrc} cname:SMID\r\n\
-    a=fmtp:{video_codec_number} {video_fmtp}\r\n",
+    a=fmtp:{video_codec_number} {video_fmtp}\r\n\
+    a=extmap:7 http://www.ietf.org/id/draft-holmer-rmcat-transport-wide-cc-extensions-01\r\n\
+    a=rtcp-fb:{video_codec_number} transport-cc\r\n",
             ice_username = viewer_session.ice_credentials.host_username,
             ice_password = viewer_session.ice_credentials.host_password,
             fingerprint = EXPECTED_FINGERPRINT,
+            stream_id = viewer_session.stream_id,
             audio_codec_number = expected_audio_codec_payload_number,
             video_codec_number = expected_video_codec_payload_number,
             audio_ssrc = viewer_session.audio_session.host_ssrc,
             video_ssrc = viewer_session.video_session.host_ssrc,
-            video_fmtp = viewer_session
-                .video_session
-                .capabilities
-                .into_iter()
-                .collect::<Vec<_>>()
-                .join(";") //todo Figure out a better way to compare FMTP
+            video_fmtp = "profile-level-id=42e01f;level-asymmetry-allowed=1;packetization-mode=1"
         );
 
-        assert_eq!(String::from(viewer_session.sdp_answer), expected_answer);
+        assert_sdp_eq(&String::from(viewer_session.sdp_answer), &expected_answer);
     }
 
     #[test]
@@ -1106,4 +1113,112 @@ mod viewer_offer {
                 .expect("Should resolve offer");
         });
     }
+
+    #[test]
+    fn answers_with_matching_msid_for_audio_and_video() {
+        let (sdp_resolver, streamer_session) = init_tests();
+
+        let viewer_session = sdp_resolver
+            .accept_viewer_offer(EXAMPLE_BROWSER_OFFERS[0], &streamer_session)
+            .expect("Should resolve offer");
+        let stream_id = viewer_session.stream_id;
+        let sdp_answer = String::from(viewer_session.sdp_answer);
+
+        assert_eq!(stream_id, streamer_session.stream_id);
+        assert!(sdp_answer.contains(&format!("a=msid-semantic:WMS {stream_id}\r\n")));
+        assert!(sdp_answer.contains(&format!("a=msid:{stream_id} {stream_id}-audio\r\n")));
+        assert!(sdp_answer.contains(&format!("a=msid:{stream_id} {stream_id}-video\r\n")));
+    }
+
+    #[test]
+    fn answers_with_codecs_allowed_by_policy() {
+        let (mut sdp_resolver, streamer_session) = init_tests();
+        sdp_resolver.set_video_codec_policy(&[VideoCodec::H264]);
+
+        // Offers VP8 and VP9 next to H264
+        let viewer_session = sdp_resolver
+            .accept_viewer_offer(EXAMPLE_BROWSER_OFFERS[0], &streamer_session)
+            .expect("Should resolve offer");
+        let sdp_answer = String::from(viewer_session.sdp_answer);
+
+        assert_eq!(viewer_session.video_session.codec, VideoCodec::H264);
+        assert!(sdp_answer.contains("m=video 52000 UDP/TLS/RTP/SAVPF 126\r\n"));
+        assert!(!sdp_answer.to_lowercase().contains("vp8"));
+
+        // Streams sent in a codec the policy excludes can't be watched
+        sdp_resolver.set_video_codec_policy(&[]);
+        sdp_resolver
+            .accept_viewer_offer(EXAMPLE_BROWSER_OFFERS[0], &streamer_session)
+            .expect_err("Should reject offer");
+    }
+
+    const STREAMER_SCREEN_SHARE_MEDIA: &str = "m=video 4557 UDP/TLS/RTP/SAVPF 96\r\nc=IN IP4 192.168.0.198\r\na=mid:2\r\na=sendonly\r\na=ssrc:1349455991 cname:0X2NGAsK9XcmnsuZ\r\na=rtcp-mux\r\na=rtpmap:96 H264/90000\r\na=fmtp:96 profile-level-id=42e01f;packetization-mode=1;level-asymmetry-allowed=1\r\n";
+    const VIEWER_SECOND_VIDEO_MEDIA: &str = "m=video 9 UDP/TLS/RTP/SAVPF 126\r\nc=IN IP4 0.0.0.0\r\na=recvonly\r\na=mid:2\r\na=rtcp-mux\r\na=rtpmap:126 H264/90000\r\na=fmtp:126 profile-level-id=42e01f;level-asymmetry-allowed=1;packetization-mode=1\r\na=setup:actpass\r\n";
+
+    #[test]
+    fn forwards_screen_share_to_second_video_media() {
+        let (sdp_resolver, _) = init_tests();
+        let streamer_offer = VALID_SDP_STREAMER_OFFER
+            .replace("a=group:BUNDLE 0 1", "a=group:BUNDLE 0 1 2")
+            + STREAMER_SCREEN_SHARE_MEDIA;
+        let streamer_session = sdp_resolver
+            .accept_stream_offer(&streamer_offer)
+            .expect("Should resolve streamer offer with screen share");
+        let viewer_offer = EXAMPLE_BROWSER_OFFERS[0]
+            .replace("a=group:BUNDLE 0 1", "a=group:BUNDLE 0 1 2")
+            + VIEWER_SECOND_VIDEO_MEDIA;
+
+        let viewer_session = sdp_resolver
+            .accept_viewer_offer(&viewer_offer, &streamer_session)
+            .expect("Should resolve offer");
+        let stream_id = viewer_session.stream_id.clone();
+        let screen_share_session = &viewer_session.extra_video_sessions[0];
+
+        assert_eq!(viewer_session.extra_video_sessions.len(), 1);
+        assert_eq!(screen_share_session.payload_number, 126);
+        let sdp_answer = String::from(viewer_session.sdp_answer);
+        assert!(sdp_answer.contains("a=group:BUNDLE 0 1 2\r\n"));
+        assert!(sdp_answer.contains(&format!(
+            "a=mid:2\r\na=msid:{stream_id} {stream_id}-video-1\r\n"
+        )));
+    }
+
+    #[test]
+    fn declines_second_video_media_of_single_track_stream() {
+        let (sdp_resolver, streamer_session) = init_tests();
+        let viewer_offer = EXAMPLE_BROWSER_OFFERS[0]
+            .replace("a=group:BUNDLE 0 1", "a=group:BUNDLE 0 1 2")
+            + VIEWER_SECOND_VIDEO_MEDIA;
+
+        let viewer_session = sdp_resolver
+            .accept_viewer_offer(&viewer_offer, &streamer_session)
+            .expect("Should resolve offer");
+
+        assert!(viewer_session.extra_video_sessions.is_empty());
+        let sdp_answer = String::from(viewer_session.sdp_answer);
+        assert!(sdp_answer.contains("a=group:BUNDLE 0 1\r\n"));
+        assert!(sdp_answer.ends_with(
+            "m=video 0 UDP/TLS/RTP/SAVPF 126\r\nc=IN IP4 127.0.0.1\r\na=inactive\r\na=mid:2\r\n"
+        ));
+    }
+
+    #[test]
+    fn produces_identical_answers_with_same_seed() {
+        let get_viewer_answer = || {
+            let (mut sdp_resolver, _) = init_tests();
+            sdp_resolver.set_identifier_seed(2137);
+            let streamer_session = sdp_resolver
+                .accept_stream_offer(VALID_SDP_STREAMER_OFFER)
+                .expect("Should resolve streamer SDP offer");
+            let viewer_session = sdp_resolver
+                .accept_viewer_offer(EXAMPLE_BROWSER_OFFERS[0], &streamer_session)
+                .expect("Should resolve offer");
+            (
+                String::from(streamer_session.sdp_answer),
+                String::from(viewer_session.sdp_answer),
+            )
+        };
+
+        assert_eq!(get_viewer_answer(), get_viewer_answer());
+    }
 }
@@ -1,5 +1,6 @@
 use std::mem;
 
+use crate::annex_b::to_annex_b;
 use crate::nal::{FragmentationRole, get_nal_packet, NALPacket};
 use crate::rtp::RTPPacket;
 
@@ -10,102 +11,85 @@ pub struct AccessUnitDecoder {
     last_seq: Option<u16>,
     timestamp: Option<u32>,
     nal_decoder: NALDecoder,
-    internal_buffer: Vec<u8>,
-    _is_loopback: bool,
-}
-enum DecodeError {
-    SequenceMismatch,
-    TimestampMismatch,
-    InvalidLastPacket,
+    nal_units: Vec<Vec<u8>>,
 }
 
 impl AccessUnitDecoder {
     pub fn new() -> Self {
         AccessUnitDecoder {
-            _is_loopback: false,
             nal_decoder: NALDecoder::new(),
             timestamp: None,
             last_seq: None,
-            internal_buffer: vec![],
+            nal_units: vec![],
         }
     }
+
+    /**
+    Returns an access unit once it's complete.
+    - The marker bit ends the access unit on its last packet
+    - A timestamp change ends the buffered access unit, in case the marker bit was never set
+     */
     pub fn process_packet(&mut self, packet: RTPPacket) -> Option<AccessUnit> {
-        if packet.marker & self.timestamp.is_none() {
-            self.last_seq = Some(packet.sequence_number);
-            self._is_loopback = true;
-            self.internal_buffer.push(0);
+        // Start decoding at the access unit boundary
+        if self.last_seq.is_none() {
+            if packet.marker {
+                self.last_seq = Some(packet.sequence_number);
+            }
             return None;
         }
 
-        if self._is_loopback {
-            self.timestamp = Some(packet.timestamp);
-            self._is_loopback = false
+        if !self.is_next_in_sequence(packet.sequence_number) {
+            self.reset();
+            return None;
         }
+        self.last_seq = Some(packet.sequence_number);
 
-        match self.get_nal(packet.clone()) {
-            Ok(buff) => {
-                self.last_seq = Some(packet.sequence_number);
-
-                if let Some(mut nal) = buff {
-                    let nal_header = nal[0];
-                    let nal_unit_type = nal_header & 0b0001_1111;
-
-                    if nal_unit_type == 7 || nal_unit_type == 8 {
-                        self.internal_buffer.push(0);
-                    }
-
-                    self.internal_buffer.extend_from_slice(&[0, 0, 1]);
-                    self.internal_buffer.append(&mut nal)
-                }
-
-                let is_last_packet = packet.marker;
-                if is_last_packet {
-                    Some(mem::replace(&mut self.internal_buffer, vec![]))
-                } else {
-                    None
-                }
-            }
-            Err(_) => {
-                self.internal_buffer.clear();
+        let previous_access_unit = match self.timestamp {
+            Some(timestamp) if timestamp != packet.timestamp => {
+                // Drop fragments of the previous access unit
                 self.nal_decoder = NALDecoder::new();
-                self.last_seq = None;
-                self.timestamp = None;
-                None
+                self.take_access_unit()
             }
+            _ => None,
+        };
+        self.timestamp = Some(packet.timestamp);
+
+        let is_last_packet = packet.marker;
+        let nal_units = self.nal_decoder.decode_nal_units(packet);
+        if is_last_packet && nal_units.is_empty() {
+            self.reset();
+            return previous_access_unit;
         }
-    }
+        self.nal_units.extend(nal_units);
 
-    fn get_nal(&mut self, packet: RTPPacket) -> Result<Option<Vec<u8>>, DecodeError> {
-        let is_last_packet_in_access_unit = packet.marker;
-        let is_next_in_seq = self
-            .last_seq
-            .map(|seq| {
-                if seq == u16::MAX {
-                    return packet.sequence_number == 0;
-                }
-                return packet.sequence_number == seq + 1;
-            })
-            .and_then(|val| val.then_some(()))
-            .is_some();
-
-        let is_matching_timestamp = self
-            .timestamp
-            .map(|seq| seq.eq(&packet.timestamp))
-            .and_then(|val| val.then_some(()))
-            .is_some();
-
-        if !is_next_in_seq {
-            return Err(DecodeError::SequenceMismatch);
+        // With both boundaries in one packet, the current access unit is emitted on the next timestamp change
+        if previous_access_unit.is_some() {
+            return previous_access_unit;
         }
-        if !is_matching_timestamp {
-            return Err(DecodeError::TimestampMismatch);
+        if is_last_packet {
+            return self.take_access_unit();
         }
-        let nal_unit = self.nal_decoder.decode_nal_unit(packet);
+        None
+    }
 
-        if is_last_packet_in_access_unit && nal_unit.is_none() {
-            return Err(DecodeError::InvalidLastPacket);
+    fn is_next_in_sequence(&self, sequence_number: u16) -> bool {
+        self.last_seq
+            .is_some_and(|seq| seq.wrapping_add(1) == sequence_number)
+    }
+
+    fn take_access_unit(&mut self) -> Option<AccessUnit> {
+        self.timestamp = None;
+        if self.nal_units.is_empty() {
+            return None;
         }
-        return Ok(nal_unit);
+        Some(to_annex_b(&mem::take(&mut self.nal_units)))
+    }
+
+    fn reset(&mut self) {
+        self.nal_units.clear();
+        self.nal_decoder = NALDecoder::new();
+        self.last_seq = None;
+        self.timestamp = None;
     }
 }
 
@@ -121,11 +105,17 @@ impl NALDecoder {
         }
     }
 
-    pub fn decode_nal_unit(&mut self, rtp_packet: RTPPacket) -> Option<Vec<u8>> {
-        let nal_packet = get_nal_packet(rtp_packet.payload.as_slice())?;
+    /** Returns NAL units completed by this packet, in decoding order.
+     */
+    pub fn decode_nal_units(&mut self, rtp_packet: RTPPacket) -> Vec<Vec<u8>> {
+        let nal_packet = match get_nal_packet(rtp_packet.payload.as_slice()) {
+            Some(nal_packet) => nal_packet,
+            None => return vec![],
+        };
 
         match nal_packet {
-            NALPacket::NALUnit(unit_packet) => Some(unit_packet.unit),
+            NALPacket::NALUnit(unit_packet) => vec![unit_packet.unit],
+            NALPacket::AggregationUnit(aggregation) => aggregation.units,
             NALPacket::FragmentationUnit(mut frag) => {
                 match frag.fragmentation_header.fragmentation_role {
                     FragmentationRole::Start => {
@@ -135,18 +125,145 @@ impl NALDecoder {
                         let header = unit_header_prefix ^ unit_payload_type;
                         self.fragmentation_buffer.push(header); // Append NAL Unit header
                         self.fragmentation_buffer.append(&mut frag.unit); // Append payload
-                        None
+                        vec![]
                     }
                     FragmentationRole::Continue => {
                         self.fragmentation_buffer.append(&mut frag.unit);
-                        None
+                        vec![]
                     }
                     FragmentationRole::End => {
                         self.fragmentation_buffer.append(&mut frag.unit);
-                        Some(mem::replace(&mut self.fragmentation_buffer, vec![]))
+                        vec![mem::replace(&mut self.fragmentation_buffer, vec![])]
                     }
                 }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::rtp::RTPPacket;
+
+    fn get_rtp_packet(
+        sequence_number: u16,
+        timestamp: u32,
+        marker: bool,
+        payload: &[u8],
+    ) -> RTPPacket {
+        let header = [
+            &[0x80, if marker { 0xe0 } else { 0x60 }][..],
+            &sequence_number.to_be_bytes(),
+            &timestamp.to_be_bytes(),
+            &[0x00, 0x00, 0x00, 0x01],
+        ]
+        .concat();
+        let buffer = [&header, payload].concat();
+        RTPPacket::try_from(buffer.as_slice()).expect("Should parse RTP packet")
+    }
+
+    mod access_unit_decoder {
+        use crate::access_unit_decoder::AccessUnitDecoder;
+        use crate::access_unit_decoder::tests::get_rtp_packet;
+
+        const NON_IDR_SLICE: [u8; 3] = [0x41, 0x9a, 0x02];
+
+        #[test]
+        fn emits_access_unit_on_marker_bit() {
+            let mut decoder = AccessUnitDecoder::new();
+            // End of the previous access unit synchronizes the decoder
+            assert_eq!(
+                decoder.process_packet(get_rtp_packet(10, 3000, true, &NON_IDR_SLICE)),
+                None
+            );
+
+            // IDR slice fragmented with FU-A across three packets
+            let first_fragment = [0x7c, 0x85, 0x88, 0x84];
+            let middle_fragment = [0x7c, 0x05, 0x00, 0x33];
+            let last_fragment = [0x7c, 0x45, 0xff];
+
+            assert_eq!(
+                decoder.process_packet(get_rtp_packet(11, 6000, false, &first_fragment)),
+                None
+            );
+            assert_eq!(
+                decoder.process_packet(get_rtp_packet(12, 6000, false, &middle_fragment)),
+                None
+            );
+            assert_eq!(
+                decoder.process_packet(get_rtp_packet(13, 6000, true, &last_fragment)),
+                Some(vec![0, 0, 0, 1, 0x65, 0x88, 0x84, 0x00, 0x33, 0xff])
+            );
+        }
+
+        #[test]
+        fn emits_access_unit_on_timestamp_change() {
+            let mut decoder = AccessUnitDecoder::new();
+            decoder.process_packet(get_rtp_packet(10, 3000, true, &NON_IDR_SLICE));
+
+            assert_eq!(
+                decoder.process_packet(get_rtp_packet(11, 6000, false, &NON_IDR_SLICE)),
+                None
+            );
+            assert_eq!(
+                decoder.process_packet(get_rtp_packet(12, 9000, false, &NON_IDR_SLICE)),
+                Some(vec![0, 0, 0, 1, 0x41, 0x9a, 0x02])
+            );
+        }
+
+        #[test]
+        fn drops_access_unit_on_sequence_gap() {
+            let mut decoder = AccessUnitDecoder::new();
+            decoder.process_packet(get_rtp_packet(10, 3000, true, &NON_IDR_SLICE));
+
+            assert_eq!(
+                decoder.process_packet(get_rtp_packet(11, 6000, false, &NON_IDR_SLICE)),
+                None
+            );
+            assert_eq!(
+                decoder.process_packet(get_rtp_packet(13, 6000, true, &NON_IDR_SLICE)),
+                None
+            );
+        }
+    }
+
+    mod nal_decoder {
+        use crate::access_unit_decoder::NALDecoder;
+        use crate::access_unit_decoder::tests::get_rtp_packet;
+
+        #[test]
+        fn splits_aggregation_unit_in_order() {
+            let sps = [0x67, 0x42, 0xc0, 0x1f];
+            let pps = [0x68, 0xce, 0x3c, 0x80];
+            let idr_slice = [0x65, 0x88, 0x84, 0x00, 0x33];
+            let payload = [
+                &[0x78][..],
+                &[0x00, 0x04],
+                &sps,
+                &[0x00, 0x04],
+                &pps,
+                &[0x00, 0x05],
+                &idr_slice,
+            ]
+            .concat();
+
+            let nal_units =
+                NALDecoder::new().decode_nal_units(get_rtp_packet(1, 3000, true, &payload));
+
+            assert_eq!(
+                nal_units,
+                vec![sps.to_vec(), pps.to_vec(), idr_slice.to_vec()]
+            );
+        }
+
+        #[test]
+        fn rejects_truncated_aggregation_unit() {
+            let payload = [0x78, 0x00, 0x04, 0x67, 0x42, 0x00, 0x09, 0x68];
+
+            let nal_units =
+                NALDecoder::new().decode_nal_units(get_rtp_packet(1, 3000, true, &payload));
+
+            assert!(nal_units.is_empty());
+        }
+    }
+}
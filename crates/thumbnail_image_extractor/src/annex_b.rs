@@ -0,0 +1,33 @@
+const NAL_TYPE_SPS: u8 = 7;
+const NAL_TYPE_PPS: u8 = 8;
+
+const START_CODE: [u8; 3] = [0, 0, 1];
+
+/**
+Writes the NAL units of an access unit as an H264 Annex B byte stream.
+https://www.itu.int/rec/T-REC-H.264 (Annex B.1.2)
+- Every NAL unit is prefixed with the 0x000001 start code
+- The first NAL unit of the access unit, SPS and PPS get an extra leading zero byte
+ */
+pub fn to_annex_b(access_unit: &[Vec<u8>]) -> Vec<u8> {
+    let stream_len = access_unit
+        .iter()
+        .map(|nal_unit| nal_unit.len() + START_CODE.len() + 1)
+        .sum();
+    let mut stream = Vec::with_capacity(stream_len);
+
+    for (index, nal_unit) in access_unit.iter().enumerate() {
+        let nal_unit_type = match nal_unit.first() {
+            Some(nal_header) => nal_header & 0b0001_1111,
+            None => continue,
+        };
+
+        if index == 0 || nal_unit_type == NAL_TYPE_SPS || nal_unit_type == NAL_TYPE_PPS {
+            stream.push(0);
+        }
+        stream.extend_from_slice(&START_CODE);
+        stream.extend_from_slice(nal_unit);
+    }
+
+    stream
+}
@@ -1,15 +1,28 @@
 use std::fmt::{Debug, Formatter};
+use std::panic::{catch_unwind, AssertUnwindSafe};
 
+use openh264::decoder::{DecodedYUV, Decoder};
 use openh264::formats::YUVSource;
 use openh264::nal_units;
 
 use crate::access_unit_decoder::AccessUnitDecoder;
 use crate::rtp::RTPPacket;
+use crate::sps::get_sps_resolution;
+use crate::timestamp_guard::TimestampGuard;
+
+/** Failed NAL units in a row after which the decoder is assumed stuck and replaced by a fresh one.
+ */
+const MAX_CONSECUTIVE_DECODE_FAILURES: usize = 30;
 
 pub struct ThumbnailExtractor {
     pub last_picture: Option<ImageData>,
+    /** Width and height signalled by the latest SPS, known before any picture is decoded.
+     */
+    pub resolution: Option<(u16, u16)>,
     au_decoder: AccessUnitDecoder,
-    h264_decoder: openh264::decoder::Decoder,
+    timestamp_guard: TimestampGuard,
+    h264_decoder: Decoder,
+    consecutive_decode_failures: usize,
 }
 
 impl Debug for ThumbnailExtractor {
@@ -22,9 +35,11 @@ impl Clone for ThumbnailExtractor {
     fn clone(&self) -> Self {
         ThumbnailExtractor {
             au_decoder: AccessUnitDecoder::new(),
-            h264_decoder: openh264::decoder::Decoder::new()
-                .expect("OpenH264 decoder should initialize"),
+            timestamp_guard: TimestampGuard::new(),
+            h264_decoder: get_h264_decoder(),
+            consecutive_decode_failures: 0,
             last_picture: self.last_picture.clone(),
+            resolution: self.resolution,
         }
     }
 }
@@ -33,37 +48,99 @@ impl ThumbnailExtractor {
     pub fn new() -> Self {
         ThumbnailExtractor {
             au_decoder: AccessUnitDecoder::new(),
+            timestamp_guard: TimestampGuard::new(),
             last_picture: None,
-            h264_decoder: openh264::decoder::Decoder::new()
-                .expect("OpenH264 decoder should initialize"),
+            resolution: None,
+            h264_decoder: get_h264_decoder(),
+            consecutive_decode_failures: 0,
         }
     }
     // Returns Some if new thumbnail image is available
     pub fn try_extract_thumbnail(&mut self, buffer: &[u8]) -> Option<()> {
         let rtp_packet = RTPPacket::try_from(buffer).ok()?;
+        if !self.timestamp_guard.is_monotonic(&rtp_packet) {
+            return None;
+        }
         let access_unit = self.au_decoder.process_packet(rtp_packet)?;
 
         for nal in nal_units(&access_unit) {
-            match self.h264_decoder.decode(nal) {
-                Ok(maybe_yuv) => {
-                    if let Some(yuv_data) = maybe_yuv {
-                        let (width, height) = yuv_data.dimensions();
-                        let mut image_buffer = vec![0u8; width * height * 3]; // Setup buffer for image of size w*h*3
-                        yuv_data.write_rgb8(&mut image_buffer);
-
-                        self.last_picture = Some(ImageData {
-                            data_buffer: image_buffer,
-                            height: height as u16,
-                            width: width as u16,
-                        });
-                        return Some(());
-                    }
-                }
-                Err(_err) => {}
+            if let Some(resolution) = get_sps_resolution(strip_start_code(nal)) {
+                self.resolution = Some(resolution);
+            }
+            if let Some(picture) = self.decode_picture(nal) {
+                self.last_picture = Some(picture);
+                return Some(());
             }
         }
         None
     }
+
+    /** Decodes a NAL unit, corrupt input never panics through and leaves the last picture in place.
+     * A panicking decoder is replaced right away, a failing one after too many failures in a row.
+     */
+    fn decode_picture(&mut self, nal: &[u8]) -> Option<ImageData> {
+        let h264_decoder = &mut self.h264_decoder;
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            h264_decoder
+                .decode(nal)
+                .map(|maybe_yuv| maybe_yuv.map(|yuv_data| get_image_data(&yuv_data)))
+        }));
+
+        match result {
+            Ok(Ok(maybe_picture)) => {
+                self.consecutive_decode_failures = 0;
+                maybe_picture
+            }
+            Ok(Err(err)) => {
+                self.consecutive_decode_failures += 1;
+                if self.consecutive_decode_failures == 1 {
+                    eprintln!("Thumbnail decoding failed: {}", err);
+                }
+                if self.consecutive_decode_failures >= MAX_CONSECUTIVE_DECODE_FAILURES {
+                    eprintln!(
+                        "Resetting thumbnail decoder after {} failures in a row",
+                        self.consecutive_decode_failures
+                    );
+                    self.reset_decoder();
+                }
+                None
+            }
+            Err(_) => {
+                eprintln!("Thumbnail decoder panicked, resetting it");
+                self.reset_decoder();
+                None
+            }
+        }
+    }
+
+    fn reset_decoder(&mut self) {
+        self.h264_decoder = get_h264_decoder();
+        self.au_decoder = AccessUnitDecoder::new();
+        self.consecutive_decode_failures = 0;
+    }
+}
+
+fn get_h264_decoder() -> Decoder {
+    Decoder::new().expect("OpenH264 decoder should initialize")
+}
+
+fn get_image_data(yuv_data: &DecodedYUV) -> ImageData {
+    let (width, height) = yuv_data.dimensions();
+    let mut image_buffer = vec![0u8; width * height * 3]; // Setup buffer for image of size w*h*3
+    yuv_data.write_rgb8(&mut image_buffer);
+
+    ImageData {
+        data_buffer: image_buffer,
+        height: height as u16,
+        width: width as u16,
+    }
+}
+
+fn strip_start_code(nal: &[u8]) -> &[u8] {
+    match nal.iter().position(|byte| *byte == 1) {
+        Some(position) if nal[..position].iter().all(|byte| *byte == 0) => &nal[position + 1..],
+        _ => nal,
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -72,3 +149,58 @@ pub struct ImageData {
     pub width: u16,
     pub height: u16,
 }
+
+#[cfg(test)]
+mod tests {
+    mod thumbnail_extractor {
+        use openh264::nal_units;
+
+        use crate::access_unit_decoder::AccessUnitDecoder;
+        use crate::extractor::{ThumbnailExtractor, MAX_CONSECUTIVE_DECODE_FAILURES};
+        use crate::rtp_dump::{get_rtp_packets, get_rtp_packets_raw};
+
+        // IDR slice with a garbage header
+        const CORRUPT_NAL_UNIT: [u8; 10] = [0, 0, 0, 1, 0x65, 0xff, 0xff, 0xff, 0x00, 0x13];
+
+        fn get_extractor_with_picture() -> ThumbnailExtractor {
+            let mut extractor = ThumbnailExtractor::new();
+            for packet in get_rtp_packets_raw() {
+                extractor.try_extract_thumbnail(&packet);
+            }
+            assert!(extractor.last_picture.is_some());
+            extractor
+        }
+
+        #[test]
+        fn keeps_last_picture_on_corrupt_nal_unit() {
+            let mut extractor = get_extractor_with_picture();
+            let last_picture = extractor.last_picture.clone().unwrap();
+
+            assert!(extractor.decode_picture(&CORRUPT_NAL_UNIT).is_none());
+
+            let kept_picture = extractor.last_picture.unwrap();
+            assert_eq!(extractor.consecutive_decode_failures, 1);
+            assert_eq!(kept_picture.data_buffer, last_picture.data_buffer);
+        }
+
+        #[test]
+        fn resets_decoder_after_consecutive_failures() {
+            let mut extractor = get_extractor_with_picture();
+
+            for _ in 0..MAX_CONSECUTIVE_DECODE_FAILURES {
+                assert!(extractor.decode_picture(&CORRUPT_NAL_UNIT).is_none());
+            }
+            assert_eq!(extractor.consecutive_decode_failures, 0);
+
+            // The timestamp guard turns down replays, so access units go to the decoder directly
+            let mut au_decoder = AccessUnitDecoder::new();
+            let is_decoded = get_rtp_packets()
+                .into_iter()
+                .filter_map(|packet| au_decoder.process_packet(packet))
+                .any(|access_unit| {
+                    nal_units(&access_unit).any(|nal| extractor.decode_picture(nal).is_some())
+                });
+            assert!(is_decoded);
+        }
+    }
+}
@@ -1,10 +1,16 @@
 pub use crate::access_unit_decoder::AccessUnitDecoder;
+pub use crate::annex_b::to_annex_b;
 pub use crate::extractor::{ImageData, ThumbnailExtractor};
 // todo expose them only to tests
-pub use crate::rtp_dump::{get_rtp_packets, get_rtp_packets_raw};
+pub use crate::rtp_dump::{
+    get_rtp_packets, get_rtp_packets_raw, read_rtp_packets, read_rtp_packets_raw,
+};
 
 mod access_unit_decoder;
+mod annex_b;
 mod extractor;
 mod nal;
 mod rtp;
 mod rtp_dump;
+mod sps;
+mod timestamp_guard;
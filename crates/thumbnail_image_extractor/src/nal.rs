@@ -143,6 +143,7 @@ impl TryFrom<u8> for NALFragmentationHeader {
 #[derive(Debug)]
 pub(crate) enum NALPacket {
     NALUnit(NALUnit),
+    AggregationUnit(AggregationUnit),
     FragmentationUnit(FragmentationUnit),
 }
 
@@ -152,6 +153,13 @@ impl Display for NALPacket {
             NALPacket::NALUnit(unit) => {
                 write!(f, "Single NAL Unit of {} length", unit.unit.len())
             }
+            NALPacket::AggregationUnit(aggregation) => {
+                write!(
+                    f,
+                    "Aggregation Unit of {} NAL Units",
+                    aggregation.units.len()
+                )
+            }
             NALPacket::FragmentationUnit(frag) => {
                 write!(
                     f,
@@ -177,6 +185,27 @@ pub(crate) struct NALUnit {
     pub(crate) unit: Vec<u8>,
 }
 
+/**
+https://datatracker.ietf.org/doc/html/rfc6184#section-5.7.1
+ 0                   1                   2                   3
+ 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
++-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+|STAP-A NAL HDR |         NALU 1 Size           | NALU 1 HDR    |
++-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+|                         NALU 1 Data                           |
+:                                                               :
+|               +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+|               | NALU 2 Size                   | NALU 2 HDR    |
++-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+|                         NALU 2 Data                           |
+:                                                               :
++-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+*/
+#[derive(Debug)]
+pub(crate) struct AggregationUnit {
+    pub(crate) units: Vec<Vec<u8>>,
+}
+
 pub fn get_nal_packet(input: &[u8]) -> Option<NALPacket> {
     let nal_unit_header = NALUnitHeader::try_from(input[0]).ok()?;
 
@@ -185,6 +214,24 @@ pub fn get_nal_packet(input: &[u8]) -> Option<NALPacket> {
             let mut buffer = Vec::from(&input[0..]);
             Some(NALPacket::NALUnit(NALUnit { unit: buffer }))
         }
+        PayloadType::STAP_A => {
+            let mut units = vec![];
+            let mut offset = 1;
+
+            while offset < input.len() {
+                let size_bytes = input.get(offset..offset + 2)?;
+                let unit_size = u16::from_be_bytes([size_bytes[0], size_bytes[1]]) as usize;
+                let unit = input.get(offset + 2..offset + 2 + unit_size)?;
+                if unit.is_empty() {
+                    return None;
+                }
+
+                units.push(Vec::from(unit));
+                offset += 2 + unit_size;
+            }
+
+            Some(NALPacket::AggregationUnit(AggregationUnit { units }))
+        }
         PayloadType::FU_A => {
             let fragmentation_header = NALFragmentationHeader::try_from(input[1]).ok()?;
             let mut buffer = Vec::from(&input[2..]);
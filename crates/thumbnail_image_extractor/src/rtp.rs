@@ -28,8 +28,8 @@ pub struct RTPPacket {
     payload_type: u8,
     pub sequence_number: u16,
     pub timestamp: u32,
-    ssrc: u32,
-    csrc: Vec<u32>,
+    pub ssrc: u32,
+    pub csrc: Vec<u32>,
     pub payload: Vec<u8>,
 }
 
@@ -37,8 +37,11 @@ pub struct RTPPacket {
 pub enum ParseError {
     PacketShort,
     MalformedPacket,
+    UnsupportedVersion,
 }
 
+const RTP_VERSION: u8 = 2;
+
 impl TryFrom<&[u8]> for RTPPacket {
     type Error = ParseError;
 
@@ -46,6 +49,9 @@ impl TryFrom<&[u8]> for RTPPacket {
         let mut reader = BufReader::new(value);
         let first_octet = reader.read_u8().map_err(|_| Self::Error::PacketShort)?;
         let version = (first_octet & 0b1100_0000) >> 6;
+        if version != RTP_VERSION {
+            return Err(Self::Error::UnsupportedVersion);
+        }
         let is_padding_set = (first_octet & 0b0010_0000) == 0b0010_0000;
         let is_extension_set = (first_octet & 0b0001_0000) == 0b0001_0000;
         let csrc_count = first_octet & 0b0000_1111;
@@ -64,7 +70,8 @@ impl TryFrom<&[u8]> for RTPPacket {
             .read_u32::<BigEndian>()
             .map_err(|_| Self::Error::PacketShort)?;
 
-        let csrc = (0..csrc_count.clone())
+        // Payload starts after 12 + 4 * CC octets
+        let csrc = (0..csrc_count)
             .map(|_| {
                 reader
                     .read_u32::<BigEndian>()
@@ -77,7 +84,16 @@ impl TryFrom<&[u8]> for RTPPacket {
             .read(&mut payload_buffer)
             .map_err(|_| Self::Error::MalformedPacket)?;
 
-        let payload = Vec::from(&payload_buffer[..bytes_read]);
+        let mut payload = Vec::from(&payload_buffer[..bytes_read]);
+
+        // Last octet of the padding holds the padding length, itself included
+        if is_padding_set {
+            let padding_length = *payload.last().ok_or(Self::Error::MalformedPacket)? as usize;
+            if padding_length == 0 || padding_length > payload.len() {
+                return Err(Self::Error::MalformedPacket);
+            }
+            payload.truncate(payload.len() - padding_length);
+        }
 
         Ok(Self {
             marker,
@@ -94,3 +110,71 @@ impl TryFrom<&[u8]> for RTPPacket {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    mod rtp_packet {
+        use crate::rtp::{ParseError, RTPPacket};
+
+        const RTP_HEADER: [u8; 12] = [
+            0x80, 0x60, 0x00, 0x01, 0x00, 0x00, 0x0b, 0xb8, 0x00, 0x00, 0x00, 0x01,
+        ];
+
+        #[test]
+        fn rejects_unsupported_version() {
+            let mut buffer = [&RTP_HEADER[..], &[0x41, 0x9a, 0x02]].concat();
+            buffer[0] = 0x40; // Version 1
+
+            let result = RTPPacket::try_from(buffer.as_slice());
+
+            assert!(matches!(result, Err(ParseError::UnsupportedVersion)));
+        }
+
+        #[test]
+        fn reads_csrc_list() {
+            let mut buffer = [
+                &RTP_HEADER[..],
+                &[0x00, 0x00, 0x00, 0x02],
+                &[0x00, 0x00, 0x00, 0x03],
+                &[0x41, 0x9a, 0x02],
+            ]
+            .concat();
+            buffer[0] |= 2; // CSRC count
+
+            let packet = RTPPacket::try_from(buffer.as_slice()).expect("Should parse RTP packet");
+
+            assert_eq!(packet.csrc, vec![2, 3]);
+            assert_eq!(packet.payload, vec![0x41, 0x9a, 0x02]);
+        }
+
+        #[test]
+        fn rejects_truncated_csrc_list() {
+            let mut buffer = [&RTP_HEADER[..], &[0x00, 0x00, 0x00, 0x02]].concat();
+            buffer[0] |= 2;
+
+            let result = RTPPacket::try_from(buffer.as_slice());
+
+            assert!(matches!(result, Err(ParseError::PacketShort)));
+        }
+
+        #[test]
+        fn trims_padding() {
+            let mut buffer = [&RTP_HEADER[..], &[0x41, 0x9a, 0x02, 0x00, 0x00, 0x03]].concat();
+            buffer[0] |= 0b0010_0000;
+
+            let packet = RTPPacket::try_from(buffer.as_slice()).expect("Should parse RTP packet");
+
+            assert_eq!(packet.payload, vec![0x41, 0x9a, 0x02]);
+        }
+
+        #[test]
+        fn rejects_padding_longer_than_payload() {
+            let mut buffer = [&RTP_HEADER[..], &[0x41, 0x05]].concat();
+            buffer[0] |= 0b0010_0000;
+
+            let result = RTPPacket::try_from(buffer.as_slice());
+
+            assert!(matches!(result, Err(ParseError::MalformedPacket)));
+        }
+    }
+}
@@ -32,49 +32,46 @@ RTP-dump format:
 +=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+
  */
 
-pub fn get_rtp_packets() -> Vec<RTPPacket> {
-    let rtp_dump = File::open("./assets/wireshark-dump-test.rtp").unwrap();
-    let mut reader = BufReader::new(rtp_dump);
-    let mut rtp_dump_header = vec![0u8; RTP_DUMP_HEADER_LEN + RD_HEADER_LEN]; // skip heading string + RT_D header
-    reader.read_exact(&mut rtp_dump_header).unwrap();
-
-    let mut rt_header_buffer = vec![0u8; 8];
-    let mut rtp_buffer = vec![0u8; 3000];
-
-    let mut rtp_packets = Vec::with_capacity(3000);
-
-    while let Ok(_) = reader.read_exact(&mut rt_header_buffer) {
-        let rt_header = get_rt_header(&rt_header_buffer);
-        rtp_buffer.resize(rt_header.rtp_length as usize, 0);
-
-        reader.read_exact(&mut rtp_buffer).unwrap();
-
-        let buffer: &[u8] = &rtp_buffer;
-        let rtp_packet = RTPPacket::try_from(buffer).unwrap();
-        rtp_packets.push(rtp_packet)
-    }
+const RTP_DUMP_PATH: &str = "./assets/wireshark-dump-test.rtp";
 
-    rtp_packets
+pub fn get_rtp_packets() -> Vec<RTPPacket> {
+    let rtp_dump = File::open(RTP_DUMP_PATH).unwrap();
+    read_rtp_packets(BufReader::new(rtp_dump))
 }
 pub fn get_rtp_packets_raw() -> Vec<Vec<u8>> {
-    let rtp_dump = File::open("./assets/wireshark-dump-test.rtp").unwrap();
-    let mut reader = BufReader::new(rtp_dump);
+    let rtp_dump = File::open(RTP_DUMP_PATH).unwrap();
+    read_rtp_packets_raw(BufReader::new(rtp_dump))
+}
+
+/** Parses RTP packets from any RTP-dump source, e.g. an in-memory `&[u8]`.
+Packets that aren't valid RTP are skipped.
+ */
+pub fn read_rtp_packets(source: impl Read) -> Vec<RTPPacket> {
+    read_rtp_packets_raw(source)
+        .iter()
+        .filter_map(|buffer| RTPPacket::try_from(buffer.as_slice()).ok())
+        .collect()
+}
+
+/** Reads raw RTP packets from any RTP-dump source. Stops at the first truncated record.
+ */
+pub fn read_rtp_packets_raw(mut source: impl Read) -> Vec<Vec<u8>> {
     let mut rtp_dump_header = vec![0u8; RTP_DUMP_HEADER_LEN + RD_HEADER_LEN]; // skip heading string + RT_D header
-    reader.read_exact(&mut rtp_dump_header).unwrap();
+    if source.read_exact(&mut rtp_dump_header).is_err() {
+        return vec![];
+    }
 
     let mut rt_header_buffer = vec![0u8; 8];
-    let mut rtp_buffer = vec![0u8; 3000];
-
     let mut rtp_packets = Vec::with_capacity(3000);
 
-    while let Ok(_) = reader.read_exact(&mut rt_header_buffer) {
+    while source.read_exact(&mut rt_header_buffer).is_ok() {
         let rt_header = get_rt_header(&rt_header_buffer);
-        rtp_buffer.resize(rt_header.rtp_length as usize, 0);
-
-        reader.read_exact(&mut rtp_buffer).unwrap();
+        let mut rtp_buffer = vec![0u8; rt_header.rtp_length as usize];
 
-        let buffer: &[u8] = &rtp_buffer;
-        rtp_packets.push(buffer.to_vec())
+        if source.read_exact(&mut rtp_buffer).is_err() {
+            break;
+        }
+        rtp_packets.push(rtp_buffer)
     }
 
     rtp_packets
@@ -0,0 +1,206 @@
+const NAL_TYPE_SPS: u8 = 7;
+
+/** Profiles whose SPS carries chroma format, bit depth and scaling matrices.
+https://www.itu.int/rec/T-REC-H.264 (7.3.2.1.1)
+ */
+const HIGH_PROFILE_IDCS: [u8; 12] = [100, 110, 122, 244, 44, 83, 86, 118, 128, 138, 139, 134];
+
+/** Reads the width and height, after cropping, from an SPS NAL unit, without decoding any picture.
+Only the syntax elements preceding the frame size are parsed, VUI parameters are ignored.
+https://www.itu.int/rec/T-REC-H.264 (7.3.2.1.1, 7.4.2.1.1)
+ */
+pub fn get_sps_resolution(nal_unit: &[u8]) -> Option<(u16, u16)> {
+    if nal_unit.first()? & 0b0001_1111 != NAL_TYPE_SPS {
+        return None;
+    }
+    let rbsp = remove_emulation_prevention(&nal_unit[1..]);
+    let mut reader = BitReader::new(&rbsp);
+
+    let profile_idc = reader.read_bits(8)? as u8;
+    // Constraint flags and level_idc
+    reader.read_bits(16)?;
+    let _seq_parameter_set_id = reader.read_ue()?;
+
+    let mut chroma_format_idc = 1;
+    let mut is_separate_colour_plane = false;
+    if HIGH_PROFILE_IDCS.contains(&profile_idc) || profile_idc == 135 {
+        chroma_format_idc = reader.read_ue()?;
+        if chroma_format_idc == 3 {
+            is_separate_colour_plane = reader.read_bit()?;
+        }
+        let _bit_depth_luma_minus8 = reader.read_ue()?;
+        let _bit_depth_chroma_minus8 = reader.read_ue()?;
+        let _qpprime_y_zero_transform_bypass_flag = reader.read_bit()?;
+        if reader.read_bit()? {
+            let scaling_list_count = if chroma_format_idc == 3 { 12 } else { 8 };
+            for index in 0..scaling_list_count {
+                if reader.read_bit()? {
+                    skip_scaling_list(&mut reader, if index < 6 { 16 } else { 64 })?;
+                }
+            }
+        }
+    }
+
+    let _log2_max_frame_num_minus4 = reader.read_ue()?;
+    match reader.read_ue()? {
+        0 => {
+            let _log2_max_pic_order_cnt_lsb_minus4 = reader.read_ue()?;
+        }
+        1 => {
+            let _delta_pic_order_always_zero_flag = reader.read_bit()?;
+            let _offset_for_non_ref_pic = reader.read_se()?;
+            let _offset_for_top_to_bottom_field = reader.read_se()?;
+            for _ in 0..reader.read_ue()? {
+                let _offset_for_ref_frame = reader.read_se()?;
+            }
+        }
+        _ => {}
+    }
+    let _max_num_ref_frames = reader.read_ue()?;
+    let _gaps_in_frame_num_value_allowed_flag = reader.read_bit()?;
+
+    let pic_width_in_mbs = reader.read_ue()? + 1;
+    let pic_height_in_map_units = reader.read_ue()? + 1;
+    let is_frame_mbs_only = reader.read_bit()?;
+    if !is_frame_mbs_only {
+        let _mb_adaptive_frame_field_flag = reader.read_bit()?;
+    }
+    let _direct_8x8_inference_flag = reader.read_bit()?;
+
+    // Interlaced streams code a field pair per map unit
+    let frame_height_factor = if is_frame_mbs_only { 1 } else { 2 };
+    let mut width = pic_width_in_mbs * 16;
+    let mut height = pic_height_in_map_units * 16 * frame_height_factor;
+
+    if reader.read_bit()? {
+        let (left, right, top, bottom) = (
+            reader.read_ue()?,
+            reader.read_ue()?,
+            reader.read_ue()?,
+            reader.read_ue()?,
+        );
+        // Crop offsets are in chroma sample units
+        let (crop_unit_x, crop_unit_y) = match chroma_format_idc {
+            _ if is_separate_colour_plane => (1, frame_height_factor),
+            0 => (1, frame_height_factor),
+            1 => (2, 2 * frame_height_factor),
+            2 => (2, frame_height_factor),
+            _ => (1, frame_height_factor),
+        };
+        width = width.checked_sub((left + right) * crop_unit_x)?;
+        height = height.checked_sub((top + bottom) * crop_unit_y)?;
+    }
+
+    Some((u16::try_from(width).ok()?, u16::try_from(height).ok()?))
+}
+
+fn skip_scaling_list(reader: &mut BitReader, size: usize) -> Option<()> {
+    let mut last_scale = 8i64;
+    let mut next_scale = 8i64;
+    for _ in 0..size {
+        if next_scale != 0 {
+            let delta_scale = reader.read_se()?;
+            next_scale = (last_scale + delta_scale + 256) % 256;
+        }
+        if next_scale != 0 {
+            last_scale = next_scale;
+        }
+    }
+    Some(())
+}
+
+/** Drops the 0x03 bytes inserted after two zero bytes, so that the payload doesn't mimic a start code.
+https://www.itu.int/rec/T-REC-H.264 (7.4.1)
+ */
+fn remove_emulation_prevention(payload: &[u8]) -> Vec<u8> {
+    let mut rbsp = Vec::with_capacity(payload.len());
+    let mut zero_count = 0;
+    for byte in payload {
+        if zero_count >= 2 && *byte == 0x03 {
+            zero_count = 0;
+            continue;
+        }
+        zero_count = if *byte == 0 { zero_count + 1 } else { 0 };
+        rbsp.push(*byte);
+    }
+    rbsp
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        BitReader { data, position: 0 }
+    }
+
+    fn read_bit(&mut self) -> Option<bool> {
+        let byte = self.data.get(self.position / 8)?;
+        let bit = (byte >> (7 - self.position % 8)) & 1;
+        self.position += 1;
+        Some(bit == 1)
+    }
+
+    fn read_bits(&mut self, count: usize) -> Option<u32> {
+        (0..count).try_fold(0u32, |value, _| Some(value << 1 | self.read_bit()? as u32))
+    }
+
+    /** Unsigned Exp-Golomb code, see 9.1.
+     */
+    fn read_ue(&mut self) -> Option<u32> {
+        let mut leading_zeros = 0;
+        while !self.read_bit()? {
+            leading_zeros += 1;
+            if leading_zeros > 31 {
+                return None;
+            }
+        }
+        Some((1u32 << leading_zeros) - 1 + self.read_bits(leading_zeros)?)
+    }
+
+    /** Signed Exp-Golomb code, mapped from the unsigned one as 1, -1, 2, -2, ...
+     */
+    fn read_se(&mut self) -> Option<i64> {
+        let value = self.read_ue()? as i64;
+        match value % 2 {
+            0 => Some(-(value / 2)),
+            _ => Some((value + 1) / 2),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod get_sps_resolution {
+        use crate::sps::get_sps_resolution;
+
+        #[test]
+        fn reads_constrained_baseline_resolution() {
+            // 1280x720, constrained baseline level 3.1 with VUI
+            let sps = [
+                0x67, 0x42, 0xc0, 0x1f, 0xda, 0x01, 0x40, 0x16, 0xe8, 0x40, 0x00, 0x00, 0x03, 0x00,
+                0x40, 0x00, 0x00, 0x0c, 0x03, 0xc6, 0x0c, 0xa8,
+            ];
+
+            assert_eq!(get_sps_resolution(&sps), Some((1280, 720)));
+        }
+
+        #[test]
+        fn applies_frame_cropping() {
+            // 1920x1088 coded, cropped by 8 rows to 1080, high profile level 4.0
+            let sps = [
+                0x67, 0x64, 0x00, 0x28, 0xac, 0xd9, 0x40, 0x78, 0x02, 0x27, 0xe5, 0x84, 0x00, 0x00,
+                0x03, 0x00, 0x04, 0x00, 0x00, 0x03, 0x00, 0xf0, 0x3c, 0x60, 0xc6, 0x58,
+            ];
+
+            assert_eq!(get_sps_resolution(&sps), Some((1920, 1080)));
+        }
+
+        #[test]
+        fn rejects_other_nal_units() {
+            assert_eq!(get_sps_resolution(&[0x68, 0xce, 0x38, 0x80]), None);
+        }
+    }
+}
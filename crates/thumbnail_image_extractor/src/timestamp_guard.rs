@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+
+use crate::rtp::RTPPacket;
+
+/** Drops packets whose RTP timestamp goes backwards within their SSRC. Corrupt streams doing so would
+ * otherwise split access units at the wrong boundaries and feed the decoder frames out of order.
+ * Timestamps wrap around, anything less than half the range ahead counts as newer.
+ */
+#[derive(Clone, Debug)]
+pub struct TimestampGuard {
+    last_timestamps: HashMap<u32, u32>,
+}
+
+impl TimestampGuard {
+    pub fn new() -> Self {
+        TimestampGuard {
+            last_timestamps: HashMap::new(),
+        }
+    }
+
+    pub fn is_monotonic(&mut self, packet: &RTPPacket) -> bool {
+        let last_timestamp = match self.last_timestamps.get(&packet.ssrc) {
+            Some(last_timestamp) => *last_timestamp,
+            None => {
+                self.last_timestamps.insert(packet.ssrc, packet.timestamp);
+                return true;
+            }
+        };
+
+        // Packets of a single frame share the timestamp
+        if packet.timestamp.wrapping_sub(last_timestamp) > u32::MAX / 2 {
+            return false;
+        }
+        self.last_timestamps.insert(packet.ssrc, packet.timestamp);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod timestamp_guard {
+        use crate::rtp::RTPPacket;
+        use crate::timestamp_guard::TimestampGuard;
+
+        fn get_rtp_packet(ssrc: u32, timestamp: u32) -> RTPPacket {
+            let buffer = [
+                &[0x80, 0x60, 0x00, 0x01][..],
+                &timestamp.to_be_bytes(),
+                &ssrc.to_be_bytes(),
+                &[0x41],
+            ]
+            .concat();
+            RTPPacket::try_from(buffer.as_slice()).expect("Should parse RTP packet")
+        }
+
+        #[test]
+        fn drops_backwards_timestamp() {
+            let mut guard = TimestampGuard::new();
+
+            assert!(guard.is_monotonic(&get_rtp_packet(1, 6000)));
+            assert!(guard.is_monotonic(&get_rtp_packet(1, 6000)));
+            assert!(!guard.is_monotonic(&get_rtp_packet(1, 3000)));
+            assert!(guard.is_monotonic(&get_rtp_packet(1, 9000)));
+        }
+
+        #[test]
+        fn accepts_timestamp_wraparound() {
+            let mut guard = TimestampGuard::new();
+
+            assert!(guard.is_monotonic(&get_rtp_packet(1, u32::MAX - 1000)));
+            assert!(guard.is_monotonic(&get_rtp_packet(1, 2000)));
+            assert!(!guard.is_monotonic(&get_rtp_packet(1, u32::MAX - 1000)));
+        }
+
+        #[test]
+        fn tracks_ssrcs_separately() {
+            let mut guard = TimestampGuard::new();
+
+            assert!(guard.is_monotonic(&get_rtp_packet(1, 6000)));
+            assert!(guard.is_monotonic(&get_rtp_packet(2, 3000)));
+        }
+    }
+}
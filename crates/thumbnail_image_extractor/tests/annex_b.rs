@@ -0,0 +1,35 @@
+use thumbnail_image_extractor::to_annex_b;
+
+#[test]
+fn places_start_codes_in_multi_nal_access_unit() {
+    let sps = vec![0x67, 0x42, 0xc0, 0x1f];
+    let pps = vec![0x68, 0xce, 0x3c, 0x80];
+    let sei = vec![0x06, 0x05, 0x01];
+    let idr_slice = vec![0x65, 0x88, 0x84, 0x00];
+
+    let stream = to_annex_b(&[sps, pps, sei, idr_slice]);
+
+    assert_eq!(
+        stream,
+        [
+            vec![0, 0, 0, 1, 0x67, 0x42, 0xc0, 0x1f],
+            vec![0, 0, 0, 1, 0x68, 0xce, 0x3c, 0x80],
+            vec![0, 0, 1, 0x06, 0x05, 0x01],
+            vec![0, 0, 1, 0x65, 0x88, 0x84, 0x00],
+        ]
+        .concat()
+    );
+}
+
+#[test]
+fn prefixes_first_nal_unit_with_zero_byte() {
+    let first_slice = vec![0x41, 0x9a, 0x02];
+    let second_slice = vec![0x41, 0x9a, 0x03];
+
+    let stream = to_annex_b(&[first_slice, second_slice]);
+
+    assert_eq!(
+        stream,
+        vec![0, 0, 0, 1, 0x41, 0x9a, 0x02, 0, 0, 1, 0x41, 0x9a, 0x03]
+    );
+}
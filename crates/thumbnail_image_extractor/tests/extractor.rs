@@ -50,3 +50,16 @@ fn find_yuv_in_extract() {
 
     assert_eq!(oks.is_empty(), false);
 }
+
+#[test]
+fn reads_resolution_matching_decoded_picture() {
+    let test_packets = get_rtp_packets_raw();
+    let mut extractor = ThumbnailExtractor::new();
+
+    for packet in test_packets {
+        extractor.try_extract_thumbnail(&packet);
+    }
+
+    let picture = extractor.last_picture.as_ref().unwrap();
+    assert_eq!(extractor.resolution, Some((picture.width, picture.height)));
+}
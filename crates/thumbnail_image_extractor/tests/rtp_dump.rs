@@ -0,0 +1,53 @@
+use thumbnail_image_extractor::{read_rtp_packets, read_rtp_packets_raw};
+
+fn get_rtp_dump(rtp_packets: &[Vec<u8>]) -> Vec<u8> {
+    let heading = b"#!rtpplay1.0 10.0.0.10/5004\n";
+    let rd_header = [0u8; 16];
+    let mut rtp_dump = [&heading[..], &rd_header].concat();
+
+    for (index, rtp_packet) in rtp_packets.iter().enumerate() {
+        let rtp_length = rtp_packet.len() as u16;
+        rtp_dump.extend_from_slice(&(rtp_length + 8).to_be_bytes());
+        rtp_dump.extend_from_slice(&rtp_length.to_be_bytes());
+        rtp_dump.extend_from_slice(&(index as u32 * 20).to_be_bytes());
+        rtp_dump.extend_from_slice(rtp_packet);
+    }
+
+    rtp_dump
+}
+
+fn get_rtp_packet(sequence_number: u16) -> Vec<u8> {
+    let header = [0x80, 0x60];
+    let timestamp = 3000u32.to_be_bytes();
+    let ssrc = 1u32.to_be_bytes();
+    let payload = [0x41, 0x9a, 0x02];
+
+    [
+        &header[..],
+        &sequence_number.to_be_bytes(),
+        &timestamp,
+        &ssrc,
+        &payload,
+    ]
+    .concat()
+}
+
+#[test]
+fn reads_rtp_packets_from_buffer() {
+    let rtp_dump = get_rtp_dump(&[get_rtp_packet(1), get_rtp_packet(2), get_rtp_packet(3)]);
+
+    let rtp_packets = read_rtp_packets(rtp_dump.as_slice());
+
+    assert_eq!(rtp_packets.len(), 3);
+    assert_eq!(rtp_packets[2].sequence_number, 3);
+}
+
+#[test]
+fn stops_at_truncated_record() {
+    let rtp_dump = get_rtp_dump(&[get_rtp_packet(1), get_rtp_packet(2)]);
+    let truncated_dump = &rtp_dump[..rtp_dump.len() - 1];
+
+    let rtp_packets = read_rtp_packets_raw(truncated_dump);
+
+    assert_eq!(rtp_packets, vec![get_rtp_packet(1)]);
+}
@@ -1,50 +1,239 @@
 use std::{fmt::Write, sync::Arc};
-use std::fs::read;
-use std::path::PathBuf;
+use std::fs::{read, write};
+use std::io;
+use std::path::{Path, PathBuf};
 
+use openssl::asn1::Asn1Time;
+use openssl::bn::{BigNum, MsbOption};
+use openssl::ec::{EcGroup, EcKey};
+use openssl::error::ErrorStack;
 use openssl::hash::MessageDigest;
-use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod, SslVerifyMode};
-use openssl::x509::X509;
+use openssl::nid::Nid;
+use openssl::pkey::{PKey, Private};
+use openssl::ssl::{SslAcceptor, SslAcceptorBuilder, SslFiletype, SslMethod, SslVerifyMode};
+use openssl::x509::{X509, X509NameBuilder};
+
+const CERT_FILE: &str = "cert.pem";
+const KEY_FILE: &str = "key.pem";
+const CERTIFICATE_COMMON_NAME: &str = "sinder";
+const CERTIFICATE_VALIDITY_DAYS: u32 = 365;
 
 pub struct SSLConfig {
     pub acceptor: Arc<SslAcceptor>,
     pub fingerprint: String,
 }
 
+/** Where the DTLS certificate comes from. Peers only check it against the SDP fingerprint, so a
+ * self-signed one works as well as any other.
+ */
+pub enum CertificateSource {
+    /** `key.pem` and `cert.pem` of the directory, managed externally.
+     */
+    Files(PathBuf),
+    /** Self-signed certificate generated at startup, a new one on every run.
+     */
+    Generated,
+    /** Self-signed certificate generated into the directory on first run, loaded on later ones.
+     */
+    Persisted(PathBuf),
+}
+
 impl SSLConfig {
-    pub fn new(cert_dir: PathBuf) -> SSLConfig {
-        let cert_path = cert_dir.join("cert.pem");
-        let cert_key_path = cert_dir.join("key.pem");
+    pub fn new(source: CertificateSource) -> SSLConfig {
         let mut acceptor_builder = SslAcceptor::mozilla_intermediate(SslMethod::dtls()).unwrap();
-        acceptor_builder
-            .set_private_key_file(cert_key_path, SslFiletype::PEM)
-            .expect("Missing private key file");
-        acceptor_builder
-            .set_certificate_chain_file(cert_path.as_path())
-            .expect("Missing cert file");
         acceptor_builder.set_verify(SslVerifyMode::NONE);
         acceptor_builder
             .set_tlsext_use_srtp("SRTP_AES128_CM_SHA1_80")
             .expect("Failed enabling DTLS extension");
 
+        let x509 = match source {
+            CertificateSource::Files(cert_dir) => {
+                load_certificate(&mut acceptor_builder, &cert_dir)
+            }
+            CertificateSource::Generated => {
+                let (private_key, x509) =
+                    generate_certificate().expect("Failed generating certificate");
+                use_certificate(&mut acceptor_builder, &private_key, &x509);
+                x509
+            }
+            CertificateSource::Persisted(cert_dir) if cert_dir.join(CERT_FILE).exists() => {
+                load_certificate(&mut acceptor_builder, &cert_dir)
+            }
+            CertificateSource::Persisted(cert_dir) => {
+                let (private_key, x509) =
+                    generate_certificate().expect("Failed generating certificate");
+                write_certificate(&cert_dir, &private_key, &x509)
+                    .expect("Failed writing certificate files");
+                eprintln!("Generated certificate in {}", cert_dir.display());
+                use_certificate(&mut acceptor_builder, &private_key, &x509);
+                x509
+            }
+        };
+
         let acceptor = Arc::new(acceptor_builder.build());
+        let fingerprint = get_fingerprint(&x509);
 
-        let cert_file = read(cert_path).expect("Failed to read cert file");
+        SSLConfig {
+            acceptor,
+            fingerprint,
+        }
+    }
 
-        let x509 = X509::from_pem(&cert_file).unwrap();
-        let x509_digest = x509.digest(MessageDigest::sha256()).unwrap();
+    /** Certificate fingerprint in form of SDP's fingerprint attribute, e.g. "sha-256 EF:53:...".
+     */
+    pub fn get_sdp_fingerprint(&self) -> String {
+        format!("sha-256 {}", self.fingerprint)
+    }
+}
 
-        let mut fingerprint = String::new();
-        for i in 0..x509_digest.len() {
-            write!(fingerprint, "{:02X}", x509_digest[i]).unwrap();
-            if i != x509_digest.len() - 1 {
-                write!(fingerprint, ":").unwrap();
-            }
+fn load_certificate(acceptor_builder: &mut SslAcceptorBuilder, cert_dir: &Path) -> X509 {
+    let cert_path = cert_dir.join(CERT_FILE);
+    let cert_key_path = cert_dir.join(KEY_FILE);
+    acceptor_builder
+        .set_private_key_file(cert_key_path, SslFiletype::PEM)
+        .expect("Missing private key file");
+    acceptor_builder
+        .set_certificate_chain_file(cert_path.as_path())
+        .expect("Missing cert file");
+
+    let cert_file = read(cert_path).expect("Failed to read cert file");
+    X509::from_pem(&cert_file).unwrap()
+}
+
+fn use_certificate(
+    acceptor_builder: &mut SslAcceptorBuilder,
+    private_key: &PKey<Private>,
+    x509: &X509,
+) {
+    acceptor_builder
+        .set_private_key(private_key)
+        .expect("Invalid private key");
+    acceptor_builder
+        .set_certificate(x509)
+        .expect("Invalid certificate");
+}
+
+/** Self-signed certificate with an ECDSA P-256 key, the key type browsers default to for WebRTC.
+ */
+fn generate_certificate() -> Result<(PKey<Private>, X509), ErrorStack> {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+    let private_key = PKey::from_ec_key(EcKey::generate(&group)?)?;
+
+    let mut name_builder = X509NameBuilder::new()?;
+    name_builder.append_entry_by_nid(Nid::COMMONNAME, CERTIFICATE_COMMON_NAME)?;
+    let name = name_builder.build();
+
+    let mut serial_number = BigNum::new()?;
+    serial_number.rand(64, MsbOption::MAYBE_ZERO, false)?;
+    let serial_number = serial_number.to_asn1_integer()?;
+    let not_before = Asn1Time::days_from_now(0)?;
+    let not_after = Asn1Time::days_from_now(CERTIFICATE_VALIDITY_DAYS)?;
+
+    let mut x509_builder = X509::builder()?;
+    x509_builder.set_version(2)?;
+    x509_builder.set_serial_number(&serial_number)?;
+    x509_builder.set_subject_name(&name)?;
+    x509_builder.set_issuer_name(&name)?;
+    x509_builder.set_pubkey(&private_key)?;
+    x509_builder.set_not_before(&not_before)?;
+    x509_builder.set_not_after(&not_after)?;
+    x509_builder.sign(&private_key, MessageDigest::sha256())?;
+
+    Ok((private_key, x509_builder.build()))
+}
+
+fn write_certificate(cert_dir: &Path, private_key: &PKey<Private>, x509: &X509) -> io::Result<()> {
+    write_private_key(
+        &cert_dir.join(KEY_FILE),
+        &private_key.private_key_to_pem_pkcs8()?,
+    )?;
+    write(cert_dir.join(CERT_FILE), x509.to_pem()?)
+}
+
+/** Private key is kept readable by the owner only.
+ */
+#[cfg(unix)]
+fn write_private_key(path: &Path, pem: &[u8]) -> io::Result<()> {
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?
+        .write_all(pem)
+}
+
+#[cfg(not(unix))]
+fn write_private_key(path: &Path, pem: &[u8]) -> io::Result<()> {
+    write(path, pem)
+}
+
+/** SHA-256 digest of the DER-encoded certificate, as colon-separated uppercase hex bytes.
+ */
+fn get_fingerprint(x509: &X509) -> String {
+    let x509_digest = x509.digest(MessageDigest::sha256()).unwrap();
+
+    let mut fingerprint = String::new();
+    for i in 0..x509_digest.len() {
+        write!(fingerprint, "{:02X}", x509_digest[i]).unwrap();
+        if i != x509_digest.len() - 1 {
+            write!(fingerprint, ":").unwrap();
         }
+    }
+    fingerprint
+}
 
-        SSLConfig {
-            acceptor,
-            fingerprint,
+#[cfg(test)]
+mod tests {
+    mod ssl_config {
+        use std::fs;
+
+        use openssl::sha::sha256;
+        use openssl::x509::X509;
+
+        use sdp::SDPResolver;
+
+        use crate::acceptor::{CertificateSource, SSLConfig, CERT_FILE};
+
+        fn get_hex_digest(der: &[u8]) -> String {
+            sha256(der)
+                .iter()
+                .map(|byte| format!("{:02X}", byte))
+                .collect::<Vec<_>>()
+                .join(":")
+        }
+
+        #[test]
+        fn generates_certificate_with_valid_fingerprint() {
+            let ssl_config = SSLConfig::new(CertificateSource::Generated);
+
+            assert!(SDPResolver::is_valid_fingerprint(
+                &ssl_config.get_sdp_fingerprint()
+            ));
+        }
+
+        #[test]
+        fn persists_generated_certificate() {
+            let cert_dir =
+                std::env::temp_dir().join(format!("certs-{}", rand::random::<u32>()));
+            fs::create_dir(&cert_dir).unwrap();
+
+            let ssl_config = SSLConfig::new(CertificateSource::Persisted(cert_dir.clone()));
+            let x509 = X509::from_pem(&fs::read(cert_dir.join(CERT_FILE)).unwrap()).unwrap();
+            assert_eq!(
+                ssl_config.fingerprint,
+                get_hex_digest(&x509.to_der().unwrap())
+            );
+
+            // Later runs load the same certificate
+            let reloaded_config = SSLConfig::new(CertificateSource::Persisted(cert_dir.clone()));
+            assert_eq!(reloaded_config.fingerprint, ssl_config.fingerprint);
+
+            fs::remove_dir_all(cert_dir).unwrap();
         }
     }
 }
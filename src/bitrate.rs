@@ -0,0 +1,99 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/** Default sliding window used for bitrate estimates.
+ */
+pub const BITRATE_WINDOW: Duration = Duration::from_secs(1);
+
+/** Counts bytes seen within a sliding time window.
+ */
+#[derive(Debug)]
+pub struct BitrateCounter {
+    window: Duration,
+    samples: VecDeque<(Instant, usize)>,
+    window_bytes: usize,
+}
+
+impl BitrateCounter {
+    pub fn new(window: Duration) -> Self {
+        BitrateCounter {
+            window,
+            samples: VecDeque::new(),
+            window_bytes: 0,
+        }
+    }
+
+    pub fn add(&mut self, bytes: usize, now: Instant) {
+        self.samples.push_back((now, bytes));
+        self.window_bytes += bytes;
+        self.evict_samples(now);
+    }
+
+    /** Bits per second averaged over the window ending at `now`.
+     */
+    pub fn bitrate_bps(&mut self, now: Instant) -> u64 {
+        self.evict_samples(now);
+        let window_millis = self.window.as_millis().max(1) as u64;
+        self.window_bytes as u64 * 8 * 1000 / window_millis
+    }
+
+    fn evict_samples(&mut self, now: Instant) {
+        while let Some((timestamp, bytes)) = self.samples.front() {
+            if now.duration_since(*timestamp) < self.window {
+                break;
+            }
+            self.window_bytes -= bytes;
+            self.samples.pop_front();
+        }
+    }
+}
+
+/** Ingress and egress bitrate of a single room.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RoomBitrate {
+    pub ingress_bps: u64,
+    pub egress_bps: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    mod bitrate_counter {
+        use std::time::{Duration, Instant};
+
+        use crate::bitrate::BitrateCounter;
+
+        #[test]
+        fn computes_bitrate_over_window() {
+            let mut counter = BitrateCounter::new(Duration::from_secs(1));
+            let start = Instant::now();
+
+            // 1000 bytes every 100ms
+            for index in 0..10 {
+                counter.add(1000, start + Duration::from_millis(index * 100));
+            }
+
+            assert_eq!(
+                counter.bitrate_bps(start + Duration::from_millis(900)),
+                80_000
+            );
+        }
+
+        #[test]
+        fn drops_samples_outside_window() {
+            let mut counter = BitrateCounter::new(Duration::from_secs(1));
+            let start = Instant::now();
+
+            for index in 0..10 {
+                counter.add(1000, start + Duration::from_millis(index * 100));
+            }
+
+            // Only samples from 600ms onwards remain
+            assert_eq!(
+                counter.bitrate_bps(start + Duration::from_millis(1500)),
+                32_000
+            );
+            assert_eq!(counter.bitrate_bps(start + Duration::from_secs(5)), 0);
+        }
+    }
+}
@@ -0,0 +1,305 @@
+use std::fs::File;
+use std::io::Write;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use byteorder::{ByteOrder, NetworkEndian};
+
+use crate::ice_registry::RoomID;
+
+/**
+pcap format, in network byte order:
+- File starts with a global header
+
+ 0                   1                   2                   3
+ 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
++-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+|                     Magic number 0xa1b2c3d4                   |
++-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+|        Major version          |         Minor version         |
++-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+|                     Time zone offset                          |
++-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+|                     Timestamp accuracy                        |
++-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+|                     Snapshot length                           |
++-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+|                     Link type                                 |
++=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+
+
+- Then each packet is preceded by a record header
+
+ 0                   1                   2                   3
+ 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
++-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+|                     Timestamp seconds                         |
++-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+|                     Timestamp microseconds                    |
++-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+|                     Captured length                           |
++-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+|                     Original length                           |
++=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+
+https://datatracker.ietf.org/doc/draft-ietf-opsawg-pcap/
+ */
+const PCAP_MAGIC_NUMBER: u32 = 0xa1b2c3d4;
+const PCAP_GLOBAL_HEADER_LEN: usize = 24;
+const PCAP_RECORD_HEADER_LEN: usize = 16;
+const PCAP_SNAPSHOT_LEN: u32 = 65535;
+/** Raw IP packets, the family is read from the version field of each packet.
+ */
+const LINKTYPE_RAW: u32 = 101;
+
+const IPV4_HEADER_LEN: usize = 20;
+const IPV6_HEADER_LEN: usize = 40;
+const UDP_HEADER_LEN: usize = 8;
+const UDP_PROTOCOL: u8 = 17;
+const HOP_LIMIT: u8 = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Inbound,
+    Outbound,
+}
+
+/** Writes the datagrams of a single session to a pcap file, for troubleshooting.
+ * Each datagram is wrapped in IP/UDP headers built from the session addresses, so that the direction shows up
+ * as source and destination, and Wireshark can decode it as STUN, DTLS or SRTP.
+ * Clones write to the same file, e.g. the DTLS transport and the shard sending SRTP.
+ */
+#[derive(Debug, Clone)]
+pub struct PacketCapture {
+    local_address: SocketAddr,
+    writer: Arc<Mutex<PcapWriter<File>>>,
+}
+
+impl PacketCapture {
+    /** Creates the capture file of a session in the capture directory, named after its start time, room and
+     * remote address.
+     */
+    pub fn create(
+        capture_dir: &Path,
+        local_address: SocketAddr,
+        remote: SocketAddr,
+        room_id: RoomID,
+    ) -> std::io::Result<Self> {
+        let started_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let remote = remote.to_string().replace([':', '[', ']'], "_");
+        let path = capture_dir.join(format!("{started_at}-{room_id}-{remote}.pcap"));
+        let writer = PcapWriter::new(File::create(path)?)?;
+
+        Ok(PacketCapture {
+            local_address,
+            writer: Arc::new(Mutex::new(writer)),
+        })
+    }
+
+    pub fn record(&self, direction: Direction, remote: SocketAddr, datagram: &[u8]) {
+        let mut writer = match self.writer.lock() {
+            Ok(writer) => writer,
+            Err(_) => return,
+        };
+        let result = writer.write_record(
+            SystemTime::now(),
+            direction,
+            self.local_address,
+            remote,
+            datagram,
+        );
+        if let Err(err) = result {
+            eprintln!("Couldn't write packet capture {}", err)
+        }
+    }
+}
+
+/** Writes pcap records to any sink, e.g. a file or an in-memory `Vec<u8>`.
+ */
+#[derive(Debug)]
+pub struct PcapWriter<W: Write> {
+    sink: W,
+}
+
+impl<W: Write> PcapWriter<W> {
+    pub fn new(mut sink: W) -> std::io::Result<Self> {
+        let mut header = [0u8; PCAP_GLOBAL_HEADER_LEN];
+        NetworkEndian::write_u32(&mut header[0..4], PCAP_MAGIC_NUMBER);
+        NetworkEndian::write_u16(&mut header[4..6], 2);
+        NetworkEndian::write_u16(&mut header[6..8], 4);
+        NetworkEndian::write_u32(&mut header[16..20], PCAP_SNAPSHOT_LEN);
+        NetworkEndian::write_u32(&mut header[20..24], LINKTYPE_RAW);
+        sink.write_all(&header)?;
+
+        Ok(PcapWriter { sink })
+    }
+
+    /** Writes a datagram sent between the local and the remote address, in the given direction.
+     */
+    pub fn write_record(
+        &mut self,
+        timestamp: SystemTime,
+        direction: Direction,
+        local: SocketAddr,
+        remote: SocketAddr,
+        datagram: &[u8],
+    ) -> std::io::Result<()> {
+        let (source, destination) = match direction {
+            Direction::Inbound => (remote, local),
+            Direction::Outbound => (local, remote),
+        };
+        let packet = create_ip_packet(source, destination, datagram);
+        let timestamp = timestamp.duration_since(UNIX_EPOCH).unwrap_or_default();
+
+        let mut header = [0u8; PCAP_RECORD_HEADER_LEN];
+        NetworkEndian::write_u32(&mut header[0..4], timestamp.as_secs() as u32);
+        NetworkEndian::write_u32(&mut header[4..8], timestamp.subsec_micros());
+        NetworkEndian::write_u32(&mut header[8..12], packet.len() as u32);
+        NetworkEndian::write_u32(&mut header[12..16], packet.len() as u32);
+
+        // Written in one go, so that a crash leaves at most one truncated record
+        self.sink.write_all(&[&header[..], &packet].concat())?;
+        self.sink.flush()
+    }
+}
+
+/** Wraps a datagram in UDP and IP headers. Mixed address families are written as IPv6, with the IPv4
+ * address mapped. The UDP checksum is left out.
+ */
+fn create_ip_packet(source: SocketAddr, destination: SocketAddr, datagram: &[u8]) -> Vec<u8> {
+    let udp_len = UDP_HEADER_LEN + datagram.len();
+    let mut udp_header = [0u8; UDP_HEADER_LEN];
+    NetworkEndian::write_u16(&mut udp_header[0..2], source.port());
+    NetworkEndian::write_u16(&mut udp_header[2..4], destination.port());
+    NetworkEndian::write_u16(&mut udp_header[4..6], udp_len as u16);
+
+    let ip_header = match (source.ip(), destination.ip()) {
+        (IpAddr::V4(source), IpAddr::V4(destination)) => {
+            let mut header = vec![0u8; IPV4_HEADER_LEN];
+            header[0] = 0x45;
+            NetworkEndian::write_u16(&mut header[2..4], (IPV4_HEADER_LEN + udp_len) as u16);
+            // Don't fragment
+            header[6] = 0x40;
+            header[8] = HOP_LIMIT;
+            header[9] = UDP_PROTOCOL;
+            header[12..16].copy_from_slice(&source.octets());
+            header[16..20].copy_from_slice(&destination.octets());
+            let checksum = get_ipv4_checksum(&header);
+            NetworkEndian::write_u16(&mut header[10..12], checksum);
+            header
+        }
+        (source, destination) => {
+            let mut header = vec![0u8; IPV6_HEADER_LEN];
+            header[0] = 0x60;
+            NetworkEndian::write_u16(&mut header[4..6], udp_len as u16);
+            header[6] = UDP_PROTOCOL;
+            header[7] = HOP_LIMIT;
+            header[8..24].copy_from_slice(&to_ipv6(source).octets());
+            header[24..40].copy_from_slice(&to_ipv6(destination).octets());
+            header
+        }
+    };
+
+    [&ip_header, &udp_header[..], datagram].concat()
+}
+
+fn to_ipv6(ip: IpAddr) -> std::net::Ipv6Addr {
+    match ip {
+        IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+        IpAddr::V6(ip) => ip,
+    }
+}
+
+/** One's complement sum of the header 16-bit words.
+https://datatracker.ietf.org/doc/html/rfc791#section-3.1
+ */
+fn get_ipv4_checksum(header: &[u8]) -> u16 {
+    let mut sum = header
+        .chunks(2)
+        .map(|word| NetworkEndian::read_u16(word) as u32)
+        .sum::<u32>();
+    while sum > 0xFFFF {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    mod packet_capture {
+        use std::fs;
+        use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+        use byteorder::{ByteOrder, NetworkEndian};
+
+        use crate::capture::{Direction, PacketCapture};
+
+        #[test]
+        fn writes_global_header_and_records() {
+            let capture_dir =
+                std::env::temp_dir().join(format!("capture-{}", rand::random::<u32>()));
+            fs::create_dir_all(&capture_dir).unwrap();
+            let local = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 52000);
+            let remote = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), 40000);
+            let datagram = [0x80, 0x60, 0x00, 0x01];
+
+            let capture = PacketCapture::create(&capture_dir, local, remote, 7).unwrap();
+            capture.record(Direction::Inbound, remote, &datagram);
+            capture.record(Direction::Outbound, remote, &datagram);
+
+            let file = fs::read_dir(&capture_dir).unwrap().next().unwrap().unwrap();
+            let pcap = fs::read(file.path()).unwrap();
+            fs::remove_dir_all(&capture_dir).unwrap();
+
+            // Global header
+            assert_eq!(NetworkEndian::read_u32(&pcap[0..4]), 0xa1b2c3d4);
+            assert_eq!(NetworkEndian::read_u16(&pcap[4..6]), 2);
+            assert_eq!(NetworkEndian::read_u16(&pcap[6..8]), 4);
+            assert_eq!(NetworkEndian::read_u32(&pcap[20..24]), 101);
+
+            // Inbound record, an IPv4/UDP packet from the remote
+            let record = &pcap[24..];
+            let captured_len = NetworkEndian::read_u32(&record[8..12]) as usize;
+            assert_eq!(captured_len, 20 + 8 + datagram.len());
+            assert_eq!(
+                NetworkEndian::read_u32(&record[12..16]) as usize,
+                captured_len
+            );
+            let packet = &record[16..16 + captured_len];
+            assert_eq!(packet[0], 0x45);
+            assert_eq!(&packet[12..16], &[10, 0, 0, 2]);
+            assert_eq!(&packet[16..20], &[10, 0, 0, 1]);
+            assert_eq!(NetworkEndian::read_u16(&packet[20..22]), 40000);
+            assert_eq!(NetworkEndian::read_u16(&packet[22..24]), 52000);
+            assert_eq!(&packet[28..], &datagram);
+
+            // Outbound record, addresses swapped
+            let record = &record[16 + captured_len..];
+            assert_eq!(record.len(), 16 + captured_len);
+            assert_eq!(&record[16 + 12..16 + 16], &[10, 0, 0, 1]);
+            assert_eq!(&record[16 + 16..16 + 20], &[10, 0, 0, 2]);
+        }
+    }
+
+    mod get_ipv4_checksum {
+        use crate::capture::get_ipv4_checksum;
+
+        #[test]
+        fn validates_with_checksum_filled_in() {
+            // Checksum field at bytes 10..12 left zeroed
+            let mut header = [
+                0x45, 0x00, 0x00, 0x73, 0x00, 0x00, 0x40, 0x00, 0x40, 0x11, 0x00, 0x00, 0xc0, 0xa8,
+                0x00, 0x01, 0xc0, 0xa8, 0x00, 0xc7,
+            ];
+
+            let checksum = get_ipv4_checksum(&header);
+            header[10..12].copy_from_slice(&checksum.to_be_bytes());
+
+            assert_eq!(checksum, 0xb861);
+            assert_eq!(get_ipv4_checksum(&header), 0);
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use std::{fmt, io, mem};
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use std::io::{Error, ErrorKind, Read, Write};
 use std::net::{SocketAddr, UdpSocket};
 
@@ -7,8 +7,11 @@ use openssl::error::ErrorStack;
 use openssl::ssl::{HandshakeError, MidHandshakeSslStream, SslStream};
 use srtp::openssl::{InboundSession, OutboundSession};
 
+use crate::capture::{Direction, PacketCapture};
 use crate::client::ClientError::{IncompletePacketRead, OpenSslError};
-use crate::config::get_global_config;
+use crate::config::{get_global_config, is_log_enabled, LogLevel};
+use crate::replay::ReplayWindow;
+use crate::rtp::{get_rtp_header_data, get_rtp_sequence_number};
 
 #[derive(Debug)]
 pub enum ClientSslState {
@@ -19,9 +22,120 @@ pub enum ClientSslState {
 
 #[derive(Debug)]
 pub struct EstablishedStream {
-    pub ssl_stream: SslStream<UDPPeerStream>,
-    pub srtp_inbound: InboundSession,
-    pub srtp_outbound: OutboundSession,
+    protection: MediaProtection,
+    /** Replay window of every SSRC, with the count of authenticated packets when it last moved.
+     */
+    replay_windows: HashMap<u32, (ReplayWindow, u64)>,
+    authenticated_packets: u64,
+}
+
+#[derive(Debug)]
+enum MediaProtection {
+    Srtp {
+        ssl_stream: SslStream<UDPPeerStream>,
+        inbound: InboundSession,
+        outbound: OutboundSession,
+    },
+    /** Plain RTP and RTCP without DTLS, so that recorded dumps can be fed through the server.
+     * Builds without the insecure-plaintext-media feature can't create it, outside of tests.
+     */
+    #[cfg(any(test, feature = "insecure-plaintext-media"))]
+    Plaintext(UDPPeerStream),
+}
+
+/** Shortest packets SRTP can process, a fixed RTP header and an RTCP header with the sender SSRC.
+ */
+const MIN_RTP_PACKET_LEN: usize = 12;
+const MIN_RTCP_PACKET_LEN: usize = 8;
+
+/** Most SSRCs a single peer keeps replay windows for, well above any negotiated session.
+ */
+const MAX_REPLAY_WINDOWS: usize = 32;
+
+impl EstablishedStream {
+    pub fn protect(&mut self, packet: &mut Vec<u8>) -> Result<(), SRTPError> {
+        check_packet_len(packet, MIN_RTP_PACKET_LEN)?;
+        match &mut self.protection {
+            MediaProtection::Srtp { outbound, .. } => Ok(outbound.protect(packet)?),
+            #[cfg(any(test, feature = "insecure-plaintext-media"))]
+            MediaProtection::Plaintext(_) => Ok(()),
+        }
+    }
+
+    pub fn protect_rtcp(&mut self, packet: &mut Vec<u8>) -> Result<(), SRTPError> {
+        check_packet_len(packet, MIN_RTCP_PACKET_LEN)?;
+        match &mut self.protection {
+            MediaProtection::Srtp { outbound, .. } => Ok(outbound.protect_rtcp(packet)?),
+            #[cfg(any(test, feature = "insecure-plaintext-media"))]
+            MediaProtection::Plaintext(_) => Ok(()),
+        }
+    }
+
+    /** Unprotects an RTP packet, rejecting packets already received on its SSRC. Windows are only
+     * created and moved once the packet is authenticated, so that forged packets can neither push
+     * them forward nor fill the map with made-up SSRCs. Past [MAX_REPLAY_WINDOWS] SSRCs, the window
+     * that has been silent the longest is dropped.
+     */
+    pub fn unprotect(&mut self, packet: &mut Vec<u8>) -> Result<(), SRTPError> {
+        let ssrc = get_rtp_header_data(packet)
+            .ok_or(SRTPError::PacketTooShort)?
+            .ssrc;
+        let sequence_number = get_rtp_sequence_number(packet);
+        let index = match self.replay_windows.get(&ssrc) {
+            Some((replay_window, _)) => replay_window.check(sequence_number)?,
+            None => ReplayWindow::new().check(sequence_number)?,
+        };
+        match &mut self.protection {
+            MediaProtection::Srtp { inbound, .. } => inbound.unprotect(packet)?,
+            #[cfg(any(test, feature = "insecure-plaintext-media"))]
+            MediaProtection::Plaintext(_) => {}
+        }
+
+        self.authenticated_packets += 1;
+        if !self.replay_windows.contains_key(&ssrc)
+            && self.replay_windows.len() >= MAX_REPLAY_WINDOWS
+        {
+            let stalest_ssrc = self
+                .replay_windows
+                .iter()
+                .min_by_key(|(_, (_, last_packet))| *last_packet)
+                .map(|(ssrc, _)| *ssrc);
+            if let Some(stalest_ssrc) = stalest_ssrc {
+                self.replay_windows.remove(&stalest_ssrc);
+            }
+        }
+        let (replay_window, last_packet) = self
+            .replay_windows
+            .entry(ssrc)
+            .or_insert_with(|| (ReplayWindow::new(), 0));
+        replay_window.register(index);
+        *last_packet = self.authenticated_packets;
+        Ok(())
+    }
+
+    pub fn unprotect_rtcp(&mut self, packet: &mut Vec<u8>) -> Result<(), SRTPError> {
+        check_packet_len(packet, MIN_RTCP_PACKET_LEN)?;
+        match &mut self.protection {
+            MediaProtection::Srtp { inbound, .. } => Ok(inbound.unprotect_rtcp(packet)?),
+            #[cfg(any(test, feature = "insecure-plaintext-media"))]
+            MediaProtection::Plaintext(_) => Ok(()),
+        }
+    }
+
+    fn get_udp_stream(&mut self) -> &mut UDPPeerStream {
+        match &mut self.protection {
+            MediaProtection::Srtp { ssl_stream, .. } => ssl_stream.get_mut(),
+            #[cfg(any(test, feature = "insecure-plaintext-media"))]
+            MediaProtection::Plaintext(udp_stream) => udp_stream,
+        }
+    }
+}
+
+fn check_packet_len(packet: &[u8], min_len: usize) -> Result<(), SRTPError> {
+    match packet.len() < min_len {
+        true => Err(SRTPError::PacketTooShort),
+        false => Ok(()),
+    }
 }
 
 #[derive(Debug)]
@@ -31,9 +145,18 @@ pub struct Client {
 }
 
 impl Client {
-    pub fn new(remote: SocketAddr, socket: UdpSocket) -> Result<Self, ErrorStack> {
-        let udp_stream = UDPPeerStream::new(socket, remote.clone());
+    pub fn new(
+        remote: SocketAddr,
+        socket: UdpSocket,
+        capture: Option<PacketCapture>,
+    ) -> Result<Self, ErrorStack> {
         let config = get_global_config();
+        #[cfg(feature = "insecure-plaintext-media")]
+        if config.udp_server_config.insecure_plaintext_media {
+            return Ok(Client::new_plaintext(remote, socket, capture));
+        }
+
+        let udp_stream = UDPPeerStream::new(socket, remote.clone(), capture);
         match config.ssl_config.acceptor.accept(udp_stream) {
             Ok(_) => unreachable!("handshake cannot finish with no incoming packets"),
             Err(HandshakeError::SetupFailure(err)) => return Err(err),
@@ -47,6 +170,38 @@ impl Client {
         }
     }
 
+    /** Client exchanging plain RTP and RTCP right away, without a DTLS handshake or SRTP.
+     */
+    #[cfg(any(test, feature = "insecure-plaintext-media"))]
+    pub fn new_plaintext(
+        remote: SocketAddr,
+        socket: UdpSocket,
+        capture: Option<PacketCapture>,
+    ) -> Self {
+        Client {
+            ssl_state: ClientSslState::Established(EstablishedStream {
+                protection: MediaProtection::Plaintext(UDPPeerStream::new(socket, remote, capture)),
+                replay_windows: HashMap::new(),
+                authenticated_packets: 0,
+            }),
+            remote_address: remote,
+        }
+    }
+
+    /** Points the DTLS/SRTP transport at a new remote address, reached through the given local socket.
+     * The negotiated keys are kept.
+     */
+    pub fn set_remote_address(&mut self, remote: SocketAddr, socket: UdpSocket) {
+        self.remote_address = remote;
+        let udp_stream = match &mut self.ssl_state {
+            ClientSslState::Handshake(mid_handshake) => mid_handshake.get_mut(),
+            ClientSslState::Established(established_stream) => established_stream.get_udp_stream(),
+            ClientSslState::Shutdown => return,
+        };
+        udp_stream.remote = remote;
+        udp_stream.socket = socket;
+    }
+
     pub fn read_packet(&mut self, packet: &[u8]) -> Result<(), ClientError> {
         self.ssl_state = match mem::replace(&mut self.ssl_state, ClientSslState::Shutdown) {
             ClientSslState::Handshake(mut mid_handshake) => {
@@ -57,15 +212,21 @@ impl Client {
 
                 match mid_handshake.handshake() {
                     Ok(ssl_stream) => {
-                        println!("DTLS handshake finished for remote {}", self.remote_address);
+                        if is_log_enabled(LogLevel::Info) {
+                            println!("DTLS handshake finished for remote {}", self.remote_address);
+                        }
                         let (inbound, outbound) =
                             srtp::openssl::session_pair(ssl_stream.ssl(), Default::default())
                                 .unwrap();
 
                         ClientSslState::Established(EstablishedStream {
-                            ssl_stream,
-                            srtp_outbound: outbound,
-                            srtp_inbound: inbound,
+                            protection: MediaProtection::Srtp {
+                                ssl_stream,
+                                inbound,
+                                outbound,
+                            },
+                            replay_windows: HashMap::new(),
+                            authenticated_packets: 0,
                         })
                     }
                     Err(handshake_error) => match handshake_error {
@@ -73,11 +234,13 @@ impl Client {
                             return Err(OpenSslError(err));
                         }
                         HandshakeError::Failure(mid_handshake) => {
-                            println!(
-                                "SSL handshake failure with remote {}: {}",
-                                self.remote_address,
-                                mid_handshake.error()
-                            );
+                            if is_log_enabled(LogLevel::Warn) {
+                                println!(
+                                    "SSL handshake failure with remote {}: {}",
+                                    self.remote_address,
+                                    mid_handshake.error()
+                                );
+                            }
                             ClientSslState::Handshake(mid_handshake)
                         }
                         HandshakeError::WouldBlock(mid_handshake) => {
@@ -86,13 +249,12 @@ impl Client {
                     },
                 }
             }
-            ClientSslState::Established(mut ssl_stream) => {
-                ssl_stream
-                    .ssl_stream
-                    .get_mut()
+            ClientSslState::Established(mut established_stream) => {
+                established_stream
+                    .get_udp_stream()
                     .incoming_packets
                     .push_back(Vec::from(packet));
-                ClientSslState::Established(ssl_stream)
+                ClientSslState::Established(established_stream)
             }
             ClientSslState::Shutdown => ClientSslState::Shutdown,
         };
@@ -122,19 +284,59 @@ impl fmt::Display for ClientError {
 
 impl std::error::Error for ClientError {}
 
+/** Failure of protecting or unprotecting a packet. Replays are expected with retransmissions and
+ * duplicated datagrams, unlike failed authentication. Media arriving before the handshake exported
+ * the keys isn't broken either, it can overtake the last handshake flight.
+ */
+#[derive(Debug, PartialEq)]
+pub enum SRTPError {
+    AuthenticationFailed,
+    ReplayDetected,
+    KeyNotReady,
+    PacketTooShort,
+    Other(srtp::Error),
+}
+
+impl From<srtp::Error> for SRTPError {
+    fn from(err: srtp::Error) -> Self {
+        match err {
+            srtp::Error::AUTH_FAIL => SRTPError::AuthenticationFailed,
+            srtp::Error::REPLAY_FAIL | srtp::Error::REPLAY_OLD => SRTPError::ReplayDetected,
+            srtp::Error::NO_CTX => SRTPError::KeyNotReady,
+            err => SRTPError::Other(err),
+        }
+    }
+}
+
+impl fmt::Display for SRTPError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            SRTPError::AuthenticationFailed => write!(f, "SRTP authentication failed"),
+            SRTPError::ReplayDetected => write!(f, "SRTP replay detected"),
+            SRTPError::KeyNotReady => write!(f, "SRTP key not ready"),
+            SRTPError::PacketTooShort => write!(f, "Packet too short for SRTP"),
+            SRTPError::Other(err) => write!(f, "SRTP error {}", err),
+        }
+    }
+}
+
+impl std::error::Error for SRTPError {}
+
 #[derive(Debug)]
 pub struct UDPPeerStream {
     socket: UdpSocket,
     remote: SocketAddr,
     incoming_packets: VecDeque<Vec<u8>>,
+    capture: Option<PacketCapture>,
 }
 
 impl UDPPeerStream {
-    pub fn new(socket: UdpSocket, remote: SocketAddr) -> Self {
+    pub fn new(socket: UdpSocket, remote: SocketAddr, capture: Option<PacketCapture>) -> Self {
         UDPPeerStream {
             incoming_packets: VecDeque::new(),
             socket,
             remote,
+            capture,
         }
     }
 }
@@ -155,6 +357,9 @@ impl Read for UDPPeerStream {
 
 impl Write for UDPPeerStream {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(capture) = &self.capture {
+            capture.record(Direction::Outbound, self.remote, buf);
+        }
         self.socket
             .send_to(buf, self.remote)
             .and_then(|_| Ok(buf.len()))
@@ -164,3 +369,190 @@ impl Write for UDPPeerStream {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    mod srtp_error {
+        use std::io::{self, Read, Write};
+        use std::net::UdpSocket;
+        use std::thread;
+        use std::time::Duration;
+
+        use openssl::ssl::{HandshakeError, SslConnector, SslMethod, SslVerifyMode};
+
+        use crate::acceptor::{CertificateSource, SSLConfig};
+        use crate::client::{check_packet_len, Client, ClientSslState, SRTPError, UDPPeerStream};
+
+        /** Remote end of the DTLS association, reading and writing whole datagrams.
+         */
+        #[derive(Debug)]
+        struct DatagramStream(UdpSocket);
+
+        impl Read for DatagramStream {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                self.0.recv(buf)
+            }
+        }
+
+        impl Write for DatagramStream {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.send(buf)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        fn bind_socket() -> UdpSocket {
+            let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+            socket
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            socket
+        }
+
+        /** Client that finished a DTLS handshake, with an RTP packet protected by the remote end.
+         */
+        fn get_established_client() -> (Client, Vec<u8>) {
+            let server_socket = bind_socket();
+            let remote_socket = bind_socket();
+            let remote = remote_socket.local_addr().unwrap();
+            remote_socket
+                .connect(server_socket.local_addr().unwrap())
+                .unwrap();
+            let acceptor = SSLConfig::new(CertificateSource::Generated).acceptor;
+            let udp_stream = UDPPeerStream::new(server_socket.try_clone().unwrap(), remote, None);
+            let mut client = match acceptor.accept(udp_stream) {
+                Err(HandshakeError::WouldBlock(mid_handshake)) => Client {
+                    ssl_state: ClientSslState::Handshake(mid_handshake),
+                    remote_address: remote,
+                },
+                _ => panic!("Handshake should wait for the remote"),
+            };
+
+            // The remote protects a packet with the keys of the finished handshake
+            let remote_peer = thread::spawn(move || {
+                let mut connector = SslConnector::builder(SslMethod::dtls()).unwrap();
+                connector.set_verify(SslVerifyMode::NONE);
+                connector
+                    .set_tlsext_use_srtp(srtp::openssl::SRTP_PROFILE_NAMES)
+                    .unwrap();
+                let ssl_stream = connector
+                    .build()
+                    .configure()
+                    .unwrap()
+                    .verify_hostname(false)
+                    .connect("localhost", DatagramStream(remote_socket))
+                    .expect("Remote should finish handshake");
+                let (_, mut outbound) =
+                    srtp::openssl::session_pair(ssl_stream.ssl(), Default::default()).unwrap();
+                let mut packet = vec![0x80, 96, 0, 1, 0, 0, 0, 1, 0, 0, 0, 7];
+                packet.extend_from_slice(&[0xAB; 100]);
+                outbound.protect(&mut packet).unwrap();
+                packet
+            });
+            let mut buffer = [0u8; 2000];
+            while matches!(client.ssl_state, ClientSslState::Handshake(_)) {
+                let (len, _) = server_socket.recv_from(&mut buffer).unwrap();
+                client.read_packet(&buffer[..len]).unwrap();
+            }
+            (client, remote_peer.join().unwrap())
+        }
+
+        #[test]
+        fn maps_tampered_auth_tag_to_authentication_failed() {
+            let (mut client, packet) = get_established_client();
+            let established_stream = match &mut client.ssl_state {
+                ClientSslState::Established(established_stream) => established_stream,
+                _ => panic!("Handshake should be finished"),
+            };
+
+            let mut tampered_packet = packet.clone();
+            *tampered_packet.last_mut().unwrap() ^= 0xFF;
+            assert_eq!(
+                established_stream.unprotect(&mut tampered_packet),
+                Err(SRTPError::AuthenticationFailed)
+            );
+            let mut packet = packet;
+            assert_eq!(established_stream.unprotect(&mut packet), Ok(()));
+            assert_eq!(packet[12..], [0xAB; 100]);
+        }
+
+        #[test]
+        fn keeps_no_replay_window_for_forged_packet() {
+            let (mut client, packet) = get_established_client();
+            let established_stream = match &mut client.ssl_state {
+                ClientSslState::Established(established_stream) => established_stream,
+                _ => panic!("Handshake should be finished"),
+            };
+
+            let mut forged_packet = packet.clone();
+            forged_packet[8..12].copy_from_slice(&1234u32.to_be_bytes());
+            assert_eq!(
+                established_stream.unprotect(&mut forged_packet),
+                Err(SRTPError::AuthenticationFailed)
+            );
+            assert!(established_stream.replay_windows.is_empty());
+
+            let mut packet = packet;
+            assert_eq!(established_stream.unprotect(&mut packet), Ok(()));
+            assert_eq!(
+                established_stream.replay_windows.keys().collect::<Vec<_>>(),
+                vec![&7]
+            );
+        }
+
+        #[test]
+        fn rejects_packet_shorter_than_header() {
+            assert_eq!(
+                check_packet_len(&[0x80, 96, 0, 1], 12),
+                Err(SRTPError::PacketTooShort)
+            );
+            assert_eq!(check_packet_len(&[0; 12], 12), Ok(()));
+        }
+    }
+
+    mod unprotect {
+        use std::net::UdpSocket;
+
+        use crate::client::{Client, ClientSslState, EstablishedStream, MAX_REPLAY_WINDOWS};
+
+        fn get_packet(ssrc: u32, sequence_number: u16) -> Vec<u8> {
+            let mut packet = vec![0x80, 96];
+            packet.extend_from_slice(&sequence_number.to_be_bytes());
+            packet.extend_from_slice(&[0, 0, 0, 0]);
+            packet.extend_from_slice(&ssrc.to_be_bytes());
+            packet
+        }
+
+        fn get_established_stream(client: &mut Client) -> &mut EstablishedStream {
+            match &mut client.ssl_state {
+                ClientSslState::Established(established_stream) => established_stream,
+                _ => panic!("Plaintext client should be established"),
+            }
+        }
+
+        #[test]
+        fn drops_window_of_stalest_ssrc_past_limit() {
+            let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+            let remote = socket.local_addr().unwrap();
+            let mut client = Client::new_plaintext(remote, socket, None);
+            let established_stream = get_established_stream(&mut client);
+
+            for ssrc in 0..MAX_REPLAY_WINDOWS as u32 {
+                established_stream
+                    .unprotect(&mut get_packet(ssrc, 1))
+                    .unwrap();
+            }
+            established_stream.unprotect(&mut get_packet(0, 2)).unwrap();
+            established_stream
+                .unprotect(&mut get_packet(MAX_REPLAY_WINDOWS as u32, 1))
+                .unwrap();
+
+            assert_eq!(established_stream.replay_windows.len(), MAX_REPLAY_WINDOWS);
+            assert!(established_stream.replay_windows.contains_key(&0));
+            assert!(!established_stream.replay_windows.contains_key(&1));
+        }
+    }
+}
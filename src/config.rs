@@ -1,9 +1,16 @@
-use std::net::{IpAddr, SocketAddr};
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use std::sync::OnceLock;
+use std::sync::{OnceLock, RwLock};
+use std::time::Duration;
 
-use crate::acceptor::SSLConfig;
+use sdp::SDPResolver;
+
+use crate::acceptor::{CertificateSource, SSLConfig};
 
 pub struct Config {
     pub ssl_config: SSLConfig,
@@ -11,76 +18,454 @@ pub struct Config {
     pub udp_server_config: UDPServerConfig,
     pub frontend_url: String,
     pub storage_dir: PathBuf,
+    /** Unix socket sessions are handed over through during rolling upgrades.
+     */
+    pub migration_socket: Option<PathBuf>,
+    /** Malformed or missing addresses, reported by [Config::validate].
+     */
+    address_error: Option<ConfigError>,
+}
+
+/** Addresses the servers bind to and advertise.
+ */
+struct BindAddresses {
+    tcp: SocketAddr,
+    udp: Vec<SocketAddr>,
+    candidates: Vec<SocketAddr>,
 }
 
 const TCP_IP_ENV: &'static str = "TCP_ADDRESS";
 const TCP_PORT_ENV: &'static str = "TCP_PORT";
 const UDP_IP_ENV: &'static str = "UDP_ADDRESS";
 const UDP_PORT_ENV: &'static str = "UDP_PORT";
+const UDP_CANDIDATE_ADDRESSES_ENV: &'static str = "UDP_CANDIDATE_ADDRESSES";
+const STUN_SERVER_ENV: &'static str = "STUN_SERVER_ADDRESS";
+const UDP_SHARD_COUNT_ENV: &'static str = "UDP_SHARD_COUNT";
+const UDP_MTU_ENV: &'static str = "UDP_MTU";
+const UDP_REUSE_PORT_ENV: &'static str = "UDP_REUSE_PORT";
+const UDP_DSCP_ENV: &'static str = "UDP_DSCP";
+const NACK_WINDOW_ENV: &'static str = "NACK_WINDOW";
+const CAPTURE_DIR_ENV: &'static str = "CAPTURE_DIR";
+const COMMAND_CHANNEL_CAPACITY_ENV: &'static str = "COMMAND_CHANNEL_CAPACITY";
+const MAX_STREAMERS_ENV: &'static str = "MAX_STREAMERS";
+const MAX_VIEWERS_PER_ROOM_ENV: &'static str = "MAX_VIEWERS_PER_ROOM";
+const LOG_LEVEL_ENV: &'static str = "LOG_LEVEL";
+const RELOADABLE_CONFIG_FILE_ENV: &'static str = "RELOADABLE_CONFIG_FILE";
+const THUMBNAIL_REFRESH_INTERVAL_ENV: &'static str = "THUMBNAIL_REFRESH_INTERVAL_MS";
+const ICE_UFRAG_LENGTH_ENV: &'static str = "ICE_UFRAG_LENGTH";
+const ICE_PWD_LENGTH_ENV: &'static str = "ICE_PWD_LENGTH";
+const SESSION_TTL_ENV: &'static str = "SESSION_TTL_MS";
+const SESSION_GRACE_PERIOD_ENV: &'static str = "SESSION_GRACE_PERIOD_MS";
+const TIMEOUT_CHECK_INTERVAL_ENV: &'static str = "TIMEOUT_CHECK_INTERVAL_MS";
+const STUN_KEEPALIVE_INTERVAL_ENV: &'static str = "STUN_KEEPALIVE_INTERVAL_MS";
+const KEYFRAME_TIMEOUT_ENV: &'static str = "KEYFRAME_TIMEOUT_MS";
+const HTTP_READ_TIMEOUT_ENV: &'static str = "HTTP_READ_TIMEOUT_MS";
+const HTTP_WRITE_TIMEOUT_ENV: &'static str = "HTTP_WRITE_TIMEOUT_MS";
+const HTTP_MAX_CONNECTIONS_ENV: &'static str = "HTTP_MAX_CONNECTIONS";
 const WHIP_TOKEN_ENV: &'static str = "WHIP_TOKEN";
+const ADMIN_TOKEN_ENV: &'static str = "ADMIN_TOKEN";
 const FRONTEND_URL_ENV: &'static str = "FRONTEND_URL";
 const STORAGE_DIR: &'static str = "STORAGE_DIR";
 const CERTS_DIR: &'static str = "CERTS_DIR";
+const MIGRATION_SOCKET_ENV: &'static str = "MIGRATION_SOCKET";
+const DTLS_CERT_MODE_ENV: &'static str = "DTLS_CERT_MODE";
+const INSECURE_PLAINTEXT_MEDIA_ENV: &'static str = "INSECURE_PLAINTEXT_MEDIA_FOR_TESTING";
+
+const DEFAULT_UDP_MTU: usize = 1500;
+const DEFAULT_NACK_WINDOW: u16 = 528;
+const DEFAULT_COMMAND_CHANNEL_CAPACITY: usize = 4096;
+const MIN_TOKEN_LENGTH: usize = 8;
+const DEFAULT_THUMBNAIL_REFRESH_INTERVAL: Duration = Duration::from_secs(120);
+const DEFAULT_HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_HTTP_MAX_CONNECTIONS: usize = 256;
 
 impl Config {
     pub fn initialize() -> Self {
-        // TCP server config
-        let tcp_ip = IpAddr::from_str(
-            &std::env::var(TCP_IP_ENV)
-                .expect(&format!("{TCP_IP_ENV} env variable should be present")),
-        )
-        .expect(&format!("${TCP_IP_ENV} should be valid IPAddr"));
-        let tcp_port = std::env::var(TCP_PORT_ENV)
-            .map(|port| {
-                port.parse::<u16>()
-                    .expect(&format!("{TCP_PORT_ENV} should be u16 integer"))
+        // Malformed addresses are reported by validate, unspecified ones stand in until then
+        let (bind_addresses, address_error) =
+            match get_bind_addresses(|env_name| std::env::var(env_name).ok()) {
+                Ok(bind_addresses) => (bind_addresses, None),
+                Err(err) => {
+                    let bind_addresses = BindAddresses {
+                        tcp: SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+                        udp: vec![],
+                        candidates: vec![],
+                    };
+                    (bind_addresses, Some(err))
+                }
+            };
+
+        // Public STUN server used to discover the server reflexive address, e.g. stun.l.google.com:19302
+        let stun_server_address = std::env::var(STUN_SERVER_ENV).ok();
+
+        // Defaults to one shard per available core
+        let shard_count = std::env::var(UDP_SHARD_COUNT_ENV)
+            .map(|count| {
+                count
+                    .parse::<NonZeroUsize>()
+                    .expect(&format!("{UDP_SHARD_COUNT_ENV} should be positive integer"))
+            })
+            .unwrap_or_else(|_| std::thread::available_parallelism().unwrap_or(NonZeroUsize::MIN))
+            .get();
+
+        // Largest datagram accepted by the UDP server, longer ones are dropped
+        let mtu = std::env::var(UDP_MTU_ENV)
+            .map(|mtu| {
+                mtu.parse::<usize>()
+                    .expect(&format!("{UDP_MTU_ENV} should be usize integer"))
+            })
+            .unwrap_or(DEFAULT_UDP_MTU);
+
+        // Lets several server processes bind the same UDP port, the kernel balances datagrams between them
+        let reuse_port = std::env::var(UDP_REUSE_PORT_ENV)
+            .map(|reuse_port| {
+                reuse_port
+                    .parse::<bool>()
+                    .expect(&format!("{UDP_REUSE_PORT_ENV} should be true or false"))
             })
-            .expect(&format!("{TCP_PORT_ENV} env variable should be present"));
+            .unwrap_or(false);
 
-        let tcp_address = SocketAddr::new(tcp_ip, tcp_port);
+        // DiffServ class of outgoing datagrams, for QoS-aware networks
+        let dscp = std::env::var(UDP_DSCP_ENV).ok().map(|dscp| {
+            dscp.parse::<u8>()
+                .ok()
+                .filter(|dscp| *dscp < 64)
+                .expect(&format!(
+                    "{UDP_DSCP_ENV} should be integer between 0 and 63"
+                ))
+        });
 
-        // UDP server config
-        let udp_ip = IpAddr::from_str(
-            &std::env::var(UDP_IP_ENV)
-                .expect(&format!("{UDP_IP_ENV} env variable should be present")),
-        )
-        .expect(&format!("${UDP_IP_ENV} should be valid IPAddr"));
+        // How many packets behind the newest one lost video packets are still requested
+        let nack_window = std::env::var(NACK_WINDOW_ENV)
+            .map(|nack_window| {
+                nack_window
+                    .parse::<u16>()
+                    .expect(&format!("{NACK_WINDOW_ENV} should be u16 integer"))
+            })
+            .unwrap_or(DEFAULT_NACK_WINDOW);
+
+        // Commands queued for the main loop, past it STUN packets and reports are dropped
+        let command_channel_capacity = std::env::var(COMMAND_CHANNEL_CAPACITY_ENV)
+            .map(|capacity| {
+                capacity.parse::<NonZeroUsize>().expect(&format!(
+                    "{COMMAND_CHANNEL_CAPACITY_ENV} should be positive integer"
+                ))
+            })
+            .map_or(DEFAULT_COMMAND_CHANNEL_CAPACITY, NonZeroUsize::get);
+
+        // Lengths of host ICE credentials, the shortest ones RFC 8839 allows by default
+        let ice_ufrag_length = get_optional_length(
+            ICE_UFRAG_LENGTH_ENV,
+            SDPResolver::MIN_ICE_USERNAME_LENGTH,
+            SDPResolver::MAX_ICE_USERNAME_LENGTH,
+        );
+        let ice_pwd_length = get_optional_length(
+            ICE_PWD_LENGTH_ENV,
+            SDPResolver::MIN_ICE_PASSWORD_LENGTH,
+            SDPResolver::MAX_ICE_PASSWORD_LENGTH,
+        );
+
+        // Debug mode writing the datagrams of each session to a pcap file, off unless set
+        let capture_dir = std::env::var(CAPTURE_DIR_ENV).ok().map(PathBuf::from);
 
-        let udp_port = std::env::var(UDP_PORT_ENV)
-            .map(|port| {
-                port.parse::<u16>()
-                    .expect(&format!("{UDP_PORT_ENV} should be u16 integer"))
+        // Keepalive tolerances
+        let session_ttl = get_optional_millis(SESSION_TTL_ENV, Duration::from_secs(5));
+        let session_grace_period =
+            get_optional_millis(SESSION_GRACE_PERIOD_ENV, Duration::from_secs(10));
+        let timeout_check_interval =
+            get_optional_millis(TIMEOUT_CHECK_INTERVAL_ENV, Duration::from_secs(3));
+
+        // Binding indications keeping NAT mappings of connected peers open, off unless set
+        let keepalive_interval = get_optional_millis(STUN_KEEPALIVE_INTERVAL_ENV, Duration::ZERO);
+        let keepalive_interval = (!keepalive_interval.is_zero()).then_some(keepalive_interval);
+
+        // Streamers are asked for a keyframe once the latest is this old, zero turns it off
+        let keyframe_timeout = get_optional_millis(KEYFRAME_TIMEOUT_ENV, Duration::from_secs(10));
+        let keyframe_timeout = (!keyframe_timeout.is_zero()).then_some(keyframe_timeout);
+
+        // Test mode exchanging plain RTP without DTLS, rejected by builds lacking the feature
+        let insecure_plaintext_media = std::env::var(INSECURE_PLAINTEXT_MEDIA_ENV)
+            .map(|plaintext_media| {
+                plaintext_media.parse::<bool>().expect(&format!(
+                    "{INSECURE_PLAINTEXT_MEDIA_ENV} should be true or false"
+                ))
             })
-            .expect(&format!("{UDP_PORT_ENV} env variable should be present"));
+            .unwrap_or(false);
 
-        let udp_address = SocketAddr::new(udp_ip, udp_port);
+        // Slow clients are dropped instead of holding on to a worker thread
+        let read_timeout = get_optional_millis(HTTP_READ_TIMEOUT_ENV, DEFAULT_HTTP_TIMEOUT);
+        let write_timeout = get_optional_millis(HTTP_WRITE_TIMEOUT_ENV, DEFAULT_HTTP_TIMEOUT);
+
+        // Simultaneous HTTP connections, further ones are turned down with 503
+        let max_connections = std::env::var(HTTP_MAX_CONNECTIONS_ENV)
+            .map(|max_connections| {
+                max_connections.parse::<NonZeroUsize>().expect(&format!(
+                    "{HTTP_MAX_CONNECTIONS_ENV} should be positive integer"
+                ))
+            })
+            .map_or(DEFAULT_HTTP_MAX_CONNECTIONS, NonZeroUsize::get);
 
         let whip_token = std::env::var(WHIP_TOKEN_ENV)
             .expect(&format!("{WHIP_TOKEN_ENV} env variable should be present"));
 
+        // Admin API is disabled unless set
+        let admin_token = std::env::var(ADMIN_TOKEN_ENV).ok();
+
         // Frontend URL
         let frontend_url =
             std::env::var(FRONTEND_URL_ENV).expect("FRONTEND_URL env should be defined");
 
         // Configurable directories
         let storage_dir = PathBuf::from(std::env::var(STORAGE_DIR).unwrap());
-        let certs_dir = PathBuf::from(std::env::var(CERTS_DIR).unwrap());
+        let migration_socket = std::env::var(MIGRATION_SOCKET_ENV).ok().map(PathBuf::from);
+        let get_certs_dir = || {
+            PathBuf::from(
+                std::env::var(CERTS_DIR)
+                    .expect(&format!("{CERTS_DIR} env variable should be present")),
+            )
+        };
+
+        // DTLS certificate, either managed externally or self-signed at startup
+        let certificate_source = match std::env::var(DTLS_CERT_MODE_ENV).as_deref() {
+            Ok("files") | Err(_) => CertificateSource::Files(get_certs_dir()),
+            Ok("generate") => CertificateSource::Generated,
+            Ok("persist") => CertificateSource::Persisted(get_certs_dir()),
+            Ok(_) => panic!("{DTLS_CERT_MODE_ENV} should be one of files, generate or persist"),
+        };
 
-        let ssl_config = SSLConfig::new(certs_dir);
+        let ssl_config = SSLConfig::new(certificate_source);
 
         Config {
             ssl_config,
             udp_server_config: UDPServerConfig {
-                address: udp_address,
+                addresses: bind_addresses.udp,
+                candidate_addresses: bind_addresses.candidates,
+                stun_server_address,
+                shard_count,
+                mtu,
+                reuse_port,
+                dscp,
+                nack_window,
+                capture_dir,
+                command_channel_capacity,
+                ice_ufrag_length,
+                ice_pwd_length,
+                session_ttl,
+                session_grace_period,
+                timeout_check_interval,
+                keepalive_interval,
+                keyframe_timeout,
+                insecure_plaintext_media,
             },
             tcp_server_config: TCPServerConfig {
                 whip_token,
-                address: tcp_address,
+                admin_token,
+                address: bind_addresses.tcp,
+                read_timeout,
+                write_timeout,
+                max_connections,
             },
             frontend_url,
             storage_dir,
+            migration_socket,
+            address_error,
+        }
+    }
+}
+
+fn get_bind_addresses(
+    get_value: impl Fn(&'static str) -> Option<String>,
+) -> Result<BindAddresses, ConfigError> {
+    let get_required_value =
+        |env_name: &'static str| get_value(env_name).ok_or(ConfigError::MissingValue(env_name));
+
+    // TCP server config
+    let tcp_ip = parse_ip_address(TCP_IP_ENV, get_required_value(TCP_IP_ENV)?.trim())?;
+    let tcp_port = parse_port(TCP_PORT_ENV, &get_required_value(TCP_PORT_ENV)?)?;
+    let tcp = SocketAddr::new(tcp_ip, tcp_port);
+
+    // UDP server config, a socket is bound on each of the comma-separated addresses
+    let udp_port = parse_port(UDP_PORT_ENV, &get_required_value(UDP_PORT_ENV)?)?;
+    let udp = parse_ip_addresses(UDP_IP_ENV, &get_required_value(UDP_IP_ENV)?)?
+        .into_iter()
+        .map(|ip| SocketAddr::new(ip, udp_port))
+        .collect::<Vec<_>>();
+
+    // Addresses advertised as ICE host candidates, e.g. both families of a dual-stack host
+    let candidates = match get_value(UDP_CANDIDATE_ADDRESSES_ENV) {
+        Some(addresses) => parse_ip_addresses(UDP_CANDIDATE_ADDRESSES_ENV, &addresses)?
+            .into_iter()
+            .map(|ip| SocketAddr::new(ip, udp_port))
+            .collect(),
+        None => udp.clone(),
+    };
+
+    Ok(BindAddresses {
+        tcp,
+        udp,
+        candidates,
+    })
+}
+
+fn parse_ip_address(env_name: &'static str, value: &str) -> Result<IpAddr, ConfigError> {
+    IpAddr::from_str(value).map_err(|_| ConfigError::InvalidIpAddress(env_name, value.to_string()))
+}
+
+/** Comma-separated IP addresses.
+ */
+fn parse_ip_addresses(env_name: &'static str, value: &str) -> Result<Vec<IpAddr>, ConfigError> {
+    value
+        .split(',')
+        .map(|ip| parse_ip_address(env_name, ip.trim()))
+        .collect()
+}
+
+fn parse_port(env_name: &'static str, value: &str) -> Result<u16, ConfigError> {
+    value
+        .parse::<u16>()
+        .map_err(|_| ConfigError::InvalidPort(env_name, value.to_string()))
+}
+
+/** Config values that parse but can't work, caught at startup instead of once the server runs.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    MissingValue(&'static str),
+    InvalidIpAddress(&'static str, String),
+    InvalidPort(&'static str, String),
+    InvalidFingerprint(String),
+    InvalidAddress(&'static str, String),
+    TokenTooShort(&'static str),
+    InvalidReloadableConfig(String),
+    PlaintextMediaNotAllowed,
+    ZeroTimeout(&'static str),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::MissingValue(env_name) => {
+                write!(f, "{env_name} env variable should be present")
+            }
+            ConfigError::InvalidIpAddress(env_name, address) => {
+                write!(f, "{env_name} \"{address}\" should be IP address")
+            }
+            ConfigError::InvalidPort(env_name, port) => {
+                write!(f, "{env_name} \"{port}\" should be u16 integer")
+            }
+            ConfigError::InvalidFingerprint(fingerprint) => write!(
+                f,
+                "certificate fingerprint \"{fingerprint}\" should be SHA-256 hex bytes"
+            ),
+            ConfigError::InvalidAddress(env_name, address) => {
+                write!(f, "{env_name} \"{address}\" should be in form of host:port")
+            }
+            ConfigError::TokenTooShort(env_name) => {
+                write!(
+                    f,
+                    "{env_name} should be at least {MIN_TOKEN_LENGTH} characters long"
+                )
+            }
+            ConfigError::InvalidReloadableConfig(message) => write!(f, "{message}"),
+            ConfigError::PlaintextMediaNotAllowed => write!(
+                f,
+                "{INSECURE_PLAINTEXT_MEDIA_ENV} needs a build with the insecure-plaintext-media feature"
+            ),
+            ConfigError::ZeroTimeout(env_name) => write!(f, "{env_name} should be above zero"),
+        }
+    }
+}
+
+impl Config {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if let Some(err) = &self.address_error {
+            return Err(err.clone());
+        }
+        validate_fingerprint(&self.ssl_config.get_sdp_fingerprint())?;
+        if let Some(address) = &self.udp_server_config.stun_server_address {
+            validate_host_address(STUN_SERVER_ENV, address)?;
+        }
+        validate_token(WHIP_TOKEN_ENV, &self.tcp_server_config.whip_token)?;
+        validate_timeout(HTTP_READ_TIMEOUT_ENV, self.tcp_server_config.read_timeout)?;
+        validate_timeout(HTTP_WRITE_TIMEOUT_ENV, self.tcp_server_config.write_timeout)?;
+        if let Some(admin_token) = &self.tcp_server_config.admin_token {
+            validate_token(ADMIN_TOKEN_ENV, admin_token)?;
         }
+        ReloadableConfig::load().map_err(ConfigError::InvalidReloadableConfig)?;
+        validate_plaintext_media(
+            self.udp_server_config.insecure_plaintext_media,
+            cfg!(feature = "insecure-plaintext-media"),
+        )?;
+        Ok(())
+    }
+}
+
+fn validate_fingerprint(fingerprint: &str) -> Result<(), ConfigError> {
+    if SDPResolver::is_valid_fingerprint(fingerprint) {
+        Ok(())
+    } else {
+        Err(ConfigError::InvalidFingerprint(fingerprint.to_string()))
+    }
+}
+
+/** Addresses resolved later on, e.g. by DNS, only have their form checked.
+ */
+fn validate_host_address(env_name: &'static str, address: &str) -> Result<(), ConfigError> {
+    match address.rsplit_once(':') {
+        Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => Ok(()),
+        Some(_) | None => Err(ConfigError::InvalidAddress(env_name, address.to_string())),
+    }
+}
+
+fn validate_token(env_name: &'static str, token: &str) -> Result<(), ConfigError> {
+    if token.len() < MIN_TOKEN_LENGTH {
+        return Err(ConfigError::TokenTooShort(env_name));
+    }
+    Ok(())
+}
+
+/** Sockets reject a zero timeout, it would otherwise only surface on the first connection.
+ */
+fn validate_timeout(env_name: &'static str, timeout: Duration) -> Result<(), ConfigError> {
+    if timeout.is_zero() {
+        return Err(ConfigError::ZeroTimeout(env_name));
     }
+    Ok(())
+}
+
+/** Plain RTP is only accepted from builds made for testing, so a deployed server can't be
+ * configured into sending unencrypted media.
+ */
+fn validate_plaintext_media(is_enabled: bool, is_feature_enabled: bool) -> Result<(), ConfigError> {
+    if is_enabled && !is_feature_enabled {
+        return Err(ConfigError::PlaintextMediaNotAllowed);
+    }
+    Ok(())
+}
+
+fn get_optional_millis(env_name: &str, default: Duration) -> Duration {
+    std::env::var(env_name)
+        .map(|millis| {
+            millis
+                .parse::<u64>()
+                .map(Duration::from_millis)
+                .expect(&format!("{env_name} should be u64 integer"))
+        })
+        .unwrap_or(default)
+}
+
+fn get_optional_length(env_name: &str, min: usize, max: usize) -> usize {
+    std::env::var(env_name)
+        .map(|length| {
+            length
+                .parse::<usize>()
+                .ok()
+                .filter(|length| (min..=max).contains(length))
+                .expect(&format!(
+                    "{env_name} should be integer between {min} and {max}"
+                ))
+        })
+        .unwrap_or(min)
 }
 
 static GLOBAL_CONFIG: OnceLock<Config> = OnceLock::new();
@@ -89,11 +474,410 @@ pub fn get_global_config() -> &'static Config {
     GLOBAL_CONFIG.get_or_init(Config::initialize)
 }
 
+/** Settings that can change while the server runs, none of them requires rebinding sockets.
+ * Values of the file at RELOADABLE_CONFIG_FILE take precedence over env variables.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReloadableConfig {
+    pub thumbnail_refresh_interval: Duration,
+    pub max_streamers: Option<usize>,
+    pub max_viewers_per_room: Option<usize>,
+    pub log_level: LogLevel,
+}
+
+/** Least severe per-peer log lines that get printed, ordered from the most severe.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Error,
+    /** Failures affecting a single peer, e.g. packets that couldn't be sent.
+     */
+    Warn,
+    /** Session lifecycle, e.g. finished handshakes and state changes.
+     */
+    Info,
+    /** Single datagrams, e.g. dropped runts. Noisy while the server is flooded.
+     */
+    Debug,
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "error" => Ok(LogLevel::Error),
+            "warn" => Ok(LogLevel::Warn),
+            "info" => Ok(LogLevel::Info),
+            "debug" => Ok(LogLevel::Debug),
+            _ => Err(format!(
+                "{LOG_LEVEL_ENV} should be one of error, warn, info or debug"
+            )),
+        }
+    }
+}
+
+impl ReloadableConfig {
+    fn load() -> Result<Self, String> {
+        let path = std::env::var(RELOADABLE_CONFIG_FILE_ENV)
+            .ok()
+            .map(PathBuf::from);
+        Self::load_from(path.as_deref())
+    }
+
+    fn load_from(path: Option<&Path>) -> Result<Self, String> {
+        let file_values = match path {
+            Some(path) => read_config_file(path)?,
+            None => HashMap::new(),
+        };
+        let get_value = |name: &str| {
+            file_values
+                .get(name)
+                .cloned()
+                .or_else(|| std::env::var(name).ok())
+        };
+
+        // Minimum time between thumbnail updates of a room
+        let thumbnail_refresh_interval = get_value(THUMBNAIL_REFRESH_INTERVAL_ENV)
+            .map(|millis| {
+                millis
+                    .parse::<u64>()
+                    .map(Duration::from_millis)
+                    .map_err(|_| format!("{THUMBNAIL_REFRESH_INTERVAL_ENV} should be u64 integer"))
+            })
+            .transpose()?
+            .unwrap_or(DEFAULT_THUMBNAIL_REFRESH_INTERVAL);
+
+        // Concurrent streamers the server takes on, unlimited unless set
+        let max_streamers = get_value(MAX_STREAMERS_ENV)
+            .map(|max_streamers| {
+                max_streamers
+                    .parse::<usize>()
+                    .map_err(|_| format!("{MAX_STREAMERS_ENV} should be usize integer"))
+            })
+            .transpose()?;
+
+        // Viewers a single room takes on, unlimited unless set
+        let max_viewers_per_room = get_value(MAX_VIEWERS_PER_ROOM_ENV)
+            .map(|max_viewers| {
+                max_viewers
+                    .parse::<usize>()
+                    .map_err(|_| format!("{MAX_VIEWERS_PER_ROOM_ENV} should be usize integer"))
+            })
+            .transpose()?;
+
+        let log_level = get_value(LOG_LEVEL_ENV)
+            .map(|log_level| log_level.parse::<LogLevel>())
+            .transpose()?
+            .unwrap_or(LogLevel::Info);
+
+        Ok(ReloadableConfig {
+            thumbnail_refresh_interval,
+            max_streamers,
+            max_viewers_per_room,
+            log_level,
+        })
+    }
+}
+
+/** Reads `NAME=value` lines, skipping blank lines and # comments.
+ */
+fn read_config_file(path: &Path) -> Result<HashMap<String, String>, String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|err| format!("Couldn't read {}: {}", path.display(), err))?;
+
+    Ok(contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect())
+}
+
+/** Current reloadable config, swapped out as a whole on reload.
+ */
+struct ReloadableConfigCell {
+    config: RwLock<ReloadableConfig>,
+}
+
+impl ReloadableConfigCell {
+    fn new(config: ReloadableConfig) -> Self {
+        ReloadableConfigCell {
+            config: RwLock::new(config),
+        }
+    }
+
+    fn get(&self) -> ReloadableConfig {
+        *self.config.read().unwrap()
+    }
+
+    /** An invalid config is reported, and the current one kept.
+     */
+    fn reload_from(&self, path: Option<&Path>) -> Result<ReloadableConfig, String> {
+        let config = ReloadableConfig::load_from(path)?;
+        *self.config.write().unwrap() = config;
+        Ok(config)
+    }
+}
+
+static RELOADABLE_CONFIG: OnceLock<ReloadableConfigCell> = OnceLock::new();
+
+fn get_reloadable_config_cell() -> &'static ReloadableConfigCell {
+    RELOADABLE_CONFIG.get_or_init(|| {
+        ReloadableConfigCell::new(ReloadableConfig::load().unwrap_or_else(|err| panic!("{}", err)))
+    })
+}
+
+pub fn get_reloadable_config() -> ReloadableConfig {
+    get_reloadable_config_cell().get()
+}
+
+/** Whether log lines of the given level are printed with the current config.
+ */
+pub fn is_log_enabled(level: LogLevel) -> bool {
+    level <= get_reloadable_config().log_level
+}
+
+pub fn reload_config() -> Result<ReloadableConfig, String> {
+    let path = std::env::var(RELOADABLE_CONFIG_FILE_ENV)
+        .ok()
+        .map(PathBuf::from);
+    get_reloadable_config_cell().reload_from(path.as_deref())
+}
+
 pub struct TCPServerConfig {
     pub address: SocketAddr,
     pub whip_token: String,
+    pub admin_token: Option<String>,
+    pub read_timeout: Duration,
+    pub write_timeout: Duration,
+    pub max_connections: usize,
 }
 
 pub struct UDPServerConfig {
-    pub address: SocketAddr,
+    pub addresses: Vec<SocketAddr>,
+    pub candidate_addresses: Vec<SocketAddr>,
+    pub stun_server_address: Option<String>,
+    pub shard_count: usize,
+    pub mtu: usize,
+    pub reuse_port: bool,
+    pub dscp: Option<u8>,
+    pub nack_window: u16,
+    pub capture_dir: Option<PathBuf>,
+    pub command_channel_capacity: usize,
+    pub ice_ufrag_length: usize,
+    pub ice_pwd_length: usize,
+    pub session_ttl: Duration,
+    pub session_grace_period: Duration,
+    pub timeout_check_interval: Duration,
+    pub keepalive_interval: Option<Duration>,
+    pub keyframe_timeout: Option<Duration>,
+    pub insecure_plaintext_media: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    mod reloadable_config_cell {
+        use std::fs;
+        use std::time::Duration;
+
+        use crate::config::{LogLevel, ReloadableConfig, ReloadableConfigCell};
+
+        #[test]
+        fn changes_thumbnail_interval_at_runtime() {
+            let path =
+                std::env::temp_dir().join(format!("reloadable-{}.env", rand::random::<u32>()));
+            let cell = ReloadableConfigCell::new(ReloadableConfig::load_from(None).unwrap());
+
+            fs::write(
+                &path,
+                "# Tuned for tests\nTHUMBNAIL_REFRESH_INTERVAL_MS=1000\n",
+            )
+            .unwrap();
+            cell.reload_from(Some(&path)).unwrap();
+
+            assert_eq!(
+                cell.get().thumbnail_refresh_interval,
+                Duration::from_secs(1)
+            );
+
+            fs::write(
+                &path,
+                "THUMBNAIL_REFRESH_INTERVAL_MS=5000\nMAX_STREAMERS=3\n",
+            )
+            .unwrap();
+            cell.reload_from(Some(&path)).unwrap();
+
+            assert_eq!(
+                cell.get().thumbnail_refresh_interval,
+                Duration::from_secs(5)
+            );
+            assert_eq!(cell.get().max_streamers, Some(3));
+
+            // Invalid values leave the running config untouched
+            fs::write(&path, "THUMBNAIL_REFRESH_INTERVAL_MS=soon\n").unwrap();
+            assert!(cell.reload_from(Some(&path)).is_err());
+            assert_eq!(
+                cell.get().thumbnail_refresh_interval,
+                Duration::from_secs(5)
+            );
+
+            fs::remove_file(path).unwrap();
+        }
+
+        #[test]
+        fn changes_viewer_limit_and_log_level_at_runtime() {
+            let path =
+                std::env::temp_dir().join(format!("reloadable-{}.env", rand::random::<u32>()));
+            let cell = ReloadableConfigCell::new(ReloadableConfig::load_from(None).unwrap());
+            assert_eq!(cell.get().log_level, LogLevel::Info);
+
+            fs::write(&path, "MAX_VIEWERS_PER_ROOM=50\nLOG_LEVEL=warn\n").unwrap();
+            cell.reload_from(Some(&path)).unwrap();
+
+            assert_eq!(cell.get().max_viewers_per_room, Some(50));
+            assert_eq!(cell.get().log_level, LogLevel::Warn);
+
+            fs::write(&path, "LOG_LEVEL=verbose\n").unwrap();
+            assert!(cell.reload_from(Some(&path)).is_err());
+            assert_eq!(cell.get().log_level, LogLevel::Warn);
+
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    mod get_bind_addresses {
+        use std::collections::HashMap;
+        use std::net::SocketAddr;
+
+        use crate::config::{
+            get_bind_addresses, ConfigError, TCP_IP_ENV, TCP_PORT_ENV, UDP_CANDIDATE_ADDRESSES_ENV,
+            UDP_IP_ENV, UDP_PORT_ENV,
+        };
+
+        fn get_values(overrides: &[(&'static str, &str)]) -> HashMap<&'static str, String> {
+            let mut values = HashMap::from([
+                (TCP_IP_ENV, "127.0.0.1".to_string()),
+                (TCP_PORT_ENV, "8080".to_string()),
+                (UDP_IP_ENV, "10.0.0.1, ::".to_string()),
+                (UDP_PORT_ENV, "52000".to_string()),
+            ]);
+            for (name, value) in overrides {
+                values.insert(name, value.to_string());
+            }
+            values
+        }
+
+        #[test]
+        fn reads_addresses_of_every_server() {
+            let values = get_values(&[(UDP_CANDIDATE_ADDRESSES_ENV, "203.0.113.7")]);
+
+            let bind_addresses = get_bind_addresses(|name| values.get(name).cloned()).unwrap();
+
+            assert_eq!(bind_addresses.tcp, "127.0.0.1:8080".parse().unwrap());
+            assert_eq!(
+                bind_addresses.udp,
+                vec![
+                    "10.0.0.1:52000".parse::<SocketAddr>().unwrap(),
+                    "[::]:52000".parse().unwrap()
+                ]
+            );
+            assert_eq!(
+                bind_addresses.candidates,
+                vec!["203.0.113.7:52000".parse::<SocketAddr>().unwrap()]
+            );
+        }
+
+        #[test]
+        fn reports_malformed_address_instead_of_panicking() {
+            let values = get_values(&[(UDP_IP_ENV, "10.0.0.1,localhost")]);
+            assert_eq!(
+                get_bind_addresses(|name| values.get(name).cloned()).err(),
+                Some(ConfigError::InvalidIpAddress(
+                    UDP_IP_ENV,
+                    "localhost".to_string()
+                ))
+            );
+
+            let values = get_values(&[(TCP_PORT_ENV, "80800")]);
+            assert_eq!(
+                get_bind_addresses(|name| values.get(name).cloned()).err(),
+                Some(ConfigError::InvalidPort(TCP_PORT_ENV, "80800".to_string()))
+            );
+
+            let mut values = get_values(&[]);
+            values.remove(UDP_PORT_ENV);
+            assert_eq!(
+                get_bind_addresses(|name| values.get(name).cloned()).err(),
+                Some(ConfigError::MissingValue(UDP_PORT_ENV))
+            );
+        }
+    }
+
+    mod validate {
+        use std::time::Duration;
+
+        use crate::config::{
+            validate_fingerprint, validate_host_address, validate_plaintext_media,
+            validate_timeout, validate_token, ConfigError, HTTP_READ_TIMEOUT_ENV, STUN_SERVER_ENV,
+            WHIP_TOKEN_ENV,
+        };
+
+        #[test]
+        fn rejects_invalid_fingerprint() {
+            let fingerprint = "sha-256 EF:53:C9";
+
+            assert_eq!(
+                validate_fingerprint(fingerprint),
+                Err(ConfigError::InvalidFingerprint(fingerprint.to_string()))
+            );
+            assert!(validate_fingerprint(
+                "sha-256 EF:53:C9:F2:E0:A0:4F:1D:5E:99:4C:20:B8:D7:DE:21:3B:58:15:C4:E5:88:87:46:\
+                65:27:F7:3B:C6:DC:EF:3B"
+            )
+            .is_ok());
+        }
+
+        #[test]
+        fn rejects_address_without_port() {
+            assert_eq!(
+                validate_host_address(STUN_SERVER_ENV, "stun.l.google.com"),
+                Err(ConfigError::InvalidAddress(
+                    STUN_SERVER_ENV,
+                    "stun.l.google.com".to_string()
+                ))
+            );
+            assert!(validate_host_address(STUN_SERVER_ENV, "stun.l.google.com:19302").is_ok());
+            assert!(validate_host_address(STUN_SERVER_ENV, "[::1]:3478").is_ok());
+        }
+
+        #[test]
+        fn rejects_short_token() {
+            assert_eq!(
+                validate_token(WHIP_TOKEN_ENV, "secret"),
+                Err(ConfigError::TokenTooShort(WHIP_TOKEN_ENV))
+            );
+            assert!(validate_token(WHIP_TOKEN_ENV, "long-enough-secret").is_ok());
+        }
+
+        #[test]
+        fn rejects_plaintext_media_without_feature() {
+            assert_eq!(
+                validate_plaintext_media(true, false),
+                Err(ConfigError::PlaintextMediaNotAllowed)
+            );
+            assert!(validate_plaintext_media(true, true).is_ok());
+            assert!(validate_plaintext_media(false, false).is_ok());
+        }
+
+        #[test]
+        fn rejects_zero_timeout() {
+            assert_eq!(
+                validate_timeout(HTTP_READ_TIMEOUT_ENV, Duration::ZERO),
+                Err(ConfigError::ZeroTimeout(HTTP_READ_TIMEOUT_ENV))
+            );
+            assert!(validate_timeout(HTTP_READ_TIMEOUT_ENV, Duration::from_millis(1)).is_ok());
+        }
+    }
 }
@@ -0,0 +1,168 @@
+use byteorder::{ByteOrder, NetworkEndian};
+
+use crate::rtcp::{is_rtcp_packet, RTCPPacketType};
+use crate::stun::{STUN_COOKIE, STUN_HEADER_LEN};
+
+/** Shortest datagram any of the demultiplexed protocols is handled with, an RTCP packet: the 4 byte
+ * common header followed by the sender SSRC. Longer minimums of the other protocols are checked by
+ * [get_packet_type] once the datagram is classified.
+ */
+pub const MIN_PACKET_LEN: usize = MIN_RTCP_PACKET_LEN;
+
+/** Fixed RTP header, without CSRCs or extensions.
+ */
+const MIN_RTP_PACKET_LEN: usize = 12;
+const MIN_RTCP_PACKET_LEN: usize = 8;
+/** DTLS record header, content type, version, epoch, sequence number and length.
+ */
+const MIN_DTLS_RECORD_LEN: usize = 13;
+
+/** Kind of a datagram received on the shared UDP port.
+ * RTCP is classified by its first header, which SRTCP leaves unencrypted.
+ */
+#[derive(Debug, PartialEq)]
+pub enum PacketType {
+    Stun,
+    Dtls,
+    Rtp,
+    Rtcp(RTCPPacketType),
+    Unknown,
+}
+
+/** Classifies a datagram by its first byte, checked in order:
+ * 1. STUN: 0-3, confirmed by the magic cookie at bytes 4-7
+ * 2. DTLS: record content type 20-63
+ * 3. RTP/RTCP: 128-191, split by payload type
+ *
+ * Anything else (ZRTP, TURN channels, garbage) is Unknown, and so is a datagram too short to hold
+ * the header of its type, so that parsers can index into the header of whatever they're given.
+ * https://datatracker.ietf.org/doc/html/rfc7983#section-7
+ */
+pub fn get_packet_type(buffer: &[u8]) -> PacketType {
+    let first_byte = match buffer.first() {
+        Some(byte) => *byte,
+        None => return PacketType::Unknown,
+    };
+
+    match first_byte {
+        0..=3 => {
+            let is_stun = buffer.len() >= STUN_HEADER_LEN
+                && NetworkEndian::read_u32(&buffer[4..8]) == STUN_COOKIE;
+            match is_stun {
+                true => PacketType::Stun,
+                false => PacketType::Unknown,
+            }
+        }
+        20..=63 if buffer.len() >= MIN_DTLS_RECORD_LEN => PacketType::Dtls,
+        128..=191 if is_rtcp_packet(buffer) => PacketType::Rtcp(RTCPPacketType::from(buffer[1])),
+        128..=191 if buffer.len() >= MIN_RTP_PACKET_LEN => PacketType::Rtp,
+        _ => PacketType::Unknown,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod get_packet_type {
+        use crate::demux::{get_packet_type, PacketType};
+        use crate::rtcp::RTCPPacketType;
+
+        #[test]
+        fn classifies_sender_report() {
+            let mut sender_report = [0u8; 28];
+            sender_report[0] = 0b1000_0000;
+            sender_report[1] = 200;
+            sender_report[3] = 6;
+
+            assert_eq!(
+                get_packet_type(&sender_report),
+                PacketType::Rtcp(RTCPPacketType::SenderReport)
+            );
+        }
+
+        #[test]
+        fn classifies_receiver_report() {
+            let receiver_report = [0b1000_0000, 201, 0, 1, 0, 0, 0, 1];
+
+            assert_eq!(
+                get_packet_type(&receiver_report),
+                PacketType::Rtcp(RTCPPacketType::ReceiverReport)
+            );
+        }
+
+        #[test]
+        fn classifies_bye() {
+            let bye = [0b1000_0001, 203, 0, 1, 0, 0, 0, 1];
+
+            assert_eq!(get_packet_type(&bye), PacketType::Rtcp(RTCPPacketType::Bye));
+        }
+
+        #[test]
+        fn classifies_dynamic_payload_type_as_rtp() {
+            let rtp_packet = [0b1000_0000, 96, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1];
+            let marked_rtp_packet = [0b1000_0000, 0b1000_0000 | 96, 0, 2, 0, 0, 0, 0, 0, 0, 0, 1];
+
+            assert_eq!(get_packet_type(&rtp_packet), PacketType::Rtp);
+            assert_eq!(get_packet_type(&marked_rtp_packet), PacketType::Rtp);
+        }
+
+        #[test]
+        fn classifies_sender_report_payload_type_as_rtcp() {
+            let rtcp_packet = [0b1000_0000, 200, 0, 1, 0, 0, 0, 1];
+
+            assert!(matches!(get_packet_type(&rtcp_packet), PacketType::Rtcp(_)));
+        }
+
+        #[test]
+        fn classifies_stun_binding_request() {
+            let mut binding_request = [0u8; 20];
+            binding_request[1] = 0x01;
+            binding_request[4..8].copy_from_slice(&[0x21, 0x12, 0xA4, 0x42]);
+
+            assert_eq!(get_packet_type(&binding_request), PacketType::Stun);
+        }
+
+        #[test]
+        fn rejects_stun_without_magic_cookie() {
+            let mut binding_request = [0u8; 20];
+            binding_request[1] = 0x01;
+
+            assert_eq!(get_packet_type(&binding_request), PacketType::Unknown);
+        }
+
+        #[test]
+        fn classifies_dtls_handshake_record() {
+            // Content type 22 (handshake), DTLS 1.2
+            let client_hello = [22, 0xFE, 0xFD, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+
+            assert_eq!(get_packet_type(&client_hello), PacketType::Dtls);
+        }
+
+        #[test]
+        fn classifies_srtp_packet() {
+            let srtp_packet = [0b1000_0000, 102, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1, 0xAB, 0xCD];
+
+            assert_eq!(get_packet_type(&srtp_packet), PacketType::Rtp);
+        }
+
+        #[test]
+        fn rejects_packets_shorter_than_their_header() {
+            let rtp_packet = [0b1000_0000, 96, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1];
+            let rtcp_packet = [0b1000_0000, 201, 0, 1, 0, 0, 0, 1];
+            let client_hello = [22, 0xFE, 0xFD, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+
+            for packet in [&rtp_packet[..], &rtcp_packet, &client_hello] {
+                let runt = &packet[..packet.len() - 1];
+                assert_eq!(get_packet_type(runt), PacketType::Unknown);
+            }
+            // RTCP sized runts aren't taken for RTP either
+            assert_eq!(get_packet_type(&rtp_packet[..8]), PacketType::Unknown);
+        }
+
+        #[test]
+        fn rejects_zrtp_first_byte() {
+            let zrtp_packet = [0x10, 0, 0, 1, 0x5A, 0x52, 0x54, 0x50];
+
+            assert_eq!(get_packet_type(&zrtp_packet), PacketType::Unknown);
+        }
+    }
+}
@@ -0,0 +1,338 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::bitrate::{BitrateCounter, BITRATE_WINDOW};
+
+/** abs-send-time ticks per second, the value has 18 fractional bits.
+ */
+const ABS_SEND_TIME_TICKS_PER_SECOND: f64 = (1 << 18) as f64;
+/** abs-send-time is a 24-bit field.
+ */
+const ABS_SEND_TIME_RANGE: i64 = 1 << 24;
+/** Packets sent within this interval of the first packet of a group form a single group, usually a frame.
+ */
+const BURST_INTERVAL_MS: f64 = 5.0;
+const TRENDLINE_WINDOW_SIZE: usize = 20;
+const TRENDLINE_SMOOTHING: f64 = 0.9;
+/** The trend is scaled by the number of deltas seen so far, up to this limit.
+ */
+const MAX_TREND_DELTAS: usize = 60;
+const TREND_GAIN: f64 = 4.0;
+const OVERUSE_THRESHOLD_MS: f64 = 12.5;
+const INCREASE_RATE_PER_SECOND: f64 = 1.08;
+const DECREASE_FACTOR: f64 = 0.85;
+/** Backing off takes a round trip to show in arriving packets, decreasing again sooner would overshoot.
+ */
+const DECREASE_INTERVAL: Duration = Duration::from_millis(200);
+const INITIAL_BITRATE_BPS: u64 = 300_000;
+const MIN_BITRATE_BPS: u64 = 50_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BandwidthUsage {
+    Normal,
+    Overusing,
+    Underusing,
+}
+
+/** Packets sent in a single burst, compared as a whole against the previous burst.
+ */
+#[derive(Debug, Clone, Copy)]
+struct PacketGroup {
+    first_send_time: i64,
+    last_send_time: i64,
+    last_arrival: Instant,
+}
+
+/** Receive-side estimate of the bandwidth available to a sender, based on the delay of its packets.
+ * Growing inter-arrival delays between packet groups mean a queue builds up on the path, the estimate
+ * then drops below the incoming bitrate. Otherwise it slowly grows, bounded by the incoming bitrate.
+ */
+#[derive(Debug)]
+pub struct DelayBasedEstimator {
+    last_abs_send_time: Option<u32>,
+    send_time: i64,
+    current_group: Option<PacketGroup>,
+    previous_group: Option<PacketGroup>,
+    first_arrival: Option<Instant>,
+    accumulated_delay_ms: f64,
+    smoothed_delay_ms: f64,
+    delay_samples: VecDeque<(f64, f64)>,
+    delta_count: usize,
+    incoming: BitrateCounter,
+    estimate_bps: u64,
+    last_update: Option<Instant>,
+    last_decrease: Option<Instant>,
+}
+
+impl DelayBasedEstimator {
+    pub fn new() -> Self {
+        DelayBasedEstimator {
+            last_abs_send_time: None,
+            send_time: 0,
+            current_group: None,
+            previous_group: None,
+            first_arrival: None,
+            accumulated_delay_ms: 0.0,
+            smoothed_delay_ms: 0.0,
+            delay_samples: VecDeque::with_capacity(TRENDLINE_WINDOW_SIZE),
+            delta_count: 0,
+            incoming: BitrateCounter::new(BITRATE_WINDOW),
+            estimate_bps: INITIAL_BITRATE_BPS,
+            last_update: None,
+            last_decrease: None,
+        }
+    }
+
+    pub fn estimate_bps(&self) -> u64 {
+        self.estimate_bps
+    }
+
+    /** Registers a received packet with its abs-send-time header extension value.
+     */
+    pub fn register_packet(&mut self, abs_send_time: u32, bytes: usize, arrival: Instant) {
+        self.incoming.add(bytes, arrival);
+        let send_time = self.unwrap_send_time(abs_send_time);
+        self.first_arrival.get_or_insert(arrival);
+
+        let group = match &mut self.current_group {
+            Some(group) => group,
+            None => {
+                self.current_group = Some(PacketGroup {
+                    first_send_time: send_time,
+                    last_send_time: send_time,
+                    last_arrival: arrival,
+                });
+                return;
+            }
+        };
+
+        // Reordered packets from an earlier group carry no new delay information
+        if send_time < group.first_send_time {
+            return;
+        }
+        if get_send_delta_ms(group.first_send_time, send_time) <= BURST_INTERVAL_MS {
+            group.last_send_time = group.last_send_time.max(send_time);
+            group.last_arrival = arrival;
+            return;
+        }
+
+        let finished_group = *group;
+        self.current_group = Some(PacketGroup {
+            first_send_time: send_time,
+            last_send_time: send_time,
+            last_arrival: arrival,
+        });
+        if let Some(previous_group) = self.previous_group.replace(finished_group) {
+            let usage = self.update_trendline(&previous_group, &finished_group);
+            self.update_estimate(usage, finished_group.last_arrival);
+        }
+    }
+
+    /** Extends the 24-bit abs-send-time, so that it keeps growing across wrap-arounds.
+     */
+    fn unwrap_send_time(&mut self, abs_send_time: u32) -> i64 {
+        if let Some(last_abs_send_time) = self.last_abs_send_time {
+            let mut delta =
+                (abs_send_time as i64 - last_abs_send_time as i64) % ABS_SEND_TIME_RANGE;
+            if delta >= ABS_SEND_TIME_RANGE / 2 {
+                delta -= ABS_SEND_TIME_RANGE;
+            } else if delta < -ABS_SEND_TIME_RANGE / 2 {
+                delta += ABS_SEND_TIME_RANGE;
+            }
+            self.send_time += delta;
+        } else {
+            self.send_time = abs_send_time as i64;
+        }
+        self.last_abs_send_time = Some(abs_send_time);
+        self.send_time
+    }
+
+    /** Fits a line through the smoothed accumulated delay of recent groups, its slope tells whether the
+     * path queue grows or drains.
+     */
+    fn update_trendline(
+        &mut self,
+        previous: &PacketGroup,
+        current: &PacketGroup,
+    ) -> BandwidthUsage {
+        let send_delta_ms = get_send_delta_ms(previous.last_send_time, current.last_send_time);
+        let arrival_delta_ms = current
+            .last_arrival
+            .duration_since(previous.last_arrival)
+            .as_secs_f64()
+            * 1000.0;
+
+        self.accumulated_delay_ms += arrival_delta_ms - send_delta_ms;
+        self.smoothed_delay_ms = TRENDLINE_SMOOTHING * self.smoothed_delay_ms
+            + (1.0 - TRENDLINE_SMOOTHING) * self.accumulated_delay_ms;
+        self.delta_count = (self.delta_count + 1).min(MAX_TREND_DELTAS);
+
+        let arrival_ms = match self.first_arrival {
+            Some(first_arrival) => {
+                current
+                    .last_arrival
+                    .duration_since(first_arrival)
+                    .as_secs_f64()
+                    * 1000.0
+            }
+            None => 0.0,
+        };
+        if self.delay_samples.len() == TRENDLINE_WINDOW_SIZE {
+            self.delay_samples.pop_front();
+        }
+        self.delay_samples
+            .push_back((arrival_ms, self.smoothed_delay_ms));
+        if self.delay_samples.len() < TRENDLINE_WINDOW_SIZE {
+            return BandwidthUsage::Normal;
+        }
+
+        let trend = get_slope(&self.delay_samples) * self.delta_count as f64 * TREND_GAIN;
+        if trend > OVERUSE_THRESHOLD_MS {
+            BandwidthUsage::Overusing
+        } else if trend < -OVERUSE_THRESHOLD_MS {
+            BandwidthUsage::Underusing
+        } else {
+            BandwidthUsage::Normal
+        }
+    }
+
+    /** Backs off below the incoming bitrate on overuse and grows multiplicatively otherwise. Draining queues
+     * keep the estimate, so that they can empty before the sender speeds up again.
+     */
+    fn update_estimate(&mut self, usage: BandwidthUsage, now: Instant) {
+        let elapsed = match self.last_update.replace(now) {
+            Some(last_update) => now.duration_since(last_update),
+            None => Duration::ZERO,
+        };
+        let incoming_bps = self.incoming.bitrate_bps(now);
+
+        match usage {
+            BandwidthUsage::Overusing => {
+                if self.last_decrease.is_some_and(|last_decrease| {
+                    now.duration_since(last_decrease) < DECREASE_INTERVAL
+                }) {
+                    return;
+                }
+                self.last_decrease = Some(now);
+                let bitrate_bps = self.estimate_bps.min(incoming_bps) as f64;
+                self.estimate_bps = (bitrate_bps * DECREASE_FACTOR) as u64;
+            }
+            BandwidthUsage::Normal => {
+                // Estimates far above what the sender actually sends can't be verified
+                let max_bitrate_bps = incoming_bps * 3 / 2 + 10_000;
+                if self.estimate_bps < max_bitrate_bps {
+                    let increase = INCREASE_RATE_PER_SECOND.powf(elapsed.as_secs_f64());
+                    self.estimate_bps =
+                        ((self.estimate_bps as f64 * increase) as u64).min(max_bitrate_bps);
+                }
+            }
+            BandwidthUsage::Underusing => {}
+        }
+        self.estimate_bps = self.estimate_bps.max(MIN_BITRATE_BPS);
+    }
+}
+
+fn get_send_delta_ms(from: i64, to: i64) -> f64 {
+    (to - from) as f64 * 1000.0 / ABS_SEND_TIME_TICKS_PER_SECOND
+}
+
+/** Least squares slope of the (x, y) samples.
+ */
+fn get_slope(samples: &VecDeque<(f64, f64)>) -> f64 {
+    let count = samples.len() as f64;
+    let mean_x = samples.iter().map(|(x, _)| x).sum::<f64>() / count;
+    let mean_y = samples.iter().map(|(_, y)| y).sum::<f64>() / count;
+
+    let (numerator, denominator) =
+        samples
+            .iter()
+            .fold((0.0, 0.0), |(numerator, denominator), (x, y)| {
+                (
+                    numerator + (x - mean_x) * (y - mean_y),
+                    denominator + (x - mean_x) * (x - mean_x),
+                )
+            });
+    match denominator == 0.0 {
+        true => 0.0,
+        false => numerator / denominator,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod delay_based_estimator {
+        use std::time::{Duration, Instant};
+
+        use crate::estimator::DelayBasedEstimator;
+
+        const FRAME_INTERVAL_MS: u64 = 33;
+        // Roughly 1Mbps at 30 frames per second
+        const FRAME_SIZE: usize = 4000;
+
+        /** Sends a frame every 33ms, each arriving later than the previous one by the added delay.
+         * Returns the arrival time of the last frame.
+         */
+        fn stream_frames(
+            estimator: &mut DelayBasedEstimator,
+            start: Instant,
+            first_frame: u64,
+            frame_count: u64,
+            added_delay_ms: u64,
+        ) -> Instant {
+            let mut arrival = start;
+            for frame in first_frame..first_frame + frame_count {
+                let send_time_ms = frame * FRAME_INTERVAL_MS;
+                let abs_send_time = ((send_time_ms << 18) / 1000) as u32 & 0x00FF_FFFF;
+                arrival += Duration::from_millis(FRAME_INTERVAL_MS + added_delay_ms);
+                estimator.register_packet(abs_send_time, FRAME_SIZE, arrival);
+            }
+            arrival
+        }
+
+        #[test]
+        fn rises_with_stable_delay() {
+            let mut estimator = DelayBasedEstimator::new();
+            let initial_estimate = estimator.estimate_bps();
+
+            stream_frames(&mut estimator, Instant::now(), 0, 150, 0);
+
+            assert!(
+                estimator.estimate_bps() > initial_estimate,
+                "estimate {} bps",
+                estimator.estimate_bps()
+            );
+        }
+
+        #[test]
+        fn falls_with_growing_delay() {
+            let mut estimator = DelayBasedEstimator::new();
+            let arrival = stream_frames(&mut estimator, Instant::now(), 0, 150, 0);
+            let stable_estimate = estimator.estimate_bps();
+
+            // Each frame queues 5ms longer than the previous one
+            stream_frames(&mut estimator, arrival, 150, 60, 5);
+
+            assert!(
+                estimator.estimate_bps() < stable_estimate,
+                "estimate {} bps",
+                estimator.estimate_bps()
+            );
+        }
+
+        #[test]
+        fn keeps_rising_across_send_time_wraparound() {
+            let mut estimator = DelayBasedEstimator::new();
+            // abs-send-time wraps after 64 seconds, 1939 frames in
+            let arrival = stream_frames(&mut estimator, Instant::now(), 1900, 30, 0);
+            let estimate_before_wrap = estimator.estimate_bps();
+
+            stream_frames(&mut estimator, arrival, 1930, 60, 0);
+
+            assert!(
+                estimator.estimate_bps() > estimate_before_wrap,
+                "estimate {} bps",
+                estimator.estimate_bps()
+            );
+        }
+    }
+}
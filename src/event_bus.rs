@@ -0,0 +1,92 @@
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::Duration;
+
+use crate::http::server::Notification;
+
+/** How often the main loop publishes the rooms status to subscribers.
+ */
+pub const ROOMS_STATUS_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone)]
+pub enum ServerEvent {
+    RoomsStatus(Arc<Notification>),
+}
+
+/** Fans the events published by the main loop out to every subscriber, e.g. `/notifications`
+ * streams, so that observers don't have to send commands of their own to the main loop.
+ * Subscribers whose receiver was dropped are forgotten on the next publish.
+ */
+pub struct EventBus {
+    subscribers: Mutex<Vec<Sender<ServerEvent>>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        EventBus {
+            subscribers: Mutex::new(Vec::new()),
+        }
+    }
+
+    /** Receives every event published from now on.
+     */
+    pub fn subscribe(&self) -> Receiver<ServerEvent> {
+        let (sender, receiver) = channel();
+        self.subscribers.lock().unwrap().push(sender);
+        receiver
+    }
+
+    pub fn has_subscribers(&self) -> bool {
+        !self.subscribers.lock().unwrap().is_empty()
+    }
+
+    pub fn publish(&self, event: ServerEvent) {
+        self.subscribers
+            .lock()
+            .unwrap()
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod publish {
+        use std::sync::Arc;
+
+        use crate::event_bus::{EventBus, ServerEvent};
+        use crate::http::server::Notification;
+
+        #[test]
+        fn delivers_event_to_every_subscriber() {
+            let event_bus = EventBus::new();
+            let first = event_bus.subscribe();
+            let second = event_bus.subscribe();
+
+            event_bus.publish(ServerEvent::RoomsStatus(Arc::new(Notification {
+                rooms: vec![],
+            })));
+
+            for subscriber in [first, second] {
+                match subscriber.try_recv() {
+                    Ok(ServerEvent::RoomsStatus(notification)) => {
+                        assert!(notification.rooms.is_empty())
+                    }
+                    Err(err) => panic!("Subscriber should receive the event: {}", err),
+                }
+            }
+        }
+
+        #[test]
+        fn forgets_dropped_subscribers() {
+            let event_bus = EventBus::new();
+            let subscriber = event_bus.subscribe();
+            drop(subscriber);
+
+            event_bus.publish(ServerEvent::RoomsStatus(Arc::new(Notification {
+                rooms: vec![],
+            })));
+
+            assert!(!event_bus.has_subscribers());
+        }
+    }
+}
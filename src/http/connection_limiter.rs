@@ -0,0 +1,68 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/** Counting semaphore capping simultaneous HTTP connections, so idle clients can't exhaust the
+ * signaling server.
+ */
+#[derive(Clone)]
+pub struct ConnectionLimiter {
+    active_connections: Arc<AtomicUsize>,
+    max_connections: usize,
+}
+
+/** Slot of a single connection, handed back to its limiter once dropped.
+ */
+pub struct ConnectionPermit {
+    active_connections: Arc<AtomicUsize>,
+}
+
+impl ConnectionLimiter {
+    pub fn new(max_connections: usize) -> Self {
+        ConnectionLimiter {
+            active_connections: Arc::new(AtomicUsize::new(0)),
+            max_connections,
+        }
+    }
+
+    /** Takes a slot for a new connection, none if all of them are taken.
+     */
+    pub fn try_acquire(&self) -> Option<ConnectionPermit> {
+        self.active_connections
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |active| {
+                (active < self.max_connections).then_some(active + 1)
+            })
+            .ok()
+            .map(|_| ConnectionPermit {
+                active_connections: self.active_connections.clone(),
+            })
+    }
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        self.active_connections.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod try_acquire {
+        use crate::http::connection_limiter::ConnectionLimiter;
+
+        #[test]
+        fn frees_slot_once_permit_drops() {
+            let limiter = ConnectionLimiter::new(2);
+
+            let first_permit = limiter.try_acquire().expect("Should take first slot");
+            let second_permit = limiter.try_acquire().expect("Should take second slot");
+            assert!(limiter.try_acquire().is_none());
+
+            drop(first_permit);
+            let third_permit = limiter.try_acquire().expect("Should take freed slot");
+            assert!(limiter.try_acquire().is_none());
+
+            drop((second_permit, third_permit));
+            assert!(limiter.try_acquire().is_some());
+        }
+    }
+}
@@ -1,12 +1,25 @@
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
 use std::net::SocketAddr;
-use std::sync::mpsc::Sender;
+use std::sync::mpsc::{sync_channel, Receiver, SendError, Sender, SyncSender, TrySendError};
 
-use crate::http::server::Notification;
+use serde_json::json;
 
+use sdp::{OfferDiagnostics, OfferRequirement, OfferSection};
+
+use crate::bitrate::RoomBitrate;
+use crate::http::response_builder::ResponseBuilder;
+use crate::http::server::{Notification, RoomDetails, RoomViewers};
+use crate::ice_registry::{ResourceID, RoomID, RoomVideo};
+use crate::migration::SessionSnapshot;
+use crate::receiver_report::LossStats;
+use crate::server::SocketIndex;
+use crate::session_state::SessionState;
+
+pub mod connection_limiter;
 pub mod parsers;
 pub mod response_builder;
+pub mod router;
 pub mod server;
 
 #[derive(Debug)]
@@ -15,6 +28,7 @@ pub struct Request {
     pub method: HTTPMethod,
     pub search: HashMap<String, String>,
     pub headers: HashMap<String, String>,
+    pub params: HashMap<String, String>,
     pub body: Option<Vec<u8>>,
 }
 
@@ -25,12 +39,13 @@ impl Display for Request {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum HTTPMethod {
     GET,
     POST,
     OPTIONS,
     DELETE,
+    PATCH,
 }
 
 impl Display for HTTPMethod {
@@ -40,38 +55,240 @@ impl Display for HTTPMethod {
             HTTPMethod::POST => write!(f, "POST"),
             HTTPMethod::OPTIONS => write!(f, "OPTIONS"),
             HTTPMethod::DELETE => write!(f, "DELETE"),
+            HTTPMethod::PATCH => write!(f, "PATCH"),
         }
     }
 }
 
+/** Error returned by route handlers, carrying a message for the JSON response body.
+ */
 #[derive(Debug)]
 pub enum HttpError {
-    NotFound,
-    Unauthorized,
-    InternalServerError,
-    BadRequest,
-    MethodNotAllowed,
+    NotFound(String),
+    Unauthorized(String),
+    InternalServerError(String),
+    BadRequest(String),
+    MethodNotAllowed(String),
+    /** Request not received in full within the read timeout.
+     */
+    RequestTimeout(String),
+    ServiceUnavailable(String),
+    /** Rejected SDP offer, along with the requirements it failed to meet.
+     */
+    UnprocessableOffer(String, Vec<(OfferSection, OfferRequirement)>),
+    /** Out of capacity, clients should retry after the given number of seconds.
+     */
+    Overloaded(String, u64),
 }
 
-impl Display for HttpError {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+impl HttpError {
+    pub fn status(&self) -> usize {
+        match self {
+            HttpError::NotFound(_) => 404,
+            HttpError::Unauthorized(_) => 401,
+            HttpError::InternalServerError(_) => 500,
+            HttpError::BadRequest(_) => 400,
+            HttpError::MethodNotAllowed(_) => 405,
+            HttpError::RequestTimeout(_) => 408,
+            HttpError::ServiceUnavailable(_) => 503,
+            HttpError::UnprocessableOffer(..) => 422,
+            HttpError::Overloaded(..) => 503,
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            HttpError::NotFound(message)
+            | HttpError::Unauthorized(message)
+            | HttpError::InternalServerError(message)
+            | HttpError::BadRequest(message)
+            | HttpError::MethodNotAllowed(message)
+            | HttpError::RequestTimeout(message)
+            | HttpError::ServiceUnavailable(message)
+            | HttpError::UnprocessableOffer(message, _)
+            | HttpError::Overloaded(message, _) => message,
+        }
+    }
+
+    pub fn body(&self) -> serde_json::Value {
+        match self {
+            HttpError::UnprocessableOffer(message, failed_requirements) => {
+                let failed_requirements = failed_requirements
+                    .iter()
+                    .map(|(section, requirement)| {
+                        json!({ "section": section.as_str(), "requirement": requirement.as_str() })
+                    })
+                    .collect::<Vec<_>>();
+                json!({ "error": message, "failed_requirements": failed_requirements })
+            }
+            _ => json!({ "error": self.message() }),
+        }
+    }
+
+    pub fn get_response_builder(&self) -> ResponseBuilder {
+        let response_builder = ResponseBuilder::new()
+            .set_status(self.status())
+            .set_header("content-type", "application/json")
+            .set_body(self.body().to_string().as_bytes());
+
         match self {
-            HttpError::NotFound => write!(f, "404 Not Found"),
-            HttpError::InternalServerError => write!(f, "500 Internal Server Error"),
-            HttpError::BadRequest => write!(f, "400 Bad Request"),
-            HttpError::MethodNotAllowed => write!(f, "405 Method Not Allowed"),
-            HttpError::Unauthorized => write!(f, "401 Unauthorized"),
+            HttpError::Overloaded(_, retry_after_secs) => {
+                response_builder.set_header("retry-after", &retry_after_secs.to_string())
+            }
+            _ => response_builder,
         }
     }
 }
 
+impl Display for HttpError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let status_text = match self {
+            HttpError::NotFound(_) => "404 Not Found",
+            HttpError::InternalServerError(_) => "500 Internal Server Error",
+            HttpError::BadRequest(_) => "400 Bad Request",
+            HttpError::MethodNotAllowed(_) => "405 Method Not Allowed",
+            HttpError::RequestTimeout(_) => "408 Request Timeout",
+            HttpError::Unauthorized(_) => "401 Unauthorized",
+            HttpError::ServiceUnavailable(_) => "503 Service Unavailable",
+            HttpError::UnprocessableOffer(..) => "422 Unprocessable Entity",
+            HttpError::Overloaded(..) => "503 Service Unavailable",
+        };
+        write!(f, "{}: {}", status_text, self.message())
+    }
+}
+
+impl From<HttpError> for Response {
+    fn from(err: HttpError) -> Self {
+        err.get_response_builder().build()
+    }
+}
+
 #[derive(Debug)]
 pub enum ServerCommand {
-    AddStreamer(String, Sender<Option<String>>),
-    AddViewer(String, u32, Sender<Option<String>>),
-    HandlePacket(Vec<u8>, SocketAddr),
+    AddStreamer(
+        String,
+        Sender<Result<(ResourceID, String), StreamerRejection>>,
+    ),
+    UpdateStreamer(
+        ResourceID,
+        String,
+        bool,
+        Sender<Result<String, RenegotiationRejection>>,
+    ),
+    AddViewer(String, u32, Sender<Result<String, ViewerRejection>>),
+    HandlePacket(Vec<u8>, SocketAddr, SocketIndex),
     SendRoomsStatus(Sender<Notification>),
+    SendRoomDetails(RoomID, Sender<Option<RoomDetails>>),
+    SendRoomViewers(RoomID, Sender<Option<RoomViewers>>),
+    RefreshSession(SocketAddr),
+    /** Reported by the shard owning the peer once its DTLS handshake finishes or fails.
+     */
+    UpdateSessionState(SocketAddr, SessionState),
+    RemovePeerSession(SocketAddr),
+    /** Replies whether the room existed.
+     */
+    TerminateRoom(RoomID, Sender<bool>),
+    ReportRoomBitrates(usize, Vec<(RoomID, RoomBitrate)>),
+    ReportViewerStats(Vec<(SocketAddr, LossStats)>),
+    ReportRoomVideo(Vec<(RoomID, RoomVideo)>),
+    Shutdown,
+    /** Re-reads the settings that can change without a restart.
+     */
+    ReloadConfig,
+    /** Stops accepting new sessions ahead of a restart, existing ones are served until they end.
+     */
+    Drain,
+    ExportSessions(Sender<Vec<SessionSnapshot>>),
     RunPeriodicChecks,
+    /** Publishes the rooms status to the subscribers of the event bus.
+     */
+    PublishRoomsStatus,
+}
+
+impl ServerCommand {
+    /** Commands the main loop can do without when it falls behind. STUN checks are retransmitted by
+     * clients, refreshes and reports are repeated by the shards, and periodic checks run again on the next tick.
+     * Session lifecycle commands and HTTP requests waiting for a reply are never dropped.
+     */
+    fn is_droppable(&self) -> bool {
+        match self {
+            ServerCommand::HandlePacket(..)
+            | ServerCommand::RefreshSession(_)
+            | ServerCommand::ReportRoomBitrates(..)
+            | ServerCommand::ReportViewerStats(_)
+            | ServerCommand::ReportRoomVideo(_)
+            | ServerCommand::RunPeriodicChecks
+            | ServerCommand::PublishRoomsStatus => true,
+            ServerCommand::AddStreamer(..)
+            | ServerCommand::UpdateStreamer(..)
+            | ServerCommand::AddViewer(..)
+            | ServerCommand::SendRoomsStatus(_)
+            | ServerCommand::SendRoomDetails(..)
+            | ServerCommand::SendRoomViewers(..)
+            | ServerCommand::UpdateSessionState(..)
+            | ServerCommand::RemovePeerSession(_)
+            | ServerCommand::TerminateRoom(..)
+            | ServerCommand::Shutdown
+            | ServerCommand::ReloadConfig
+            | ServerCommand::Drain
+            | ServerCommand::ExportSessions(_) => false,
+        }
+    }
+}
+
+/** Creates the bounded channel of the main loop, so that a stalled main loop can't buffer
+ * an unbounded number of commands, e.g. during a STUN flood.
+ */
+pub fn create_server_channel(capacity: usize) -> (ServerSender, Receiver<ServerCommand>) {
+    let (sender, receiver) = sync_channel(capacity);
+    (ServerSender { sender }, receiver)
+}
+
+/** Sending half of the main loop channel. Once the channel is full, droppable commands are discarded,
+ * others wait until the main loop makes room for them.
+ */
+#[derive(Debug, Clone)]
+pub struct ServerSender {
+    sender: SyncSender<ServerCommand>,
+}
+
+impl ServerSender {
+    pub fn send(&self, command: ServerCommand) -> Result<(), SendError<ServerCommand>> {
+        if !command.is_droppable() {
+            return self.sender.send(command);
+        }
+        match self.sender.try_send(command) {
+            Ok(()) | Err(TrySendError::Full(_)) => Ok(()),
+            Err(TrySendError::Disconnected(command)) => Err(SendError(command)),
+        }
+    }
+}
+
+/** Why the main loop turned down a streamer.
+ */
+#[derive(Debug)]
+pub enum StreamerRejection {
+    InvalidOffer(OfferDiagnostics),
+    StreamerLimitReached,
+    Draining,
+}
+
+/** Why the main loop turned down the renegotiation of a stream.
+ */
+#[derive(Debug)]
+pub enum RenegotiationRejection {
+    StreamerNotFound,
+    InvalidOffer(OfferDiagnostics),
+}
+
+/** Why the main loop turned down a viewer.
+ */
+#[derive(Debug)]
+pub enum ViewerRejection {
+    RoomNotFound,
+    InvalidOffer(OfferDiagnostics),
+    ViewerLimitReached,
+    Draining,
 }
 
 pub struct Response {
@@ -84,3 +301,96 @@ impl Response {
         &self._inner
     }
 }
+
+#[cfg(test)]
+mod tests {
+    mod http_error {
+        use serde_json::json;
+
+        use sdp::{OfferRequirement, OfferSection};
+
+        use crate::http::HttpError;
+
+        #[test]
+        fn maps_variants_to_status() {
+            let cases = [
+                (HttpError::NotFound("not found".to_string()), 404),
+                (HttpError::Unauthorized("unauthorized".to_string()), 401),
+                (HttpError::BadRequest("bad request".to_string()), 400),
+                (HttpError::MethodNotAllowed("not allowed".to_string()), 405),
+                (HttpError::RequestTimeout("too slow".to_string()), 408),
+                (HttpError::InternalServerError("failed".to_string()), 500),
+                (HttpError::ServiceUnavailable("starting".to_string()), 503),
+                (HttpError::Overloaded("full".to_string(), 30), 503),
+                (
+                    HttpError::UnprocessableOffer("rejected".to_string(), vec![]),
+                    422,
+                ),
+            ];
+
+            for (err, status) in cases {
+                assert_eq!(err.status(), status, "{}", err);
+            }
+        }
+
+        #[test]
+        fn puts_message_in_json_body() {
+            let err = HttpError::BadRequest("target_id should be u32".to_string());
+
+            assert_eq!(err.body(), json!({ "error": "target_id should be u32" }));
+            assert_eq!(err.to_string(), "400 Bad Request: target_id should be u32");
+        }
+
+        #[test]
+        fn lists_failed_offer_requirements_in_json_body() {
+            let err = HttpError::UnprocessableOffer(
+                "Couldn't accept stream offer".to_string(),
+                vec![(OfferSection::Video, OfferRequirement::RTCPMux)],
+            );
+
+            assert_eq!(
+                err.body(),
+                json!({
+                    "error": "Couldn't accept stream offer",
+                    "failed_requirements": [{ "section": "video", "requirement": "rtcp-mux" }]
+                })
+            );
+        }
+    }
+
+    mod server_sender {
+        use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+        use std::thread;
+
+        use crate::http::{create_server_channel, ServerCommand};
+
+        #[test]
+        fn drops_packets_but_keeps_lifecycle_commands_on_overflow() {
+            let remote = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 52000);
+            let (sender, receiver) = create_server_channel(2);
+
+            for _ in 0..3 {
+                sender
+                    .send(ServerCommand::HandlePacket(vec![0], remote, 0))
+                    .unwrap();
+            }
+            // Waits for room instead of being dropped
+            let lifecycle_sender = thread::spawn({
+                let sender = sender.clone();
+                move || {
+                    sender
+                        .send(ServerCommand::RemovePeerSession(remote))
+                        .unwrap()
+                }
+            });
+
+            let commands = (0..3).map(|_| receiver.recv().unwrap()).collect::<Vec<_>>();
+            lifecycle_sender.join().unwrap();
+
+            assert!(matches!(commands[0], ServerCommand::HandlePacket(..)));
+            assert!(matches!(commands[1], ServerCommand::HandlePacket(..)));
+            assert!(matches!(commands[2], ServerCommand::RemovePeerSession(_)));
+            assert!(receiver.try_recv().is_err());
+        }
+    }
+}
@@ -1,34 +1,83 @@
 use std::collections::HashMap;
-use std::io::{BufRead, BufReader, Read};
+use std::io;
+use std::io::{BufRead, BufReader, ErrorKind, Read};
 use std::net::TcpStream;
+use std::time::{Duration, Instant};
 
-use crate::http::{HttpError, HTTPMethod, Request, Response};
-use crate::http::response_builder::ResponseBuilder;
+use crate::http::{HTTPMethod, Request};
 
-pub fn parse_http(stream: &mut TcpStream) -> Option<Request> {
-    let mut buff_reader =
-        BufReader::new(stream.try_clone().expect("Should clone TCP stream socket")).take(15000);
+/** Why no request could be read from a connection.
+ */
+#[derive(Debug, PartialEq)]
+pub enum RequestError {
+    /** Malformed or cut short, e.g. by the client closing the connection.
+     */
+    Malformed,
+    /** Not received in full within the read timeout.
+     */
+    TimedOut,
+}
+
+impl From<io::Error> for RequestError {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            ErrorKind::WouldBlock | ErrorKind::TimedOut => RequestError::TimedOut,
+            _ => RequestError::Malformed,
+        }
+    }
+}
+
+/** Reads from a stream until a deadline, however slowly the client trickles in its bytes.
+ */
+struct DeadlineReader {
+    stream: TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(ErrorKind::TimedOut.into());
+        }
+        self.stream.set_read_timeout(Some(remaining))?;
+        self.stream.read(buf)
+    }
+}
+
+/** Reads a single request, which has to arrive in full within `read_timeout`.
+ */
+pub fn parse_http(stream: &mut TcpStream, read_timeout: Duration) -> Result<Request, RequestError> {
+    let deadline_reader = DeadlineReader {
+        stream: stream.try_clone().expect("Should clone TCP stream socket"),
+        deadline: Instant::now() + read_timeout,
+    };
+    let mut buff_reader = BufReader::new(deadline_reader).take(15000);
 
     let mut request_line = String::new();
-    buff_reader.read_line(&mut request_line);
+    buff_reader.read_line(&mut request_line)?;
 
     let mut request_line = request_line.split(" ");
 
-    let method = request_line.next()?;
-    let pathname = request_line.next()?;
+    let method = request_line.next().ok_or(RequestError::Malformed)?;
+    let pathname = request_line.next().ok_or(RequestError::Malformed)?;
     let method = match method {
         "GET" => HTTPMethod::GET,
         "POST" => HTTPMethod::POST,
         "OPTIONS" => HTTPMethod::OPTIONS,
         "DELETE" => HTTPMethod::DELETE,
+        "PATCH" => HTTPMethod::PATCH,
         _ => {
-            return None;
+            return Err(RequestError::Malformed);
         }
     };
 
     let pathname_split = pathname.split_once("?");
     let (path, search) = match &pathname_split {
-        Some((path, search)) => (path.to_string(), parse_search(search)?),
+        Some((path, search)) => (
+            path.to_string(),
+            parse_search(search).ok_or(RequestError::Malformed)?,
+        ),
         None => (pathname.to_string(), HashMap::new()),
     };
 
@@ -36,12 +85,12 @@ pub fn parse_http(stream: &mut TcpStream) -> Option<Request> {
 
     loop {
         let mut header_line = String::new();
-        buff_reader.read_line(&mut header_line);
+        buff_reader.read_line(&mut header_line)?;
 
         if header_line.trim().is_empty() {
             break;
         }
-        let (key, value) = header_line.split_once(":")?;
+        let (key, value) = header_line.split_once(":").ok_or(RequestError::Malformed)?;
         let key = key.trim().to_lowercase();
         let value = value.trim().to_string();
         headers.insert(key, value);
@@ -52,16 +101,20 @@ pub fn parse_http(stream: &mut TcpStream) -> Option<Request> {
         .map(|length| length.parse::<usize>().ok())
         .flatten();
 
-    let body = content_length.map(|length| {
-        let mut body = vec![0u8; length];
-        buff_reader.read_exact(&mut body);
-        body
-    });
+    let body = match content_length {
+        Some(length) => {
+            let mut body = vec![0u8; length];
+            buff_reader.read_exact(&mut body)?;
+            Some(body)
+        }
+        None => None,
+    };
 
-    Some(Request {
+    Ok(Request {
         method,
         headers,
         search,
+        params: HashMap::new(),
         body,
         path,
     })
@@ -77,14 +130,52 @@ fn parse_search(search: &str) -> Option<HashMap<String, String>> {
 
     Some(search_map)
 }
-pub fn map_http_err_to_response(err: HttpError) -> Response {
-    let status = match err {
-        HttpError::NotFound => 404,
-        HttpError::Unauthorized => 401,
-        HttpError::InternalServerError => 500,
-        HttpError::BadRequest => 404,
-        HttpError::MethodNotAllowed => 405,
-    };
 
-    ResponseBuilder::new().set_status(status).build()
+#[cfg(test)]
+mod tests {
+    mod parse_http {
+        use std::io::Write;
+        use std::net::{TcpListener, TcpStream};
+        use std::time::{Duration, Instant};
+
+        use crate::http::parsers::{parse_http, RequestError};
+
+        const READ_TIMEOUT: Duration = Duration::from_millis(200);
+
+        /** Server side of a connection the client wrote `data` into.
+         */
+        fn get_connection(data: &[u8]) -> (TcpStream, TcpStream) {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+            client.write_all(data).unwrap();
+            let (server, _) = listener.accept().unwrap();
+            (server, client)
+        }
+
+        #[test]
+        fn reads_complete_request() {
+            let (mut stream, _client) =
+                get_connection(b"GET /rooms?id=1 HTTP/1.1\r\nHost: sinder\r\n\r\n");
+
+            let request = parse_http(&mut stream, READ_TIMEOUT).expect("Should read request");
+
+            assert_eq!(request.path, "/rooms");
+            assert_eq!(request.search["id"], "1");
+            assert_eq!(request.headers["host"], "sinder");
+        }
+
+        #[test]
+        fn drops_partial_request_after_timeout() {
+            // Headers never end, the client keeps the connection open
+            let (mut stream, _client) = get_connection(b"GET /rooms HTTP/1.1\r\nHost: sinder\r\n");
+            let started_at = Instant::now();
+
+            assert_eq!(
+                parse_http(&mut stream, READ_TIMEOUT).err(),
+                Some(RequestError::TimedOut)
+            );
+            assert!(started_at.elapsed() >= READ_TIMEOUT);
+            assert!(started_at.elapsed() < READ_TIMEOUT * 5);
+        }
+    }
 }
@@ -4,9 +4,9 @@ use crate::config::get_global_config;
 use crate::http::Response;
 
 pub struct ResponseBuilder {
-    status: Option<usize>,
-    headers: HashMap<String, String>,
-    body: Option<Vec<u8>>,
+    pub(crate) status: Option<usize>,
+    pub(crate) headers: HashMap<String, String>,
+    pub(crate) body: Option<Vec<u8>>,
 }
 
 impl ResponseBuilder {
@@ -44,10 +44,14 @@ impl ResponseBuilder {
         let status_text = match status {
             200 => "OK",
             201 => "CREATED",
+            304 => "NOT MODIFIED",
             400 => "BAD REQUEST",
             401 => "UNAUTHORIZED",
             404 => "NOT FOUND",
             405 => "METHOD NOT ALLOWED",
+            408 => "REQUEST TIMEOUT",
+            500 => "INTERNAL SERVER ERROR",
+            503 => "SERVICE UNAVAILABLE",
             _ => "",
         };
 
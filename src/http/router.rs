@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+
+use crate::http::{HttpError, HTTPMethod, Response};
+
+/** Routing table matching requests by method and path pattern.
+Pattern segments starting with `:` capture path parameters, e.g. `/images/:image`.
+ */
+pub struct Router<H> {
+    routes: Vec<Route<H>>,
+}
+
+struct Route<H> {
+    method: HTTPMethod,
+    segments: Vec<PathSegment>,
+    handler: H,
+}
+
+enum PathSegment {
+    Static(String),
+    Param(String),
+}
+
+#[derive(Debug)]
+pub struct RouteMatch<'a, H> {
+    pub handler: &'a H,
+    pub params: HashMap<String, String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RouteError {
+    NotFound,
+    MethodNotAllowed(Vec<HTTPMethod>),
+}
+
+impl<H> Router<H> {
+    pub fn new() -> Self {
+        Router { routes: vec![] }
+    }
+
+    pub fn add_route(mut self, method: HTTPMethod, pattern: &str, handler: H) -> Self {
+        let segments = get_path_segments(pattern)
+            .map(|segment| match segment.strip_prefix(":") {
+                Some(param) => PathSegment::Param(param.to_string()),
+                None => PathSegment::Static(segment.to_string()),
+            })
+            .collect();
+
+        self.routes.push(Route {
+            method,
+            segments,
+            handler,
+        });
+        self
+    }
+
+    /** Finds the handler for the request. A path registered only for other methods resolves to MethodNotAllowed.
+     */
+    pub fn resolve(
+        &self,
+        method: &HTTPMethod,
+        path: &str,
+    ) -> Result<RouteMatch<'_, H>, RouteError> {
+        let mut allowed_methods = vec![];
+
+        for route in &self.routes {
+            if let Some(params) = route.match_path(path) {
+                if route.method.eq(method) {
+                    return Ok(RouteMatch {
+                        handler: &route.handler,
+                        params,
+                    });
+                }
+                allowed_methods.push(route.method.clone());
+            }
+        }
+
+        if allowed_methods.is_empty() {
+            Err(RouteError::NotFound)
+        } else {
+            Err(RouteError::MethodNotAllowed(allowed_methods))
+        }
+    }
+}
+
+impl<H> Route<H> {
+    fn match_path(&self, path: &str) -> Option<HashMap<String, String>> {
+        let path_segments = get_path_segments(path).collect::<Vec<_>>();
+        if path_segments.len() != self.segments.len() {
+            return None;
+        }
+
+        let mut params = HashMap::new();
+        for (segment, path_segment) in self.segments.iter().zip(path_segments) {
+            match segment {
+                PathSegment::Static(name) if name.eq(path_segment) => {}
+                PathSegment::Param(name) if !path_segment.is_empty() => {
+                    params.insert(name.clone(), path_segment.to_string());
+                }
+                _ => return None,
+            }
+        }
+
+        Some(params)
+    }
+}
+
+fn get_path_segments(path: &str) -> impl Iterator<Item = &str> {
+    path.trim_matches('/').split("/")
+}
+
+impl RouteError {
+    /** Value of the `Allow` header for 405 responses.
+     */
+    pub fn allow_header(&self) -> Option<String> {
+        match self {
+            RouteError::NotFound => None,
+            RouteError::MethodNotAllowed(methods) => Some(
+                methods
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ),
+        }
+    }
+}
+
+impl From<RouteError> for Response {
+    fn from(err: RouteError) -> Self {
+        match &err {
+            RouteError::NotFound => {
+                Response::from(HttpError::NotFound("Unknown route".to_string()))
+            }
+            RouteError::MethodNotAllowed(_) => {
+                let allow_header = err.allow_header().unwrap_or_default();
+                HttpError::MethodNotAllowed(format!("Allowed methods: {}", allow_header))
+                    .get_response_builder()
+                    .set_header("Allow", &allow_header)
+                    .build()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod resolve {
+        use std::collections::HashMap;
+
+        use crate::http::HTTPMethod;
+        use crate::http::router::{RouteError, Router};
+
+        fn get_router() -> Router<&'static str> {
+            Router::new()
+                .add_route(HTTPMethod::POST, "/whip", "whip")
+                .add_route(HTTPMethod::POST, "/whep", "whep")
+                .add_route(HTTPMethod::OPTIONS, "/whep", "whep_options")
+                .add_route(HTTPMethod::GET, "/images/:image", "image")
+        }
+
+        #[test]
+        fn resolves_registered_route() {
+            let router = get_router();
+
+            let route = router
+                .resolve(&HTTPMethod::OPTIONS, "/whep")
+                .expect("Should resolve route");
+
+            assert_eq!(*route.handler, "whep_options");
+            assert!(route.params.is_empty());
+        }
+
+        #[test]
+        fn rejects_unregistered_method_with_allow_header() {
+            let router = get_router();
+
+            let err = router
+                .resolve(&HTTPMethod::GET, "/whip")
+                .expect_err("Should reject GET");
+
+            assert_eq!(err, RouteError::MethodNotAllowed(vec![HTTPMethod::POST]));
+            assert_eq!(err.allow_header(), Some("POST".to_string()));
+        }
+
+        #[test]
+        fn captures_path_params() {
+            let router = get_router();
+
+            let route = router
+                .resolve(&HTTPMethod::GET, "/images/12.webp")
+                .expect("Should resolve route");
+
+            assert_eq!(*route.handler, "image");
+            assert_eq!(
+                route.params,
+                HashMap::from([("image".to_string(), "12.webp".to_string())])
+            );
+        }
+
+        #[test]
+        fn rejects_unknown_path() {
+            let router = get_router();
+
+            assert_eq!(
+                router.resolve(&HTTPMethod::GET, "/images").err(),
+                Some(RouteError::NotFound)
+            );
+            assert_eq!(
+                router.resolve(&HTTPMethod::POST, "/whip/1").err(),
+                Some(RouteError::NotFound)
+            );
+        }
+    }
+}
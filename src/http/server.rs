@@ -1,71 +1,230 @@
 use std::fs;
-use std::io::Write;
-use std::net::{TcpListener, TcpStream};
+use std::io;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
 use std::path::Path;
-use std::sync::mpsc::{channel, Sender};
-use std::thread::sleep;
-use std::time::Duration;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::channel;
+use std::time::UNIX_EPOCH;
 
+use flate2::read::GzDecoder;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use threadpool::ThreadPool;
 
-use crate::config::get_global_config;
-use crate::http::{HttpError, HTTPMethod, Request, Response, ServerCommand};
-use crate::http::parsers::{map_http_err_to_response, parse_http};
+use sdp::{OfferDiagnostics, VideoCodec};
+
+use crate::config::{get_global_config, get_reloadable_config};
+use crate::event_bus::{EventBus, ServerEvent};
+use crate::http::{
+    HttpError, HTTPMethod, RenegotiationRejection, Request, Response, ServerCommand, ServerSender,
+    StreamerRejection, ViewerRejection,
+};
+use crate::http::connection_limiter::{ConnectionLimiter, ConnectionPermit};
+use crate::http::parsers::{parse_http, RequestError};
 use crate::http::response_builder::ResponseBuilder;
+use crate::http::router::Router;
+use crate::ice_registry::{ResourceID, Room as RegistryRoom, RoomID, SessionRegistry};
+use crate::thumbnail::{
+    get_thumbnail_etag, get_thumbnail_etag_path, get_thumbnail_path, THUMBNAIL_CONTENT_TYPE,
+};
 
-pub fn start_http_server(sender: Sender<ServerCommand>) {
-    let pool = ThreadPool::new(60);
-    let listener = TcpListener::bind(get_global_config().tcp_server_config.address).unwrap();
-    println!(
-        "Running TCP server at {}",
-        get_global_config().tcp_server_config.address
-    );
-    for mut stream in listener.incoming() {
+/** Upper bound on the size of an SDP offer after decompression.
+ */
+const MAX_SDP_OFFER_SIZE: usize = 64 * 1024;
+
+/** Seconds streamers turned down by the streamer limit are asked to wait before retrying.
+ */
+const STREAMER_LIMIT_RETRY_AFTER_SECS: u64 = 30;
+
+/** Seconds viewers turned down by the viewer limit of a room are asked to wait before retrying.
+ */
+const VIEWER_LIMIT_RETRY_AFTER_SECS: u64 = 30;
+
+/** Seconds clients turned down by the connection limit are asked to wait before retrying.
+ */
+const CONNECTION_LIMIT_RETRY_AFTER_SECS: u64 = 1;
+
+/** Handlers of the HTTP API, resolved by the router.
+ */
+enum Route {
+    Whip,
+    WhipUpdate,
+    Whep,
+    WhepOptions,
+    Images,
+    Thumbnail,
+    Rooms,
+    RoomDetails,
+    RoomViewers,
+    Notifications,
+    Health,
+    Readiness,
+    AdminRoomDelete,
+}
+
+fn get_router() -> Router<Route> {
+    Router::new()
+        .add_route(HTTPMethod::POST, "/whip", Route::Whip)
+        .add_route(HTTPMethod::PATCH, "/whip/:resource_id", Route::WhipUpdate)
+        .add_route(HTTPMethod::POST, "/whep", Route::Whep)
+        .add_route(HTTPMethod::OPTIONS, "/whep", Route::WhepOptions)
+        .add_route(HTTPMethod::GET, "/images", Route::Images)
+        .add_route(HTTPMethod::GET, "/images/:image", Route::Images)
+        .add_route(HTTPMethod::GET, "/rooms", Route::Rooms)
+        .add_route(HTTPMethod::GET, "/rooms/:room_id", Route::RoomDetails)
+        .add_route(
+            HTTPMethod::GET,
+            "/rooms/:room_id/thumbnail",
+            Route::Thumbnail,
+        )
+        .add_route(
+            HTTPMethod::GET,
+            "/rooms/:room_id/viewers",
+            Route::RoomViewers,
+        )
+        .add_route(HTTPMethod::GET, "/notifications", Route::Notifications)
+        .add_route(HTTPMethod::GET, "/healthz", Route::Health)
+        .add_route(HTTPMethod::GET, "/readyz", Route::Readiness)
+        .add_route(
+            HTTPMethod::DELETE,
+            "/admin/rooms/:room_id",
+            Route::AdminRoomDelete,
+        )
+}
+
+/** Binds the HTTP listener to the configured address, e.g. a port a reverse proxy forwards to.
+ */
+pub fn bind_http_listener(address: SocketAddr) -> io::Result<TcpListener> {
+    let listener = TcpListener::bind(address)?;
+    println!("Running TCP server at {}", listener.local_addr()?);
+    Ok(listener)
+}
+
+/** Starts accepting HTTP requests. `is_ready` is reported by `/readyz`, the UDP side of the server
+ * sets it once media can be handled.
+ */
+pub fn start_http_server(
+    listener: TcpListener,
+    sender: ServerSender,
+    is_ready: Arc<AtomicBool>,
+    event_bus: Arc<EventBus>,
+) {
+    let router = Arc::new(get_router());
+    let tcp_server_config = &get_global_config().tcp_server_config;
+    let pool = get_worker_pool(tcp_server_config.max_connections);
+    let read_timeout = tcp_server_config.read_timeout;
+    let write_timeout = tcp_server_config.write_timeout;
+    let connection_limiter = ConnectionLimiter::new(tcp_server_config.max_connections);
+    let refusal = Response::from(HttpError::Overloaded(
+        "Connection limit reached".to_string(),
+        CONNECTION_LIMIT_RETRY_AFTER_SECS,
+    ));
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                eprintln!("Couldn't accept TCP connection {}", err);
+                continue;
+            }
+        };
+        if let Err(err) = stream.set_write_timeout(Some(write_timeout)) {
+            eprintln!("Couldn't set HTTP write timeout {}", err)
+        }
+        let Some(permit) = accept_connection(&mut stream, &connection_limiter, &refusal) else {
+            continue;
+        };
         let sender = sender.clone();
+        let router = router.clone();
+        let is_ready = is_ready.clone();
+        let event_bus = event_bus.clone();
 
         pool.execute(move || {
-            let mut stream = stream.unwrap();
-            if let Some(request) = parse_http(&mut stream) {
-                match request.path.as_str() {
-                    "/whip" => {
-                        let response = whip_route(request, sender.clone())
-                            .unwrap_or_else(map_http_err_to_response);
-                        stream.write_all(response.as_bytes()).unwrap()
-                    }
-                    "/whep" => {
-                        let response = match &request.method {
-                            HTTPMethod::POST => whep_route(request, sender.clone())
-                                .unwrap_or_else(map_http_err_to_response),
-                            HTTPMethod::OPTIONS => options_route(),
-                            _ => map_http_err_to_response(HttpError::MethodNotAllowed),
-                        };
-                        stream.write_all(response.as_bytes()).unwrap()
-                    }
-                    "/images" => {
-                        let response =
-                            images_route(request).unwrap_or_else(map_http_err_to_response);
-                        stream.write_all(response.as_bytes());
-                    }
-                    "/rooms" => {
-                        let response =
-                            rooms_route(sender.clone()).unwrap_or_else(map_http_err_to_response);
-                        stream.write_all(response.as_bytes());
-                    }
-                    "/notifications" => {
-                        notification_route(&mut stream, sender.clone());
-                    }
-                    _ => {
-                        let response = map_http_err_to_response(HttpError::NotFound);
-                        stream.write_all(response.as_bytes());
+            // Held until the connection is done with, including long lived notification streams
+            let _permit = permit;
+            let request = parse_http(&mut stream, read_timeout);
+            if let Err(RequestError::TimedOut) = &request {
+                let response = Response::from(HttpError::RequestTimeout(
+                    "Request wasn't received in time".to_string(),
+                ));
+                if let Err(err) = stream.write_all(response.as_bytes()) {
+                    eprintln!("Couldn't write HTTP response {}", err)
+                }
+            }
+            if let Ok(mut request) = request {
+                let response = match router.resolve(&request.method, &request.path) {
+                    Ok(route) => {
+                        request.params = route.params;
+                        match route.handler {
+                            Route::Whip => {
+                                whip_route(request, sender).unwrap_or_else(Response::from)
+                            }
+                            Route::WhipUpdate => {
+                                whip_update_route(request, sender).unwrap_or_else(Response::from)
+                            }
+                            Route::Whep => {
+                                whep_route(request, sender).unwrap_or_else(Response::from)
+                            }
+                            Route::WhepOptions => options_route(),
+                            Route::Images => images_route(request).unwrap_or_else(Response::from),
+                            Route::Thumbnail => {
+                                thumbnail_route(request).unwrap_or_else(Response::from)
+                            }
+                            Route::Rooms => rooms_route(sender).unwrap_or_else(Response::from),
+                            Route::RoomDetails => {
+                                room_details_route(request, sender).unwrap_or_else(Response::from)
+                            }
+                            Route::RoomViewers => {
+                                room_viewers_route(request, sender).unwrap_or_else(Response::from)
+                            }
+                            Route::Notifications => {
+                                return notification_route(&mut stream, &event_bus);
+                            }
+                            Route::Health => get_health().build(),
+                            Route::Readiness => get_readiness(&is_ready)
+                                .map(ResponseBuilder::build)
+                                .unwrap_or_else(Response::from),
+                            Route::AdminRoomDelete => admin_room_delete_route(request, sender)
+                                .unwrap_or_else(Response::from),
+                        }
                     }
+                    Err(err) => Response::from(err),
+                };
+
+                if let Err(err) = stream.write_all(response.as_bytes()) {
+                    eprintln!("Couldn't write HTTP response {}", err)
                 }
             }
         });
     }
 }
 
-fn rooms_route(sender: Sender<ServerCommand>) -> Result<Response, HttpError> {
+/** One worker for every connection the limiter lets in. Long lived `/notifications` streams keep
+ * their worker, so a smaller pool would queue accepted connections rather than refuse them.
+ */
+fn get_worker_pool(max_connections: usize) -> ThreadPool {
+    ThreadPool::new(max_connections)
+}
+
+/** Takes a slot for a new connection, or answers it with `refusal` right away if none is left.
+ * The connection is closed once the returned permit and the stream get dropped.
+ */
+fn accept_connection(
+    stream: &mut TcpStream,
+    connection_limiter: &ConnectionLimiter,
+    refusal: &Response,
+) -> Option<ConnectionPermit> {
+    let permit = connection_limiter.try_acquire();
+    if permit.is_none() {
+        if let Err(err) = stream.write_all(refusal.as_bytes()) {
+            eprintln!("Couldn't write HTTP response {}", err)
+        }
+    }
+    permit
+}
+
+fn rooms_route(sender: ServerSender) -> Result<Response, HttpError> {
     let notification_channel = channel::<Notification>();
     sender
         .clone()
@@ -77,7 +236,7 @@ fn rooms_route(sender: Sender<ServerCommand>) -> Result<Response, HttpError> {
     let notification = notification_channel
         .1
         .recv()
-        .map_err(|_| HttpError::InternalServerError)?;
+        .map_err(|_| HttpError::InternalServerError("Couldn't read rooms status".to_string()))?;
 
     let payload = serde_json::to_string(&notification).unwrap();
 
@@ -88,14 +247,89 @@ fn rooms_route(sender: Sender<ServerCommand>) -> Result<Response, HttpError> {
         .build())
 }
 
-fn notification_route(stream: &mut TcpStream, sender: Sender<ServerCommand>) {
-    let notification_channel = channel::<Notification>();
+fn room_details_route(request: Request, sender: ServerSender) -> Result<Response, HttpError> {
+    let room_id = request
+        .params
+        .get("room_id")
+        .ok_or(HttpError::BadRequest("Missing room id".to_string()))?
+        .parse::<RoomID>()
+        .map_err(|_| HttpError::BadRequest("Room id should be u32".to_string()))?;
+
+    let (tx, rx) = channel::<Option<RoomDetails>>();
     sender
-        .clone()
-        .send(ServerCommand::SendRoomsStatus(
-            notification_channel.0.clone(),
-        ))
+        .send(ServerCommand::SendRoomDetails(room_id, tx))
         .expect("ServerCommand channel should remain open");
+
+    let room_details = rx
+        .recv()
+        .map_err(|_| HttpError::InternalServerError("Couldn't read room details".to_string()))?
+        .ok_or(HttpError::NotFound("Room not found".to_string()))?;
+
+    let payload = serde_json::to_string(&room_details).unwrap();
+
+    Ok(ResponseBuilder::new()
+        .set_status(200)
+        .set_header("content-type", "application/json")
+        .set_body(payload.as_bytes())
+        .build())
+}
+
+fn room_viewers_route(request: Request, sender: ServerSender) -> Result<Response, HttpError> {
+    let room_id = request
+        .params
+        .get("room_id")
+        .ok_or(HttpError::BadRequest("Missing room id".to_string()))?
+        .parse::<RoomID>()
+        .map_err(|_| HttpError::BadRequest("Room id should be u32".to_string()))?;
+
+    let (tx, rx) = channel::<Option<RoomViewers>>();
+    sender
+        .send(ServerCommand::SendRoomViewers(room_id, tx))
+        .expect("ServerCommand channel should remain open");
+
+    let room_viewers = rx
+        .recv()
+        .map_err(|_| HttpError::InternalServerError("Couldn't read room viewers".to_string()))?
+        .ok_or(HttpError::NotFound("Room not found".to_string()))?;
+
+    let payload = serde_json::to_string(&room_viewers).unwrap();
+
+    Ok(ResponseBuilder::new()
+        .set_status(200)
+        .set_header("content-type", "application/json")
+        .set_body(payload.as_bytes())
+        .build())
+}
+
+/** Terminates a room, disconnecting its streamer and all of its viewers.
+ */
+fn admin_room_delete_route(request: Request, sender: ServerSender) -> Result<Response, HttpError> {
+    authorize_admin(&request)?;
+
+    let room_id = request
+        .params
+        .get("room_id")
+        .ok_or(HttpError::BadRequest("Missing room id".to_string()))?
+        .parse::<RoomID>()
+        .map_err(|_| HttpError::BadRequest("Room id should be u32".to_string()))?;
+
+    let (tx, rx) = channel::<bool>();
+    sender
+        .send(ServerCommand::TerminateRoom(room_id, tx))
+        .expect("ServerCommand channel should remain open");
+
+    let is_terminated = rx
+        .recv()
+        .map_err(|_| HttpError::InternalServerError("Couldn't terminate room".to_string()))?;
+    if !is_terminated {
+        return Err(HttpError::NotFound("Room not found".to_string()));
+    }
+
+    Ok(ResponseBuilder::new().set_status(204).build())
+}
+
+fn notification_route(stream: &mut TcpStream, event_bus: &EventBus) {
+    let events = event_bus.subscribe();
     let response = ResponseBuilder::new()
         .set_status(200)
         .set_header("Connection", "keep-alive")
@@ -109,70 +343,225 @@ fn notification_route(stream: &mut TcpStream, sender: Sender<ServerCommand>) {
         return; // broken pipe
     }
 
-    loop {
-        if let Ok(notification) = notification_channel.1.recv() {
-            if let Err(_) = stream
-                .write_all(format_notification_to_string(notification).as_bytes())
-                .and_then(|_| stream.flush())
-            {
-                return; // broken pipe
-            }
-
-            sleep(Duration::from_secs(1));
-            sender
-                .clone()
-                .send(ServerCommand::SendRoomsStatus(
-                    notification_channel.0.clone(),
-                ))
-                .expect("ServerCommand channel should remain open");
+    while let Ok(ServerEvent::RoomsStatus(notification)) = events.recv() {
+        if let Err(_) = stream
+            .write_all(format_notification_to_string(&notification).as_bytes())
+            .and_then(|_| stream.flush())
+        {
+            return; // broken pipe
         }
     }
 }
 
-fn format_notification_to_string(notification: Notification) -> String {
+fn format_notification_to_string(notification: &Notification) -> String {
     let payload = serde_json::to_string(&notification).unwrap();
     format!("data: {}\r\n\r\n", payload)
 }
 
-fn whip_route(
-    request: Request,
-    command_sender: Sender<ServerCommand>,
-) -> Result<Response, HttpError> {
+fn authorize_streamer(request: &Request) -> Result<(), HttpError> {
     let config = get_global_config();
 
     let bearer_token = request
         .headers
         .get("authorization")
-        .ok_or(HttpError::Unauthorized)?;
+        .ok_or(HttpError::Unauthorized(
+            "Missing authorization header".to_string(),
+        ))?;
 
     if !bearer_token.eq(&format!("Bearer {}", config.tcp_server_config.whip_token)) {
-        return Err(HttpError::Unauthorized);
+        return Err(HttpError::Unauthorized("Invalid bearer token".to_string()));
     }
 
-    let sdp_offer = request
-        .body
-        .and_then(|body| String::from_utf8(body).ok())
-        .ok_or(HttpError::BadRequest)?;
+    Ok(())
+}
+
+/** Admin requests carry their own token, so that streamers can't manage rooms.
+ */
+fn authorize_admin(request: &Request) -> Result<(), HttpError> {
+    let admin_token = get_global_config()
+        .tcp_server_config
+        .admin_token
+        .as_ref()
+        .ok_or(HttpError::Unauthorized("Admin API is disabled".to_string()))?;
+
+    let bearer_token = request
+        .headers
+        .get("authorization")
+        .ok_or(HttpError::Unauthorized(
+            "Missing authorization header".to_string(),
+        ))?;
+
+    if !bearer_token.eq(&format!("Bearer {}", admin_token)) {
+        return Err(HttpError::Unauthorized("Invalid bearer token".to_string()));
+    }
+
+    Ok(())
+}
+
+fn whip_route(request: Request, command_sender: ServerSender) -> Result<Response, HttpError> {
+    authorize_streamer(&request)?;
+
+    let sdp_offer = get_sdp_offer(request)?;
 
-    let (tx, rx) = channel::<Option<String>>();
+    let (tx, rx) = channel::<Result<(ResourceID, String), StreamerRejection>>();
 
     command_sender
         .send(ServerCommand::AddStreamer(sdp_offer, tx))
         .expect("SessionCommand channel should remain open");
 
-    let sdp_answer = rx
+    let (resource_id, sdp_answer) = rx
         .recv()
         .expect("SessionCommand channel should remain open")
-        .ok_or(HttpError::NotFound)?;
+        .map_err(|rejection| match rejection {
+            StreamerRejection::InvalidOffer(diagnostics) => {
+                get_offer_error("Couldn't accept stream offer", diagnostics)
+            }
+            StreamerRejection::StreamerLimitReached => HttpError::Overloaded(
+                "Streamer limit reached".to_string(),
+                STREAMER_LIMIT_RETRY_AFTER_SECS,
+            ),
+            StreamerRejection::Draining => get_draining_error(),
+        })?;
 
     Ok(ResponseBuilder::new()
         .set_status(201)
         .set_header("content-type", "application/sdp")
-        .set_header("location", "http://localhost:8080/whip")
+        .set_header(
+            "location",
+            &format!("http://localhost:8080/whip/{}", resource_id),
+        )
+        .set_body(sdp_answer.as_bytes())
+        .build())
+}
+
+/** Renegotiates the stream of the WHIP resource, e.g. after the streamer switched codecs.
+ * WHIP clients mark ICE restarts with `If-Match: "*"`.
+ */
+fn whip_update_route(
+    request: Request,
+    command_sender: ServerSender,
+) -> Result<Response, HttpError> {
+    authorize_streamer(&request)?;
+
+    let resource_id = request
+        .params
+        .get("resource_id")
+        .and_then(|resource_id| resource_id.parse::<ResourceID>().ok())
+        .ok_or(HttpError::BadRequest(
+            "resource_id should be u32".to_string(),
+        ))?;
+    let is_ice_restart = request
+        .headers
+        .get("if-match")
+        .is_some_and(|if_match| if_match.eq("\"*\""));
+
+    let sdp_offer = get_sdp_offer(request)?;
+
+    let (tx, rx) = channel::<Result<String, RenegotiationRejection>>();
+
+    command_sender
+        .send(ServerCommand::UpdateStreamer(
+            resource_id,
+            sdp_offer,
+            is_ice_restart,
+            tx,
+        ))
+        .expect("SessionCommand channel should remain open");
+
+    let sdp_answer = rx
+        .recv()
+        .expect("SessionCommand channel should remain open")
+        .map_err(|rejection| match rejection {
+            RenegotiationRejection::StreamerNotFound => {
+                HttpError::NotFound("Stream resource not found".to_string())
+            }
+            // Covers changed ICE credentials without an ICE restart as well
+            RenegotiationRejection::InvalidOffer(diagnostics) => {
+                get_offer_error("Couldn't renegotiate stream offer", diagnostics)
+            }
+        })?;
+
+    Ok(ResponseBuilder::new()
+        .set_status(200)
+        .set_header("content-type", "application/sdp")
         .set_body(sdp_answer.as_bytes())
         .build())
 }
 
+/** Reads the SDP offer from the request body, inflating it first if the client sent it with `Content-Encoding: gzip`.
+ */
+fn get_sdp_offer(request: Request) -> Result<String, HttpError> {
+    let body = request.body.ok_or(HttpError::BadRequest(
+        "Body should be a UTF-8 SDP offer".to_string(),
+    ))?;
+
+    let body = match request.headers.get("content-encoding").map(String::as_str) {
+        None | Some("identity") => body,
+        Some("gzip") => inflate_gzip_body(&body)?,
+        Some(encoding) => {
+            return Err(HttpError::BadRequest(format!(
+                "Unsupported content encoding {}",
+                encoding
+            )))
+        }
+    };
+
+    String::from_utf8(body)
+        .map_err(|_| HttpError::BadRequest("Body should be a UTF-8 SDP offer".to_string()))
+}
+
+/** Inflates a gzip body, refusing to read past MAX_SDP_OFFER_SIZE so compressed payloads can't blow up memory.
+ */
+fn inflate_gzip_body(body: &[u8]) -> Result<Vec<u8>, HttpError> {
+    let mut inflated_body = Vec::new();
+    GzDecoder::new(body)
+        .take(MAX_SDP_OFFER_SIZE as u64 + 1)
+        .read_to_end(&mut inflated_body)
+        .map_err(|_| HttpError::BadRequest("Body should be valid gzip".to_string()))?;
+
+    if inflated_body.len() > MAX_SDP_OFFER_SIZE {
+        return Err(HttpError::BadRequest(format!(
+            "Decompressed body exceeds {} bytes",
+            MAX_SDP_OFFER_SIZE
+        )));
+    }
+
+    Ok(inflated_body)
+}
+
+/** Liveness probe. Answered without going through the main loop, so that it stays fast under load.
+ */
+fn get_health() -> ResponseBuilder {
+    ResponseBuilder::new()
+        .set_status(200)
+        .set_header("content-type", "application/json")
+        .set_body(json!({ "status": "ok" }).to_string().as_bytes())
+}
+
+/** Readiness probe, failing until the UDP socket is bound and the SDP resolver is initialized.
+ */
+fn get_readiness(is_ready: &AtomicBool) -> Result<ResponseBuilder, HttpError> {
+    if !is_ready.load(Ordering::Acquire) {
+        return Err(HttpError::ServiceUnavailable(
+            "Server is starting or draining".to_string(),
+        ));
+    }
+
+    Ok(get_health())
+}
+
+/** Clients turned down by a draining server are expected to retry against its successor.
+ */
+fn get_draining_error() -> HttpError {
+    HttpError::ServiceUnavailable("Server is draining".to_string())
+}
+
+fn get_offer_error(message: &str, diagnostics: OfferDiagnostics) -> HttpError {
+    let message = format!("{}: {}", message, diagnostics.error);
+    eprintln!("{}", message);
+    HttpError::UnprocessableOffer(message, diagnostics.failed_requirements)
+}
+
 fn options_route() -> Response {
     ResponseBuilder::new()
         .set_status(204)
@@ -181,31 +570,36 @@ fn options_route() -> Response {
         .build()
 }
 
-fn whep_route(
-    request: Request,
-    command_sender: Sender<ServerCommand>,
-) -> Result<Response, HttpError> {
+fn whep_route(request: Request, command_sender: ServerSender) -> Result<Response, HttpError> {
     let target_id = request
         .search
         .get("target_id")
-        .ok_or(HttpError::BadRequest)?
+        .ok_or(HttpError::BadRequest("Missing target_id".to_string()))?
         .to_string()
         .parse::<u32>()
-        .map_err(|_| HttpError::BadRequest)?;
+        .map_err(|_| HttpError::BadRequest("target_id should be u32".to_string()))?;
 
-    let (tx, rx) = channel::<Option<String>>();
+    let (tx, rx) = channel::<Result<String, ViewerRejection>>();
 
-    let body = request
-        .body
-        .and_then(|body| String::from_utf8(body).ok())
-        .ok_or(HttpError::BadRequest)?;
+    let body = get_sdp_offer(request)?;
 
     command_sender
         .send(ServerCommand::AddViewer(body, target_id, tx))
         .expect("Session Command channel should remain open");
 
-    // todo Handle unsupported codecs
-    let sdp_answer = rx.recv().unwrap().ok_or(HttpError::BadRequest)?;
+    let sdp_answer = rx.recv().unwrap().map_err(|rejection| match rejection {
+        ViewerRejection::InvalidOffer(diagnostics) => {
+            get_offer_error("Couldn't accept viewer offer", diagnostics)
+        }
+        ViewerRejection::RoomNotFound => {
+            HttpError::BadRequest("Couldn't accept viewer offer".to_string())
+        }
+        ViewerRejection::ViewerLimitReached => HttpError::Overloaded(
+            "Viewer limit of the room reached".to_string(),
+            VIEWER_LIMIT_RETRY_AFTER_SECS,
+        ),
+        ViewerRejection::Draining => get_draining_error(),
+    })?;
 
     let cors_origin = &get_global_config().frontend_url;
 
@@ -223,26 +617,207 @@ fn whep_route(
 }
 
 fn images_route(request: Request) -> Result<Response, HttpError> {
+    // Image name is either a path param or a search param
     let file_name = request
-        .search
+        .params
         .get("image")
-        .ok_or(HttpError::BadRequest)?
+        .or(request.search.get("image"))
+        .ok_or(HttpError::BadRequest("Missing image".to_string()))?
         .as_str();
 
     let parsed_name = Path::new(file_name)
         .file_name()
-        .ok_or(HttpError::BadRequest)?;
+        .ok_or(HttpError::BadRequest("Invalid image name".to_string()))?;
     let mut file_pathname = get_global_config().storage_dir.clone();
     file_pathname.push(parsed_name);
-    let target_file = fs::read(file_pathname).map_err(|_| HttpError::NotFound)?;
+    let target_file =
+        fs::read(file_pathname).map_err(|_| HttpError::NotFound("Image not found".to_string()))?;
 
-    Ok(ResponseBuilder::new()
+    let etag = get_thumbnail_etag(&target_file);
+    Ok(get_thumbnail_response_builder(target_file, &etag).build())
+}
+
+fn thumbnail_route(request: Request) -> Result<Response, HttpError> {
+    let room_id = request
+        .params
+        .get("room_id")
+        .ok_or(HttpError::BadRequest("Missing room id".to_string()))?
+        .parse::<u32>()
+        .map_err(|_| HttpError::BadRequest("Room id should be u32".to_string()))?;
+
+    let if_none_match = request.headers.get("if-none-match");
+
+    get_room_thumbnail(&get_global_config().storage_dir, room_id, if_none_match)
+        .map(ResponseBuilder::build)
+}
+
+/** Latest thumbnail of the room, stored by the media shards. Rooms get their first thumbnail once a picture is decoded.
+Responds with 304 when the `If-None-Match` header matches the thumbnail ETag.
+ */
+fn get_room_thumbnail(
+    storage_dir: &Path,
+    room_id: u32,
+    if_none_match: Option<&String>,
+) -> Result<ResponseBuilder, HttpError> {
+    let read_thumbnail = || {
+        fs::read(get_thumbnail_path(storage_dir, room_id))
+            .map_err(|_| HttpError::NotFound("Room has no thumbnail yet".to_string()))
+    };
+    let etag = match fs::read_to_string(get_thumbnail_etag_path(storage_dir, room_id)) {
+        Ok(etag) => etag,
+        Err(_) => get_thumbnail_etag(&read_thumbnail()?),
+    };
+
+    if if_none_match.is_some_and(|if_none_match| is_matching_etag(if_none_match, &etag)) {
+        return Ok(set_thumbnail_cache_headers(
+            ResponseBuilder::new().set_status(304),
+            &etag,
+        ));
+    }
+
+    Ok(get_thumbnail_response_builder(read_thumbnail()?, &etag))
+}
+
+fn is_matching_etag(if_none_match: &str, etag: &str) -> bool {
+    if_none_match
+        .split(",")
+        .map(str::trim)
+        .any(|value| value == "*" || value == etag)
+}
+
+fn get_thumbnail_response_builder(thumbnail: Vec<u8>, etag: &str) -> ResponseBuilder {
+    let response_builder = ResponseBuilder::new()
         .set_status(200)
-        .set_header("Content-Type", "image/webp")
-        .add_body(target_file)
-        .build())
+        .set_header("Content-Type", THUMBNAIL_CONTENT_TYPE)
+        .add_body(thumbnail);
+
+    set_thumbnail_cache_headers(response_builder, etag)
+}
+
+fn set_thumbnail_cache_headers(response_builder: ResponseBuilder, etag: &str) -> ResponseBuilder {
+    // Browsers may reuse the thumbnail until it gets refreshed
+    let cache_control = format!(
+        "max-age={}",
+        get_reloadable_config().thumbnail_refresh_interval.as_secs()
+    );
+
+    response_builder
+        .set_header("Cache-Control", &cache_control)
+        .set_header("ETag", etag)
+}
+
+pub fn get_rooms_status(session_registry: &SessionRegistry) -> Notification {
+    let rooms = session_registry
+        .get_rooms()
+        .into_iter()
+        .map(|room| {
+            let video_codec = get_video_codec(session_registry, &room);
+
+            let bitrate = room.bitrate();
+            let viewer_stats = room
+                .viewer_ids
+                .iter()
+                .filter_map(|id| session_registry.get_session(*id))
+                .filter_map(|session| session.loss_stats)
+                .collect::<Vec<_>>();
+            let viewer_fraction_lost = match viewer_stats.len() {
+                0 => 0.0,
+                count => {
+                    viewer_stats
+                        .iter()
+                        .map(|stats| stats.fraction_lost)
+                        .sum::<f64>()
+                        / count as f64
+                }
+            };
+            let delivery_ratios = viewer_stats
+                .iter()
+                .filter_map(|stats| stats.delivery_ratio)
+                .collect::<Vec<_>>();
+            let viewer_delivery_ratio = match delivery_ratios.len() {
+                0 => None,
+                count => Some(delivery_ratios.iter().sum::<f64>() / count as f64),
+            };
+
+            Room {
+                viewer_count: room.viewer_ids.len(),
+                id: room.id,
+                uptime_secs: room.created_at.elapsed().as_secs(),
+                video_codec,
+                ingress_bitrate_bps: bitrate.ingress_bps,
+                egress_bitrate_bps: bitrate.egress_bps,
+                viewer_fraction_lost,
+                viewer_delivery_ratio,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    Notification { rooms }
+}
+
+/** Detailed stats of a single room, None for unknown rooms.
+ */
+pub fn get_room_details(
+    session_registry: &SessionRegistry,
+    room_id: RoomID,
+) -> Option<RoomDetails> {
+    let room = session_registry.get_room(room_id)?;
+
+    Some(RoomDetails {
+        id: room.id,
+        viewer_count: room.viewer_ids.len(),
+        video_codec: get_video_codec(session_registry, room),
+        resolution: room
+            .video
+            .resolution
+            .map(|(width, height)| Resolution { width, height }),
+        ingress_bitrate_bps: room.bitrate().ingress_bps,
+        keyframe_seen: room.video.is_keyframe_seen,
+        last_keyframe_unix_ms: room
+            .video
+            .last_keyframe_time
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|elapsed| elapsed.as_millis() as u64),
+        streamer_state: session_registry
+            .get_session(room.owner_id)
+            .map(|session| session.state().as_str().to_string())
+            .unwrap_or_default(),
+    })
+}
+
+/** Viewers of a single room, None for unknown rooms. Credentials of the sessions are left out.
+ */
+pub fn get_room_viewers(
+    session_registry: &SessionRegistry,
+    room_id: RoomID,
+) -> Option<RoomViewers> {
+    let viewers = session_registry
+        .get_room_viewers(room_id)?
+        .into_iter()
+        .map(|session| ViewerSummary {
+            id: session.id,
+            joined_secs_ago: session.created_at.elapsed().as_secs(),
+            last_seen_secs_ago: session.ttl.elapsed().as_secs(),
+            is_stale: session.is_stale,
+            state: session.state().as_str().to_string(),
+        })
+        .collect();
+
+    Some(RoomViewers { viewers })
+}
+
+fn get_video_codec(session_registry: &SessionRegistry, room: &RegistryRoom) -> String {
+    session_registry
+        .get_session(room.owner_id)
+        .map(|session| match session.media_session.video_session.codec {
+            VideoCodec::H264 => "H264".to_string(),
+        })
+        .unwrap_or_default()
 }
 
+/** Rooms status served by `GET /rooms` and the `/notifications` event stream.
+ * Field names are part of the public API, do not rename them.
+ */
 #[derive(Serialize, Deserialize)]
 pub struct Notification {
     pub rooms: Vec<Room>,
@@ -250,6 +825,490 @@ pub struct Notification {
 
 #[derive(Serialize, Deserialize)]
 pub struct Room {
+    /// Number of viewers currently joined to the room
     pub viewer_count: usize,
+    /// Room id, used as the `target_id` of the WHEP endpoint
     pub id: u32,
+    /// Seconds elapsed since the streamer opened the room
+    pub uptime_secs: u64,
+    /// Video codec of the stream, e.g. `H264`
+    pub video_codec: String,
+    /// Bits per second received from the streamer over the last second
+    pub ingress_bitrate_bps: u64,
+    /// Bits per second forwarded to all viewers over the last second
+    pub egress_bitrate_bps: u64,
+    /// Average fraction of packets lost by viewers, between 0 and 1, as reported in RTCP Receiver Reports
+    pub viewer_fraction_lost: f64,
+    /// Average fraction of sent packets that reached viewers, null until viewers send Receiver Reports
+    pub viewer_delivery_ratio: Option<f64>,
+}
+
+/** Room stats served by `GET /rooms/{id}`. Field names are part of the public API, do not rename them.
+ */
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RoomDetails {
+    /// Room id, used as the `target_id` of the WHEP endpoint
+    pub id: u32,
+    /// Number of viewers currently joined to the room
+    pub viewer_count: usize,
+    /// Video codec of the stream, e.g. `H264`
+    pub video_codec: String,
+    /// Resolution of the stream, null until a picture is decoded
+    pub resolution: Option<Resolution>,
+    /// Bits per second received from the streamer over the last second
+    pub ingress_bitrate_bps: u64,
+    /// Whether the streamer has sent a keyframe, viewers can't decode anything before it
+    pub keyframe_seen: bool,
+    /// Unix time in milliseconds of the latest keyframe, null until one is received
+    pub last_keyframe_unix_ms: Option<u64>,
+    /// Connection state of the streamer, e.g. `connected`
+    pub streamer_state: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Resolution {
+    pub width: u16,
+    pub height: u16,
+}
+
+/** Viewers of a room served by `GET /rooms/{id}/viewers`. Field names are part of the public API,
+ * do not rename them.
+ */
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RoomViewers {
+    pub viewers: Vec<ViewerSummary>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ViewerSummary {
+    /// Viewer session id
+    pub id: ResourceID,
+    /// Seconds elapsed since the viewer joined the room
+    pub joined_secs_ago: u64,
+    /// Seconds elapsed since the viewer's connection was last refreshed
+    pub last_seen_secs_ago: u64,
+    /// Whether the viewer went silent for longer than the session TTL
+    pub is_stale: bool,
+    /// Connection state of the viewer, media is only forwarded once `connected`
+    pub state: String,
+}
+
+#[cfg(test)]
+mod tests {
+    mod bind_http_listener {
+        use std::net::{Ipv4Addr, SocketAddr};
+
+        use crate::http::server::bind_http_listener;
+
+        #[test]
+        fn listens_on_configured_address() {
+            let address = SocketAddr::from((Ipv4Addr::LOCALHOST, 0));
+
+            let listener = bind_http_listener(address).expect("Should bind HTTP listener");
+            let local_address = listener.local_addr().unwrap();
+
+            assert_eq!(local_address.ip(), address.ip());
+            // A taken address is reported, rather than served by another process
+            assert!(bind_http_listener(local_address).is_err());
+        }
+    }
+
+    mod accept_connection {
+        use std::io::{Read, Write};
+        use std::net::{TcpListener, TcpStream};
+        use std::sync::{Arc, Mutex};
+        use std::sync::mpsc::channel;
+        use std::time::Duration;
+
+        use crate::http::connection_limiter::ConnectionLimiter;
+        use crate::http::Response;
+        use crate::http::server::{accept_connection, get_worker_pool};
+
+        const REFUSAL: &[u8] = b"HTTP/1.1 503 SERVICE UNAVAILABLE\r\nretry-after: 1\r\n\r\n";
+
+        fn connect(listener: &TcpListener) -> (TcpStream, TcpStream) {
+            let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+            let (server, _) = listener.accept().unwrap();
+            (server, client)
+        }
+
+        #[test]
+        fn refuses_connections_beyond_limit() {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let connection_limiter = ConnectionLimiter::new(1);
+            let refusal = Response {
+                _inner: REFUSAL.to_vec(),
+                status: 503,
+            };
+
+            let (mut open_server, mut open_client) = connect(&listener);
+            let open_permit = accept_connection(&mut open_server, &connection_limiter, &refusal);
+            assert!(open_permit.is_some());
+
+            let (mut refused_server, mut refused_client) = connect(&listener);
+            let refused_permit =
+                accept_connection(&mut refused_server, &connection_limiter, &refusal);
+            assert!(refused_permit.is_none());
+            drop(refused_server);
+            let mut refused_response = Vec::new();
+            refused_client.read_to_end(&mut refused_response).unwrap();
+            assert_eq!(refused_response, REFUSAL);
+
+            // The connection taken first is still served
+            open_server.write_all(b"pong").unwrap();
+            let mut open_response = [0u8; 4];
+            open_client.read_exact(&mut open_response).unwrap();
+            assert_eq!(&open_response, b"pong");
+
+            drop(open_permit);
+            let (mut next_server, _next_client) = connect(&listener);
+            assert!(accept_connection(&mut next_server, &connection_limiter, &refusal).is_some());
+        }
+
+        #[test]
+        fn refuses_connections_while_long_lived_ones_hold_workers() {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let max_connections = 2;
+            let connection_limiter = ConnectionLimiter::new(max_connections);
+            let pool = get_worker_pool(max_connections);
+            let refusal = Response {
+                _inner: REFUSAL.to_vec(),
+                status: 503,
+            };
+            let (started_tx, started_rx) = channel();
+            let (release_tx, release_rx) = channel::<()>();
+            let release_rx = Arc::new(Mutex::new(release_rx));
+
+            // Stand-ins for notification streams, holding their worker until released
+            let mut clients = Vec::new();
+            for _ in 0..max_connections {
+                let (mut server, client) = connect(&listener);
+                let permit = accept_connection(&mut server, &connection_limiter, &refusal)
+                    .expect("Should take a slot");
+                let started_tx = started_tx.clone();
+                let release_rx = release_rx.clone();
+                pool.execute(move || {
+                    let _permit = permit;
+                    let _server = server;
+                    started_tx.send(()).unwrap();
+                    let _ = release_rx.lock().unwrap().recv();
+                });
+                clients.push(client);
+            }
+            // Every accepted connection is being served, none is waiting for a worker
+            for _ in 0..max_connections {
+                started_rx
+                    .recv_timeout(Duration::from_secs(5))
+                    .expect("Accepted connection should get a worker");
+            }
+
+            let (mut refused_server, mut refused_client) = connect(&listener);
+            let refused_permit =
+                accept_connection(&mut refused_server, &connection_limiter, &refusal);
+            assert!(refused_permit.is_none());
+            drop(refused_server);
+            let mut refused_response = Vec::new();
+            refused_client.read_to_end(&mut refused_response).unwrap();
+            assert_eq!(refused_response, REFUSAL);
+
+            drop(release_tx);
+            pool.join();
+            let (mut next_server, _next_client) = connect(&listener);
+            assert!(accept_connection(&mut next_server, &connection_limiter, &refusal).is_some());
+        }
+    }
+
+    mod get_room_thumbnail {
+        use std::fs;
+        use std::path::PathBuf;
+
+        use crate::http::HttpError;
+        use crate::http::server::get_room_thumbnail;
+        use crate::thumbnail::store_thumbnail;
+
+        fn get_storage_dir() -> PathBuf {
+            let storage_dir =
+                std::env::temp_dir().join(format!("thumbnails-{}", rand::random::<u32>()));
+            fs::create_dir_all(&storage_dir).unwrap();
+            storage_dir
+        }
+
+        #[test]
+        fn serves_thumbnail_with_caching_headers() {
+            let storage_dir = get_storage_dir();
+            store_thumbnail(&storage_dir, 7, &[1, 2, 3]).unwrap();
+
+            let response_builder =
+                get_room_thumbnail(&storage_dir, 7, None).expect("Should serve thumbnail");
+            fs::remove_dir_all(&storage_dir).unwrap();
+
+            assert_eq!(response_builder.status, Some(200));
+            assert_eq!(
+                response_builder.headers.get("Content-Type"),
+                Some(&"image/webp".to_string())
+            );
+            assert_eq!(
+                response_builder.headers.get("Cache-Control"),
+                Some(&"max-age=120".to_string())
+            );
+            assert!(response_builder.headers.contains_key("ETag"));
+            assert_eq!(response_builder.body, Some(vec![1, 2, 3]));
+        }
+
+        #[test]
+        fn responds_not_modified_for_matching_etag() {
+            let storage_dir = get_storage_dir();
+            store_thumbnail(&storage_dir, 7, &[1, 2, 3]).unwrap();
+
+            let response_builder =
+                get_room_thumbnail(&storage_dir, 7, None).expect("Should serve thumbnail");
+            let etag = response_builder.headers.get("ETag").unwrap().clone();
+            let conditional_response_builder =
+                get_room_thumbnail(&storage_dir, 7, Some(&etag)).expect("Should serve thumbnail");
+            fs::remove_dir_all(&storage_dir).unwrap();
+
+            assert_eq!(conditional_response_builder.status, Some(304));
+            assert_eq!(
+                conditional_response_builder.headers.get("ETag"),
+                Some(&etag)
+            );
+            assert_eq!(conditional_response_builder.body, None);
+        }
+
+        #[test]
+        fn serves_updated_thumbnail_for_stale_etag() {
+            let storage_dir = get_storage_dir();
+            store_thumbnail(&storage_dir, 7, &[1, 2, 3]).unwrap();
+            let stale_etag =
+                get_room_thumbnail(&storage_dir, 7, None).unwrap().headers["ETag"].clone();
+            store_thumbnail(&storage_dir, 7, &[4, 5, 6]).unwrap();
+
+            let response_builder = get_room_thumbnail(&storage_dir, 7, Some(&stale_etag))
+                .expect("Should serve thumbnail");
+            fs::remove_dir_all(&storage_dir).unwrap();
+
+            assert_eq!(response_builder.status, Some(200));
+            assert_ne!(response_builder.headers.get("ETag"), Some(&stale_etag));
+            assert_eq!(response_builder.body, Some(vec![4, 5, 6]));
+        }
+
+        #[test]
+        fn rejects_room_without_thumbnail() {
+            let storage_dir = get_storage_dir();
+
+            let result = get_room_thumbnail(&storage_dir, 7, None);
+            fs::remove_dir_all(&storage_dir).unwrap();
+
+            assert!(matches!(result, Err(HttpError::NotFound(_))));
+        }
+    }
+
+    mod get_sdp_offer {
+        use std::collections::HashMap;
+        use std::io::Write;
+
+        use flate2::Compression;
+        use flate2::write::GzEncoder;
+
+        use crate::http::{HttpError, HTTPMethod, Request};
+        use crate::http::server::{get_sdp_offer, MAX_SDP_OFFER_SIZE};
+        use crate::test_utils::{get_sdp_resolver, STREAMER_OFFER};
+
+        fn get_gzip_request(body: &[u8]) -> Request {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(body).unwrap();
+
+            Request {
+                path: "/whip".to_string(),
+                method: HTTPMethod::POST,
+                search: HashMap::new(),
+                headers: HashMap::from([("content-encoding".to_string(), "gzip".to_string())]),
+                params: HashMap::new(),
+                body: Some(encoder.finish().unwrap()),
+            }
+        }
+
+        #[test]
+        fn inflates_gzip_offer() {
+            let request = get_gzip_request(STREAMER_OFFER.as_bytes());
+
+            let sdp_offer = get_sdp_offer(request).expect("Should inflate offer");
+
+            assert_eq!(sdp_offer, STREAMER_OFFER);
+            assert!(get_sdp_resolver().accept_stream_offer(&sdp_offer).is_ok());
+        }
+
+        #[test]
+        fn rejects_oversized_gzip_body() {
+            let request = get_gzip_request(&vec![b'a'; MAX_SDP_OFFER_SIZE + 1]);
+
+            let result = get_sdp_offer(request);
+
+            assert!(matches!(result, Err(HttpError::BadRequest(_))));
+        }
+    }
+
+    mod get_readiness {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        use crate::http::server::get_readiness;
+
+        #[test]
+        fn responds_unavailable_until_ready() {
+            let is_ready = AtomicBool::new(false);
+
+            let result = get_readiness(&is_ready);
+            assert!(matches!(result, Err(ref err) if err.status() == 503));
+
+            is_ready.store(true, Ordering::Release);
+            let response_builder = get_readiness(&is_ready).expect("Should be ready");
+            assert_eq!(response_builder.status, Some(200));
+        }
+    }
+
+    mod get_rooms_status {
+        use serde_json::json;
+
+        use crate::http::server::get_rooms_status;
+        use crate::ice_registry::SessionRegistry;
+        use crate::test_utils::get_streamer_session;
+
+        #[test]
+        fn serializes_room_with_viewers() {
+            let mut registry = SessionRegistry::new();
+            registry.add_streamer(get_streamer_session());
+            let room_id = registry.get_rooms()[0].id;
+            registry.add_viewer(get_streamer_session(), room_id);
+            registry.add_viewer(get_streamer_session(), room_id);
+
+            let payload = serde_json::to_value(get_rooms_status(&registry)).unwrap();
+
+            assert_eq!(
+                payload,
+                json!({
+                    "rooms": [{
+                        "viewer_count": 2,
+                        "id": room_id,
+                        "uptime_secs": 0,
+                        "video_codec": "H264",
+                        "ingress_bitrate_bps": 0,
+                        "egress_bitrate_bps": 0,
+                        "viewer_fraction_lost": 0.0,
+                        "viewer_delivery_ratio": null
+                    }]
+                })
+            );
+        }
+    }
+
+    mod get_room_details {
+        use std::time::{Duration, UNIX_EPOCH};
+
+        use serde_json::json;
+
+        use crate::http::server::get_room_details;
+        use crate::ice_registry::{RoomVideo, SessionRegistry};
+        use crate::session_state::SessionState;
+        use crate::test_utils::get_streamer_session;
+
+        #[test]
+        fn reports_resolution_once_known() {
+            let mut registry = SessionRegistry::new();
+            registry.add_streamer(get_streamer_session());
+            let room_id = registry.get_rooms()[0].id;
+
+            let payload = serde_json::to_value(get_room_details(&registry, room_id)).unwrap();
+            assert_eq!(payload["resolution"], json!(null));
+            assert_eq!(payload["keyframe_seen"], json!(false));
+            assert_eq!(payload["last_keyframe_unix_ms"], json!(null));
+
+            registry.update_room_video(
+                room_id,
+                RoomVideo {
+                    resolution: Some((1280, 720)),
+                    is_keyframe_seen: true,
+                    last_keyframe_time: Some(UNIX_EPOCH + Duration::from_millis(1_700_000_000_123)),
+                },
+            );
+            let payload = serde_json::to_value(get_room_details(&registry, room_id)).unwrap();
+
+            assert_eq!(
+                payload,
+                json!({
+                    "id": room_id,
+                    "viewer_count": 0,
+                    "video_codec": "H264",
+                    "resolution": { "width": 1280, "height": 720 },
+                    "ingress_bitrate_bps": 0,
+                    "keyframe_seen": true,
+                    "last_keyframe_unix_ms": 1_700_000_000_123u64,
+                    "streamer_state": "new"
+                })
+            );
+        }
+
+        #[test]
+        fn reports_streamer_state() {
+            let mut registry = SessionRegistry::new();
+            let streamer_id = registry
+                .add_streamer(get_streamer_session())
+                .expect("Should add streamer");
+            let room_id = registry.get_rooms()[0].id;
+            registry.nominate_address("127.0.0.1:52000".parse().unwrap(), &streamer_id);
+            registry
+                .get_session_mut(streamer_id)
+                .expect("Streamer session should exist")
+                .set_state(SessionState::Connected)
+                .expect("Handshaking session should connect");
+
+            let payload = serde_json::to_value(get_room_details(&registry, room_id)).unwrap();
+
+            assert_eq!(payload["streamer_state"], json!("connected"));
+        }
+
+        #[test]
+        fn skips_unknown_room() {
+            let registry = SessionRegistry::new();
+
+            assert!(get_room_details(&registry, 1).is_none());
+        }
+    }
+
+    mod get_room_viewers {
+        use serde_json::json;
+
+        use crate::http::server::get_room_viewers;
+        use crate::ice_registry::SessionRegistry;
+        use crate::test_utils::get_streamer_session;
+
+        #[test]
+        fn serializes_viewers_without_credentials() {
+            let mut registry = SessionRegistry::new();
+            registry.add_streamer(get_streamer_session());
+            let room_id = registry.get_rooms()[0].id;
+            let viewer_id = registry.add_viewer(get_streamer_session(), room_id);
+
+            let payload = serde_json::to_value(get_room_viewers(&registry, room_id)).unwrap();
+
+            assert_eq!(
+                payload,
+                json!({
+                    "viewers": [{
+                        "id": viewer_id,
+                        "joined_secs_ago": 0,
+                        "last_seen_secs_ago": 0,
+                        "is_stale": false,
+                        "state": "new"
+                    }]
+                })
+            );
+        }
+
+        #[test]
+        fn skips_unknown_room() {
+            let registry = SessionRegistry::new();
+
+            assert!(get_room_viewers(&registry, 1).is_none());
+        }
+    }
 }
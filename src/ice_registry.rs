@@ -1,28 +1,48 @@
 use std::collections::{HashMap, HashSet};
 use std::net::SocketAddr;
-use std::time::Instant;
+use std::time::{Duration, Instant, SystemTime};
 
 use rand::{RngCore, thread_rng};
 
 use sdp::NegotiatedSession;
-use thumbnail_image_extractor::ThumbnailExtractor;
 
-use crate::client::Client;
+use crate::bitrate::RoomBitrate;
+use crate::receiver_report::LossStats;
+use crate::session_state::{InvalidTransition, SessionState};
 
-type RoomID = u32;
-type ResourceID = u32;
+pub type RoomID = u32;
+pub type ResourceID = u32;
 
 pub struct SessionRegistry {
     sessions: HashMap<ResourceID, Session>,
     username_map: HashMap<SessionUsername, ResourceID>,
     address_map: HashMap<SocketAddr, ResourceID>,
     rooms: HashMap<RoomID, Room>,
+    max_streamers: Option<usize>,
+    max_viewers_per_room: Option<usize>,
+    is_draining: bool,
 }
 #[derive(Clone)]
 pub struct Room {
     pub id: u32,
     pub owner_id: u32,
     pub viewer_ids: HashSet<u32>,
+    pub created_at: Instant,
+    pub video: RoomVideo,
+    shard_bitrates: HashMap<usize, RoomBitrate>,
+}
+
+/** Properties of a room's video stream, reported by the shard handling its streamer.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RoomVideo {
+    /** Width and height signalled by the latest SPS of the stream.
+     */
+    pub resolution: Option<(u16, u16)>,
+    pub is_keyframe_seen: bool,
+    /** Wall-clock time of the latest keyframe.
+     */
+    pub last_keyframe_time: Option<SystemTime>,
 }
 
 impl Room {
@@ -31,18 +51,96 @@ impl Room {
             id,
             owner_id,
             viewer_ids: HashSet::new(),
+            created_at: Instant::now(),
+            video: RoomVideo::default(),
+            shard_bitrates: HashMap::new(),
         }
     }
+
+    /** Room bitrate summed over reports of all media shards.
+     */
+    pub fn bitrate(&self) -> RoomBitrate {
+        self.shard_bitrates
+            .values()
+            .fold(RoomBitrate::default(), |total, bitrate| RoomBitrate {
+                ingress_bps: total.ingress_bps + bitrate.ingress_bps,
+                egress_bps: total.egress_bps + bitrate.egress_bps,
+            })
+    }
 }
 
 impl SessionRegistry {
     pub fn new() -> Self {
+        Self::with_max_streamers(None)
+    }
+
+    /** Registry accepting at most `max_streamers` concurrent streamers, if given.
+     */
+    pub fn with_max_streamers(max_streamers: Option<usize>) -> Self {
         SessionRegistry {
             sessions: HashMap::new(),
             username_map: HashMap::new(),
             address_map: HashMap::new(),
             rooms: HashMap::new(),
+            max_streamers,
+            max_viewers_per_room: None,
+            is_draining: false,
+        }
+    }
+
+    /** Changes the streamer limit. Streamers past a lowered limit stay, new ones are turned down.
+     */
+    pub fn set_max_streamers(&mut self, max_streamers: Option<usize>) {
+        self.max_streamers = max_streamers;
+    }
+
+    /** Changes the viewer limit of every room. Viewers past a lowered limit stay, new ones are
+     * turned down until enough leave.
+     */
+    pub fn set_max_viewers_per_room(&mut self, max_viewers_per_room: Option<usize>) {
+        self.max_viewers_per_room = max_viewers_per_room;
+    }
+
+    /** Whether the room has as many viewers as the limit allows. Unknown rooms are never full.
+     */
+    pub fn is_room_full(&self, room_id: RoomID) -> bool {
+        self.rooms.get(&room_id).is_some_and(|room| {
+            self.max_viewers_per_room
+                .is_some_and(|max_viewers| room.viewer_ids.len() >= max_viewers)
+        })
+    }
+
+    /** Turns down new sessions from now on, registered ones stay until they end.
+     */
+    pub fn start_draining(&mut self) {
+        self.is_draining = true;
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.is_draining
+    }
+
+    /** Registers a session handed over by a draining predecessor, under its previous IDs.
+     * Streamers have to be restored before their viewers, viewers of missing rooms are dropped.
+     */
+    pub fn restore_session(&mut self, session: Session) -> bool {
+        match &session.connection_type {
+            ConnectionType::Streamer(streamer) => {
+                let room_id = streamer.owned_room_id;
+                self.rooms.insert(room_id, Room::new(room_id, session.id));
+            }
+            ConnectionType::Viewer(viewer) => match self.rooms.get_mut(&viewer.room_id) {
+                Some(room) => {
+                    room.viewer_ids.insert(session.id);
+                }
+                None => return false,
+            },
         }
+        self.username_map
+            .insert(get_session_username(&session.media_session), session.id);
+        self.sessions.insert(session.id, session);
+
+        true
     }
 
     pub fn get_room_ids(&self) -> Vec<RoomID> {
@@ -60,11 +158,48 @@ impl SessionRegistry {
         self.rooms.get(&room_id)
     }
 
-    pub fn nominate_client(&mut self, client: Client, id: &ResourceID) -> Option<ResourceID> {
-        let address = client.remote_address.clone();
+    /** Viewer sessions of a room in the order they joined, None for unknown rooms.
+     */
+    pub fn get_room_viewers(&self, room_id: RoomID) -> Option<Vec<&Session>> {
+        let room = self.rooms.get(&room_id)?;
+        let mut viewers = room
+            .viewer_ids
+            .iter()
+            .filter_map(|id| self.sessions.get(id))
+            .collect::<Vec<_>>();
+        viewers.sort_by_key(|session| (session.created_at, session.id));
+
+        Some(viewers)
+    }
+
+    pub fn update_room_bitrate(
+        &mut self,
+        room_id: RoomID,
+        shard_index: usize,
+        bitrate: RoomBitrate,
+    ) {
+        if let Some(room) = self.rooms.get_mut(&room_id) {
+            room.shard_bitrates.insert(shard_index, bitrate);
+        }
+    }
+
+    pub fn update_room_video(&mut self, room_id: RoomID, video: RoomVideo) {
+        if let Some(room) = self.rooms.get_mut(&room_id) {
+            room.video = video;
+        }
+    }
+
+    /** Binds the session to its nominated address, which starts the DTLS handshake.
+     */
+    pub fn nominate_address(&mut self, address: SocketAddr, id: &ResourceID) -> Option<ResourceID> {
         self.sessions
             .get_mut(id)
-            .map(|session| session.client = Some(client))
+            .map(|session| {
+                session.remote_address = Some(address);
+                if let Err(err) = session.set_state(SessionState::DtlsHandshaking) {
+                    eprintln!("Session {} {}", session.id, err)
+                }
+            })
             .and_then(|_| {
                 self.address_map.insert(address, id.clone());
                 Some(id.clone())
@@ -77,11 +212,54 @@ impl SessionRegistry {
         self.sessions.values_mut().collect()
     }
 
-    pub fn remove_session(&mut self, id: ResourceID) {
+    /** Refreshes the session TTL. A stale session receiving a binding from a new address is rebound to it,
+     * in which case the previous address is returned.
+     */
+    pub fn revive_session(&mut self, id: ResourceID, remote: SocketAddr) -> Option<SocketAddr> {
+        let session = self.sessions.get_mut(&id)?;
+        let was_stale = session.is_stale;
+        session.ttl = Instant::now();
+        session.is_stale = false;
+
+        match session.remote_address {
+            Some(previous) if was_stale && previous != remote => {
+                session.remote_address = Some(remote);
+                self.address_map.remove(&previous);
+                self.address_map.insert(remote, id);
+                Some(previous)
+            }
+            _ => None,
+        }
+    }
+
+    /** Marks sessions that have not been refreshed within the given TTL as stale.
+     */
+    pub fn mark_stale_sessions(&mut self, session_ttl: Duration) {
+        for session in self.sessions.values_mut() {
+            if session.ttl.elapsed() > session_ttl {
+                session.is_stale = true;
+            }
+        }
+    }
+
+    /** Returns ids of sessions that have not been refreshed within the given TTL.
+     */
+    pub fn get_stale_session_ids(&self, session_ttl: Duration) -> Vec<ResourceID> {
+        self.sessions
+            .values()
+            .filter(|session| session.ttl.elapsed() > session_ttl)
+            .map(|session| session.id)
+            .collect()
+    }
+
+    /** Removes the session and returns its nominated remote address, if there was one.
+     */
+    pub fn remove_session(&mut self, id: ResourceID) -> Option<SocketAddr> {
         let session = self
             .sessions
-            .get(&id)
+            .get_mut(&id)
             .expect("Session should be established in order to remove it");
+        let _ = session.set_state(SessionState::Closed);
 
         // Clear username map
         let host_username = session.media_session.ice_credentials.host_username.clone();
@@ -97,7 +275,8 @@ impl SessionRegistry {
         self.username_map.remove(&session_username);
 
         // Clear address map if applicable
-        if let Some(remote) = session.client.as_ref().map(|client| client.remote_address) {
+        let remote_address = session.remote_address;
+        if let Some(remote) = remote_address {
             self.address_map.remove(&remote);
         }
 
@@ -117,6 +296,58 @@ impl SessionRegistry {
         }
 
         self.sessions.remove(&id);
+
+        remote_address
+    }
+
+    /** Removes a room along with its streamer and viewer sessions. Returns the nominated addresses
+     * of the removed sessions, or None if the room doesn't exist.
+     */
+    pub fn remove_room(&mut self, room_id: RoomID) -> Option<Vec<SocketAddr>> {
+        let room = self.rooms.get(&room_id)?;
+        // Viewers go first, removing the streamer removes the room
+        let session_ids = room
+            .viewer_ids
+            .iter()
+            .copied()
+            .chain([room.owner_id])
+            .collect::<Vec<_>>();
+
+        let mut remote_addresses = vec![];
+        for id in session_ids {
+            if !self.sessions.contains_key(&id) {
+                continue;
+            }
+            if let Some(remote) = self.remove_session(id) {
+                remote_addresses.push(remote);
+            }
+        }
+
+        Some(remote_addresses)
+    }
+
+    /** Replaces the media session of a renegotiated session, keeping its resource id and room.
+     * On ICE restart the previous credentials stop matching and the session may get rebound
+     * to the address the client binds from with the new credentials.
+     */
+    pub fn update_media_session(
+        &mut self,
+        id: ResourceID,
+        media_session: NegotiatedSession,
+    ) -> Option<&Session> {
+        let session = self.sessions.get_mut(&id)?;
+        let previous_username = get_session_username(&session.media_session);
+        let session_username = get_session_username(&media_session);
+
+        if previous_username.ne(&session_username) {
+            self.username_map.remove(&previous_username);
+            self.username_map.insert(session_username, id);
+            // Clients usually restart ICE after changing networks, allow resuming from a new address
+            session.is_stale = true;
+        }
+        session.media_session = media_session;
+
+        Some(session)
     }
 
     pub fn get_session_mut(&mut self, id: ResourceID) -> Option<&mut Session> {
@@ -151,7 +382,16 @@ impl SessionRegistry {
             .and_then(|id| self.sessions.get(id))
     }
 
-    pub fn add_streamer(&mut self, negotiated_session: NegotiatedSession) -> ResourceID {
+    /** Registers a streamer along with its room. Returns None if the streamer limit is reached.
+     */
+    pub fn add_streamer(&mut self, negotiated_session: NegotiatedSession) -> Option<ResourceID> {
+        // Each streamer owns exactly one room
+        if self
+            .max_streamers
+            .is_some_and(|max_streamers| self.rooms.len() >= max_streamers)
+        {
+            return None;
+        }
         let room_id = get_random_id();
 
         let streamer_session = Session::new_streamer(negotiated_session, room_id);
@@ -178,7 +418,7 @@ impl SessionRegistry {
         self.rooms.insert(room_id, room); // Update rooms map
         self.sessions.insert(resource_id, streamer_session); // Update sessions map
 
-        resource_id
+        Some(resource_id)
     }
 
     pub fn add_viewer(
@@ -211,10 +451,14 @@ impl SessionRegistry {
 #[derive(Debug)]
 pub struct Session {
     pub id: ResourceID,
+    pub created_at: Instant,
     pub ttl: Instant,
-    pub client: Option<Client>,
+    pub remote_address: Option<SocketAddr>,
+    pub is_stale: bool,
+    pub loss_stats: Option<LossStats>,
     pub media_session: NegotiatedSession,
     pub connection_type: ConnectionType,
+    state: SessionState,
 }
 
 impl Session {
@@ -223,13 +467,15 @@ impl Session {
 
         Session {
             id,
+            created_at: Instant::now(),
             ttl: Instant::now(),
-            client: None,
+            remote_address: None,
+            is_stale: false,
+            loss_stats: None,
             media_session,
+            state: SessionState::New,
             connection_type: ConnectionType::Streamer(Streamer {
                 owned_room_id: room_id,
-                thumbnail_extractor: ThumbnailExtractor::new(),
-                image_timestamp: None,
             }),
         }
     }
@@ -238,12 +484,47 @@ impl Session {
         let id = get_random_id();
         Session {
             id,
+            created_at: Instant::now(),
             ttl: Instant::now(),
-            client: None,
+            remote_address: None,
+            is_stale: false,
+            loss_stats: None,
             media_session,
+            state: SessionState::New,
             connection_type: ConnectionType::Viewer(Viewer { room_id: target_id }),
         }
     }
+
+    /** Session handed over by a draining predecessor, waiting for its peer to bind again.
+     */
+    pub fn restore(
+        id: ResourceID,
+        media_session: NegotiatedSession,
+        connection_type: ConnectionType,
+    ) -> Self {
+        Session {
+            id,
+            created_at: Instant::now(),
+            ttl: Instant::now(),
+            remote_address: None,
+            is_stale: false,
+            loss_stats: None,
+            media_session,
+            state: SessionState::New,
+            connection_type,
+        }
+    }
+
+    pub fn state(&self) -> SessionState {
+        self.state
+    }
+
+    /** Moves the session to the next state, unless the transition is illegal.
+     */
+    pub fn set_state(&mut self, next: SessionState) -> Result<(), InvalidTransition> {
+        self.state
+            .transition(next, format_args!("[session={}]", self.id))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -254,22 +535,445 @@ pub enum ConnectionType {
 
 #[derive(Debug, Clone)]
 pub struct Viewer {
-    room_id: ResourceID,
+    pub room_id: RoomID,
 }
 
 #[derive(Debug, Clone)]
 pub struct Streamer {
     pub owned_room_id: u32,
-    pub thumbnail_extractor: ThumbnailExtractor,
-    pub image_timestamp: Option<Instant>,
 }
 
+/** Length limits of an ICE username fragment.
+https://datatracker.ietf.org/doc/html/rfc8839#section-5.4
+ */
+const MIN_UFRAG_LENGTH: usize = 4;
+const MAX_UFRAG_LENGTH: usize = 256;
+
 #[derive(Hash, Eq, PartialEq, Debug)]
 pub struct SessionUsername {
     pub remote: String,
     pub host: String,
 }
 
+#[derive(Debug, PartialEq)]
+pub enum SessionUsernameError {
+    MissingSeparator,
+    EmptyUfrag,
+    InvalidUfragLength,
+}
+
+/** Parses the STUN USERNAME of a binding request, the colon-joined `host:remote` ufrags.
+ */
+impl TryFrom<&str> for SessionUsername {
+    type Error = SessionUsernameError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let (host, remote) = value
+            .split_once(":")
+            .ok_or(SessionUsernameError::MissingSeparator)?;
+
+        for ufrag in [host, remote] {
+            if ufrag.is_empty() {
+                return Err(SessionUsernameError::EmptyUfrag);
+            }
+            if !(MIN_UFRAG_LENGTH..=MAX_UFRAG_LENGTH).contains(&ufrag.len()) {
+                return Err(SessionUsernameError::InvalidUfragLength);
+            }
+        }
+
+        Ok(SessionUsername {
+            host: host.to_owned(),
+            remote: remote.to_owned(),
+        })
+    }
+}
+
+fn get_session_username(media_session: &NegotiatedSession) -> SessionUsername {
+    SessionUsername {
+        host: media_session.ice_credentials.host_username.clone(),
+        remote: media_session.ice_credentials.remote_username.clone(),
+    }
+}
+
 fn get_random_id() -> u32 {
     thread_rng().next_u32()
 }
+
+#[cfg(test)]
+mod tests {
+    mod session_username {
+        use crate::ice_registry::{SessionUsername, SessionUsernameError};
+
+        #[test]
+        fn parses_valid_username() {
+            assert_eq!(
+                SessionUsername::try_from("aBcD:E2Fr"),
+                Ok(SessionUsername {
+                    host: "aBcD".to_string(),
+                    remote: "E2Fr".to_string(),
+                })
+            );
+        }
+
+        #[test]
+        fn rejects_empty_part() {
+            assert_eq!(
+                SessionUsername::try_from("aBcD:"),
+                Err(SessionUsernameError::EmptyUfrag)
+            );
+            assert_eq!(
+                SessionUsername::try_from(":E2Fr"),
+                Err(SessionUsernameError::EmptyUfrag)
+            );
+            assert_eq!(
+                SessionUsername::try_from("aBcDE2Fr"),
+                Err(SessionUsernameError::MissingSeparator)
+            );
+        }
+
+        #[test]
+        fn rejects_over_length_ufrag() {
+            let username = format!("aBcD:{}", "a".repeat(257));
+
+            assert_eq!(
+                SessionUsername::try_from(username.as_str()),
+                Err(SessionUsernameError::InvalidUfragLength)
+            );
+            assert_eq!(
+                SessionUsername::try_from("aBc:E2Fr"),
+                Err(SessionUsernameError::InvalidUfragLength)
+            );
+        }
+    }
+
+    mod nominate_address {
+        use std::net::SocketAddr;
+
+        use crate::ice_registry::SessionRegistry;
+        use crate::session_state::SessionState;
+        use crate::test_utils::get_streamer_session;
+
+        #[test]
+        fn starts_dtls_handshake() {
+            let mut registry = SessionRegistry::new();
+            let remote: SocketAddr = "10.0.0.1:50000".parse().unwrap();
+            let id = registry
+                .add_streamer(get_streamer_session())
+                .expect("Should add streamer");
+            assert_eq!(
+                registry.get_session(id).map(|session| session.state()),
+                Some(SessionState::New)
+            );
+
+            registry.nominate_address(remote, &id);
+
+            let session = registry
+                .get_session_by_address(&remote)
+                .expect("Session should be bound to nominated address");
+            assert_eq!(session.state(), SessionState::DtlsHandshaking);
+        }
+    }
+
+    mod revive_session {
+        use std::net::SocketAddr;
+        use std::time::Duration;
+
+        use crate::ice_registry::SessionRegistry;
+        use crate::test_utils::get_streamer_session;
+
+        #[test]
+        fn rebinds_stale_session_to_new_address() {
+            let mut registry = SessionRegistry::new();
+            let previous_remote: SocketAddr = "10.0.0.1:50000".parse().unwrap();
+            let new_remote: SocketAddr = "10.0.0.2:50001".parse().unwrap();
+            let id = registry
+                .add_streamer(get_streamer_session())
+                .expect("Should add streamer");
+            registry.nominate_address(previous_remote, &id);
+
+            // Session went silent past its TTL, but is still within the grace window
+            registry.mark_stale_sessions(Duration::ZERO);
+
+            assert_eq!(
+                registry.revive_session(id, new_remote),
+                Some(previous_remote)
+            );
+            let session = registry
+                .get_session_by_address(&new_remote)
+                .expect("Session should be bound to new address");
+            assert_eq!(session.id, id);
+            assert!(!session.is_stale);
+            assert!(registry.get_session_by_address(&previous_remote).is_none());
+        }
+
+        #[test]
+        fn keeps_live_session_address() {
+            let mut registry = SessionRegistry::new();
+            let previous_remote: SocketAddr = "10.0.0.1:50000".parse().unwrap();
+            let new_remote: SocketAddr = "10.0.0.2:50001".parse().unwrap();
+            let id = registry
+                .add_streamer(get_streamer_session())
+                .expect("Should add streamer");
+            registry.nominate_address(previous_remote, &id);
+
+            assert_eq!(registry.revive_session(id, new_remote), None);
+            assert!(registry.get_session_by_address(&previous_remote).is_some());
+            assert!(registry.get_session_by_address(&new_remote).is_none());
+        }
+    }
+
+    mod update_media_session {
+        use crate::ice_registry::{ConnectionType, SessionRegistry};
+        use crate::test_utils::{get_sdp_resolver, get_streamer_session, STREAMER_OFFER};
+
+        #[test]
+        fn preserves_resource_id_on_codec_change() {
+            let mut registry = SessionRegistry::new();
+            let media_session = get_streamer_session();
+            let id = registry
+                .add_streamer(media_session.clone())
+                .expect("Should add streamer");
+            let updated_offer = STREAMER_OFFER
+                .replace("SAVPF 96", "SAVPF 102")
+                .replace(":96 ", ":102 ");
+            let updated_media_session = get_sdp_resolver()
+                .update_stream_offer(&updated_offer, &media_session, false)
+                .expect("Should renegotiate offer");
+
+            let session = registry
+                .update_media_session(id, updated_media_session)
+                .expect("Session should be updated");
+
+            assert_eq!(session.id, id);
+            assert_eq!(session.media_session.video_session.payload_number, 102);
+            let room_id = match &session.connection_type {
+                ConnectionType::Streamer(streamer) => streamer.owned_room_id,
+                ConnectionType::Viewer(_) => panic!("Session should remain a streamer"),
+            };
+            assert_eq!(
+                registry.get_room(room_id).map(|room| room.owner_id),
+                Some(id)
+            );
+        }
+    }
+
+    mod ice_restart {
+        use std::net::SocketAddr;
+
+        use crate::ice_registry::{get_session_username, SessionRegistry};
+        use crate::test_utils::{get_sdp_resolver, get_streamer_session, STREAMER_OFFER};
+
+        #[test]
+        fn rotates_session_username() {
+            let mut registry = SessionRegistry::new();
+            let previous_remote: SocketAddr = "10.0.0.1:50000".parse().unwrap();
+            let new_remote: SocketAddr = "10.0.0.2:50001".parse().unwrap();
+            let media_session = get_streamer_session();
+            let id = registry
+                .add_streamer(media_session.clone())
+                .expect("Should add streamer");
+            registry.nominate_address(previous_remote, &id);
+            let restart_offer = STREAMER_OFFER.replace("ice-ufrag:E2Fr", "ice-ufrag:R3st");
+            let restarted_media_session = get_sdp_resolver()
+                .update_stream_offer(&restart_offer, &media_session, true)
+                .expect("Should restart ICE");
+            let restarted_username = get_session_username(&restarted_media_session);
+
+            registry.update_media_session(id, restarted_media_session);
+
+            assert!(registry
+                .get_session_by_username_mut(&get_session_username(&media_session))
+                .is_none());
+            let session = registry
+                .get_session_by_username_mut(&restarted_username)
+                .expect("New credentials should match the session");
+            assert_eq!(session.id, id);
+            assert_eq!(
+                registry.revive_session(id, new_remote),
+                Some(previous_remote)
+            );
+        }
+    }
+
+    mod get_stale_session_ids {
+        use std::thread::sleep;
+        use std::time::Duration;
+
+        use crate::ice_registry::SessionRegistry;
+        use crate::test_utils::get_streamer_session;
+
+        #[test]
+        fn reaps_session_after_short_ttl() {
+            let mut registry = SessionRegistry::new();
+            let id = registry
+                .add_streamer(get_streamer_session())
+                .expect("Should add streamer");
+
+            sleep(Duration::from_millis(30));
+
+            assert_eq!(
+                registry.get_stale_session_ids(Duration::from_millis(10)),
+                vec![id]
+            );
+            assert!(registry
+                .get_stale_session_ids(Duration::from_secs(5))
+                .is_empty());
+        }
+    }
+
+    mod add_streamer {
+        use crate::ice_registry::SessionRegistry;
+        use crate::test_utils::get_streamer_session;
+
+        #[test]
+        fn rejects_streamers_past_limit_until_one_leaves() {
+            let mut registry = SessionRegistry::with_max_streamers(Some(2));
+            let first_id = registry
+                .add_streamer(get_streamer_session())
+                .expect("Should add first streamer");
+            registry
+                .add_streamer(get_streamer_session())
+                .expect("Should add second streamer");
+
+            assert!(registry.add_streamer(get_streamer_session()).is_none());
+            assert_eq!(registry.get_rooms().len(), 2);
+
+            registry.remove_session(first_id);
+
+            assert!(registry.add_streamer(get_streamer_session()).is_some());
+        }
+    }
+
+    mod is_room_full {
+        use crate::ice_registry::SessionRegistry;
+        use crate::test_utils::get_streamer_session;
+
+        #[test]
+        fn fills_room_at_viewer_limit() {
+            let mut registry = SessionRegistry::new();
+            let streamer_id = registry.add_streamer(get_streamer_session()).unwrap();
+            let room_id = registry.get_rooms()[0].id;
+            let viewer_id = registry.add_viewer(get_streamer_session(), room_id);
+            assert!(!registry.is_room_full(room_id));
+
+            registry.set_max_viewers_per_room(Some(1));
+            assert!(registry.is_room_full(room_id));
+
+            registry.remove_session(viewer_id);
+            assert!(!registry.is_room_full(room_id));
+            registry.remove_session(streamer_id);
+            assert!(!registry.is_room_full(room_id));
+        }
+    }
+
+    mod restore_session {
+        use std::collections::HashSet;
+
+        use crate::ice_registry::{get_session_username, Session, SessionRegistry};
+        use crate::test_utils::get_streamer_session;
+
+        #[test]
+        fn restores_rooms_under_previous_ids() {
+            let mut registry = SessionRegistry::new();
+            let streamer = Session::new_streamer(get_streamer_session(), 7);
+            let streamer_id = streamer.id;
+            let session_username = get_session_username(&streamer.media_session);
+            let viewer = Session::new_viewer(7, get_streamer_session());
+            let viewer_id = viewer.id;
+
+            assert!(registry.restore_session(streamer));
+            assert_eq!(
+                registry
+                    .get_session_by_username_mut(&session_username)
+                    .map(|session| session.id),
+                Some(streamer_id)
+            );
+            assert!(registry.restore_session(viewer));
+            // Room of the viewer is gone
+            assert!(!registry.restore_session(Session::new_viewer(8, get_streamer_session())));
+
+            let room = registry.get_room(7).expect("Should restore room");
+            assert_eq!(room.owner_id, streamer_id);
+            assert_eq!(room.viewer_ids, HashSet::from([viewer_id]));
+            assert_eq!(registry.get_all_sessions().len(), 2);
+        }
+    }
+
+    mod get_room_viewers {
+        use crate::ice_registry::SessionRegistry;
+        use crate::test_utils::get_streamer_session;
+
+        #[test]
+        fn lists_viewers_with_distinct_ids() {
+            let mut registry = SessionRegistry::new();
+            registry
+                .add_streamer(get_streamer_session())
+                .expect("Should add streamer");
+            let room_id = registry.get_rooms()[0].id;
+            let first_id = registry.add_viewer(get_streamer_session(), room_id);
+            let second_id = registry.add_viewer(get_streamer_session(), room_id);
+
+            let mut viewer_ids = registry
+                .get_room_viewers(room_id)
+                .expect("Should list viewers")
+                .iter()
+                .map(|session| session.id)
+                .collect::<Vec<_>>();
+            viewer_ids.sort();
+            let mut expected_ids = vec![first_id, second_id];
+            expected_ids.sort();
+
+            assert_ne!(first_id, second_id);
+            assert_eq!(viewer_ids, expected_ids);
+        }
+
+        #[test]
+        fn rejects_unknown_room() {
+            let registry = SessionRegistry::new();
+
+            assert!(registry.get_room_viewers(1).is_none());
+        }
+    }
+
+    mod remove_room {
+        use std::net::SocketAddr;
+
+        use crate::ice_registry::SessionRegistry;
+        use crate::test_utils::get_streamer_session;
+
+        #[test]
+        fn removes_streamer_and_viewer_sessions() {
+            let mut registry = SessionRegistry::new();
+            let streamer_remote: SocketAddr = "10.0.0.1:50000".parse().unwrap();
+            let viewer_remote: SocketAddr = "10.0.0.2:50000".parse().unwrap();
+            let streamer_id = registry
+                .add_streamer(get_streamer_session())
+                .expect("Should add streamer");
+            let room_id = registry.get_rooms()[0].id;
+            let viewer_id = registry.add_viewer(get_streamer_session(), room_id);
+            // Viewer that never got nominated
+            registry.add_viewer(get_streamer_session(), room_id);
+            registry.nominate_address(streamer_remote, &streamer_id);
+            registry.nominate_address(viewer_remote, &viewer_id);
+
+            let mut remotes = registry.remove_room(room_id).expect("Should remove room");
+            remotes.sort();
+
+            assert_eq!(remotes, vec![streamer_remote, viewer_remote]);
+            assert!(registry.get_room(room_id).is_none());
+            assert!(registry.get_all_sessions().is_empty());
+        }
+
+        #[test]
+        fn rejects_unknown_room() {
+            let mut registry = SessionRegistry::new();
+            registry
+                .add_streamer(get_streamer_session())
+                .expect("Should add streamer");
+            let room_id = registry.get_rooms()[0].id;
+
+            assert!(registry.remove_room(room_id.wrapping_add(1)).is_none());
+            assert_eq!(registry.get_all_sessions().len(), 1);
+        }
+    }
+}
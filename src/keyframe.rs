@@ -0,0 +1,196 @@
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::rtp::is_keyframe_packet;
+
+/** Time between the first keyframe requests, giving the streamer a chance to respond.
+ */
+const KEYFRAME_REQUEST_INTERVAL: Duration = Duration::from_secs(2);
+
+/** Unanswered requests are repeated at doubling intervals, up to this one. An encoder that keeps
+ * ignoring them isn't flooded with PLIs.
+ */
+const MAX_KEYFRAME_REQUEST_INTERVAL: Duration = Duration::from_secs(30);
+
+/** Tracks when a streamer last sent a keyframe, and decides when to ask for a new one.
+ * Viewers joining mid-stream, or recovering from loss, can't decode anything until then.
+ */
+pub struct KeyframeMonitor {
+    timeout: Option<Duration>,
+    started_at: Instant,
+    last_keyframe: Option<Instant>,
+    last_keyframe_time: Option<SystemTime>,
+    last_request: Option<Instant>,
+    request_interval: Duration,
+}
+
+impl KeyframeMonitor {
+    /** Monitor asking for a keyframe once the latest is older than `timeout`, if given.
+     */
+    pub fn new(now: Instant, timeout: Option<Duration>) -> Self {
+        KeyframeMonitor {
+            timeout,
+            started_at: now,
+            last_keyframe: None,
+            last_keyframe_time: None,
+            last_request: None,
+            request_interval: KEYFRAME_REQUEST_INTERVAL,
+        }
+    }
+
+    /** Registers a video RTP packet received at `now`, returning whether it carries a keyframe.
+     */
+    pub fn register_packet(&mut self, buffer: &[u8], now: Instant, wall_clock: SystemTime) -> bool {
+        let is_keyframe = is_keyframe_packet(buffer);
+        if is_keyframe {
+            self.last_keyframe = Some(now);
+            self.last_keyframe_time = Some(wall_clock);
+            self.last_request = None;
+            self.request_interval = KEYFRAME_REQUEST_INTERVAL;
+        }
+        is_keyframe
+    }
+
+    pub fn is_keyframe_seen(&self) -> bool {
+        self.last_keyframe.is_some()
+    }
+
+    /** Wall-clock time of the latest keyframe.
+     */
+    pub fn last_keyframe_time(&self) -> Option<SystemTime> {
+        self.last_keyframe_time
+    }
+
+    /** Whether the latest keyframe, or the stream start if there was none, is older than the
+     * timeout. A due request is counted as sent, and each one left unanswered doubles the wait
+     * before the next.
+     */
+    pub fn is_keyframe_request_due(&mut self, now: Instant) -> bool {
+        let timeout = match self.timeout {
+            Some(timeout) => timeout,
+            None => return false,
+        };
+        let last_keyframe = self.last_keyframe.unwrap_or(self.started_at);
+        if now.saturating_duration_since(last_keyframe) < timeout {
+            return false;
+        }
+
+        match self.last_request {
+            Some(last_request)
+                if now.saturating_duration_since(last_request) < self.request_interval =>
+            {
+                false
+            }
+            Some(_) => {
+                self.request_interval =
+                    (self.request_interval * 2).min(MAX_KEYFRAME_REQUEST_INTERVAL);
+                self.last_request = Some(now);
+                true
+            }
+            None => {
+                self.last_request = Some(now);
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod keyframe_monitor {
+        use std::time::{Duration, Instant, SystemTime};
+
+        use crate::keyframe::KeyframeMonitor;
+
+        const IDR_NAL_HEADER: u8 = 0x65;
+        const SLICE_NAL_HEADER: u8 = 0x41;
+        const TIMEOUT: Duration = Duration::from_secs(10);
+
+        fn get_video_packet(nal_header: u8) -> Vec<u8> {
+            let mut packet = vec![0x80, 96, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+            packet.extend_from_slice(&[nal_header, 0xAA]);
+            packet
+        }
+
+        #[test]
+        fn updates_timestamp_on_idr() {
+            let now = Instant::now();
+            let wall_clock = SystemTime::now();
+            let mut monitor = KeyframeMonitor::new(now, Some(TIMEOUT));
+
+            assert!(!monitor.register_packet(&get_video_packet(SLICE_NAL_HEADER), now, wall_clock));
+            assert_eq!(monitor.last_keyframe_time(), None);
+
+            let later = wall_clock + Duration::from_secs(3);
+            assert!(monitor.register_packet(&get_video_packet(IDR_NAL_HEADER), now, later));
+            assert_eq!(monitor.last_keyframe_time(), Some(later));
+            assert!(monitor.is_keyframe_seen());
+
+            monitor.register_packet(&get_video_packet(SLICE_NAL_HEADER), now, wall_clock);
+            assert_eq!(monitor.last_keyframe_time(), Some(later));
+        }
+
+        #[test]
+        fn requests_keyframe_once_latest_is_stale() {
+            let now = Instant::now();
+            let mut monitor = KeyframeMonitor::new(now, Some(TIMEOUT));
+            monitor.register_packet(&get_video_packet(IDR_NAL_HEADER), now, SystemTime::now());
+
+            assert!(!monitor.is_keyframe_request_due(now + Duration::from_secs(1)));
+            assert!(monitor.is_keyframe_request_due(now + TIMEOUT));
+        }
+
+        #[test]
+        fn requests_keyframe_of_stream_without_one() {
+            let now = Instant::now();
+            let mut monitor = KeyframeMonitor::new(now, Some(TIMEOUT));
+
+            assert!(!monitor.is_keyframe_request_due(now));
+            assert!(monitor.is_keyframe_request_due(now + TIMEOUT));
+        }
+
+        #[test]
+        fn backs_off_while_streamer_does_not_respond() {
+            let now = Instant::now();
+            let mut monitor = KeyframeMonitor::new(now, Some(TIMEOUT));
+            let stale = now + TIMEOUT;
+
+            let request_offsets = (0..120)
+                .map(|secs| stale + Duration::from_secs(secs))
+                .filter(|&tick| monitor.is_keyframe_request_due(tick))
+                .map(|tick| tick.duration_since(stale).as_secs())
+                .collect::<Vec<_>>();
+
+            // Doubling intervals, capped at 30 seconds
+            assert_eq!(request_offsets, vec![0, 2, 6, 14, 30, 60, 90]);
+        }
+
+        #[test]
+        fn restarts_backoff_after_keyframe() {
+            let now = Instant::now();
+            let mut monitor = KeyframeMonitor::new(now, Some(TIMEOUT));
+            let stale = now + TIMEOUT;
+            assert!(monitor.is_keyframe_request_due(stale));
+            assert!(monitor.is_keyframe_request_due(stale + Duration::from_secs(2)));
+
+            let keyframe_at = stale + Duration::from_secs(3);
+            monitor.register_packet(
+                &get_video_packet(IDR_NAL_HEADER),
+                keyframe_at,
+                SystemTime::now(),
+            );
+
+            assert!(!monitor.is_keyframe_request_due(keyframe_at + Duration::from_secs(1)));
+            let stale = keyframe_at + TIMEOUT;
+            assert!(monitor.is_keyframe_request_due(stale));
+            assert!(monitor.is_keyframe_request_due(stale + Duration::from_secs(2)));
+        }
+
+        #[test]
+        fn stays_quiet_without_timeout() {
+            let now = Instant::now();
+            let mut monitor = KeyframeMonitor::new(now, None);
+
+            assert!(!monitor.is_keyframe_request_due(now + Duration::from_secs(3600)));
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use std::fmt::{Display, Formatter};
+use std::net::SocketAddr;
+
+use crate::ice_registry::RoomID;
+
+/** Peer context prefixed to log lines of the media shards, so that lines of a single session can be found,
+ * e.g. `[remote=127.0.0.1:52000 room=7] Couldn't send RTP data`.
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct LogContext {
+    remote: SocketAddr,
+    room_id: RoomID,
+}
+
+impl LogContext {
+    pub fn new(remote: SocketAddr, room_id: RoomID) -> Self {
+        LogContext { remote, room_id }
+    }
+}
+
+impl Display for LogContext {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[remote={} room={}]", self.remote, self.room_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod log_context {
+        use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+        use crate::log_context::LogContext;
+
+        #[test]
+        fn prefixes_forwarding_log_line_with_room() {
+            let remote = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 52000);
+            let log_context = LogContext::new(remote, 7);
+
+            let log_line = format!("{} Couldn't send RTP data {}", log_context, "timed out");
+
+            assert_eq!(
+                log_line,
+                "[remote=127.0.0.1:52000 room=7] Couldn't send RTP data timed out"
+            );
+        }
+    }
+}
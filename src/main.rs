@@ -1,68 +1,214 @@
-use std::net::UdpSocket;
-use std::sync::mpsc::Sender;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread;
 use std::thread::sleep;
 use std::time::{Duration, Instant};
 
-use crate::config::get_global_config;
-use crate::http::server::{Notification, Room, start_http_server};
-use crate::http::ServerCommand;
-use crate::ice_registry::ConnectionType;
-use crate::server::UDPServer;
-use crate::thumbnail::save_thumbnail_to_storage;
+#[cfg(unix)]
+use signal_hook::consts::{SIGHUP, SIGUSR1};
+#[cfg(unix)]
+use signal_hook::iterator::Signals;
+use socket2::{Domain, Protocol, Socket, SockRef, Type};
+
+use crate::config::{get_global_config, is_log_enabled, reload_config, LogLevel};
+use crate::demux::{get_packet_type, PacketType, MIN_PACKET_LEN};
+use crate::event_bus::{EventBus, ServerEvent, ROOMS_STATUS_INTERVAL};
+use crate::http::server::{
+    bind_http_listener, get_room_details, get_room_viewers, get_rooms_status, start_http_server,
+};
+use crate::http::{
+    create_server_channel, ServerCommand, ServerSender, StreamerRejection, ViewerRejection,
+};
+use crate::migration::{import_sessions, start_migration_listener, SessionRole, SessionSnapshot};
+use crate::pacer::PACING_INTERVAL;
+use crate::rtcp_interval::REPORT_POLL_INTERVAL;
+use crate::server::{SocketIndex, UDPServer};
+use crate::session_state::SessionState;
+use crate::shard::{MediaShard, ShardCommand, ShardRouter};
+use crate::stun::{create_stun_binding_request, get_xor_mapped_address, STUN_TRANSACTION_ID_LEN};
+use crate::supervisor::supervise;
+use crate::transport::UdpTransport;
 
 mod acceptor;
+mod bitrate;
+mod capture;
 mod client;
 mod config;
+mod demux;
+mod estimator;
+mod event_bus;
 mod http;
 mod ice_registry;
+mod keyframe;
+mod log_context;
+mod migration;
+mod nack;
+mod pacer;
+mod receiver_report;
+mod replay;
+mod rtcp;
+mod rtcp_interval;
 mod rtp;
+mod sender_report;
 mod server;
+mod session_state;
+mod shard;
+mod simulcast;
 mod stun;
+mod supervisor;
+#[cfg(test)]
+mod test_utils;
+mod throttle;
 mod thumbnail;
+mod transport;
+
+const STUN_PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+const STUN_PROBE_ATTEMPTS: usize = 3;
 
 fn main() {
-    let (server_command_sender, server_command_receiver) =
-        std::sync::mpsc::channel::<ServerCommand>();
-    let socket = build_udp_socket();
-    let mut udp_server = UDPServer::new(socket.try_clone().unwrap());
+    // Config mistakes surface before anything starts, rather than as panics of running threads
+    if let Err(err) = get_global_config().validate() {
+        eprintln!("Invalid config: {}", err);
+        std::process::exit(1);
+    }
+    if get_global_config().udp_server_config.insecure_plaintext_media {
+        eprintln!("Media is exchanged as plain RTP without DTLS or SRTP, only meant for testing");
+    }
 
+    let (server_command_sender, server_command_receiver) = create_server_channel(
+        get_global_config()
+            .udp_server_config
+            .command_channel_capacity,
+    );
+    // HTTP goes up first, so that load balancers can see the server starting
+    let is_ready = Arc::new(AtomicBool::new(false));
+    let event_bus = Arc::new(EventBus::new());
+    // A taken HTTP address stops the server right away, rather than panicking the HTTP thread
+    let http_address = get_global_config().tcp_server_config.address;
+    let listener = bind_http_listener(http_address).unwrap_or_else(|err| {
+        eprintln!("Couldn't bind HTTP server to {}: {}", http_address, err);
+        std::process::exit(1);
+    });
     thread::spawn({
         let server_command_sender = server_command_sender.clone();
-        move || start_http_server(server_command_sender)
+        let is_ready = is_ready.clone();
+        let event_bus = event_bus.clone();
+        move || start_http_server(listener, server_command_sender, is_ready, event_bus)
     });
+
+    let sockets = build_udp_sockets();
+    // Only the first socket is probed, multi-homed hosts rarely share a single public address
+    let server_reflexive_address = get_global_config()
+        .udp_server_config
+        .stun_server_address
+        .as_ref()
+        .and_then(|stun_server| probe_server_reflexive_address(&sockets[0], stun_server));
+    let shard_router = start_media_shards(&sockets, &server_command_sender);
+    let mut udp_server = UDPServer::new(
+        Box::new(UdpTransport::new(clone_sockets(&sockets))),
+        shard_router.clone(),
+        server_reflexive_address,
+    );
+    // Sessions of a draining predecessor are taken over before any client gets to bind
+    if let Some(migration_socket) = &get_global_config().migration_socket {
+        restore_sessions(&mut udp_server, migration_socket);
+    }
+
+    start_udp_servers(
+        &sockets,
+        get_global_config().udp_server_config.mtu,
+        &server_command_sender,
+        &shard_router,
+    );
     thread::spawn({
         let sender = server_command_sender.clone();
-        let socket = socket.try_clone().unwrap();
-        move || start_udp_server(socket, sender)
+        move || start_timeout_interval(sender)
     });
     thread::spawn({
         let sender = server_command_sender.clone();
-        move || start_timeout_interval(sender)
+        move || start_rooms_status_interval(sender)
     });
+    thread::spawn({
+        let shard_router = shard_router.clone();
+        move || start_sender_report_interval(shard_router)
+    });
+    if let Some(interval) = get_global_config().udp_server_config.keepalive_interval {
+        let shard_router = shard_router.clone();
+        thread::spawn(move || start_keepalive_interval(shard_router, interval));
+    }
+    thread::spawn(move || start_pacing_interval(shard_router));
+    ctrlc::set_handler({
+        let sender = server_command_sender.clone();
+        move || {
+            let _ = sender.send(ServerCommand::Shutdown);
+        }
+    })
+    .expect("Should register shutdown signal handler");
+    thread::spawn({
+        let sender = server_command_sender.clone();
+        move || start_signal_listener(sender)
+    });
+    is_ready.store(true, Ordering::Release);
 
     loop {
         match server_command_receiver
             .recv()
             .expect("Server channel should be open")
         {
-            ServerCommand::HandlePacket(packet, remote) => {
-                udp_server.process_packet(&packet, remote)
+            ServerCommand::HandlePacket(packet, remote, socket_index) => {
+                udp_server.process_packet(&packet, remote, socket_index)
+            }
+            ServerCommand::AddStreamer(_, response_tx)
+                if udp_server.session_registry.is_draining() =>
+            {
+                response_tx
+                    .send(Err(StreamerRejection::Draining))
+                    .expect("Response channel should remain open")
             }
             ServerCommand::AddStreamer(sdp_offer, response_tx) => {
-                let negotiated_session =
-                    udp_server.sdp_resolver.accept_stream_offer(&sdp_offer).ok();
+                let negotiated_session = udp_server
+                    .sdp_resolver
+                    .accept_stream_offer_with_diagnostics(&sdp_offer)
+                    .map_err(StreamerRejection::InvalidOffer);
 
-                let response = negotiated_session.map(|session| {
+                let response = negotiated_session.and_then(|session| {
                     let sdp_answer = String::from(session.sdp_answer.clone());
-                    udp_server.session_registry.add_streamer(session);
-                    sdp_answer
+                    let resource_id = udp_server
+                        .session_registry
+                        .add_streamer(session)
+                        .ok_or(StreamerRejection::StreamerLimitReached)?;
+                    Ok((resource_id, sdp_answer))
                 });
 
                 response_tx
                     .send(response)
                     .expect("Response channel should remain open")
             }
+            ServerCommand::UpdateStreamer(resource_id, sdp_offer, is_ice_restart, response_tx) => {
+                let response =
+                    udp_server.renegotiate_streamer(resource_id, &sdp_offer, is_ice_restart);
+
+                response_tx
+                    .send(response)
+                    .expect("Response channel should remain open")
+            }
+            ServerCommand::AddViewer(_, _, response_tx)
+                if udp_server.session_registry.is_draining() =>
+            {
+                response_tx
+                    .send(Err(ViewerRejection::Draining))
+                    .expect("Response channel should remain open")
+            }
+            ServerCommand::AddViewer(_, target_id, response_tx)
+                if udp_server.session_registry.is_room_full(target_id) =>
+            {
+                response_tx
+                    .send(Err(ViewerRejection::ViewerLimitReached))
+                    .expect("Response channel should remain open")
+            }
             ServerCommand::AddViewer(sdp_offer, target_id, response_tx) => {
                 let streamer_session = udp_server
                     .session_registry
@@ -76,18 +222,20 @@ fn main() {
                     })
                     .flatten();
 
-                let viewer_media_session = streamer_session.and_then(|media_session| {
-                    udp_server
-                        .sdp_resolver
-                        .accept_viewer_offer(&sdp_offer, media_session)
-                        .ok()
-                });
-                let response = viewer_media_session.and_then(|media_session| {
+                let viewer_media_session = streamer_session
+                    .ok_or(ViewerRejection::RoomNotFound)
+                    .and_then(|media_session| {
+                        udp_server
+                            .sdp_resolver
+                            .accept_viewer_offer_with_diagnostics(&sdp_offer, media_session)
+                            .map_err(ViewerRejection::InvalidOffer)
+                    });
+                let response = viewer_media_session.map(|media_session| {
                     let sdp_answer = String::from(media_session.sdp_answer.clone());
                     udp_server
                         .session_registry
                         .add_viewer(media_session, target_id);
-                    Some(sdp_answer)
+                    sdp_answer
                 });
 
                 response_tx
@@ -95,109 +243,601 @@ fn main() {
                     .expect("Response channel should remain open")
             }
             ServerCommand::SendRoomsStatus(reply_channel) => {
-                let rooms = udp_server.session_registry.get_rooms();
-                let notification = Notification {
-                    rooms: rooms
-                        .into_iter()
-                        .map(|room| Room {
-                            viewer_count: room.viewer_ids.len(),
-                            id: room.id,
-                        })
-                        .collect::<Vec<_>>(),
-                };
+                let notification = get_rooms_status(&udp_server.session_registry);
                 reply_channel.send(notification);
             }
-            ServerCommand::RunPeriodicChecks => {
-                // todo Move these into separate functions
-
-                // *** Save thumbnails ***
+            // Computed once for all the subscribers, and only when someone listens
+            ServerCommand::PublishRoomsStatus => {
+                if event_bus.has_subscribers() {
+                    let notification = get_rooms_status(&udp_server.session_registry);
+                    event_bus.publish(ServerEvent::RoomsStatus(Arc::new(notification)));
+                }
+            }
+            ServerCommand::SendRoomDetails(room_id, reply_channel) => {
+                let room_details = get_room_details(&udp_server.session_registry, room_id);
+                let _ = reply_channel.send(room_details);
+            }
+            ServerCommand::SendRoomViewers(room_id, reply_channel) => {
+                let room_viewers = get_room_viewers(&udp_server.session_registry, room_id);
+                let _ = reply_channel.send(room_viewers);
+            }
+            ServerCommand::RefreshSession(remote) => {
+                if let Some(session) = udp_server
+                    .session_registry
+                    .get_session_by_address_mut(&remote)
+                {
+                    session.ttl = Instant::now();
+                }
+            }
+            ServerCommand::UpdateSessionState(remote, state) => {
+                let session = udp_server
+                    .session_registry
+                    .get_session_by_address_mut(&remote);
+                if let Some(session) = session {
+                    if let Err(err) = session.set_state(state) {
+                        eprintln!("Session {} {}", session.id, err)
+                    }
+                    // Peers failing the DTLS handshake can't recover, free their session right away
+                    if session.state() == SessionState::Failed {
+                        let resource_id = session.id;
+                        udp_server.remove_session(resource_id);
+                    }
+                }
+            }
+            ServerCommand::TerminateRoom(room_id, response_tx) => {
+                let is_terminated = udp_server.terminate_room(room_id);
 
-                // Get all ImageData of streamers that:
-                // - Have an ImageData ready
-                // - Have no thumbnail or enough time has passed for the thumbnail to be updated
-                let thumbnails_to_update = udp_server
+                response_tx
+                    .send(is_terminated)
+                    .expect("Response channel should remain open")
+            }
+            ServerCommand::RemovePeerSession(remote) => {
+                // Peer announced leaving with RTCP BYE
+                let resource_id = udp_server
+                    .session_registry
+                    .get_session_by_address(&remote)
+                    .map(|session| session.id);
+                if let Some(resource_id) = resource_id {
+                    udp_server.remove_session(resource_id);
+                }
+            }
+            ServerCommand::Shutdown => {
+                udp_server.shutdown();
+                std::process::exit(0);
+            }
+            // Shards and the HTTP server read the rest of the reloaded config as they go
+            ServerCommand::ReloadConfig => match reload_config() {
+                Ok(config) => {
+                    let session_registry = &mut udp_server.session_registry;
+                    session_registry.set_max_streamers(config.max_streamers);
+                    session_registry.set_max_viewers_per_room(config.max_viewers_per_room);
+                    eprintln!("Reloaded config {:?}", config)
+                }
+                Err(err) => eprintln!("Couldn't reload config, keeping the current one: {}", err),
+            },
+            ServerCommand::Drain => {
+                udp_server.session_registry.start_draining();
+                // Load balancers stop sending new clients over
+                is_ready.store(false, Ordering::Release);
+                if let Some(migration_socket) = &get_global_config().migration_socket {
+                    if let Err(err) =
+                        start_migration_listener(migration_socket, server_command_sender.clone())
+                    {
+                        eprintln!(
+                            "Couldn't export sessions at {:?}: {}",
+                            migration_socket, err
+                        )
+                    }
+                }
+                eprintln!("Draining, new sessions are turned down");
+            }
+            ServerCommand::ExportSessions(reply_channel) => {
+                let snapshots = udp_server
                     .session_registry
-                    .get_all_sessions_mut()
+                    .get_all_sessions()
                     .into_iter()
-                    .filter_map(|session| match &mut session.connection_type {
-                        ConnectionType::Viewer(_) => None,
-                        ConnectionType::Streamer(streamer) => {
-                            let should_update_thumbnail = streamer.image_timestamp.is_none()
-                                || streamer
-                                    .image_timestamp
-                                    .unwrap()
-                                    .elapsed()
-                                    .gt(&Duration::from_secs(120));
-
-                            if should_update_thumbnail
-                                && streamer.thumbnail_extractor.last_picture.is_some()
-                            {
-                                // Update new thumbnail timestamp
-                                streamer.image_timestamp = Some(Instant::now());
-                                let last_picture = streamer
-                                    .thumbnail_extractor
-                                    .last_picture
-                                    .as_ref()
-                                    .unwrap()
-                                    .clone();
-                                return Some((streamer.owned_room_id, last_picture));
-                            }
-                            None
-                        }
-                    })
-                    .collect::<Vec<_>>();
-
-                for (thumbnail_id, thumbnail_data) in thumbnails_to_update {
-                    thread::spawn(move || save_thumbnail_to_storage(thumbnail_id, thumbnail_data));
+                    .map(SessionSnapshot::from)
+                    .collect();
+                let _ = reply_channel.send(snapshots);
+            }
+            ServerCommand::ReportRoomBitrates(shard_index, bitrates) => {
+                for (room_id, bitrate) in bitrates {
+                    udp_server
+                        .session_registry
+                        .update_room_bitrate(room_id, shard_index, bitrate);
                 }
+            }
+            ServerCommand::ReportRoomVideo(room_videos) => {
+                for (room_id, video) in room_videos {
+                    udp_server
+                        .session_registry
+                        .update_room_video(room_id, video);
+                }
+            }
+            ServerCommand::ReportViewerStats(viewer_stats) => {
+                for (remote, stats) in viewer_stats {
+                    if let Some(session) = udp_server
+                        .session_registry
+                        .get_session_by_address_mut(&remote)
+                    {
+                        session.loss_stats = Some(stats);
+                    }
+                }
+            }
+            ServerCommand::RunPeriodicChecks => {
+                // *** Save thumbnails ***
+                udp_server
+                    .shard_router
+                    .broadcast(|| ShardCommand::RunPeriodicChecks);
 
                 // *** Remove stale sessions ***
-                let sessions: Vec<_> = udp_server
+                // Sessions past their TTL may still resume within the grace period
+                let udp_config = &get_global_config().udp_server_config;
+                udp_server
                     .session_registry
-                    .get_all_sessions()
-                    .iter()
-                    .map(|&session| (session.id.clone(), session.ttl))
-                    .collect();
+                    .mark_stale_sessions(udp_config.session_ttl);
+                let stale_session_ids = udp_server.session_registry.get_stale_session_ids(
+                    udp_config.session_ttl + udp_config.session_grace_period,
+                );
 
-                for (id, ttl) in sessions {
-                    if ttl.elapsed() > Duration::from_secs(5) {
-                        udp_server.session_registry.remove_session(id);
-                    }
+                for id in stale_session_ids {
+                    udp_server.remove_session(id);
                 }
             }
         }
     }
 }
 
-fn start_timeout_interval(sender: Sender<ServerCommand>) {
+fn start_timeout_interval(sender: ServerSender) {
+    let interval = get_global_config().udp_server_config.timeout_check_interval;
     loop {
-        sleep(Duration::from_secs(3));
+        sleep(interval);
         sender
             .send(ServerCommand::RunPeriodicChecks)
             .expect("Server channel should be open");
     }
 }
 
-fn start_udp_server(socket: UdpSocket, sender: Sender<ServerCommand>) {
+fn start_rooms_status_interval(sender: ServerSender) {
     loop {
-        let mut buffer = [0; 3600];
-        if let Ok((bytes_read, remote)) = socket.recv_from(&mut buffer) {
-            sender
-                .send(ServerCommand::HandlePacket(
-                    Vec::from(&buffer[..bytes_read]),
-                    remote,
-                ))
-                .expect("Command channel should be open")
-        }
+        sleep(ROOMS_STATUS_INTERVAL);
+        sender
+            .send(ServerCommand::PublishRoomsStatus)
+            .expect("Server channel should be open");
+    }
+}
+
+fn start_sender_report_interval(shard_router: ShardRouter) {
+    loop {
+        sleep(REPORT_POLL_INTERVAL);
+        shard_router.broadcast(|| ShardCommand::SendSenderReports);
     }
 }
 
-fn build_udp_socket() -> UdpSocket {
-    let global_config = get_global_config();
-    let socket = UdpSocket::bind(global_config.udp_server_config.address).unwrap();
+/** Reloads the config on every SIGHUP, starts draining on SIGUSR1.
+ */
+#[cfg(unix)]
+fn start_signal_listener(sender: ServerSender) {
+    let mut signals = Signals::new([SIGHUP, SIGUSR1]).expect("Should register signal handlers");
+    for signal in signals.forever() {
+        let command = match signal {
+            SIGUSR1 => ServerCommand::Drain,
+            _ => ServerCommand::ReloadConfig,
+        };
+        sender.send(command).expect("Server channel should be open");
+    }
+}
+
+#[cfg(not(unix))]
+fn start_signal_listener(_sender: ServerSender) {}
+
+/** Takes over sessions of a draining predecessor, if one is exporting them at the socket.
+ * Streamers go first, so that the rooms of their viewers exist.
+ */
+fn restore_sessions(udp_server: &mut UDPServer, migration_socket: &Path) {
+    let mut snapshots = match import_sessions(migration_socket) {
+        Ok(snapshots) => snapshots,
+        Err(err) => {
+            eprintln!("No sessions imported from {:?}: {}", migration_socket, err);
+            return;
+        }
+    };
+    snapshots.sort_by_key(|snapshot| matches!(snapshot.role, SessionRole::Viewer { .. }));
+
+    let mut restored_count = 0;
+    for snapshot in snapshots {
+        let id = snapshot.id;
+        let is_restored = snapshot
+            .restore()
+            .map(|session| udp_server.session_registry.restore_session(session));
+        match is_restored {
+            Ok(true) => restored_count += 1,
+            Ok(false) => eprintln!("Dropping session {} of a room that wasn't handed over", id),
+            Err(err) => eprintln!("Couldn't restore session {}: {:?}", id, err),
+        }
+    }
     println!(
-        "Running UDP server at {}",
-        global_config.udp_server_config.address
+        "Imported {} sessions from {:?}",
+        restored_count, migration_socket
     );
+}
+
+fn start_keepalive_interval(shard_router: ShardRouter, interval: Duration) {
+    loop {
+        sleep(interval);
+        shard_router.broadcast(|| ShardCommand::SendKeepalives);
+    }
+}
+
+fn start_pacing_interval(shard_router: ShardRouter) {
+    loop {
+        sleep(PACING_INTERVAL);
+        shard_router.broadcast(|| ShardCommand::SendPacedPackets);
+    }
+}
+
+/** Reads every socket on its own thread. ICE checks of all sockets end up in the main loop,
+ * tagged with the index of the socket they were received on.
+ */
+fn start_udp_servers(
+    sockets: &[UdpSocket],
+    mtu: usize,
+    sender: &ServerSender,
+    shard_router: &ShardRouter,
+) {
+    for (socket_index, socket) in clone_sockets(sockets).into_iter().enumerate() {
+        let sender = sender.clone();
+        let shard_router = shard_router.clone();
+        thread::spawn(move || start_udp_server(socket, socket_index, mtu, sender, shard_router));
+    }
+}
+
+fn start_udp_server(
+    socket: UdpSocket,
+    socket_index: SocketIndex,
+    mtu: usize,
+    sender: ServerSender,
+    shard_router: ShardRouter,
+) {
+    // One byte past the MTU, so that truncated datagrams can be told apart
+    let mut buffer = vec![0; mtu + 1];
+    loop {
+        if let Some((packet, remote)) = receive_datagram(&socket, &mut buffer) {
+            // ICE checks go through the main loop, media goes straight to the owning shard
+            match get_packet_type(&packet) {
+                PacketType::Stun => sender
+                    .send(ServerCommand::HandlePacket(packet, remote, socket_index))
+                    .expect("Command channel should be open"),
+                PacketType::Dtls | PacketType::Rtp | PacketType::Rtcp(_) => {
+                    shard_router.send(&remote, ShardCommand::HandlePacket(packet, remote))
+                }
+                PacketType::Unknown => {}
+            }
+        }
+    }
+}
+
+/** Receives a single datagram. The OS silently truncates datagrams longer than the buffer,
+ * so one that fills the buffer completely is dropped instead of being processed partially.
+ * Runts too short to hold any header are dropped before the parsers index into them.
+ */
+fn receive_datagram(socket: &UdpSocket, buffer: &mut [u8]) -> Option<(Vec<u8>, SocketAddr)> {
+    let (bytes_read, remote) = socket.recv_from(buffer).ok()?;
+    if bytes_read == buffer.len() {
+        eprintln!(
+            "Dropping datagram from {} exceeding {} bytes",
+            remote,
+            buffer.len() - 1
+        );
+        return None;
+    }
+    if bytes_read < MIN_PACKET_LEN {
+        if is_log_enabled(LogLevel::Debug) {
+            eprintln!("Dropping {} byte runt datagram from {}", bytes_read, remote);
+        }
+        return None;
+    }
+
+    Some((Vec::from(&buffer[..bytes_read]), remote))
+}
+
+fn start_media_shards(sockets: &[UdpSocket], server_sender: &ServerSender) -> ShardRouter {
+    let shard_count = get_global_config().udp_server_config.shard_count;
+    let (senders, receivers): (Vec<_>, Vec<_>) = (0..shard_count)
+        .map(|_| std::sync::mpsc::channel::<ShardCommand>())
+        .unzip();
+    let shard_router = ShardRouter::new(senders);
+
+    for (index, receiver) in receivers.into_iter().enumerate() {
+        let sockets = clone_sockets(sockets);
+        let shard_router = shard_router.clone();
+        let server_sender = server_sender.clone();
+        thread::spawn(move || {
+            let create_shard = || {
+                MediaShard::new(
+                    index,
+                    clone_sockets(&sockets),
+                    shard_router.clone(),
+                    server_sender.clone(),
+                )
+            };
+            supervise(&format!("Media shard {}", index), create_shard, receiver)
+        });
+    }
+    println!("Running {} media shards", shard_count);
+
+    shard_router
+}
+
+fn build_udp_sockets() -> Vec<UdpSocket> {
+    let udp_config = &get_global_config().udp_server_config;
+    udp_config
+        .addresses
+        .iter()
+        .map(|address| {
+            let socket = bind_udp_socket(*address, udp_config.reuse_port).unwrap();
+            if let Some(dscp) = udp_config.dscp {
+                set_dscp(&socket, dscp).unwrap();
+            }
+            println!("Running UDP server at {}", address);
+            socket
+        })
+        .collect()
+}
+
+/** Binds a UDP socket, with `SO_REUSEPORT` set beforehand if requested so that other processes may bind the same port.
+ */
+fn bind_udp_socket(address: SocketAddr, reuse_port: bool) -> io::Result<UdpSocket> {
+    let socket = Socket::new(
+        Domain::for_address(address),
+        Type::DGRAM,
+        Some(Protocol::UDP),
+    )?;
+    if reuse_port {
+        set_reuse_port(&socket)?;
+    }
+    socket.bind(&address.into())?;
+    Ok(socket.into())
+}
+
+#[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+fn set_reuse_port(socket: &Socket) -> io::Result<()> {
+    socket.set_reuse_port(true)
+}
+
+#[cfg(not(all(unix, not(any(target_os = "solaris", target_os = "illumos")))))]
+fn set_reuse_port(_socket: &Socket) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "SO_REUSEPORT is not supported on this platform",
+    ))
+}
+
+/** Marks datagrams sent from the socket with the DSCP, the upper six bits of the IPv4 ToS / IPv6 Traffic Class.
+ */
+#[cfg(any(
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "linux",
+    target_os = "macos"
+))]
+fn set_dscp(socket: &UdpSocket, dscp: u8) -> io::Result<()> {
+    let traffic_class = (dscp as u32) << 2;
+    let socket_ref = SockRef::from(socket);
+    match socket.local_addr()? {
+        SocketAddr::V4(_) => socket_ref.set_tos(traffic_class),
+        SocketAddr::V6(_) => socket_ref.set_tclass_v6(traffic_class),
+    }
+}
+
+#[cfg(not(any(
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "linux",
+    target_os = "macos"
+)))]
+fn set_dscp(_socket: &UdpSocket, _dscp: u8) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "DSCP marking is not supported on this platform",
+    ))
+}
+
+fn clone_sockets(sockets: &[UdpSocket]) -> Vec<UdpSocket> {
+    sockets
+        .iter()
+        .map(|socket| socket.try_clone().unwrap())
+        .collect()
+}
+
+/** Asks the STUN server for the public address of the socket. Must run before the socket is read elsewhere.
+ */
+fn probe_server_reflexive_address(socket: &UdpSocket, stun_server: &str) -> Option<SocketAddr> {
+    let is_ipv4_socket = socket.local_addr().ok()?.is_ipv4();
+    let stun_server_address = match stun_server.to_socket_addrs() {
+        Ok(mut addresses) => addresses.find(|address| address.is_ipv4() == is_ipv4_socket),
+        Err(err) => {
+            eprintln!("Couldn't resolve STUN server {}: {}", stun_server, err);
+            None
+        }
+    }?;
+
+    let transaction_id = rand::random::<[u8; STUN_TRANSACTION_ID_LEN]>();
+    let request = create_stun_binding_request(transaction_id);
     socket
+        .set_read_timeout(Some(STUN_PROBE_TIMEOUT))
+        .expect("Should set socket read timeout");
+
+    let mut buffer = [0; 1500];
+    let mut server_reflexive_address = None;
+    for _ in 0..STUN_PROBE_ATTEMPTS {
+        if let Err(err) = socket.send_to(&request, stun_server_address) {
+            eprintln!("Couldn't send STUN request {}", err);
+            break;
+        }
+        if let Ok((bytes_read, remote)) = socket.recv_from(&mut buffer) {
+            if remote == stun_server_address {
+                server_reflexive_address =
+                    get_xor_mapped_address(&buffer[..bytes_read], transaction_id);
+            }
+        }
+        if server_reflexive_address.is_some() {
+            break;
+        }
+    }
+
+    socket
+        .set_read_timeout(None)
+        .expect("Should clear socket read timeout");
+
+    match server_reflexive_address {
+        Some(address) => println!("Discovered server reflexive address {}", address),
+        None => eprintln!("STUN probe failed, advertising host candidates only"),
+    }
+    server_reflexive_address
+}
+
+#[cfg(test)]
+mod tests {
+    mod start_udp_servers {
+        use std::net::UdpSocket;
+        use std::sync::mpsc::channel;
+        use std::time::Duration;
+
+        use crate::http::{create_server_channel, ServerCommand};
+        use crate::shard::{ShardCommand, ShardRouter};
+        use crate::start_udp_servers;
+        use crate::stun::create_stun_binding_request;
+
+        #[test]
+        fn tags_packets_with_receiving_socket() {
+            let sockets = vec![
+                UdpSocket::bind("127.0.0.1:0").unwrap(),
+                UdpSocket::bind("127.0.0.1:0").unwrap(),
+            ];
+            let remote_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+            let (server_tx, server_rx) = create_server_channel(16);
+            let (shard_tx, _shard_rx) = channel::<ShardCommand>();
+            let request = create_stun_binding_request([7; 12]);
+
+            start_udp_servers(
+                &sockets,
+                1500,
+                &server_tx,
+                &ShardRouter::new(vec![shard_tx]),
+            );
+            for socket in sockets.iter().rev() {
+                remote_socket
+                    .send_to(&request, socket.local_addr().unwrap())
+                    .unwrap();
+            }
+
+            let mut socket_indexes = (0..sockets.len())
+                .map(|_| match server_rx.recv_timeout(Duration::from_secs(1)) {
+                    Ok(ServerCommand::HandlePacket(packet, remote, socket_index)) => {
+                        assert_eq!(packet, request);
+                        assert_eq!(remote, remote_socket.local_addr().unwrap());
+                        socket_index
+                    }
+                    _ => panic!("Each socket should route its packet"),
+                })
+                .collect::<Vec<_>>();
+            socket_indexes.sort();
+            assert_eq!(socket_indexes, vec![0, 1]);
+        }
+    }
+
+    mod bind_udp_socket {
+        use crate::bind_udp_socket;
+
+        #[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+        #[test]
+        fn shares_port_with_reuse_port() {
+            let socket = bind_udp_socket("127.0.0.1:0".parse().unwrap(), true).unwrap();
+            let address = socket.local_addr().unwrap();
+
+            assert!(bind_udp_socket(address, true).is_ok());
+        }
+
+        #[test]
+        fn rejects_taken_port_without_reuse_port() {
+            let socket = bind_udp_socket("127.0.0.1:0".parse().unwrap(), false).unwrap();
+
+            assert!(bind_udp_socket(socket.local_addr().unwrap(), false).is_err());
+        }
+    }
+
+    #[cfg(any(
+        target_os = "android",
+        target_os = "freebsd",
+        target_os = "linux",
+        target_os = "macos"
+    ))]
+    mod set_dscp {
+        use std::net::UdpSocket;
+
+        use socket2::SockRef;
+
+        use crate::set_dscp;
+
+        #[test]
+        fn marks_ipv4_socket() {
+            let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+
+            set_dscp(&socket, 46).unwrap();
+
+            assert_eq!(SockRef::from(&socket).tos().unwrap(), 46 << 2);
+        }
+    }
+
+    mod receive_datagram {
+        use std::net::UdpSocket;
+
+        use crate::receive_datagram;
+
+        #[test]
+        fn drops_datagram_longer_than_buffer() {
+            let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+            let remote_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+            let mut buffer = [0; 1201];
+
+            remote_socket
+                .send_to(&[1; 1300], socket.local_addr().unwrap())
+                .unwrap();
+            remote_socket
+                .send_to(&[2; 1200], socket.local_addr().unwrap())
+                .unwrap();
+
+            assert_eq!(receive_datagram(&socket, &mut buffer), None);
+            assert_eq!(
+                receive_datagram(&socket, &mut buffer),
+                Some((vec![2; 1200], remote_socket.local_addr().unwrap()))
+            );
+        }
+
+        #[test]
+        fn drops_runt_datagrams() {
+            let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+            let remote_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+            let mut buffer = [0; 1201];
+
+            // Empty, then the leading bytes of an RTP packet and of a STUN message
+            for runt in [&[][..], &[128], &[0, 1, 0]] {
+                remote_socket
+                    .send_to(runt, socket.local_addr().unwrap())
+                    .unwrap();
+            }
+            remote_socket
+                .send_to(&[128; 12], socket.local_addr().unwrap())
+                .unwrap();
+
+            for _ in 0..3 {
+                assert_eq!(receive_datagram(&socket, &mut buffer), None);
+            }
+            assert_eq!(
+                receive_datagram(&socket, &mut buffer),
+                Some((vec![128; 12], remote_socket.local_addr().unwrap()))
+            );
+        }
+    }
 }
@@ -0,0 +1,423 @@
+/*! Hands sessions over to a successor process during rolling upgrades. A draining server exports
+the negotiated parameters of its sessions over a unix socket, the successor restores them at
+startup, so that clients keep their resource and room IDs. Media keys aren't carried over,
+peers handshake DTLS again once they bind to the successor.
+ */
+use std::collections::HashSet;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use sdp::{
+    AudioCodec, AudioSession, ICECredentials, NegotiatedSession, NegotiatedSessionBuilder,
+    SDPParseError, SimulcastSession, VideoCodec, VideoSession,
+};
+
+use crate::http::{ServerCommand, ServerSender};
+use crate::ice_registry::{ConnectionType, ResourceID, RoomID, Session, Streamer, Viewer};
+
+/** Minimal state of a session a successor needs to recognize its peer and keep forwarding its
+ * media, i.e. ICE credentials, SSRCs and the negotiated header extensions of every track.
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionSnapshot {
+    pub id: ResourceID,
+    pub role: SessionRole,
+    pub ice_credentials: ICECredentialsSnapshot,
+    pub audio: AudioSnapshot,
+    pub video: VideoSnapshot,
+    /** Missing from snapshots of predecessors that didn't export extra tracks yet.
+     */
+    #[serde(default)]
+    pub extra_video_sessions: Vec<VideoSnapshot>,
+    pub stream_id: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SessionRole {
+    Streamer { owned_room_id: RoomID },
+    Viewer { room_id: RoomID },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ICECredentialsSnapshot {
+    pub host_username: String,
+    pub host_password: String,
+    pub remote_username: String,
+    pub remote_password: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AudioSnapshot {
+    pub payload_number: usize,
+    pub host_ssrc: u32,
+    pub remote_ssrc: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VideoSnapshot {
+    pub payload_number: usize,
+    pub host_ssrc: u32,
+    pub remote_ssrc: Option<u32>,
+    pub remote_rtx_ssrc: Option<u32>,
+    /** Sorted, so that snapshots of the same session are equal.
+     */
+    pub capabilities: Vec<String>,
+    pub simulcast: Option<SimulcastSnapshot>,
+    pub abs_send_time_extension_id: Option<u8>,
+    pub transport_cc_extension_id: Option<u8>,
+    pub fir_supported: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SimulcastSnapshot {
+    pub rids: Vec<String>,
+    pub rid_extension_id: u8,
+}
+
+impl From<&Session> for SessionSnapshot {
+    fn from(session: &Session) -> Self {
+        let media_session = &session.media_session;
+        let ice_credentials = &media_session.ice_credentials;
+        let audio_session = &media_session.audio_session;
+
+        SessionSnapshot {
+            id: session.id,
+            role: match &session.connection_type {
+                ConnectionType::Streamer(streamer) => SessionRole::Streamer {
+                    owned_room_id: streamer.owned_room_id,
+                },
+                ConnectionType::Viewer(viewer) => SessionRole::Viewer {
+                    room_id: viewer.room_id,
+                },
+            },
+            ice_credentials: ICECredentialsSnapshot {
+                host_username: ice_credentials.host_username.clone(),
+                host_password: ice_credentials.host_password.clone(),
+                remote_username: ice_credentials.remote_username.clone(),
+                remote_password: ice_credentials.remote_password.clone(),
+            },
+            audio: AudioSnapshot {
+                payload_number: audio_session.payload_number,
+                host_ssrc: audio_session.host_ssrc,
+                remote_ssrc: audio_session.remote_ssrc,
+            },
+            video: VideoSnapshot::from(&media_session.video_session),
+            extra_video_sessions: media_session
+                .extra_video_sessions
+                .iter()
+                .map(VideoSnapshot::from)
+                .collect(),
+            stream_id: media_session.stream_id.clone(),
+        }
+    }
+}
+
+impl From<&VideoSession> for VideoSnapshot {
+    fn from(video_session: &VideoSession) -> Self {
+        let mut capabilities = video_session
+            .capabilities
+            .iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        capabilities.sort();
+
+        VideoSnapshot {
+            payload_number: video_session.payload_number,
+            host_ssrc: video_session.host_ssrc,
+            remote_ssrc: video_session.remote_ssrc,
+            remote_rtx_ssrc: video_session.remote_rtx_ssrc,
+            capabilities,
+            simulcast: video_session
+                .simulcast
+                .as_ref()
+                .map(|simulcast| SimulcastSnapshot {
+                    rids: simulcast.rids.clone(),
+                    rid_extension_id: simulcast.rid_extension_id,
+                }),
+            abs_send_time_extension_id: video_session.abs_send_time_extension_id,
+            transport_cc_extension_id: video_session.transport_cc_extension_id,
+            fir_supported: video_session.fir_supported,
+        }
+    }
+}
+
+impl VideoSnapshot {
+    fn get_video_session(&self) -> VideoSession {
+        VideoSession {
+            // Sessions are only ever negotiated with H264 video
+            codec: VideoCodec::H264,
+            payload_number: self.payload_number,
+            host_ssrc: self.host_ssrc,
+            remote_ssrc: self.remote_ssrc,
+            remote_cname: None,
+            remote_rtx_ssrc: self.remote_rtx_ssrc,
+            capabilities: HashSet::from_iter(self.capabilities.iter().cloned()),
+            simulcast: self.simulcast.as_ref().map(|simulcast| SimulcastSession {
+                rids: simulcast.rids.clone(),
+                rid_extension_id: simulcast.rid_extension_id,
+            }),
+            abs_send_time_extension_id: self.abs_send_time_extension_id,
+            transport_cc_extension_id: self.transport_cc_extension_id,
+            fir_supported: self.fir_supported,
+        }
+    }
+}
+
+impl SessionSnapshot {
+    /** Rebuilds the session, without a nominated address until its peer binds again.
+     */
+    pub fn restore(self) -> Result<Session, SDPParseError> {
+        let media_session = self.get_media_session()?;
+        let connection_type = match self.role {
+            SessionRole::Streamer { owned_room_id } => {
+                ConnectionType::Streamer(Streamer { owned_room_id })
+            }
+            SessionRole::Viewer { room_id } => ConnectionType::Viewer(Viewer { room_id }),
+        };
+
+        Ok(Session::restore(self.id, media_session, connection_type))
+    }
+
+    fn get_media_session(&self) -> Result<NegotiatedSession, SDPParseError> {
+        let mut media_session = NegotiatedSessionBuilder::new()
+            .set_ice_credentials(ICECredentials {
+                host_username: self.ice_credentials.host_username.clone(),
+                host_password: self.ice_credentials.host_password.clone(),
+                remote_username: self.ice_credentials.remote_username.clone(),
+                remote_password: self.ice_credentials.remote_password.clone(),
+            })
+            // Sessions are only ever negotiated with Opus audio
+            .set_audio_session(AudioSession {
+                codec: AudioCodec::Opus,
+                payload_number: self.audio.payload_number,
+                host_ssrc: self.audio.host_ssrc,
+                remote_ssrc: self.audio.remote_ssrc,
+                remote_cname: None,
+            })
+            .set_video_session(self.video.get_video_session())
+            .build()?;
+        media_session.extra_video_sessions = self
+            .extra_video_sessions
+            .iter()
+            .map(VideoSnapshot::get_video_session)
+            .collect();
+        media_session.stream_id = self.stream_id.clone();
+
+        Ok(media_session)
+    }
+}
+
+/** Serves snapshots of the current sessions to every successor connecting to the socket.
+ * Snapshots hold ICE credentials, so the socket is only accessible to the owner of the process.
+ */
+#[cfg(unix)]
+pub fn start_migration_listener(path: &Path, sender: ServerSender) -> io::Result<()> {
+    use std::fs::{self, Permissions};
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+    use std::os::unix::net::UnixListener;
+    use std::thread;
+
+    // A socket left behind by a previous process would fail the bind, anything else is kept
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => fs::remove_file(path)?,
+        Ok(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} exists and isn't a socket", path.display()),
+            ))
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err),
+    }
+    let listener = UnixListener::bind(path)?;
+    fs::set_permissions(path, Permissions::from_mode(0o600))?;
+    println!("Exporting sessions at {}", path.display());
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let result = stream.and_then(|stream| export_sessions(stream, &sender));
+            if let Err(err) = result {
+                eprintln!("Couldn't export sessions {}", err)
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(unix)]
+fn export_sessions(stream: impl io::Write, sender: &ServerSender) -> io::Result<()> {
+    let (tx, rx) = std::sync::mpsc::channel::<Vec<SessionSnapshot>>();
+    sender
+        .send(ServerCommand::ExportSessions(tx))
+        .expect("Server channel should be open");
+    let snapshots = rx
+        .recv()
+        .expect("ExportSessions channel should remain open");
+
+    serde_json::to_writer(stream, &snapshots)?;
+    Ok(())
+}
+
+/** Reads the session snapshots of a draining predecessor.
+ */
+#[cfg(unix)]
+pub fn import_sessions(path: &Path) -> io::Result<Vec<SessionSnapshot>> {
+    let stream = std::os::unix::net::UnixStream::connect(path)?;
+    Ok(serde_json::from_reader(stream)?)
+}
+
+#[cfg(not(unix))]
+pub fn start_migration_listener(_path: &Path, _sender: ServerSender) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Session migration needs unix sockets",
+    ))
+}
+
+#[cfg(not(unix))]
+pub fn import_sessions(_path: &Path) -> io::Result<Vec<SessionSnapshot>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Session migration needs unix sockets",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    mod session_snapshot {
+        use sdp::SimulcastSession;
+
+        use crate::ice_registry::{ConnectionType, Session};
+        use crate::migration::{SessionRole, SessionSnapshot};
+        use crate::test_utils::get_streamer_session;
+
+        #[test]
+        fn restores_negotiated_parameters() {
+            let mut media_session = get_streamer_session();
+            let video_session = &mut media_session.video_session;
+            video_session.simulcast = Some(SimulcastSession {
+                rids: vec!["h".to_string(), "l".to_string()],
+                rid_extension_id: 4,
+            });
+            video_session.abs_send_time_extension_id = Some(3);
+            video_session.transport_cc_extension_id = Some(5);
+            let mut extra_video_session = video_session.clone();
+            extra_video_session.remote_ssrc = Some(1234);
+            extra_video_session.simulcast = None;
+            media_session.extra_video_sessions = vec![extra_video_session];
+            let session = Session::new_streamer(media_session, 7);
+            let snapshot = SessionSnapshot::from(&session);
+
+            let serialized = serde_json::to_string(&snapshot).unwrap();
+            let restored_snapshot: SessionSnapshot = serde_json::from_str(&serialized).unwrap();
+            assert_eq!(restored_snapshot, snapshot);
+            assert_eq!(
+                restored_snapshot.role,
+                SessionRole::Streamer { owned_room_id: 7 }
+            );
+
+            let restored = restored_snapshot.restore().expect("Should restore session");
+            let (media_session, restored_media_session) =
+                (&session.media_session, &restored.media_session);
+            assert_eq!(restored.id, session.id);
+            assert!(matches!(
+                restored.connection_type,
+                ConnectionType::Streamer(ref streamer) if streamer.owned_room_id == 7
+            ));
+            assert_eq!(
+                restored_media_session.ice_credentials.host_username,
+                media_session.ice_credentials.host_username
+            );
+            assert_eq!(
+                restored_media_session.ice_credentials.remote_password,
+                media_session.ice_credentials.remote_password
+            );
+            assert_eq!(
+                restored_media_session.audio_session.remote_ssrc,
+                media_session.audio_session.remote_ssrc
+            );
+            assert_eq!(
+                restored_media_session.video_session.host_ssrc,
+                media_session.video_session.host_ssrc
+            );
+            assert_eq!(
+                restored_media_session.video_session.capabilities,
+                media_session.video_session.capabilities
+            );
+            let restored_video_session = &restored_media_session.video_session;
+            assert_eq!(
+                restored_video_session.simulcast,
+                media_session.video_session.simulcast
+            );
+            assert_eq!(restored_video_session.abs_send_time_extension_id, Some(3));
+            assert_eq!(restored_video_session.transport_cc_extension_id, Some(5));
+            assert_eq!(restored_media_session.get_extra_video_index(1234), Some(0));
+            assert_eq!(restored_media_session.stream_id, media_session.stream_id);
+            // Nothing negotiated is lost on the way
+            assert_eq!(SessionSnapshot::from(&restored), snapshot);
+        }
+
+        #[test]
+        fn reads_snapshot_without_extra_video_sessions() {
+            let session = Session::new_streamer(get_streamer_session(), 7);
+            let mut serialized = serde_json::to_value(SessionSnapshot::from(&session)).unwrap();
+            serialized
+                .as_object_mut()
+                .unwrap()
+                .remove("extra_video_sessions");
+
+            let snapshot: SessionSnapshot = serde_json::from_value(serialized).unwrap();
+            assert!(snapshot.extra_video_sessions.is_empty());
+        }
+    }
+
+    #[cfg(unix)]
+    mod import_sessions {
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+        use std::thread;
+
+        use crate::http::{create_server_channel, ServerCommand};
+        use crate::ice_registry::Session;
+        use crate::migration::{import_sessions, start_migration_listener, SessionSnapshot};
+        use crate::test_utils::get_streamer_session;
+
+        #[test]
+        fn reads_sessions_exported_by_predecessor() {
+            let path =
+                std::env::temp_dir().join(format!("migration-{}.sock", rand::random::<u32>()));
+            let (sender, receiver) = create_server_channel(16);
+            let snapshot = SessionSnapshot::from(&Session::new_streamer(get_streamer_session(), 7));
+            start_migration_listener(&path, sender).unwrap();
+
+            // Stands in for the main loop of the predecessor
+            thread::spawn({
+                let snapshot = snapshot.clone();
+                move || match receiver.recv() {
+                    Ok(ServerCommand::ExportSessions(reply_channel)) => {
+                        reply_channel.send(vec![snapshot]).unwrap()
+                    }
+                    _ => panic!("Listener should ask for sessions"),
+                }
+            });
+
+            assert_eq!(import_sessions(&path).unwrap(), vec![snapshot]);
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+            fs::remove_file(&path).unwrap();
+        }
+
+        #[test]
+        fn keeps_file_that_isnt_a_socket() {
+            let path = std::env::temp_dir().join(format!("migration-{}", rand::random::<u32>()));
+            fs::write(&path, "sessions").unwrap();
+            let (sender, _receiver) = create_server_channel(16);
+
+            assert!(start_migration_listener(&path, sender).is_err());
+            assert_eq!(fs::read_to_string(&path).unwrap(), "sessions");
+            fs::remove_file(&path).unwrap();
+        }
+    }
+}
@@ -0,0 +1,280 @@
+use std::time::{Duration, Instant};
+
+use sdp::VideoSession;
+
+use crate::rtcp::create_nack_packet;
+
+/** Minimum time between two NACKs requesting the same packet, roughly a round trip. Requesting it again
+ * sooner only adds load to an already congested link, as the retransmission may still be on its way.
+ */
+const NACK_RESEND_INTERVAL: Duration = Duration::from_millis(100);
+
+struct MissingPacket {
+    sequence_number: u16,
+    last_nacked_at: Option<Instant>,
+}
+
+/** Tracks the sequence numbers of a streamer's video stream, so that lost packets can be requested again.
+ */
+pub struct Reporter {
+    /** How far behind the newest sequence number lost packets are still requested. Packets older than that
+     * are given up on - a window larger than the streamer's retransmission buffer or the viewers' jitter
+     * buffers only wastes bandwidth requesting packets that can't be recovered or are no longer useful.
+     */
+    nack_window: u16,
+    highest_sequence_number: Option<u16>,
+    missing_packets: Vec<MissingPacket>,
+}
+
+impl Reporter {
+    pub fn new(nack_window: u16) -> Self {
+        Reporter {
+            nack_window,
+            highest_sequence_number: None,
+            missing_packets: Vec::new(),
+        }
+    }
+
+    pub fn register_packet(&mut self, sequence_number: u16) {
+        let highest_sequence_number = match self.highest_sequence_number {
+            Some(highest_sequence_number) => highest_sequence_number,
+            None => {
+                self.highest_sequence_number = Some(sequence_number);
+                return;
+            }
+        };
+
+        // Sequence numbers wrap around, anything less than half the range ahead is newer
+        let distance = sequence_number.wrapping_sub(highest_sequence_number);
+        if distance == 0 {
+            return;
+        }
+        if distance > u16::MAX / 2 {
+            // Late or retransmitted packet
+            self.missing_packets
+                .retain(|missing| missing.sequence_number != sequence_number);
+            return;
+        }
+
+        if distance > self.nack_window {
+            // Stream restarted or lost too much to recover
+            self.missing_packets.clear();
+        } else {
+            self.missing_packets
+                .extend((1..distance).map(|offset| MissingPacket {
+                    sequence_number: highest_sequence_number.wrapping_add(offset),
+                    last_nacked_at: None,
+                }));
+        }
+        self.highest_sequence_number = Some(sequence_number);
+        let nack_window = self.nack_window;
+        self.missing_packets
+            .retain(|missing| sequence_number.wrapping_sub(missing.sequence_number) < nack_window);
+    }
+
+    /** Follows the SSRC of the streamer's video, which browsers change when e.g. the camera is toggled. The new
+     * stream numbers its packets from scratch, so the packets missing from the previous one are given up on.
+     * Only SSRCs of authenticated packets may be passed, otherwise anyone could redirect the NACKs.
+     * Returns the previous SSRC if it changed.
+     */
+    pub fn update_media_ssrc(
+        &mut self,
+        video_session: &mut VideoSession,
+        ssrc: u32,
+    ) -> Option<u32> {
+        if video_session.remote_ssrc == Some(ssrc) {
+            return None;
+        }
+
+        self.highest_sequence_number = None;
+        self.missing_packets.clear();
+        video_session.remote_ssrc.replace(ssrc)
+    }
+
+    /** Creates a Generic NACK requesting the missing packets. Nothing is requested until the SSRC of the
+     * streamer's video is known, as a NACK addressed to an unknown source would be ignored. Packets NACKed
+     * within the last [NACK_RESEND_INTERVAL] are left out.
+     */
+    pub fn check_packet_integrity(
+        &mut self,
+        sender_ssrc: u32,
+        video_session: &VideoSession,
+        now: Instant,
+    ) -> Option<Vec<u8>> {
+        let media_ssrc = video_session.require_remote_ssrc().ok()?;
+        let lost_sequence_numbers: Vec<u16> = self
+            .missing_packets
+            .iter_mut()
+            .filter(|missing| {
+                missing.last_nacked_at.is_none_or(|last_nacked_at| {
+                    now.duration_since(last_nacked_at) >= NACK_RESEND_INTERVAL
+                })
+            })
+            .map(|missing| {
+                missing.last_nacked_at = Some(now);
+                missing.sequence_number
+            })
+            .collect();
+        if lost_sequence_numbers.is_empty() {
+            return None;
+        }
+
+        Some(create_nack_packet(
+            sender_ssrc,
+            media_ssrc,
+            &lost_sequence_numbers,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod reporter {
+        use std::collections::HashSet;
+        use std::time::{Duration, Instant};
+
+        use sdp::{VideoCodec, VideoSession};
+
+        use crate::nack::Reporter;
+        use crate::rtcp::create_nack_packet;
+
+        fn get_video_session(remote_ssrc: Option<u32>) -> VideoSession {
+            VideoSession {
+                codec: VideoCodec::H264,
+                payload_number: 96,
+                host_ssrc: 1,
+                remote_ssrc,
+                remote_cname: None,
+                remote_rtx_ssrc: None,
+                capabilities: HashSet::new(),
+                simulcast: None,
+                abs_send_time_extension_id: None,
+                transport_cc_extension_id: None,
+                fir_supported: false,
+            }
+        }
+
+        #[test]
+        fn requests_lost_packets() {
+            let mut reporter = Reporter::new(528);
+
+            for sequence_number in [65534, 65535, 2, 3, 0] {
+                reporter.register_packet(sequence_number);
+            }
+
+            assert_eq!(
+                reporter.check_packet_integrity(1, &get_video_session(Some(2)), Instant::now()),
+                Some(create_nack_packet(1, 2, &[1]))
+            );
+        }
+
+        #[test]
+        fn skips_nack_until_remote_ssrc_is_known() {
+            let mut reporter = Reporter::new(528);
+
+            for sequence_number in [10, 13] {
+                reporter.register_packet(sequence_number);
+            }
+
+            assert_eq!(
+                reporter.check_packet_integrity(1, &get_video_session(None), Instant::now()),
+                None
+            );
+            assert!(reporter
+                .check_packet_integrity(1, &get_video_session(Some(2)), Instant::now())
+                .is_some());
+        }
+
+        #[test]
+        fn skips_nack_within_resend_interval() {
+            let mut reporter = Reporter::new(528);
+            let video_session = get_video_session(Some(2));
+            let start = Instant::now();
+
+            for sequence_number in [10, 12] {
+                reporter.register_packet(sequence_number);
+            }
+            assert_eq!(
+                reporter.check_packet_integrity(1, &video_session, start),
+                Some(create_nack_packet(1, 2, &[11]))
+            );
+
+            reporter.register_packet(14);
+            assert_eq!(
+                reporter.check_packet_integrity(
+                    1,
+                    &video_session,
+                    start + Duration::from_millis(50)
+                ),
+                Some(create_nack_packet(1, 2, &[13]))
+            );
+            assert_eq!(
+                reporter.check_packet_integrity(
+                    1,
+                    &video_session,
+                    start + Duration::from_millis(60)
+                ),
+                None
+            );
+            assert_eq!(
+                reporter.check_packet_integrity(
+                    1,
+                    &video_session,
+                    start + Duration::from_millis(100)
+                ),
+                Some(create_nack_packet(1, 2, &[11]))
+            );
+        }
+
+        #[test]
+        fn resets_on_new_media_ssrc() {
+            let mut reporter = Reporter::new(528);
+            let mut video_session = get_video_session(Some(2));
+
+            for sequence_number in [10, 13] {
+                reporter.register_packet(sequence_number);
+            }
+            assert_eq!(reporter.update_media_ssrc(&mut video_session, 2), None);
+            assert_eq!(reporter.update_media_ssrc(&mut video_session, 3), Some(2));
+
+            // New stream starts at an unrelated sequence number
+            for sequence_number in [500, 502] {
+                reporter.register_packet(sequence_number);
+            }
+
+            assert_eq!(video_session.remote_ssrc, Some(3));
+            assert_eq!(
+                reporter.check_packet_integrity(1, &video_session, Instant::now()),
+                Some(create_nack_packet(1, 3, &[501]))
+            );
+        }
+
+        #[test]
+        fn requests_loss_just_inside_window() {
+            let mut reporter = Reporter::new(10);
+
+            for sequence_number in (0..=10).filter(|sequence_number| *sequence_number != 1) {
+                reporter.register_packet(sequence_number);
+            }
+
+            assert_eq!(
+                reporter.check_packet_integrity(1, &get_video_session(Some(2)), Instant::now()),
+                Some(create_nack_packet(1, 2, &[1]))
+            );
+        }
+
+        #[test]
+        fn skips_loss_just_outside_window() {
+            let mut reporter = Reporter::new(10);
+
+            for sequence_number in (0..=11).filter(|sequence_number| *sequence_number != 1) {
+                reporter.register_packet(sequence_number);
+            }
+
+            assert_eq!(
+                reporter.check_packet_integrity(1, &get_video_session(Some(2)), Instant::now()),
+                None
+            );
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::rtp::is_keyframe_packet;
+
+/** How often shards release paced packets.
+ */
+pub const PACING_INTERVAL: Duration = Duration::from_millis(5);
+
+/** Bitrate assumed until the viewer reports one with REMB.
+ */
+const DEFAULT_TARGET_BITRATE_BPS: u64 = 2_500_000;
+
+/** Packets are sent faster than the target bitrate, so that the queue drains well within a frame interval
+ * and the pacer adds little latency.
+ */
+const PACING_FACTOR: f64 = 2.5;
+
+/** Longest the queued packets may take to drain at the paced rate. Past that, packets are dropped
+ * rather than delayed further, as happens when the viewer's REMB is far below the stream bitrate.
+ */
+const MAX_QUEUE_DELAY: Duration = Duration::from_millis(300);
+
+/** Queue size kept regardless of a low target bitrate, so that a whole keyframe still fits.
+ */
+const MIN_MAX_QUEUED_BYTES: usize = 256 * 1024;
+
+/** Spreads viewer video egress over time with a token bucket, so that a keyframe burst doesn't overrun
+ * the viewer's buffers. The bucket holds at most a single [PACING_INTERVAL] worth of bytes.
+ * Keyframe packets skip ahead of queued delta frame packets, which are also the first to be dropped
+ * once the queue holds more than [MAX_QUEUE_DELAY] worth of bytes.
+ */
+pub struct Pacer {
+    target_bps: u64,
+    budget_bytes: f64,
+    last_refill: Option<Instant>,
+    keyframe_queue: VecDeque<Vec<u8>>,
+    queue: VecDeque<Vec<u8>>,
+    queued_bytes: usize,
+}
+
+impl Pacer {
+    pub fn new() -> Self {
+        Pacer {
+            target_bps: DEFAULT_TARGET_BITRATE_BPS,
+            budget_bytes: 0.0,
+            last_refill: None,
+            keyframe_queue: VecDeque::new(),
+            queue: VecDeque::new(),
+            queued_bytes: 0,
+        }
+    }
+
+    pub fn set_target_bitrate(&mut self, bitrate_bps: u64) {
+        self.target_bps = bitrate_bps;
+    }
+
+    pub fn enqueue(&mut self, packet: Vec<u8>) {
+        self.queued_bytes += packet.len();
+        if is_keyframe_packet(&packet) {
+            self.keyframe_queue.push_back(packet);
+        } else {
+            self.queue.push_back(packet);
+        }
+        self.drop_excess();
+    }
+
+    /** Takes the queued packets fitting in the budget accumulated until now.
+     */
+    pub fn release(&mut self, now: Instant) -> Vec<Vec<u8>> {
+        self.refill(now);
+
+        let mut packets = Vec::new();
+        // The last packet may overdraw the budget, it's paid back before anything else is sent
+        while self.budget_bytes > 0.0 {
+            let packet = match self
+                .keyframe_queue
+                .pop_front()
+                .or_else(|| self.queue.pop_front())
+            {
+                Some(packet) => packet,
+                None => break,
+            };
+            self.budget_bytes -= packet.len() as f64;
+            self.queued_bytes -= packet.len();
+            packets.push(packet);
+        }
+
+        packets
+    }
+
+    /** Drops delta frame packets, then keyframe packets, oldest first, until the queue fits.
+     */
+    fn drop_excess(&mut self) {
+        let delay_bytes = self.get_bytes_per_sec() * MAX_QUEUE_DELAY.as_secs_f64();
+        let max_queued_bytes = (delay_bytes as usize).max(MIN_MAX_QUEUED_BYTES);
+        while self.queued_bytes > max_queued_bytes {
+            let packet = match self
+                .queue
+                .pop_front()
+                .or_else(|| self.keyframe_queue.pop_front())
+            {
+                Some(packet) => packet,
+                None => break,
+            };
+            self.queued_bytes -= packet.len();
+        }
+    }
+
+    fn get_bytes_per_sec(&self) -> f64 {
+        self.target_bps as f64 * PACING_FACTOR / 8.0
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let bytes_per_sec = self.get_bytes_per_sec();
+        let max_budget_bytes = bytes_per_sec * PACING_INTERVAL.as_secs_f64();
+        let elapsed = self.last_refill.map_or(PACING_INTERVAL, |last_refill| {
+            now.duration_since(last_refill)
+        });
+
+        self.last_refill = Some(now);
+        self.budget_bytes =
+            (self.budget_bytes + bytes_per_sec * elapsed.as_secs_f64()).min(max_budget_bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod pacer {
+        use std::time::{Duration, Instant};
+
+        use crate::pacer::{Pacer, MIN_MAX_QUEUED_BYTES, PACING_INTERVAL};
+
+        const IDR_NAL_HEADER: u8 = 0x65;
+        const SLICE_NAL_HEADER: u8 = 0x41;
+
+        fn get_video_packet(sequence_number: u16, nal_header: u8) -> Vec<u8> {
+            let mut packet = vec![0x80, 96];
+            packet.extend_from_slice(&sequence_number.to_be_bytes());
+            packet.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+            packet.push(nal_header);
+            packet.resize(1000, 0);
+            packet
+        }
+
+        #[test]
+        fn releases_burst_over_time() {
+            let mut pacer = Pacer::new();
+            // 312.5 bytes per millisecond after the pacing factor, 1562.5 bytes per interval
+            pacer.set_target_bitrate(1_000_000);
+            let start = Instant::now();
+
+            for sequence_number in 0..10 {
+                pacer.enqueue(get_video_packet(sequence_number, SLICE_NAL_HEADER));
+            }
+
+            assert_eq!(pacer.release(start).len(), 2);
+            assert_eq!(pacer.release(start).len(), 0);
+
+            let mut released = 2;
+            let mut intervals = 0;
+            while released < 10 {
+                intervals += 1;
+                released += pacer.release(start + PACING_INTERVAL * intervals).len();
+            }
+            assert!(intervals >= 5, "released burst in {intervals} intervals");
+        }
+
+        #[test]
+        fn releases_keyframe_packets_first() {
+            let mut pacer = Pacer::new();
+            pacer.set_target_bitrate(1_000_000);
+            let start = Instant::now();
+
+            pacer.enqueue(get_video_packet(0, SLICE_NAL_HEADER));
+            pacer.enqueue(get_video_packet(1, SLICE_NAL_HEADER));
+            pacer.enqueue(get_video_packet(2, IDR_NAL_HEADER));
+
+            let released = pacer.release(start + Duration::from_millis(100));
+
+            assert_eq!(
+                released,
+                vec![
+                    get_video_packet(2, IDR_NAL_HEADER),
+                    get_video_packet(0, SLICE_NAL_HEADER)
+                ]
+            );
+        }
+
+        #[test]
+        fn keeps_queue_bounded_under_sustained_overload() {
+            let mut pacer = Pacer::new();
+            // Far below the stream, which sends 1000 byte packets every millisecond
+            pacer.set_target_bitrate(50_000);
+            let start = Instant::now();
+
+            for sequence_number in 0..10_000u16 {
+                let now = start + Duration::from_millis(sequence_number as u64);
+                let nal_header = match sequence_number % 1000 < 50 {
+                    true => IDR_NAL_HEADER,
+                    false => SLICE_NAL_HEADER,
+                };
+                pacer.enqueue(get_video_packet(sequence_number, nal_header));
+                pacer.release(now);
+                assert!(pacer.queued_bytes <= MIN_MAX_QUEUED_BYTES);
+            }
+        }
+
+        #[test]
+        fn drops_oldest_delta_packets_first() {
+            let mut pacer = Pacer::new();
+            pacer.set_target_bitrate(50_000);
+            // One packet more than the queue holds
+            let packet_count = (MIN_MAX_QUEUED_BYTES / 1000) as u16;
+
+            pacer.enqueue(get_video_packet(0, IDR_NAL_HEADER));
+            for sequence_number in 1..=packet_count {
+                pacer.enqueue(get_video_packet(sequence_number, SLICE_NAL_HEADER));
+            }
+
+            let start = Instant::now();
+            let released = pacer.release(start);
+            assert_eq!(released, vec![get_video_packet(0, IDR_NAL_HEADER)]);
+            let released = pacer.release(start + Duration::from_secs(1));
+            assert_eq!(released, vec![get_video_packet(2, SLICE_NAL_HEADER)]);
+        }
+    }
+}
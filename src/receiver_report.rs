@@ -0,0 +1,171 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::rtcp::ReportBlock;
+use crate::sender_report::OutboundStream;
+
+const REPORT_HISTORY_LENGTH: usize = 16;
+
+/** Loss statistics of a viewer, as reported through RTCP Receiver Reports.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LossStats {
+    /// Fraction of packets lost since the previous report, between 0 and 1
+    pub fraction_lost: f64,
+    pub cumulative_lost: i64,
+    /// Interarrival jitter in RTP timestamp units
+    pub jitter: u32,
+    /// Fraction of packets sent by the server that reached the viewer, between 0 and 1
+    pub delivery_ratio: Option<f64>,
+}
+
+/** Rolling history of report blocks received from a single viewer.
+ */
+#[derive(Debug)]
+pub struct ReceiverReportHistory {
+    reports: VecDeque<ReportBlock>,
+}
+
+impl ReceiverReportHistory {
+    pub fn new() -> Self {
+        ReceiverReportHistory {
+            reports: VecDeque::with_capacity(REPORT_HISTORY_LENGTH),
+        }
+    }
+
+    pub fn add_report(&mut self, report: ReportBlock) {
+        if self.reports.len() == REPORT_HISTORY_LENGTH {
+            self.reports.pop_front();
+        }
+        self.reports.push_back(report);
+    }
+
+    /** Combines the latest report of each reported SSRC. The worst fraction lost and jitter are used.
+     */
+    pub fn get_loss_stats(&self) -> Option<LossStats> {
+        let mut seen_ssrcs = HashSet::new();
+        self.reports
+            .iter()
+            .rev()
+            .filter(|report| seen_ssrcs.insert(report.ssrc))
+            .map(|report| LossStats {
+                fraction_lost: report.fraction_lost as f64 / 256.0,
+                cumulative_lost: report.cumulative_lost as i64,
+                jitter: report.jitter,
+                delivery_ratio: None,
+            })
+            .reduce(|total, stats| LossStats {
+                fraction_lost: total.fraction_lost.max(stats.fraction_lost),
+                cumulative_lost: total.cumulative_lost + stats.cumulative_lost,
+                jitter: total.jitter.max(stats.jitter),
+                delivery_ratio: None,
+            })
+    }
+
+    /** Share of the packets sent on the reported SSRCs that the viewer didn't report as lost.
+     * The viewer's cumulative loss is set against our own count of packets sent on each SSRC.
+     */
+    pub fn get_delivery_ratio(
+        &self,
+        outbound_streams: &HashMap<u32, OutboundStream>,
+    ) -> Option<f64> {
+        let mut seen_ssrcs = HashSet::new();
+        let (sent, lost) = self
+            .reports
+            .iter()
+            .rev()
+            .filter(|report| seen_ssrcs.insert(report.ssrc))
+            .filter_map(|report| {
+                let stream = outbound_streams.get(&report.ssrc)?;
+                Some((
+                    stream.packet_count() as i64,
+                    report.cumulative_lost.max(0) as i64,
+                ))
+            })
+            .fold((0, 0), |(sent, lost), (stream_sent, stream_lost)| {
+                (sent + stream_sent, lost + stream_lost)
+            });
+
+        match sent {
+            0 => None,
+            sent => Some((sent - lost).max(0) as f64 / sent as f64),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod receiver_report_history {
+        use std::collections::HashMap;
+        use std::time::Instant;
+
+        use crate::receiver_report::ReceiverReportHistory;
+        use crate::rtcp::get_receiver_report_blocks;
+        use crate::sender_report::OutboundStream;
+
+        fn get_receiver_report(ssrc: u8, fraction_lost: u8, cumulative_lost: u8) -> Vec<u8> {
+            let mut packet = vec![0b1000_0001, 201, 0, 7, 0, 0, 0, 1];
+            packet.extend_from_slice(&[0, 0, 0, ssrc, fraction_lost, 0, 0, cumulative_lost]);
+            packet.extend_from_slice(&[0, 0, 0, 100, 0, 0, 0, 20]);
+            packet.extend_from_slice(&[0; 8]);
+            packet
+        }
+
+        #[test]
+        fn reports_loss_from_receiver_report() {
+            let mut history = ReceiverReportHistory::new();
+            assert!(history.get_loss_stats().is_none());
+
+            // 10% of packets lost
+            for report in get_receiver_report_blocks(&get_receiver_report(1, 26, 5)) {
+                history.add_report(report);
+            }
+
+            let stats = history.get_loss_stats().expect("Should report loss stats");
+            assert!((stats.fraction_lost - 0.1).abs() < 0.01);
+            assert_eq!(stats.cumulative_lost, 5);
+            assert_eq!(stats.jitter, 20);
+        }
+
+        #[test]
+        fn uses_latest_report_per_ssrc() {
+            let mut history = ReceiverReportHistory::new();
+            let reports = [
+                get_receiver_report(1, 128, 50),
+                get_receiver_report(2, 0, 3),
+                get_receiver_report(1, 0, 60),
+            ];
+            for report in reports
+                .iter()
+                .flat_map(|packet| get_receiver_report_blocks(packet))
+            {
+                history.add_report(report);
+            }
+
+            let stats = history.get_loss_stats().unwrap();
+            assert_eq!(stats.fraction_lost, 0.0);
+            assert_eq!(stats.cumulative_lost, 63);
+        }
+
+        #[test]
+        fn compares_loss_with_sent_packets() {
+            let now = Instant::now();
+            let mut history = ReceiverReportHistory::new();
+            let mut outbound_streams = HashMap::new();
+            let mut stream = OutboundStream::new(1, 90000, 0, now);
+            for _ in 0..50 {
+                stream.register_packet(0, 1000, now);
+            }
+            outbound_streams.insert(1, stream);
+            assert_eq!(history.get_delivery_ratio(&outbound_streams), None);
+
+            // Reports of SSRCs the viewer wasn't sent are left out
+            for packet in [get_receiver_report(1, 0, 5), get_receiver_report(2, 0, 40)] {
+                for report in get_receiver_report_blocks(&packet) {
+                    history.add_report(report);
+                }
+            }
+
+            assert_eq!(history.get_delivery_ratio(&outbound_streams), Some(0.9));
+        }
+    }
+}
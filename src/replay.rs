@@ -0,0 +1,164 @@
+use crate::client::SRTPError;
+
+/** Number of packets behind the newest one that are still accepted, the RFC 3711 minimum.
+ */
+const REPLAY_WINDOW_SIZE: u64 = 64;
+
+/** Sliding window of the SRTP packet indices received on a single SSRC. The index extends the 16-bit
+ * sequence number with the rollover counter (ROC), so that the window keeps working across wrap-arounds.
+ * Packets newer than the window are accepted, packets within it only once and older ones never.
+ */
+#[derive(Debug)]
+pub struct ReplayWindow {
+    highest_index: Option<u64>,
+    /** Bit N is set when the packet N indices behind the highest one was received.
+     */
+    received: u64,
+}
+
+impl ReplayWindow {
+    pub fn new() -> Self {
+        ReplayWindow {
+            highest_index: None,
+            received: 0,
+        }
+    }
+
+    /** Estimates the packet index of the sequence number and rejects it if already seen or too old.
+     * The index has to be passed to [ReplayWindow::register] once the packet is authenticated.
+     */
+    pub fn check(&self, sequence_number: u16) -> Result<u64, SRTPError> {
+        let highest_index = match self.highest_index {
+            Some(highest_index) => highest_index,
+            None => return Ok(sequence_number as u64),
+        };
+
+        // The ROC is guessed so that the index lands closest to the highest one
+        let delta = sequence_number.wrapping_sub(highest_index as u16) as i16 as i64;
+        let index = match u64::try_from(highest_index as i64 + delta) {
+            Ok(index) => index,
+            Err(_) => return Err(SRTPError::ReplayDetected),
+        };
+        if index > highest_index {
+            return Ok(index);
+        }
+
+        let offset = highest_index - index;
+        if offset >= REPLAY_WINDOW_SIZE || self.received & (1 << offset) != 0 {
+            return Err(SRTPError::ReplayDetected);
+        }
+
+        Ok(index)
+    }
+
+    pub fn register(&mut self, index: u64) {
+        let highest_index = match self.highest_index {
+            Some(highest_index) => highest_index,
+            None => {
+                self.highest_index = Some(index);
+                self.received = 1;
+                return;
+            }
+        };
+
+        if index > highest_index {
+            let shift = index - highest_index;
+            self.received = match shift < REPLAY_WINDOW_SIZE {
+                true => self.received << shift | 1,
+                false => 1,
+            };
+            self.highest_index = Some(index);
+        } else {
+            self.received |= 1 << (highest_index - index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod replay_window {
+        use crate::client::SRTPError;
+        use crate::replay::ReplayWindow;
+
+        fn receive(
+            replay_window: &mut ReplayWindow,
+            sequence_number: u16,
+        ) -> Result<u64, SRTPError> {
+            let index = replay_window.check(sequence_number)?;
+            replay_window.register(index);
+            Ok(index)
+        }
+
+        #[test]
+        fn accepts_new_packet() {
+            let mut replay_window = ReplayWindow::new();
+
+            assert_eq!(receive(&mut replay_window, 100), Ok(100));
+            assert_eq!(receive(&mut replay_window, 101), Ok(101));
+            assert_eq!(receive(&mut replay_window, 300), Ok(300));
+        }
+
+        #[test]
+        fn rejects_replayed_packet() {
+            let mut replay_window = ReplayWindow::new();
+
+            for sequence_number in 100..110 {
+                receive(&mut replay_window, sequence_number).unwrap();
+            }
+
+            assert_eq!(
+                receive(&mut replay_window, 105),
+                Err(SRTPError::ReplayDetected)
+            );
+            assert_eq!(
+                receive(&mut replay_window, 109),
+                Err(SRTPError::ReplayDetected)
+            );
+        }
+
+        #[test]
+        fn accepts_reordered_packet_within_window() {
+            let mut replay_window = ReplayWindow::new();
+
+            for sequence_number in [100, 102, 103] {
+                receive(&mut replay_window, sequence_number).unwrap();
+            }
+
+            assert_eq!(receive(&mut replay_window, 101), Ok(101));
+            assert_eq!(
+                receive(&mut replay_window, 101),
+                Err(SRTPError::ReplayDetected)
+            );
+        }
+
+        #[test]
+        fn rejects_packet_older_than_window() {
+            let mut replay_window = ReplayWindow::new();
+
+            for sequence_number in [100, 164] {
+                receive(&mut replay_window, sequence_number).unwrap();
+            }
+
+            assert_eq!(
+                receive(&mut replay_window, 100),
+                Err(SRTPError::ReplayDetected)
+            );
+            assert_eq!(receive(&mut replay_window, 101), Ok(101));
+        }
+
+        #[test]
+        fn tracks_rollover_counter() {
+            let mut replay_window = ReplayWindow::new();
+
+            for sequence_number in [65534, 65535, 1] {
+                receive(&mut replay_window, sequence_number).unwrap();
+            }
+
+            assert_eq!(receive(&mut replay_window, 0), Ok(65536));
+            assert_eq!(
+                receive(&mut replay_window, 65535),
+                Err(SRTPError::ReplayDetected)
+            );
+        }
+    }
+}
@@ -0,0 +1,711 @@
+use byteorder::{ByteOrder, NetworkEndian};
+
+use crate::sender_report::SenderReport;
+
+const RTCP_VERSION: u8 = 2;
+pub const SENDER_REPORT_TYPE: u8 = 200;
+pub const RECEIVER_REPORT_TYPE: u8 = 201;
+pub const BYE_PACKET_TYPE: u8 = 203;
+pub const TRANSPORT_LAYER_FEEDBACK_TYPE: u8 = 205;
+pub const PAYLOAD_SPECIFIC_FEEDBACK_TYPE: u8 = 206;
+const APPLICATION_LAYER_FEEDBACK_FORMAT: u8 = 15;
+const GENERIC_NACK_FORMAT: u8 = 1;
+const PICTURE_LOSS_INDICATION_FORMAT: u8 = 1;
+const FULL_INTRA_REQUEST_FORMAT: u8 = 4;
+const REMB_IDENTIFIER: &[u8; 4] = b"REMB";
+
+/** With rtcp-mux, a payload type of 64-95 in the second byte (marker bit masked out) denotes RTCP.
+RTP payload types in that range are not allowed on multiplexed sessions.
+https://datatracker.ietf.org/doc/html/rfc5761#section-4
+ */
+pub fn is_rtcp_packet(buffer: &[u8]) -> bool {
+    buffer.len() >= 8 && (64..=95).contains(&(buffer[1] & 0b0111_1111))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RTCPPacketType {
+    SenderReport,
+    ReceiverReport,
+    Bye,
+    PayloadSpecificFeedback,
+    Other(u8),
+}
+
+impl From<u8> for RTCPPacketType {
+    fn from(value: u8) -> Self {
+        match value {
+            SENDER_REPORT_TYPE => RTCPPacketType::SenderReport,
+            RECEIVER_REPORT_TYPE => RTCPPacketType::ReceiverReport,
+            BYE_PACKET_TYPE => RTCPPacketType::Bye,
+            PAYLOAD_SPECIFIC_FEEDBACK_TYPE => RTCPPacketType::PayloadSpecificFeedback,
+            other => RTCPPacketType::Other(other),
+        }
+    }
+}
+
+/** Lists the types of all packets in a compound RTCP packet.
+ * Only the first header is readable before SRTCP unprotect, so call this on decrypted packets.
+ */
+pub fn get_rtcp_packet_types(buffer: &[u8]) -> Vec<RTCPPacketType> {
+    let mut packet_types = Vec::new();
+    let mut offset = 0;
+    while let Some(header) = buffer.get(offset..offset + 4) {
+        let packet_length = (NetworkEndian::read_u16(&header[2..4]) as usize + 1) * 4;
+        if buffer.len() < offset + packet_length {
+            break;
+        }
+
+        packet_types.push(RTCPPacketType::from(header[1]));
+        offset += packet_length;
+    }
+    packet_types
+}
+
+/** Collects the sources leaving with the BYE packets of a compound RTCP packet.
+ */
+pub fn get_bye_ssrcs(buffer: &[u8]) -> Vec<u32> {
+    let mut ssrcs = Vec::new();
+    let mut offset = 0;
+    while let Some(header) = buffer.get(offset..offset + 4) {
+        let packet_length = (NetworkEndian::read_u16(&header[2..4]) as usize + 1) * 4;
+        let packet = match buffer.get(offset..offset + packet_length) {
+            Some(packet) => packet,
+            None => break,
+        };
+
+        if header[1] == BYE_PACKET_TYPE {
+            let source_count = (header[0] & 0b0001_1111) as usize;
+            ssrcs.extend(
+                packet[4..]
+                    .chunks_exact(4)
+                    .take(source_count)
+                    .map(NetworkEndian::read_u32),
+            );
+        }
+
+        offset += packet_length;
+    }
+    ssrcs
+}
+
+/**
+https://datatracker.ietf.org/doc/html/rfc3550#section-6.4.1
+       +=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+
+report |                 SSRC_1 (SSRC of first source)                 |
+block  +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+  1    | fraction lost |       cumulative number of packets lost       |
+       +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+       |           extended highest sequence number received           |
+       +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+       |                      interarrival jitter                      |
+       +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+       |                         last SR (LSR)                         |
+       +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+       |                   delay since last SR (DLSR)                  |
+       +=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReportBlock {
+    pub ssrc: u32,
+    pub fraction_lost: u8,
+    pub cumulative_lost: i32,
+    pub extended_highest_sequence: u32,
+    pub jitter: u32,
+    pub last_sender_report: u32,
+    pub delay_since_last_sender_report: u32,
+}
+
+const REPORT_BLOCK_LENGTH: usize = 24;
+
+impl TryFrom<&[u8]> for ReportBlock {
+    type Error = ();
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        if value.len() < REPORT_BLOCK_LENGTH {
+            return Err(());
+        }
+        // Cumulative lost is a signed 24-bit integer
+        let cumulative_lost = (NetworkEndian::read_i32(&value[4..8]) << 8) >> 8;
+
+        Ok(ReportBlock {
+            ssrc: NetworkEndian::read_u32(&value[0..4]),
+            fraction_lost: value[4],
+            cumulative_lost,
+            extended_highest_sequence: NetworkEndian::read_u32(&value[8..12]),
+            jitter: NetworkEndian::read_u32(&value[12..16]),
+            last_sender_report: NetworkEndian::read_u32(&value[16..20]),
+            delay_since_last_sender_report: NetworkEndian::read_u32(&value[20..24]),
+        })
+    }
+}
+
+/** Collects report blocks of all Receiver Reports in a compound RTCP packet.
+ */
+pub fn get_receiver_report_blocks(buffer: &[u8]) -> Vec<ReportBlock> {
+    let mut report_blocks = Vec::new();
+    let mut offset = 0;
+    while let Some(header) = buffer.get(offset..offset + 4) {
+        let packet_length = (NetworkEndian::read_u16(&header[2..4]) as usize + 1) * 4;
+        let packet = match buffer.get(offset..offset + packet_length) {
+            Some(packet) => packet,
+            None => break,
+        };
+
+        if header[1] == RECEIVER_REPORT_TYPE {
+            let report_count = (header[0] & 0b0001_1111) as usize;
+            // Report blocks follow the header and reporter SSRC
+            report_blocks.extend(
+                packet[8.min(packet.len())..]
+                    .chunks(REPORT_BLOCK_LENGTH)
+                    .take(report_count)
+                    .filter_map(|block| ReportBlock::try_from(block).ok()),
+            );
+        }
+
+        offset += packet_length;
+    }
+    report_blocks
+}
+
+/** Finds a REMB message in a compound RTCP packet and returns the estimated bitrate in bps.
+https://datatracker.ietf.org/doc/html/draft-alvestrand-rmcat-remb-03#section-2.2
+    0                   1                   2                   3
+    0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+   |V=2|P| FMT=15  |   PT=206      |             length            |
+   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+   |                  SSRC of packet sender                        |
+   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+   |                  SSRC of media source                         |
+   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+   |  Unique identifier 'R' 'E' 'M' 'B'                            |
+   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+   |  Num SSRC     | BR Exp    |  BR Mantissa                      |
+   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+   |   SSRC feedback                                               |
+   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+*/
+pub fn get_remb_bitrate(buffer: &[u8]) -> Option<u64> {
+    let mut offset = 0;
+    while let Some(header) = buffer.get(offset..offset + 4) {
+        let packet_length = (NetworkEndian::read_u16(&header[2..4]) as usize + 1) * 4;
+        let packet = buffer.get(offset..offset + packet_length)?;
+
+        let format = header[0] & 0b0001_1111;
+        if header[1] == PAYLOAD_SPECIFIC_FEEDBACK_TYPE
+            && format == APPLICATION_LAYER_FEEDBACK_FORMAT
+            && packet.get(12..16) == Some(REMB_IDENTIFIER)
+        {
+            let bitrate_bytes = packet.get(16..20)?;
+            let exponent = bitrate_bytes[1] >> 2;
+            let mantissa = NetworkEndian::read_u32(bitrate_bytes) & 0x0003_FFFF;
+            return (mantissa as u64).checked_shl(exponent as u32);
+        }
+
+        offset += packet_length;
+    }
+    None
+}
+
+/**
+https://datatracker.ietf.org/doc/html/rfc3550#section-6.4.1
+        0                   1                   2                   3
+        0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+       +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+header |V=2|P|    RC   |   PT=SR=200   |             length            |
+       +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+       |                         SSRC of sender                        |
+       +=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+
+sender |              NTP timestamp, most significant word             |
+info   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+       |             NTP timestamp, least significant word             |
+       +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+       |                         RTP timestamp                         |
+       +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+       |                     sender's packet count                     |
+       +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+       |                      sender's octet count                     |
+       +=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+
+*/
+pub fn create_sender_report_packet(report: &SenderReport) -> Vec<u8> {
+    let mut packet = vec![0u8; 28];
+    packet[0] = RTCP_VERSION << 6;
+    packet[1] = SENDER_REPORT_TYPE;
+    // Length in 32-bit words minus one
+    NetworkEndian::write_u16(&mut packet[2..4], 6);
+    NetworkEndian::write_u32(&mut packet[4..8], report.ssrc);
+    NetworkEndian::write_u64(&mut packet[8..16], report.ntp_timestamp);
+    NetworkEndian::write_u32(&mut packet[16..20], report.rtp_timestamp);
+    NetworkEndian::write_u32(&mut packet[20..24], report.packet_count);
+    NetworkEndian::write_u32(&mut packet[24..28], report.octet_count);
+
+    packet
+}
+
+/**
+https://datatracker.ietf.org/doc/html/rfc3550#section-6.6
+       0                   1                   2                   3
+       0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+      +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+      |V=2|P|    SC   |   PT=BYE=203  |             length            |
+      +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+      |                           SSRC/CSRC                           |
+      +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+      :                              ...                              :
+      +=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+
+
+Every RTCP packet has to be compound and start with a report, so the BYE follows the given Sender
+Report, or an empty Receiver Report of the first source if no media was sent.
+https://datatracker.ietf.org/doc/html/rfc3550#section-6.1
+*/
+pub fn create_bye_packet(report: Option<&SenderReport>, ssrcs: &[u32]) -> Vec<u8> {
+    // Source count is a 5-bit field
+    let ssrcs = &ssrcs[..ssrcs.len().min(31)];
+
+    let mut packet = match report {
+        Some(report) => create_sender_report_packet(report),
+        None => create_empty_receiver_report_packet(ssrcs.first().copied().unwrap_or(0)),
+    };
+    let bye_offset = packet.len();
+    packet.resize(bye_offset + 4 + ssrcs.len() * 4, 0);
+    packet[bye_offset] = (RTCP_VERSION << 6) | ssrcs.len() as u8;
+    packet[bye_offset + 1] = BYE_PACKET_TYPE;
+    // Length in 32-bit words minus one
+    NetworkEndian::write_u16(
+        &mut packet[bye_offset + 2..bye_offset + 4],
+        ssrcs.len() as u16,
+    );
+
+    for (index, ssrc) in ssrcs.iter().enumerate() {
+        let offset = bye_offset + 4 + index * 4;
+        NetworkEndian::write_u32(&mut packet[offset..offset + 4], *ssrc);
+    }
+
+    packet
+}
+
+/** Receiver Report without report blocks.
+https://datatracker.ietf.org/doc/html/rfc3550#section-6.4.2
+ */
+fn create_empty_receiver_report_packet(ssrc: u32) -> Vec<u8> {
+    let mut packet = vec![0u8; 8];
+    packet[0] = RTCP_VERSION << 6;
+    packet[1] = RECEIVER_REPORT_TYPE;
+    // Length in 32-bit words minus one
+    NetworkEndian::write_u16(&mut packet[2..4], 1);
+    NetworkEndian::write_u32(&mut packet[4..8], ssrc);
+
+    packet
+}
+
+/**
+https://datatracker.ietf.org/doc/html/rfc4585#section-6.2.1
+       0                   1                   2                   3
+       0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+      +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+      |V=2|P| FMT=1   |   PT=RTPFB=205  |          length             |
+      +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+      |                  SSRC of packet sender                        |
+      +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+      |                  SSRC of media source                         |
+      +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+      |            PID                |             BLP               |
+      +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+      :                              ...                              :
+      +=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+
+Lost sequence numbers are expected in order. Each PID covers the 16 sequence numbers following it through the BLP bitmask.
+*/
+pub fn create_nack_packet(
+    sender_ssrc: u32,
+    media_ssrc: u32,
+    lost_sequence_numbers: &[u16],
+) -> Vec<u8> {
+    let mut lost_packets: Vec<(u16, u16)> = Vec::new();
+    for sequence_number in lost_sequence_numbers {
+        match lost_packets.last_mut() {
+            Some((pid, blp)) if (1..=16).contains(&sequence_number.wrapping_sub(*pid)) => {
+                *blp |= 1 << (sequence_number.wrapping_sub(*pid) - 1)
+            }
+            _ => lost_packets.push((*sequence_number, 0)),
+        }
+    }
+
+    let mut packet = vec![0u8; 12 + lost_packets.len() * 4];
+    packet[0] = (RTCP_VERSION << 6) | GENERIC_NACK_FORMAT;
+    packet[1] = TRANSPORT_LAYER_FEEDBACK_TYPE;
+    // Length in 32-bit words minus one
+    NetworkEndian::write_u16(&mut packet[2..4], 2 + lost_packets.len() as u16);
+    NetworkEndian::write_u32(&mut packet[4..8], sender_ssrc);
+    NetworkEndian::write_u32(&mut packet[8..12], media_ssrc);
+
+    for (index, (pid, blp)) in lost_packets.iter().enumerate() {
+        let offset = 12 + index * 4;
+        NetworkEndian::write_u16(&mut packet[offset..offset + 2], *pid);
+        NetworkEndian::write_u16(&mut packet[offset + 2..offset + 4], *blp);
+    }
+
+    packet
+}
+
+/**
+https://datatracker.ietf.org/doc/html/rfc4585#section-6.3.1
+Payload-specific feedback with no Feedback Control Information, asking the media source for a keyframe.
+*/
+pub fn create_pli_packet(sender_ssrc: u32, media_ssrc: u32) -> Vec<u8> {
+    let mut packet = vec![0u8; 12];
+    packet[0] = (RTCP_VERSION << 6) | PICTURE_LOSS_INDICATION_FORMAT;
+    packet[1] = PAYLOAD_SPECIFIC_FEEDBACK_TYPE;
+    // Length in 32-bit words minus one
+    NetworkEndian::write_u16(&mut packet[2..4], 2);
+    NetworkEndian::write_u32(&mut packet[4..8], sender_ssrc);
+    NetworkEndian::write_u32(&mut packet[8..12], media_ssrc);
+
+    packet
+}
+
+/**
+https://datatracker.ietf.org/doc/html/rfc5104#section-4.3.1
+Payload-specific feedback asking the media source for a decoder refresh point. The media source SSRC of the
+common header is always 0, the source is named in the Feedback Control Information along with a sequence
+number. Repeated requests for the same keyframe keep the number, each new request increments it.
+*/
+pub fn create_fir_packet(sender_ssrc: u32, media_ssrc: u32, sequence_number: u8) -> Vec<u8> {
+    let mut packet = vec![0u8; 20];
+    packet[0] = (RTCP_VERSION << 6) | FULL_INTRA_REQUEST_FORMAT;
+    packet[1] = PAYLOAD_SPECIFIC_FEEDBACK_TYPE;
+    // Length in 32-bit words minus one
+    NetworkEndian::write_u16(&mut packet[2..4], 4);
+    NetworkEndian::write_u32(&mut packet[4..8], sender_ssrc);
+    NetworkEndian::write_u32(&mut packet[12..16], media_ssrc);
+    packet[16] = sequence_number;
+
+    packet
+}
+
+/** REMB message announcing the estimated bitrate for the given media sources, see [get_remb_bitrate] for
+the layout. The media source SSRC of the common header is always 0.
+https://datatracker.ietf.org/doc/html/draft-alvestrand-rmcat-remb-03#section-2.2
+*/
+pub fn create_remb_packet(sender_ssrc: u32, bitrate_bps: u64, media_ssrcs: &[u32]) -> Vec<u8> {
+    // Source count is an 8-bit field
+    let media_ssrcs = &media_ssrcs[..media_ssrcs.len().min(255)];
+
+    // Mantissa is an 18-bit field, the exponent 6-bit
+    let mut exponent = 0u32;
+    while bitrate_bps >> exponent > 0x0003_FFFF && exponent < 63 {
+        exponent += 1;
+    }
+    let mantissa = (bitrate_bps >> exponent).min(0x0003_FFFF) as u32;
+
+    let mut packet = vec![0u8; 20 + media_ssrcs.len() * 4];
+    packet[0] = (RTCP_VERSION << 6) | APPLICATION_LAYER_FEEDBACK_FORMAT;
+    packet[1] = PAYLOAD_SPECIFIC_FEEDBACK_TYPE;
+    // Length in 32-bit words minus one
+    NetworkEndian::write_u16(&mut packet[2..4], 4 + media_ssrcs.len() as u16);
+    NetworkEndian::write_u32(&mut packet[4..8], sender_ssrc);
+    packet[12..16].copy_from_slice(REMB_IDENTIFIER);
+    NetworkEndian::write_u32(
+        &mut packet[16..20],
+        (media_ssrcs.len() as u32) << 24 | exponent << 18 | mantissa,
+    );
+
+    for (index, ssrc) in media_ssrcs.iter().enumerate() {
+        let offset = 20 + index * 4;
+        NetworkEndian::write_u32(&mut packet[offset..offset + 4], *ssrc);
+    }
+
+    packet
+}
+
+#[cfg(test)]
+mod tests {
+    mod get_receiver_report_blocks {
+        use crate::rtcp::{get_receiver_report_blocks, ReportBlock};
+
+        #[test]
+        fn reads_report_block() {
+            let receiver_report = [
+                0b1000_0001,
+                201,
+                0,
+                7,
+                0,
+                0,
+                0,
+                1,
+                // Report block
+                0,
+                0,
+                0,
+                2,
+                26,
+                0xFF,
+                0xFF,
+                0xFE,
+                0,
+                1,
+                0,
+                10,
+                0,
+                0,
+                0,
+                30,
+                0,
+                0,
+                0,
+                4,
+                0,
+                0,
+                0,
+                5,
+            ];
+
+            assert_eq!(
+                get_receiver_report_blocks(&receiver_report),
+                vec![ReportBlock {
+                    ssrc: 2,
+                    fraction_lost: 26,
+                    cumulative_lost: -2,
+                    extended_highest_sequence: 65546,
+                    jitter: 30,
+                    last_sender_report: 4,
+                    delay_since_last_sender_report: 5,
+                }]
+            );
+        }
+    }
+
+    mod get_remb_bitrate {
+        use crate::rtcp::get_remb_bitrate;
+
+        #[test]
+        fn reads_bitrate_from_compound_packet() {
+            let receiver_report = [0b1000_0000, 201, 0, 1, 0, 0, 0, 1];
+            let remb = [
+                0b1000_1111,
+                206,
+                0,
+                4,
+                0,
+                0,
+                0,
+                1,
+                0,
+                0,
+                0,
+                0,
+                b'R',
+                b'E',
+                b'M',
+                b'B',
+                // 1 SSRC, exponent 2, mantissa 250000
+                1,
+                0b0000_1011,
+                0xD0,
+                0x90,
+            ];
+            let packet = [&receiver_report[..], &remb[..]].concat();
+
+            assert_eq!(get_remb_bitrate(&packet), Some(1_000_000));
+        }
+
+        #[test]
+        fn ignores_other_feedback() {
+            let pli = [0b1000_0001, 206, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2];
+
+            assert_eq!(get_remb_bitrate(&pli), None);
+        }
+    }
+
+    mod get_rtcp_packet_types {
+        use crate::rtcp::{get_rtcp_packet_types, RTCPPacketType};
+
+        #[test]
+        fn lists_compound_packet_types() {
+            let receiver_report = [0b1000_0000, 201, 0, 1, 0, 0, 0, 1];
+            let bye = [0b1000_0001, 203, 0, 1, 0, 0, 0, 1];
+            let packet = [&receiver_report[..], &bye[..]].concat();
+
+            assert_eq!(
+                get_rtcp_packet_types(&packet),
+                vec![RTCPPacketType::ReceiverReport, RTCPPacketType::Bye]
+            );
+        }
+    }
+
+    mod get_bye_ssrcs {
+        use crate::rtcp::{create_bye_packet, get_bye_ssrcs};
+
+        #[test]
+        fn collects_sources_of_compound_packet() {
+            let receiver_report = [0b1000_0000, 201, 0, 1, 0, 0, 0, 1];
+            let packet = create_bye_packet(None, &[0x01020304, 0x0A0B0C0D]);
+
+            assert_eq!(get_bye_ssrcs(&packet), vec![0x01020304, 0x0A0B0C0D]);
+            assert_eq!(get_bye_ssrcs(&receiver_report), Vec::<u32>::new());
+        }
+    }
+
+    mod create_bye_packet {
+        use crate::rtcp::{
+            create_bye_packet, create_sender_report_packet, get_rtcp_packet_types, RTCPPacketType,
+        };
+        use crate::sender_report::SenderReport;
+
+        #[test]
+        fn follows_sender_report() {
+            let report = SenderReport {
+                ssrc: 0x01020304,
+                ntp_timestamp: 1,
+                rtp_timestamp: 2,
+                packet_count: 3,
+                octet_count: 4,
+            };
+            let packet = create_bye_packet(Some(&report), &[0x01020304, 0x0A0B0C0D]);
+
+            assert_eq!(
+                get_rtcp_packet_types(&packet),
+                vec![RTCPPacketType::SenderReport, RTCPPacketType::Bye]
+            );
+            assert_eq!(packet[..28], create_sender_report_packet(&report));
+            assert_eq!(packet.len(), 28 + 12);
+        }
+
+        #[test]
+        fn follows_empty_receiver_report_without_media_sent() {
+            let packet = create_bye_packet(None, &[0x01020304, 0x0A0B0C0D]);
+
+            assert_eq!(
+                packet,
+                vec![
+                    0b1000_0000,
+                    201,
+                    0,
+                    1,
+                    0x01,
+                    0x02,
+                    0x03,
+                    0x04,
+                    0b1000_0010,
+                    203,
+                    0,
+                    2,
+                    0x01,
+                    0x02,
+                    0x03,
+                    0x04,
+                    0x0A,
+                    0x0B,
+                    0x0C,
+                    0x0D
+                ]
+            );
+        }
+    }
+
+    mod create_nack_packet {
+        use crate::rtcp::create_nack_packet;
+
+        #[test]
+        fn packs_lost_packets_into_pid_and_bitmask() {
+            let packet = create_nack_packet(1, 2, &[65534, 0, 14, 100]);
+
+            assert_eq!(
+                packet,
+                vec![
+                    0b1000_0001,
+                    205,
+                    0,
+                    4,
+                    0,
+                    0,
+                    0,
+                    1,
+                    0,
+                    0,
+                    0,
+                    2,
+                    // PID 65534 followed by 0 and 14
+                    0xFF,
+                    0xFE,
+                    0b1000_0000,
+                    0b0000_0010,
+                    // PID 100
+                    0,
+                    100,
+                    0,
+                    0
+                ]
+            );
+        }
+    }
+    mod create_pli_packet {
+        use crate::rtcp::{create_pli_packet, get_remb_bitrate};
+
+        #[test]
+        fn writes_header_and_sources() {
+            let packet = create_pli_packet(1, 2);
+
+            assert_eq!(packet, vec![0b1000_0001, 206, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2]);
+            assert_eq!(get_remb_bitrate(&packet), None);
+        }
+    }
+    mod create_fir_packet {
+        use crate::rtcp::{create_fir_packet, get_remb_bitrate};
+
+        #[test]
+        fn writes_header_and_request_entry() {
+            let packet = create_fir_packet(1, 2, 7);
+
+            assert_eq!(
+                packet,
+                vec![
+                    0b1000_0100,
+                    206,
+                    0,
+                    4,
+                    0,
+                    0,
+                    0,
+                    1,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                    2,
+                    7,
+                    0,
+                    0,
+                    0
+                ]
+            );
+            assert_eq!(get_remb_bitrate(&packet), None);
+        }
+    }
+    mod create_remb_packet {
+        use byteorder::{ByteOrder, NetworkEndian};
+
+        use crate::rtcp::{create_remb_packet, get_remb_bitrate};
+
+        #[test]
+        fn writes_bitrate_and_sources() {
+            let packet = create_remb_packet(1, 1_000_000, &[2, 3]);
+
+            assert_eq!(packet.len(), 28);
+            assert_eq!(NetworkEndian::read_u16(&packet[2..4]), 6);
+            assert_eq!(packet[20..28], [0, 0, 0, 2, 0, 0, 0, 3]);
+            assert_eq!(get_remb_bitrate(&packet), Some(1_000_000));
+        }
+
+        #[test]
+        fn rounds_down_bitrate_exceeding_mantissa() {
+            // 2^18 + 1 needs an exponent of 1, losing the lowest bit
+            let packet = create_remb_packet(1, 262_145, &[2]);
+
+            assert_eq!(get_remb_bitrate(&packet), Some(262_144));
+        }
+    }
+}
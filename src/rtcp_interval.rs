@@ -0,0 +1,283 @@
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+
+/** How often shards check whether RTCP reports of their peers are due.
+ */
+pub const REPORT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/** Share of the session bandwidth taken up by RTCP.
+ */
+const RTCP_BANDWIDTH_FRACTION: f64 = 0.05;
+
+/** Share of the RTCP bandwidth given to senders, when they are few.
+ */
+const SENDER_BANDWIDTH_FRACTION: f64 = 0.25;
+const RECEIVER_BANDWIDTH_FRACTION: f64 = 1.0 - SENDER_BANDWIDTH_FRACTION;
+
+/** Makes up for timer reconsideration pushing the average interval above the computed one.
+ */
+const COMPENSATION: f64 = std::f64::consts::E - 1.5;
+
+/** RFC 3550 suggests 5 seconds, too long for viewers to wait for a Sender Report to sync audio
+ * and video. Browsers send their own reports every second, so the server does the same.
+ */
+const MIN_REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/** Expected size of the first report, before the average of the sent ones is known.
+ */
+const INITIAL_AVERAGE_REPORT_SIZE: f64 = 100.0;
+
+/** Participants and bandwidth of an RTP session, as seen by the server.
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct RTCPSessionState {
+    pub members: usize,
+    pub senders: usize,
+    pub we_sent: bool,
+    pub bandwidth_bps: u64,
+}
+
+impl RTCPSessionState {
+    /** A session between the server and a single peer, where only one side sends media.
+     */
+    pub fn point_to_point(we_sent: bool, bandwidth_bps: u64) -> Self {
+        RTCPSessionState {
+            members: 2,
+            senders: 1,
+            we_sent,
+            bandwidth_bps,
+        }
+    }
+}
+
+/** Deterministic RTCP interval of RFC 3550 section 6.3.1, before randomization.
+ */
+pub fn get_deterministic_interval(
+    session: &RTCPSessionState,
+    average_report_size: f64,
+    initial: bool,
+) -> Duration {
+    let min_interval = if initial {
+        MIN_REPORT_INTERVAL / 2
+    } else {
+        MIN_REPORT_INTERVAL
+    };
+
+    // Bytes per second
+    let mut rtcp_bandwidth = session.bandwidth_bps as f64 / 8.0 * RTCP_BANDWIDTH_FRACTION;
+    let mut members = session.members;
+
+    // Few senders get a dedicated share, so their reports aren't drowned out by receivers
+    if session.senders as f64 <= session.members as f64 * SENDER_BANDWIDTH_FRACTION {
+        if session.we_sent {
+            rtcp_bandwidth *= SENDER_BANDWIDTH_FRACTION;
+            members = session.senders;
+        } else {
+            rtcp_bandwidth *= RECEIVER_BANDWIDTH_FRACTION;
+            members = session.members - session.senders;
+        }
+    }
+
+    // Without media there is no bandwidth to share, stick to the minimum
+    if rtcp_bandwidth <= 0.0 {
+        return min_interval;
+    }
+
+    Duration::from_secs_f64(average_report_size * members as f64 / rtcp_bandwidth).max(min_interval)
+}
+
+/** Randomized RTCP interval, spreading the reports of participants that joined together.
+ */
+pub fn get_randomized_interval(
+    session: &RTCPSessionState,
+    average_report_size: f64,
+    initial: bool,
+    rng: &mut impl Rng,
+) -> Duration {
+    get_deterministic_interval(session, average_report_size, initial)
+        .mul_f64(rng.gen_range(0.5..1.5) / COMPENSATION)
+}
+
+/** Paces the RTCP reports sent to a single peer.
+ * Due reports are reconsidered against the current session state, and postponed if the interval
+ * grew in the meantime.
+ */
+#[derive(Debug)]
+pub struct RTCPScheduler {
+    previous_report: Instant,
+    next_report: Instant,
+    average_report_size: f64,
+    initial: bool,
+}
+
+impl RTCPScheduler {
+    pub fn new(now: Instant) -> Self {
+        RTCPScheduler {
+            previous_report: now,
+            next_report: now,
+            average_report_size: INITIAL_AVERAGE_REPORT_SIZE,
+            initial: true,
+        }
+    }
+
+    /** Whether a report should be sent at `now`.
+     */
+    pub fn is_report_due(
+        &mut self,
+        session: &RTCPSessionState,
+        now: Instant,
+        rng: &mut impl Rng,
+    ) -> bool {
+        if now < self.next_report {
+            return false;
+        }
+
+        let interval =
+            get_randomized_interval(session, self.average_report_size, self.initial, rng);
+        let next_report = self.previous_report + interval;
+        if next_report <= now {
+            true
+        } else {
+            self.next_report = next_report;
+            false
+        }
+    }
+
+    /** Registers a report of `report_size` bytes sent at `now` and schedules the next one.
+     */
+    pub fn register_report(
+        &mut self,
+        report_size: usize,
+        session: &RTCPSessionState,
+        now: Instant,
+        rng: &mut impl Rng,
+    ) {
+        self.average_report_size =
+            report_size as f64 / 16.0 + self.average_report_size * 15.0 / 16.0;
+        self.initial = false;
+        self.previous_report = now;
+        self.next_report =
+            now + get_randomized_interval(session, self.average_report_size, self.initial, rng);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod get_deterministic_interval {
+        use crate::rtcp_interval::{
+            get_deterministic_interval, RTCPSessionState, MIN_REPORT_INTERVAL,
+        };
+
+        fn get_receiver_session(members: usize) -> RTCPSessionState {
+            RTCPSessionState {
+                members,
+                senders: 1,
+                we_sent: false,
+                bandwidth_bps: 100_000,
+            }
+        }
+
+        #[test]
+        fn grows_with_number_of_participants() {
+            let intervals = [2, 10, 100, 1000].map(|members| {
+                get_deterministic_interval(&get_receiver_session(members), 100.0, false)
+            });
+
+            assert_eq!(intervals[0], MIN_REPORT_INTERVAL);
+            assert!(intervals.windows(2).all(|pair| pair[0] < pair[1]));
+            // 625 B/s for RTCP, 75% of which is shared by 999 receivers
+            assert!((intervals[3].as_secs_f64() - 213.12).abs() < 1e-6);
+        }
+
+        #[test]
+        fn gives_senders_their_own_share() {
+            let session = RTCPSessionState {
+                members: 100,
+                senders: 2,
+                we_sent: true,
+                bandwidth_bps: 10_000,
+            };
+
+            // 62.5 B/s for RTCP, 25% of which is shared by 2 senders
+            let interval = get_deterministic_interval(&session, 100.0, false);
+
+            assert!((interval.as_secs_f64() - 12.8).abs() < 1e-6);
+        }
+
+        #[test]
+        fn halves_minimum_of_initial_report() {
+            let session = RTCPSessionState::point_to_point(true, 0);
+
+            assert_eq!(
+                get_deterministic_interval(&session, 100.0, true),
+                MIN_REPORT_INTERVAL / 2
+            );
+            assert_eq!(
+                get_deterministic_interval(&session, 100.0, false),
+                MIN_REPORT_INTERVAL
+            );
+        }
+    }
+
+    mod rtcp_scheduler {
+        use std::time::{Duration, Instant};
+
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        use crate::rtcp_interval::{
+            get_randomized_interval, RTCPScheduler, RTCPSessionState, COMPENSATION,
+            MIN_REPORT_INTERVAL,
+        };
+
+        #[test]
+        fn randomizes_interval_around_deterministic_one() {
+            let mut rng = StdRng::seed_from_u64(0);
+            let session = RTCPSessionState::point_to_point(true, 2_000_000);
+
+            for _ in 0..100 {
+                let interval = get_randomized_interval(&session, 100.0, false, &mut rng);
+
+                assert!(interval >= MIN_REPORT_INTERVAL.mul_f64(0.5 / COMPENSATION));
+                assert!(interval < MIN_REPORT_INTERVAL.mul_f64(1.5 / COMPENSATION));
+            }
+        }
+
+        #[test]
+        fn sends_reports_once_interval_elapses() {
+            let mut rng = StdRng::seed_from_u64(0);
+            let session = RTCPSessionState::point_to_point(true, 2_000_000);
+            let now = Instant::now();
+            let mut scheduler = RTCPScheduler::new(now);
+
+            assert!(!scheduler.is_report_due(&session, now, &mut rng));
+            assert!(scheduler.is_report_due(&session, now + Duration::from_secs(1), &mut rng));
+
+            let now = now + Duration::from_secs(1);
+            scheduler.register_report(120, &session, now, &mut rng);
+
+            assert!(!scheduler.is_report_due(&session, now, &mut rng));
+            assert!(scheduler.is_report_due(&session, now + Duration::from_secs(2), &mut rng));
+        }
+
+        #[test]
+        fn postpones_reports_when_session_grows() {
+            let mut rng = StdRng::seed_from_u64(0);
+            let session = RTCPSessionState::point_to_point(false, 100_000);
+            let now = Instant::now();
+            let mut scheduler = RTCPScheduler::new(now);
+            scheduler.register_report(100, &session, now, &mut rng);
+
+            let grown_session = RTCPSessionState {
+                members: 1000,
+                ..session
+            };
+            let later = now + Duration::from_secs(2);
+
+            assert!(scheduler.is_report_due(&session, later, &mut rng));
+            assert!(!scheduler.is_report_due(&grown_session, later, &mut rng));
+            assert!(!scheduler.is_report_due(&session, later, &mut rng));
+        }
+    }
+}
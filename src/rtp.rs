@@ -1,3 +1,6 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
 use byteorder::{ByteOrder, NetworkEndian};
 
 use sdp::NegotiatedSession;
@@ -16,17 +19,20 @@ https://datatracker.ietf.org/doc/html/rfc3550#section-5.1
 |            contributing source (CSRC) identifiers             |
 |                             ....                              |
 +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+Returns false, leaving the packet untouched, if the viewer doesn't receive the packet's track
+or the packet is too short to hold an RTP header.
 */
 pub fn remap_rtp_header(
     buffer: &mut [u8],
     streamer_session: &NegotiatedSession,
     viewer_session: &NegotiatedSession,
-) {
-    let mapped_header = get_mapped_header(
-        get_rtp_header_data(buffer),
-        streamer_session,
-        viewer_session,
-    );
+) -> bool {
+    let mapped_header = match get_rtp_header_data(buffer)
+        .and_then(|header| get_mapped_header(header, streamer_session, viewer_session))
+    {
+        Some(mapped_header) => mapped_header,
+        None => return false,
+    };
 
     // Second byte contains for Marker & PayloadType fields.
     // Marker is the most significant bit, the rest 7-bits make for the payload number;
@@ -42,44 +48,531 @@ pub fn remap_rtp_header(
 
     // Replace SSRC bits with new ssrc value
     NetworkEndian::write_u32(&mut buffer[8..12], mapped_header.ssrc);
+    true
 }
 
 fn get_mapped_header(
     original_header: RTPHeader,
     streamer_session: &NegotiatedSession,
     viewer_session: &NegotiatedSession,
-) -> RTPHeader {
+) -> Option<RTPHeader> {
     if streamer_session.audio_session.payload_number == original_header.payload_type as usize {
-        RTPHeader {
+        return Some(RTPHeader {
             ssrc: viewer_session.audio_session.host_ssrc,
             payload_type: viewer_session.audio_session.payload_number as u8,
             marker_set: original_header.marker_set,
+        });
+    }
+
+    // Extra video tracks, e.g. a screen share, are told apart from the primary one by SSRC
+    let video_session = match streamer_session.get_extra_video_index(original_header.ssrc) {
+        Some(index) => viewer_session.extra_video_sessions.get(index)?,
+        None => &viewer_session.video_session,
+    };
+    Some(RTPHeader {
+        ssrc: video_session.host_ssrc,
+        payload_type: video_session.payload_number as u8,
+        marker_set: original_header.marker_set,
+    })
+}
+
+/** Rebases the RTP timestamps of a viewer's streams onto a common origin, set by the first packet forwarded
+ * to the viewer. Each stream starts at the ticks elapsed since that origin, so that audio and video begin
+ * aligned no matter where the streamer's timelines are. Offsets wrap around along with the timestamps.
+ */
+pub struct TimestampRebaser {
+    origin: Option<Instant>,
+    offsets: HashMap<u32, u32>,
+}
+
+impl TimestampRebaser {
+    pub fn new() -> Self {
+        TimestampRebaser {
+            origin: None,
+            offsets: HashMap::new(),
         }
-    } else {
-        RTPHeader {
-            ssrc: viewer_session.video_session.host_ssrc,
-            payload_type: viewer_session.video_session.payload_number as u8,
-            marker_set: original_header.marker_set,
+    }
+
+    /** Rewrites the timestamp of a remapped packet, returning the rebased value.
+     * The offset of a stream is fixed by its first packet.
+     */
+    pub fn rebase(&mut self, buffer: &mut [u8], clock_rate: u32, now: Instant) -> Option<u32> {
+        let ssrc = get_rtp_header_data(buffer)?.ssrc;
+        let timestamp = get_rtp_timestamp(buffer);
+        let origin = *self.origin.get_or_insert(now);
+
+        let offset = *self.offsets.entry(ssrc).or_insert_with(|| {
+            let elapsed_ticks = now.duration_since(origin).as_secs_f64() * clock_rate as f64;
+            (elapsed_ticks as u32).wrapping_sub(timestamp)
+        });
+        let rebased_timestamp = timestamp.wrapping_add(offset);
+        NetworkEndian::write_u32(&mut buffer[4..8], rebased_timestamp);
+        Some(rebased_timestamp)
+    }
+}
+
+/** Stamps the packets forwarded to a viewer with transport-wide sequence numbers, used by the
+ * viewer's congestion control. The 16-bit counter is shared by all stamped packets and wraps
+ * around.
+https://datatracker.ietf.org/doc/html/draft-holmer-rmcat-transport-wide-cc-extensions-01
+ */
+pub struct TransportSequencer {
+    next_sequence_number: u16,
+}
+
+impl TransportSequencer {
+    pub fn new() -> Self {
+        TransportSequencer {
+            next_sequence_number: 0,
         }
     }
+
+    /** Replaces the header extension of a remapped packet with the transport-wide sequence
+     * number, returning the stamped value. Extensions forwarded from the streamer use IDs
+     * negotiated with the streamer, which mean nothing to the viewer.
+     */
+    pub fn stamp(&mut self, buffer: &mut Vec<u8>, extension_id: u8) -> Option<u16> {
+        let csrc_count = (buffer.first()? & 0b0000_1111) as usize;
+        let header_length = 12 + csrc_count * 4;
+        let payload_offset = get_rtp_payload_offset(buffer)?;
+
+        let sequence_number = self.next_sequence_number;
+        self.next_sequence_number = sequence_number.wrapping_add(1);
+
+        // One-byte header with a single 2-byte element, padded to a 32-bit word
+        let mut extension = [0u8; 8];
+        NetworkEndian::write_u16(&mut extension[0..2], ONE_BYTE_EXTENSION_PROFILE);
+        NetworkEndian::write_u16(&mut extension[2..4], 1);
+        extension[4] = (extension_id << 4) | 1;
+        NetworkEndian::write_u16(&mut extension[5..7], sequence_number);
+
+        buffer.splice(header_length..payload_offset, extension);
+        buffer[0] |= 0b0001_0000;
+        Some(sequence_number)
+    }
 }
 
 // todo We could use a common struct (like RTPPacket from thumbnail_image_extractor) for this.
 pub struct RTPHeader {
     marker_set: bool,
     pub payload_type: u8,
-    ssrc: u32,
+    pub ssrc: u32,
 }
-pub fn get_rtp_header_data(buffer: &[u8]) -> RTPHeader {
-    let first_byte = buffer[1];
+/** Reads the fixed RTP header, none if the buffer is too short to hold one.
+ */
+pub fn get_rtp_header_data(buffer: &[u8]) -> Option<RTPHeader> {
+    let first_byte = *buffer.get(1)?;
 
     let marker_set = (first_byte & 0b1000_0000) == 0b1000_0000;
     let payload_type = first_byte & 0b0111_1111;
-    let ssrc = NetworkEndian::read_u32(&buffer[8..12]);
+    let ssrc = NetworkEndian::read_u32(buffer.get(8..12)?);
 
-    RTPHeader {
+    Some(RTPHeader {
         payload_type,
         marker_set,
         ssrc,
+    })
+}
+
+pub fn get_rtp_sequence_number(buffer: &[u8]) -> u16 {
+    NetworkEndian::read_u16(&buffer[2..4])
+}
+
+pub fn get_rtp_timestamp(buffer: &[u8]) -> u32 {
+    NetworkEndian::read_u32(&buffer[4..8])
+}
+
+/** Offset of the RTP payload, accounting for CSRC identifiers and the header extension.
+ */
+pub fn get_rtp_payload_offset(buffer: &[u8]) -> Option<usize> {
+    let first_byte = *buffer.first()?;
+    let csrc_count = (first_byte & 0b0000_1111) as usize;
+    let has_extension = (first_byte & 0b0001_0000) != 0;
+
+    let mut offset = 12 + csrc_count * 4;
+    if has_extension {
+        let extension_length = NetworkEndian::read_u16(buffer.get(offset + 2..offset + 4)?);
+        offset += 4 + extension_length as usize * 4;
+    }
+
+    (offset < buffer.len()).then_some(offset)
+}
+
+const ONE_BYTE_EXTENSION_PROFILE: u16 = 0xBEDE;
+const TWO_BYTE_EXTENSION_PROFILE: u16 = 0x1000;
+// One-byte header ID reserved to stop processing the extension
+const ONE_BYTE_EXTENSION_STOP_ID: u8 = 15;
+
+/** Value of the header extension element with the given ID, in either the one-byte or two-byte format.
+https://datatracker.ietf.org/doc/html/rfc8285#section-4
+ */
+pub fn get_rtp_header_extension(buffer: &[u8], id: u8) -> Option<&[u8]> {
+    let first_byte = *buffer.first()?;
+    if (first_byte & 0b0001_0000) == 0 {
+        return None;
+    }
+    let csrc_count = (first_byte & 0b0000_1111) as usize;
+    let offset = 12 + csrc_count * 4;
+
+    let profile = NetworkEndian::read_u16(buffer.get(offset..offset + 2)?);
+    let extension_length = NetworkEndian::read_u16(buffer.get(offset + 2..offset + 4)?) as usize;
+    let elements = buffer.get(offset + 4..offset + 4 + extension_length * 4)?;
+    let element_header_size = match profile {
+        ONE_BYTE_EXTENSION_PROFILE => 1,
+        // The lower 4 bits are application specific
+        profile if profile & 0xFFF0 == TWO_BYTE_EXTENSION_PROFILE => 2,
+        _ => return None,
+    };
+
+    let mut position = 0;
+    while let Some(&element_id) = elements.get(position) {
+        // Padding between elements
+        if element_id == 0 {
+            position += 1;
+            continue;
+        }
+        let (element_id, length) = match element_header_size {
+            1 if element_id >> 4 == ONE_BYTE_EXTENSION_STOP_ID => return None,
+            1 => (element_id >> 4, (element_id & 0b0000_1111) as usize + 1),
+            _ => (element_id, *elements.get(position + 1)? as usize),
+        };
+
+        let value_offset = position + element_header_size;
+        let value = elements.get(value_offset..value_offset + length)?;
+        if element_id == id {
+            return Some(value);
+        }
+        position = value_offset + length;
+    }
+
+    None
+}
+
+/** 24-bit abs-send-time of a packet, seconds in 6.18 fixed point wrapping every 64 seconds.
+https://webrtc.googlesource.com/src/+/refs/heads/main/docs/native-code/rtp-hdrext/abs-send-time
+ */
+pub fn get_abs_send_time(buffer: &[u8], id: u8) -> Option<u32> {
+    let value = get_rtp_header_extension(buffer, id)?;
+    (value.len() == 3).then(|| NetworkEndian::read_u24(value))
+}
+
+const NAL_TYPE_IDR: u8 = 5;
+const NAL_TYPE_SPS: u8 = 7;
+const NAL_TYPE_PPS: u8 = 8;
+const NAL_TYPE_STAP_A: u8 = 24;
+const NAL_TYPE_FU_A: u8 = 28;
+
+/** Whether an H264 RTP packet carries keyframe data (IDR slice or parameter sets).
+https://datatracker.ietf.org/doc/html/rfc6184#section-5.3
+ */
+pub fn is_keyframe_packet(buffer: &[u8]) -> bool {
+    let payload = match get_rtp_payload_offset(buffer) {
+        Some(offset) => &buffer[offset..],
+        None => return false,
+    };
+    let is_keyframe_nal = |nal_type: u8| {
+        nal_type == NAL_TYPE_IDR || nal_type == NAL_TYPE_SPS || nal_type == NAL_TYPE_PPS
+    };
+
+    match payload[0] & 0b0001_1111 {
+        NAL_TYPE_FU_A => payload
+            .get(1)
+            .is_some_and(|fu_header| is_keyframe_nal(fu_header & 0b0001_1111)),
+        NAL_TYPE_STAP_A => {
+            // Aggregated NAL units, each prefixed with 16-bit size
+            let mut offset = 1;
+            while let Some(size_bytes) = payload.get(offset..offset + 2) {
+                let nal_size = NetworkEndian::read_u16(size_bytes) as usize;
+                match payload.get(offset + 2) {
+                    Some(nal_header) if is_keyframe_nal(nal_header & 0b0001_1111) => return true,
+                    Some(_) => offset += 2 + nal_size,
+                    None => break,
+                }
+            }
+            false
+        }
+        nal_type => is_keyframe_nal(nal_type),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod timestamp_rebaser {
+        use std::time::{Duration, Instant};
+
+        use byteorder::{ByteOrder, NetworkEndian};
+
+        use crate::rtp::{get_rtp_timestamp, TimestampRebaser};
+
+        const AUDIO_SSRC: u32 = 1;
+        const VIDEO_SSRC: u32 = 2;
+        const AUDIO_CLOCK_RATE: u32 = 48000;
+        const VIDEO_CLOCK_RATE: u32 = 90000;
+
+        fn get_packet(ssrc: u32, timestamp: u32) -> Vec<u8> {
+            let mut packet = vec![0u8; 13];
+            packet[0] = 0b1000_0000;
+            NetworkEndian::write_u32(&mut packet[4..8], timestamp);
+            NetworkEndian::write_u32(&mut packet[8..12], ssrc);
+            packet
+        }
+
+        #[test]
+        fn aligns_first_audio_and_video_timestamps() {
+            let mut rebaser = TimestampRebaser::new();
+            let origin = Instant::now();
+            let mut audio_packet = get_packet(AUDIO_SSRC, 3_000_000_000);
+            let mut video_packet = get_packet(VIDEO_SSRC, 12_345);
+
+            let audio_timestamp = rebaser.rebase(&mut audio_packet, AUDIO_CLOCK_RATE, origin);
+            let video_timestamp = rebaser.rebase(
+                &mut video_packet,
+                VIDEO_CLOCK_RATE,
+                origin + Duration::from_millis(20),
+            );
+
+            assert_eq!(audio_timestamp, Some(0));
+            assert_eq!(video_timestamp, Some(1800));
+            assert_eq!(get_rtp_timestamp(&audio_packet), 0);
+            assert_eq!(get_rtp_timestamp(&video_packet), 1800);
+        }
+
+        #[test]
+        fn keeps_stream_offset_across_wraparound() {
+            let mut rebaser = TimestampRebaser::new();
+            let now = Instant::now();
+
+            let first_timestamp = rebaser.rebase(
+                &mut get_packet(AUDIO_SSRC, u32::MAX - 99),
+                AUDIO_CLOCK_RATE,
+                now,
+            );
+            let wrapped_timestamp = rebaser.rebase(
+                &mut get_packet(AUDIO_SSRC, 860),
+                AUDIO_CLOCK_RATE,
+                now + Duration::from_millis(20),
+            );
+
+            assert_eq!(first_timestamp, Some(0));
+            assert_eq!(wrapped_timestamp, Some(960));
+        }
+    }
+
+    mod transport_sequencer {
+        use byteorder::{ByteOrder, NetworkEndian};
+
+        use crate::rtp::{get_rtp_header_extension, get_rtp_payload_offset, TransportSequencer};
+
+        const EXTENSION_ID: u8 = 5;
+        const PAYLOAD: [u8; 4] = [1, 2, 3, 4];
+
+        fn get_packet() -> Vec<u8> {
+            let mut packet = vec![0u8; 12];
+            packet[0] = 0b1000_0000;
+            packet.extend_from_slice(&PAYLOAD);
+            packet
+        }
+
+        fn get_packet_with_extension() -> Vec<u8> {
+            let mut packet = vec![0u8; 12];
+            packet[0] = 0b1001_0000;
+            // abs-send-time with the streamer's ID
+            packet.extend_from_slice(&[0xBE, 0xDE, 0x00, 0x01, 0x32, 0xAA, 0xBB, 0xCC]);
+            packet.extend_from_slice(&PAYLOAD);
+            packet
+        }
+
+        fn read_sequence_number(packet: &[u8]) -> u16 {
+            let value = get_rtp_header_extension(packet, EXTENSION_ID)
+                .expect("Should carry transport-wide sequence number");
+            NetworkEndian::read_u16(value)
+        }
+
+        #[test]
+        fn stamps_incrementing_sequence_numbers() {
+            let mut sequencer = TransportSequencer::new();
+
+            let sequence_numbers: Vec<u16> = (0..3)
+                .map(|_| {
+                    let mut packet = get_packet();
+                    sequencer.stamp(&mut packet, EXTENSION_ID);
+                    read_sequence_number(&packet)
+                })
+                .collect();
+
+            assert_eq!(sequence_numbers, vec![0, 1, 2]);
+        }
+
+        #[test]
+        fn wraps_around_sequence_number() {
+            let mut sequencer = TransportSequencer::new();
+            sequencer.next_sequence_number = u16::MAX;
+
+            assert_eq!(
+                sequencer.stamp(&mut get_packet(), EXTENSION_ID),
+                Some(u16::MAX)
+            );
+            assert_eq!(sequencer.stamp(&mut get_packet(), EXTENSION_ID), Some(0));
+        }
+
+        #[test]
+        fn replaces_forwarded_extension() {
+            let mut sequencer = TransportSequencer::new();
+            let mut packet = get_packet_with_extension();
+
+            sequencer.stamp(&mut packet, EXTENSION_ID);
+
+            assert_eq!(read_sequence_number(&packet), 0);
+            assert_eq!(get_rtp_header_extension(&packet, 3), None);
+            let payload_offset = get_rtp_payload_offset(&packet).expect("Should have payload");
+            assert_eq!(&packet[payload_offset..], &PAYLOAD);
+        }
+    }
+
+    mod remap_rtp_header {
+        use std::collections::HashSet;
+
+        use byteorder::{ByteOrder, NetworkEndian};
+
+        use sdp::{
+            AudioCodec, AudioSession, ICECredentials, NegotiatedSession, NegotiatedSessionBuilder,
+            VideoCodec, VideoSession,
+        };
+
+        use crate::rtp::{get_rtp_header_data, remap_rtp_header};
+
+        fn get_session(
+            audio_payload_number: usize,
+            video_payload_number: usize,
+        ) -> NegotiatedSession {
+            NegotiatedSessionBuilder::new()
+                .set_ice_credentials(ICECredentials {
+                    host_username: "host".to_string(),
+                    host_password: "host-password".to_string(),
+                    remote_username: "remote".to_string(),
+                    remote_password: "remote-password".to_string(),
+                })
+                .set_audio_session(AudioSession {
+                    codec: AudioCodec::Opus,
+                    payload_number: audio_payload_number,
+                    host_ssrc: audio_payload_number as u32,
+                    remote_ssrc: None,
+                    remote_cname: None,
+                })
+                .set_video_session(VideoSession {
+                    codec: VideoCodec::H264,
+                    payload_number: video_payload_number,
+                    host_ssrc: video_payload_number as u32,
+                    remote_ssrc: None,
+                    remote_cname: None,
+                    remote_rtx_ssrc: None,
+                    capabilities: HashSet::from(["packetization-mode=1".to_string()]),
+                    simulcast: None,
+                    abs_send_time_extension_id: None,
+                    transport_cc_extension_id: None,
+                    fir_supported: false,
+                })
+                .build()
+                .expect("Should build negotiated session")
+        }
+
+        fn get_packet(payload_type: u8, marker_set: bool) -> Vec<u8> {
+            let mut packet = vec![0u8; 13];
+            packet[0] = 0b1000_0000;
+            packet[1] = payload_type | if marker_set { 0b1000_0000 } else { 0 };
+            NetworkEndian::write_u32(&mut packet[8..12], 1);
+            packet
+        }
+
+        #[test]
+        fn rewrites_video_payload_type_to_viewer_one() {
+            let streamer_session = get_session(111, 96);
+            let viewer_session = get_session(109, 126);
+            let mut packet = get_packet(96, true);
+
+            remap_rtp_header(&mut packet, &streamer_session, &viewer_session);
+
+            let header = get_rtp_header_data(&packet).unwrap();
+            assert_eq!(header.payload_type, 126);
+            assert_eq!(header.ssrc, viewer_session.video_session.host_ssrc);
+            assert_eq!(packet[1] & 0b1000_0000, 0b1000_0000);
+        }
+
+        #[test]
+        fn rewrites_audio_payload_type_to_viewer_one() {
+            let streamer_session = get_session(111, 96);
+            let viewer_session = get_session(109, 126);
+            let mut packet = get_packet(111, false);
+
+            remap_rtp_header(&mut packet, &streamer_session, &viewer_session);
+
+            let header = get_rtp_header_data(&packet).unwrap();
+            assert_eq!(header.payload_type, 109);
+            assert_eq!(header.ssrc, viewer_session.audio_session.host_ssrc);
+            assert_eq!(packet[1] & 0b1000_0000, 0);
+        }
+
+        #[test]
+        fn maps_screen_share_by_ssrc() {
+            let mut streamer_session = get_session(111, 96);
+            let mut screen_share_session = streamer_session.video_session.clone();
+            screen_share_session.remote_ssrc = Some(7);
+            streamer_session
+                .extra_video_sessions
+                .push(screen_share_session);
+            let mut viewer_session = get_session(109, 126);
+            let mut viewer_screen_share_session = viewer_session.video_session.clone();
+            viewer_screen_share_session.host_ssrc = 300;
+            viewer_session
+                .extra_video_sessions
+                .push(viewer_screen_share_session);
+            let mut packet = get_packet(96, false);
+            NetworkEndian::write_u32(&mut packet[8..12], 7);
+
+            assert!(remap_rtp_header(
+                &mut packet,
+                &streamer_session,
+                &viewer_session
+            ));
+
+            let header = get_rtp_header_data(&packet).unwrap();
+            assert_eq!(header.payload_type, 126);
+            assert_eq!(header.ssrc, 300);
+        }
+
+        #[test]
+        fn skips_screen_share_of_single_track_viewer() {
+            let mut streamer_session = get_session(111, 96);
+            let mut screen_share_session = streamer_session.video_session.clone();
+            screen_share_session.remote_ssrc = Some(7);
+            streamer_session
+                .extra_video_sessions
+                .push(screen_share_session);
+            let viewer_session = get_session(109, 126);
+            let mut packet = get_packet(96, false);
+            NetworkEndian::write_u32(&mut packet[8..12], 7);
+
+            assert!(!remap_rtp_header(
+                &mut packet,
+                &streamer_session,
+                &viewer_session
+            ));
+            assert_eq!(get_rtp_header_data(&packet).unwrap().ssrc, 7);
+        }
+
+        #[test]
+        fn skips_packet_shorter_than_header() {
+            let streamer_session = get_session(111, 96);
+            let viewer_session = get_session(109, 126);
+            let mut packet = get_packet(96, false);
+            packet.truncate(11);
+
+            assert!(get_rtp_header_data(&packet).is_none());
+            assert!(!remap_rtp_header(
+                &mut packet,
+                &streamer_session,
+                &viewer_session
+            ));
+        }
     }
 }
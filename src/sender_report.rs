@@ -0,0 +1,136 @@
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use sdp::NegotiatedSession;
+
+/** Seconds between the NTP epoch (1900) and the UNIX epoch (1970).
+ */
+const NTP_UNIX_OFFSET_SECS: u64 = 2_208_988_800;
+
+/** Sending state of a single outbound SSRC, as needed for RTCP Sender Reports.
+ */
+#[derive(Debug)]
+pub struct OutboundStream {
+    pub ssrc: u32,
+    clock_rate: u32,
+    last_rtp_timestamp: u32,
+    last_packet_time: Instant,
+    packet_count: u32,
+    octet_count: u32,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SenderReport {
+    pub ssrc: u32,
+    pub ntp_timestamp: u64,
+    pub rtp_timestamp: u32,
+    pub packet_count: u32,
+    pub octet_count: u32,
+}
+
+impl OutboundStream {
+    pub fn new(ssrc: u32, clock_rate: u32, rtp_timestamp: u32, now: Instant) -> Self {
+        OutboundStream {
+            ssrc,
+            clock_rate,
+            last_rtp_timestamp: rtp_timestamp,
+            last_packet_time: now,
+            packet_count: 0,
+            octet_count: 0,
+        }
+    }
+
+    pub fn register_packet(&mut self, rtp_timestamp: u32, payload_length: usize, now: Instant) {
+        self.last_rtp_timestamp = rtp_timestamp;
+        self.last_packet_time = now;
+        self.packet_count = self.packet_count.wrapping_add(1);
+        self.octet_count = self.octet_count.wrapping_add(payload_length as u32);
+    }
+
+    /** Packets sent on this SSRC so far.
+     */
+    pub fn packet_count(&self) -> u32 {
+        self.packet_count
+    }
+
+    /** Maps the wallclock time of the report onto the RTP timeline of the last forwarded packet.
+     */
+    pub fn create_report(&self, now: Instant, wallclock: SystemTime) -> SenderReport {
+        let elapsed_ticks =
+            now.duration_since(self.last_packet_time).as_secs_f64() * self.clock_rate as f64;
+
+        SenderReport {
+            ssrc: self.ssrc,
+            ntp_timestamp: get_ntp_timestamp(wallclock),
+            rtp_timestamp: self
+                .last_rtp_timestamp
+                .wrapping_add(elapsed_ticks.round() as u32),
+            packet_count: self.packet_count,
+            octet_count: self.octet_count,
+        }
+    }
+}
+
+/** 64-bit NTP timestamp: seconds since 1900 in the upper half, fraction of a second in the lower half.
+ */
+pub fn get_ntp_timestamp(wallclock: SystemTime) -> u64 {
+    let since_unix_epoch = wallclock
+        .duration_since(UNIX_EPOCH)
+        .expect("System time should be past UNIX epoch");
+    let seconds = since_unix_epoch.as_secs() + NTP_UNIX_OFFSET_SECS;
+    let fraction = ((since_unix_epoch.subsec_nanos() as u64) << 32) / 1_000_000_000;
+
+    (seconds << 32) | fraction
+}
+
+/** RTP clock rate of the outbound SSRC, if it belongs to the given session.
+ */
+pub fn get_clock_rate(media_session: &NegotiatedSession, ssrc: u32) -> Option<u32> {
+    if let Some(video_session) = media_session
+        .get_video_sessions()
+        .find(|video_session| video_session.host_ssrc == ssrc)
+    {
+        return Some(video_session.codec.clock_rate());
+    }
+    if ssrc == media_session.audio_session.host_ssrc {
+        return Some(media_session.audio_session.codec.clock_rate());
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    mod outbound_stream {
+        use std::time::{Duration, Instant, SystemTime};
+
+        use crate::sender_report::OutboundStream;
+
+        #[test]
+        fn advances_rtp_timestamp_with_ntp_time() {
+            let start = Instant::now();
+            let wallclock = SystemTime::now();
+            let mut stream = OutboundStream::new(1, 90000, 1000, start);
+            stream.register_packet(3000, 1200, start);
+
+            let first_report = stream.create_report(start + Duration::from_secs(1), wallclock);
+            let second_report = stream.create_report(
+                start + Duration::from_secs(2),
+                wallclock + Duration::from_secs(1),
+            );
+
+            // One second in NTP units corresponds to 90000 ticks of video clock
+            assert_eq!(first_report.rtp_timestamp, 93000);
+            assert_eq!(
+                second_report.ntp_timestamp - first_report.ntp_timestamp,
+                1 << 32
+            );
+            assert_eq!(
+                second_report
+                    .rtp_timestamp
+                    .wrapping_sub(first_report.rtp_timestamp),
+                90000
+            );
+            assert_eq!(second_report.packet_count, 1);
+            assert_eq!(second_report.octet_count, 1200);
+        }
+    }
+}
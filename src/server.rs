@@ -1,58 +1,185 @@
-use std::io::Write;
-use std::net::{SocketAddr, UdpSocket};
-use std::time::Instant;
+use std::net::SocketAddr;
+use std::sync::mpsc::channel;
+use std::time::{Duration, Instant};
 
 use sdp::SDPResolver;
 
-use crate::client::{Client, ClientSslState};
-use crate::config::get_global_config;
-use crate::ice_registry::{ConnectionType, SessionRegistry};
-use crate::rtp::{get_rtp_header_data, remap_rtp_header};
+use crate::config::{get_global_config, get_reloadable_config, is_log_enabled, LogLevel};
+use crate::http::RenegotiationRejection;
+use crate::ice_registry::{ConnectionType, ResourceID, RoomID, SessionRegistry};
+use crate::shard::{ShardCommand, ShardRouter};
 use crate::stun::{create_stun_success, get_stun_packet, ICEStunMessageType};
+use crate::transport::MediaTransport;
 
+/** Upper bound on how long shutdown waits for shards to flush queued packets.
+ */
+const SHUTDOWN_DRAIN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/** Position of a bound UDP socket in the list of sockets shared by the server and the media shards.
+ */
+pub type SocketIndex = usize;
+
+/** Control plane of the UDP server: keeps the session registry and answers ICE checks.
+ * Media of nominated peers is handled by the shard owning their address.
+ */
 pub struct UDPServer {
     pub session_registry: SessionRegistry,
     pub sdp_resolver: SDPResolver,
-    inbound_buffer: Vec<u8>,
-    outbound_buffer: Vec<u8>,
-    socket: UdpSocket,
+    pub shard_router: ShardRouter,
+    transport: Box<dyn MediaTransport>,
 }
 
 impl UDPServer {
-    pub fn new(socket: UdpSocket) -> Self {
+    pub fn new(
+        transport: Box<dyn MediaTransport>,
+        shard_router: ShardRouter,
+        server_reflexive_address: Option<SocketAddr>,
+    ) -> Self {
         let config = get_global_config();
+        let mut sdp_resolver = SDPResolver::new(
+            &config.ssl_config.get_sdp_fingerprint(),
+            &config.udp_server_config.candidate_addresses,
+        );
+        if let Some(public_address) = server_reflexive_address {
+            sdp_resolver.add_server_reflexive_candidate(public_address);
+        }
+        sdp_resolver.set_ice_credential_lengths(
+            config.udp_server_config.ice_ufrag_length,
+            config.udp_server_config.ice_pwd_length,
+        );
+
+        let reloadable_config = get_reloadable_config();
+        let mut session_registry =
+            SessionRegistry::with_max_streamers(reloadable_config.max_streamers);
+        session_registry.set_max_viewers_per_room(reloadable_config.max_viewers_per_room);
+        UDPServer::with_resolver(transport, sdp_resolver, shard_router, session_registry)
+    }
+
+    /** Server answering ICE checks with an already configured resolver, independent of the global
+     * config.
+     */
+    pub fn with_resolver(
+        transport: Box<dyn MediaTransport>,
+        sdp_resolver: SDPResolver,
+        shard_router: ShardRouter,
+        session_registry: SessionRegistry,
+    ) -> Self {
         UDPServer {
-            sdp_resolver: SDPResolver::new(
-                format!("sha-256 {}", config.ssl_config.fingerprint).as_str(),
-                config.udp_server_config.address,
-            ),
-            inbound_buffer: Vec::with_capacity(2000),
-            outbound_buffer: Vec::with_capacity(2000),
-            socket,
-            session_registry: SessionRegistry::new(),
+            sdp_resolver,
+            shard_router,
+            transport,
+            session_registry,
+        }
+    }
+
+    /** Handles ICE checks and binding indications received on the socket at `socket_index`.
+     * Other STUN messages are ignored.
+     */
+    pub fn process_packet(&mut self, data: &[u8], remote: SocketAddr, socket_index: SocketIndex) {
+        if let Some(stun_packet) = get_stun_packet(data) {
+            self.handle_stun_packet(&remote, socket_index, stun_packet)
+        }
+    }
+
+    /** Renegotiates the stream of a streamer session, keeping its resource id and room.
+     * Shards get the updated session so that forwarded media is remapped to the new payload types.
+     */
+    pub fn renegotiate_streamer(
+        &mut self,
+        id: ResourceID,
+        sdp_offer: &str,
+        is_ice_restart: bool,
+    ) -> Result<String, RenegotiationRejection> {
+        let session = self
+            .session_registry
+            .get_session(id)
+            .ok_or(RenegotiationRejection::StreamerNotFound)?;
+        let room_id = match &session.connection_type {
+            ConnectionType::Streamer(streamer) => streamer.owned_room_id,
+            ConnectionType::Viewer(_) => return Err(RenegotiationRejection::StreamerNotFound),
+        };
+
+        let media_session = self
+            .sdp_resolver
+            .update_stream_offer_with_diagnostics(sdp_offer, &session.media_session, is_ice_restart)
+            .map_err(RenegotiationRejection::InvalidOffer)?;
+        let sdp_answer = String::from(media_session.sdp_answer.clone());
+
+        self.shard_router.broadcast(|| {
+            ShardCommand::UpdateStreamerSession(room_id, Box::new(media_session.clone()))
+        });
+        self.session_registry
+            .update_media_session(id, media_session);
+
+        Ok(sdp_answer)
+    }
+
+    pub fn remove_session(&mut self, id: ResourceID) {
+        if let Some(remote) = self.session_registry.remove_session(id) {
+            self.shard_router
+                .send(&remote, ShardCommand::RemovePeer(remote));
+        }
+    }
+
+    /** Terminates a room along with its streamer and viewers, peers get an RTCP BYE before being dropped.
+     * Returns false if the room doesn't exist.
+     */
+    pub fn terminate_room(&mut self, room_id: RoomID) -> bool {
+        let remotes = match self.session_registry.remove_room(room_id) {
+            Some(remotes) => remotes,
+            None => return false,
+        };
+
+        // Shards process commands of a peer in order, so the BYE is sent before the peer is dropped
+        for remote in remotes {
+            self.shard_router
+                .send(&remote, ShardCommand::SendBye(remote));
+            self.shard_router
+                .send(&remote, ShardCommand::RemovePeer(remote));
         }
+        true
     }
 
-    pub fn process_packet(&mut self, data: &[u8], remote: SocketAddr) {
-        self.inbound_buffer.clear();
-        self.inbound_buffer
-            .write_all(data)
-            .expect("Failed to write to internal buffer");
+    /** Sends RTCP BYE to every nominated peer and waits for shards to drain in-flight packets.
+     * Returns once all shards are drained or the drain timeout has passed.
+     */
+    pub fn shutdown(&self) {
+        let bye_count = enqueue_bye(&self.session_registry, &self.shard_router);
+        println!("Shutting down, sent BYE to {} peers", bye_count);
 
-        match get_stun_packet(&self.inbound_buffer) {
-            Some(stun_packet) => self.handle_stun_packet(&remote, stun_packet),
-            None => self.handle_other_packets(&remote),
+        let (ack_tx, ack_rx) = channel::<()>();
+        let shard_count = self.shard_router.shard_count();
+        for index in 0..shard_count {
+            self.shard_router
+                .send_to_shard(index, ShardCommand::Drain(ack_tx.clone()));
+        }
+
+        let deadline = Instant::now() + SHUTDOWN_DRAIN_TIMEOUT;
+        for _ in 0..shard_count {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if ack_rx.recv_timeout(remaining).is_err() {
+                eprintln!("Timed out waiting for media shards to drain");
+                return;
+            }
         }
     }
 
-    fn handle_stun_packet(&mut self, remote: &SocketAddr, stun_packet: ICEStunMessageType) {
+    fn handle_stun_packet(
+        &mut self,
+        remote: &SocketAddr,
+        socket_index: SocketIndex,
+        stun_packet: ICEStunMessageType,
+    ) {
         match stun_packet {
             ICEStunMessageType::LiveCheck(msg) => {
-                if let Some(session) = self
+                let resource_id = self
                     .session_registry
                     .get_session_by_username_mut(&msg.username_attribute)
-                {
-                    session.ttl = Instant::now();
+                    .map(|session| session.id);
+
+                if let Some(resource_id) = resource_id {
+                    self.revive_session(remote, socket_index, resource_id);
+                    let session = self.session_registry.get_session(resource_id).unwrap();
 
                     let mut buffer: [u8; 200] = [0; 200];
                     let bytes_written = create_stun_success(
@@ -64,7 +191,8 @@ impl UDPServer {
                     .expect("Failed to create STUN success response");
 
                     let output_buffer = &buffer[0..bytes_written];
-                    if let Err(error) = self.socket.send_to(output_buffer, remote) {
+                    if let Err(error) = self.transport.send_to(socket_index, output_buffer, *remote)
+                    {
                         eprintln!("Error writing to remote {}", error)
                     }
                 }
@@ -73,22 +201,21 @@ impl UDPServer {
                 if let Some(resource_id) = self
                     .session_registry
                     .get_session_by_username_mut(&msg.username_attribute)
-                    .map(|session| {
-                        session.ttl = Instant::now();
-                        session.id.clone()
-                    })
+                    .map(|session| session.id)
                 {
+                    self.revive_session(remote, socket_index, resource_id);
+
                     let is_new_client = self
                         .session_registry
                         .get_session_mut(resource_id)
-                        .map(|session| session.client.is_none())
+                        .map(|session| session.remote_address.is_none())
                         .unwrap();
 
+                    // Hand the peer over to its shard before the remote starts the DTLS handshake
                     if is_new_client {
-                        let client = Client::new(remote.clone(), self.socket.try_clone().unwrap())
-                            .expect("Should create a Client");
-
-                        self.session_registry.nominate_client(client, &resource_id);
+                        self.session_registry
+                            .nominate_address(*remote, &resource_id);
+                        self.bind_peer(remote, socket_index, resource_id);
                     }
 
                     let credentials = &self
@@ -105,120 +232,324 @@ impl UDPServer {
                             .expect("Should create STUN success response");
 
                     let output_buffer = &buffer[0..bytes_written];
-                    if let Err(error) = self.socket.send_to(output_buffer, remote) {
+                    if let Err(error) = self.transport.send_to(socket_index, output_buffer, *remote)
+                    {
                         eprintln!("Error writing to remote {}", error)
                     }
                 };
             }
+            // Keepalives of a nominated peer only refresh its session, no response is sent
+            ICEStunMessageType::Indication => {
+                if let Some(resource_id) = self
+                    .session_registry
+                    .get_session_by_address_mut(remote)
+                    .map(|session| session.id)
+                {
+                    self.revive_session(remote, socket_index, resource_id);
+                }
+            }
         }
     }
 
-    fn handle_other_packets(&mut self, remote: &SocketAddr) {
-        let sender_session = self.session_registry.get_session_by_address_mut(remote);
+    /** Refreshes the session, moving its peer to the new address if a stale session resumed elsewhere.
+     */
+    fn revive_session(
+        &mut self,
+        remote: &SocketAddr,
+        socket_index: SocketIndex,
+        resource_id: ResourceID,
+    ) {
+        if let Some(previous) = self.session_registry.revive_session(resource_id, *remote) {
+            if is_log_enabled(LogLevel::Info) {
+                println!("Session {} resumed from {}", resource_id, remote);
+            }
+            self.shard_router.send(
+                &previous,
+                ShardCommand::MigratePeer(previous, *remote, socket_index),
+            );
+        }
+    }
 
-        let is_client_established = sender_session
-            .as_ref()
-            .and_then(|session| session.client.as_ref())
-            .is_some();
+    fn bind_peer(&self, remote: &SocketAddr, socket_index: SocketIndex, resource_id: ResourceID) {
+        let session = self
+            .session_registry
+            .get_session(resource_id)
+            .expect("Nominated session should be established");
+        let media_session = Box::new(session.media_session.clone());
 
-        // Sender session has not yet established a Client
-        if !is_client_established {
-            return;
+        let command = match &session.connection_type {
+            ConnectionType::Streamer(streamer) => ShardCommand::AddStreamer(
+                *remote,
+                socket_index,
+                streamer.owned_room_id,
+                media_session,
+            ),
+            ConnectionType::Viewer(viewer) => {
+                let streamer_media_session = self
+                    .session_registry
+                    .get_room(viewer.room_id)
+                    .and_then(|room| self.session_registry.get_session(room.owner_id))
+                    .map(|streamer_session| Box::new(streamer_session.media_session.clone()));
+
+                match streamer_media_session {
+                    Some(streamer_media_session) => ShardCommand::AddViewer(
+                        *remote,
+                        socket_index,
+                        viewer.room_id,
+                        media_session,
+                        streamer_media_session,
+                    ),
+                    // Room was closed before the viewer got nominated
+                    None => return,
+                }
+            }
+        };
+
+        self.shard_router.send(remote, command);
+    }
+}
+
+/** Enqueues an RTCP BYE for every session with a nominated address. Returns the number of BYEs enqueued.
+ */
+fn enqueue_bye(session_registry: &SessionRegistry, shard_router: &ShardRouter) -> usize {
+    let remotes = session_registry
+        .get_all_sessions()
+        .into_iter()
+        .filter_map(|session| session.remote_address)
+        .collect::<Vec<_>>();
+
+    for remote in &remotes {
+        shard_router.send(remote, ShardCommand::SendBye(*remote));
+    }
+
+    remotes.len()
+}
+
+#[cfg(test)]
+mod tests {
+    mod enqueue_bye {
+        use std::net::SocketAddr;
+        use std::sync::mpsc::channel;
+
+        use crate::ice_registry::SessionRegistry;
+        use crate::server::enqueue_bye;
+        use crate::shard::{ShardCommand, ShardRouter};
+        use crate::test_utils::get_streamer_session;
+
+        #[test]
+        fn enqueues_bye_per_nominated_session() {
+            let (shard_tx, shard_rx) = channel::<ShardCommand>();
+            let router = ShardRouter::new(vec![shard_tx]);
+            let mut registry = SessionRegistry::new();
+
+            let first_remote: SocketAddr = "10.0.0.1:50000".parse().unwrap();
+            let second_remote: SocketAddr = "10.0.0.2:50000".parse().unwrap();
+            let first_id = registry.add_streamer(get_streamer_session()).unwrap();
+            let second_id = registry.add_streamer(get_streamer_session()).unwrap();
+            // Session that never got nominated has no peer to notify
+            registry.add_streamer(get_streamer_session());
+            registry.nominate_address(first_remote, &first_id);
+            registry.nominate_address(second_remote, &second_id);
+
+            let bye_count = enqueue_bye(&registry, &router);
+
+            assert_eq!(bye_count, 2);
+            let mut remotes = shard_rx
+                .try_iter()
+                .map(|command| match command {
+                    ShardCommand::SendBye(remote) => remote,
+                    _ => panic!("Only BYE commands should be enqueued"),
+                })
+                .collect::<Vec<_>>();
+            remotes.sort();
+            assert_eq!(remotes, vec![first_remote, second_remote]);
         }
+    }
 
-        let sender_session = sender_session.unwrap();
-        let sender_client = sender_session.client.as_mut().unwrap();
+    mod renegotiate_streamer {
+        use std::sync::mpsc::channel;
 
-        // Update session TTL
-        sender_session.ttl = Instant::now();
+        use sdp::SDPParseError;
 
-        match &mut sender_session.connection_type {
-            ConnectionType::Viewer(_) => {
-                if let ClientSslState::Handshake(_) = &mut sender_client.ssl_state {
-                    if let Err(err) = sender_client.read_packet(&self.inbound_buffer) {
-                        eprintln!("Failed reading packet from {} with error {}", remote, err)
-                    }
+        use crate::http::RenegotiationRejection;
+        use crate::ice_registry::SessionRegistry;
+        use crate::server::UDPServer;
+        use crate::shard::{ShardCommand, ShardRouter};
+        use crate::test_utils::{get_sdp_resolver, get_streamer_session, STREAMER_OFFER};
+        use crate::transport::MockTransport;
+
+        #[test]
+        fn tells_unknown_resource_from_rejected_offer() {
+            let (shard_tx, _shard_rx) = channel::<ShardCommand>();
+            let mut server = UDPServer::with_resolver(
+                Box::new(MockTransport::default()),
+                get_sdp_resolver(),
+                ShardRouter::new(vec![shard_tx]),
+                SessionRegistry::new(),
+            );
+            let resource_id = server
+                .session_registry
+                .add_streamer(get_streamer_session())
+                .unwrap();
+            let changed_ufrag_offer = STREAMER_OFFER.replace("ice-ufrag:E2Fr", "ice-ufrag:X9Yz");
+
+            assert!(matches!(
+                server.renegotiate_streamer(resource_id + 1, STREAMER_OFFER, false),
+                Err(RenegotiationRejection::StreamerNotFound)
+            ));
+            assert!(matches!(
+                server.renegotiate_streamer(resource_id, &changed_ufrag_offer, false),
+                Err(RenegotiationRejection::InvalidOffer(diagnostics))
+                    if matches!(diagnostics.error, SDPParseError::ICERestartRequired)
+            ));
+            assert!(matches!(
+                server.renegotiate_streamer(resource_id, "v=0\r\n", false),
+                Err(RenegotiationRejection::InvalidOffer(_))
+            ));
+            assert!(server
+                .renegotiate_streamer(resource_id, STREAMER_OFFER, false)
+                .is_ok());
+        }
+    }
+
+    mod process_packet {
+        use std::net::SocketAddr;
+        use std::sync::mpsc::channel;
+        use std::time::{Duration, Instant};
+
+        use crate::ice_registry::SessionRegistry;
+        use crate::server::UDPServer;
+        use crate::shard::{ShardCommand, ShardRouter};
+        use crate::stun::{create_stun_binding_indication, STUN_COOKIE};
+        use crate::test_utils::{get_sdp_resolver, get_streamer_session};
+        use crate::transport::MockTransport;
+
+        const TRANSACTION_ID: [u8; 12] = [7; 12];
+
+        /** Binding request nominating the pair, the server doesn't check its message integrity.
+         */
+        fn get_nomination_request(username: &str) -> Vec<u8> {
+            let mut username = username.as_bytes().to_vec();
+            let username_length = username.len() as u16;
+            username.resize(username.len().div_ceil(4) * 4, 0);
+
+            let mut attributes = vec![0x00, 0x06];
+            attributes.extend_from_slice(&username_length.to_be_bytes());
+            attributes.extend_from_slice(&username);
+            // USE-CANDIDATE
+            attributes.extend_from_slice(&[0x00, 0x25, 0, 0]);
+            // MESSAGE-INTEGRITY
+            attributes.extend_from_slice(&[0x00, 0x08, 0, 20]);
+            attributes.extend_from_slice(&[0; 20]);
+
+            let mut packet = vec![0x00, 0x01];
+            packet.extend_from_slice(&(attributes.len() as u16).to_be_bytes());
+            packet.extend_from_slice(&STUN_COOKIE.to_be_bytes());
+            packet.extend_from_slice(&TRANSACTION_ID);
+            packet.extend_from_slice(&attributes);
+            packet
+        }
+
+        #[test]
+        fn answers_nomination_and_hands_peer_to_shard() {
+            let transport = MockTransport::default();
+            let (shard_tx, shard_rx) = channel::<ShardCommand>();
+            let mut server = UDPServer::with_resolver(
+                Box::new(transport.clone()),
+                get_sdp_resolver(),
+                ShardRouter::new(vec![shard_tx]),
+                SessionRegistry::new(),
+            );
+            let resource_id = server
+                .session_registry
+                .add_streamer(get_streamer_session())
+                .unwrap();
+            let credentials = &server
+                .session_registry
+                .get_session(resource_id)
+                .unwrap()
+                .media_session
+                .ice_credentials;
+            let username = format!(
+                "{}:{}",
+                credentials.host_username, credentials.remote_username
+            );
+            let remote: SocketAddr = "192.168.0.198:4557".parse().unwrap();
+
+            server.process_packet(&get_nomination_request(&username), remote, 1);
+
+            let sent = transport.take_sent();
+            assert_eq!(sent.len(), 1);
+            let (socket_index, destination, response) = &sent[0];
+            assert_eq!((*socket_index, *destination), (1, remote));
+            // Binding success response to the same transaction
+            assert_eq!(response[0..2], [0x01, 0x01]);
+            assert_eq!(response[8..20], TRANSACTION_ID);
+
+            match shard_rx.try_recv() {
+                Ok(ShardCommand::AddStreamer(peer_remote, socket_index, ..)) => {
+                    assert_eq!((peer_remote, socket_index), (remote, 1))
                 }
+                _ => panic!("Nominated streamer should be handed to its shard"),
             }
-            ConnectionType::Streamer(streamer) => match &mut sender_client.ssl_state {
-                ClientSslState::Handshake(_) => {
-                    if let Err(e) = sender_client.read_packet(&self.inbound_buffer) {
-                        eprintln!("Error reading packet mid handshake {}", e)
-                    }
-                }
-                ClientSslState::Established(ssl_stream) => {
-                    if let Ok(_) = ssl_stream.srtp_inbound.unprotect(&mut self.inbound_buffer) {
-                        let room_id = streamer.owned_room_id;
-
-                        let is_video_packet = get_rtp_header_data(&self.inbound_buffer)
-                            .payload_type
-                            .eq(&(sender_session.media_session.video_session.payload_number as u8));
-
-                        if is_video_packet {
-                            streamer
-                                .thumbnail_extractor
-                                .try_extract_thumbnail(&self.inbound_buffer);
-                        }
-
-                        let viewer_ids = self
-                            .session_registry
-                            .get_room(room_id)
-                            .expect("Streamer room should exist")
-                            .viewer_ids
-                            .clone()
-                            .into_iter();
-
-                        for id in viewer_ids {
-                            let streamer_media = self
-                                .session_registry
-                                .get_session_by_address_mut(&remote)
-                                .expect("Streamer session should be established")
-                                .media_session
-                                .clone();
-                            let viewer_session = self.session_registry.get_session_mut(id).expect("Viewer session should be established if viewer id belongs to a room");
-
-                            // If viewer has yet elected a Client, skip it
-                            if viewer_session.client.is_none() {
-                                continue;
-                            }
-
-                            let viewer_client = viewer_session.client.as_mut().unwrap();
-
-                            if let ClientSslState::Established(ssl_stream) =
-                                &mut viewer_client.ssl_state
-                            {
-                                // Write to temp buffer
-                                self.outbound_buffer.clear();
-                                self.outbound_buffer
-                                    .write(&self.inbound_buffer)
-                                    .expect("Should write to outbound buffer");
-
-                                // Remap Payload Type and SSRC to match negotiated values
-                                remap_rtp_header(
-                                    &mut self.outbound_buffer,
-                                    &streamer_media,
-                                    &viewer_session.media_session,
-                                );
-
-                                // Convert RTP to SRTP and send to remote
-                                if let Ok(_) =
-                                    ssl_stream.srtp_outbound.protect(&mut self.outbound_buffer)
-                                {
-                                    if let Err(err) = self.socket.send_to(
-                                        &self.outbound_buffer,
-                                        viewer_client.remote_address,
-                                    ) {
-                                        eprintln!("Couldn't send RTP data {}", err)
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-                ClientSslState::Shutdown => {
-                    todo!("Handle shutdown case?")
-                }
-            },
+            assert_eq!(
+                server
+                    .session_registry
+                    .get_session(resource_id)
+                    .unwrap()
+                    .remote_address,
+                Some(remote)
+            );
+        }
+
+        #[test]
+        fn refreshes_session_on_indication_without_responding() {
+            let transport = MockTransport::default();
+            let (shard_tx, shard_rx) = channel::<ShardCommand>();
+            let mut server = UDPServer::with_resolver(
+                Box::new(transport.clone()),
+                get_sdp_resolver(),
+                ShardRouter::new(vec![shard_tx]),
+                SessionRegistry::new(),
+            );
+            let remote: SocketAddr = "192.168.0.198:4557".parse().unwrap();
+            let resource_id = server
+                .session_registry
+                .add_streamer(get_streamer_session())
+                .unwrap();
+            server
+                .session_registry
+                .nominate_address(remote, &resource_id);
+            let last_refresh = Instant::now() - Duration::from_secs(3);
+            server
+                .session_registry
+                .get_session_mut(resource_id)
+                .unwrap()
+                .ttl = last_refresh;
+
+            server.process_packet(&create_stun_binding_indication(TRANSACTION_ID), remote, 0);
+
+            let session = server.session_registry.get_session(resource_id).unwrap();
+            assert!(session.ttl > last_refresh);
+            assert!(transport.take_sent().is_empty());
+            assert!(shard_rx.try_recv().is_err());
+        }
+
+        #[test]
+        fn ignores_non_stun_datagrams() {
+            let transport = MockTransport::default();
+            let (shard_tx, shard_rx) = channel::<ShardCommand>();
+            let mut server = UDPServer::with_resolver(
+                Box::new(transport.clone()),
+                get_sdp_resolver(),
+                ShardRouter::new(vec![shard_tx]),
+                SessionRegistry::new(),
+            );
+
+            server.process_packet(&[0x80, 96, 0, 1], "10.0.0.1:5000".parse().unwrap(), 0);
+
+            assert!(transport.take_sent().is_empty());
+            assert!(shard_rx.try_recv().is_err());
         }
     }
 }
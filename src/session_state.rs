@@ -0,0 +1,164 @@
+use std::fmt::{Display, Formatter};
+
+use crate::config::{is_log_enabled, LogLevel};
+
+/** Lifecycle of a peer connection. A session starts once its offer is answered, begins the DTLS
+ * handshake when ICE nominates its address and carries media once the handshake is done. Failed
+ * and closed sessions never carry media again.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionState {
+    New,
+    DtlsHandshaking,
+    Connected,
+    Failed,
+    Closed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidTransition {
+    pub from: SessionState,
+    pub to: SessionState,
+}
+
+impl SessionState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SessionState::New => "new",
+            SessionState::DtlsHandshaking => "dtls-handshaking",
+            SessionState::Connected => "connected",
+            SessionState::Failed => "failed",
+            SessionState::Closed => "closed",
+        }
+    }
+
+    pub fn can_transition_to(&self, next: SessionState) -> bool {
+        matches!(
+            (self, next),
+            (SessionState::New, SessionState::DtlsHandshaking)
+                | (SessionState::DtlsHandshaking, SessionState::Connected)
+                | (
+                    SessionState::New | SessionState::DtlsHandshaking | SessionState::Connected,
+                    SessionState::Failed
+                )
+                | (
+                    SessionState::New
+                        | SessionState::DtlsHandshaking
+                        | SessionState::Connected
+                        | SessionState::Failed,
+                    SessionState::Closed
+                )
+        )
+    }
+
+    /** Media is only forwarded to and from connected sessions.
+     */
+    pub fn is_connected(&self) -> bool {
+        *self == SessionState::Connected
+    }
+
+    /** Moves to the next state, logging the transition under the given context. An illegal
+     * transition leaves the state untouched.
+     */
+    pub fn transition(
+        &mut self,
+        next: SessionState,
+        log_context: impl Display,
+    ) -> Result<(), InvalidTransition> {
+        if !self.can_transition_to(next) {
+            return Err(InvalidTransition {
+                from: *self,
+                to: next,
+            });
+        }
+
+        if is_log_enabled(LogLevel::Info) {
+            eprintln!("{} Session state {} -> {}", log_context, self, next);
+        }
+        *self = next;
+        Ok(())
+    }
+}
+
+impl Display for SessionState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl Display for InvalidTransition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "illegal session state transition {} -> {}",
+            self.from, self.to
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod session_state {
+        use crate::session_state::{InvalidTransition, SessionState};
+
+        const LOG_CONTEXT: &str = "[session=1]";
+
+        #[test]
+        fn follows_lifecycle_to_close() {
+            let mut state = SessionState::New;
+
+            for next in [
+                SessionState::DtlsHandshaking,
+                SessionState::Connected,
+                SessionState::Closed,
+            ] {
+                state
+                    .transition(next, LOG_CONTEXT)
+                    .expect("Transition should be legal");
+                assert_eq!(state, next);
+            }
+        }
+
+        #[test]
+        fn fails_from_any_open_state() {
+            for from in [
+                SessionState::New,
+                SessionState::DtlsHandshaking,
+                SessionState::Connected,
+            ] {
+                let mut state = from;
+
+                assert_eq!(state.transition(SessionState::Failed, LOG_CONTEXT), Ok(()));
+                assert_eq!(state.transition(SessionState::Closed, LOG_CONTEXT), Ok(()));
+            }
+        }
+
+        #[test]
+        fn rejects_illegal_transitions() {
+            for (from, to) in [
+                (SessionState::New, SessionState::Connected),
+                (SessionState::Connected, SessionState::DtlsHandshaking),
+                (SessionState::Connected, SessionState::Connected),
+                (SessionState::Failed, SessionState::Connected),
+                (SessionState::Closed, SessionState::New),
+                (SessionState::Closed, SessionState::Failed),
+            ] {
+                let mut state = from;
+
+                assert_eq!(
+                    state.transition(to, LOG_CONTEXT),
+                    Err(InvalidTransition { from, to })
+                );
+                assert_eq!(state, from);
+            }
+        }
+
+        #[test]
+        fn forwards_media_only_when_connected() {
+            assert!(SessionState::Connected.is_connected());
+            assert!(!SessionState::New.is_connected());
+            assert!(!SessionState::DtlsHandshaking.is_connected());
+            assert!(!SessionState::Failed.is_connected());
+        }
+    }
+}
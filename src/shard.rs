@@ -0,0 +1,2006 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::io::Write;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use sdp::{NegotiatedSession, VideoSession};
+use thumbnail_image_extractor::ThumbnailExtractor;
+
+use crate::bitrate::{BitrateCounter, RoomBitrate, BITRATE_WINDOW};
+use crate::capture::{Direction, PacketCapture};
+use crate::client::{Client, ClientSslState, SRTPError};
+use crate::config::{get_global_config, get_reloadable_config, is_log_enabled, LogLevel};
+use crate::demux::{get_packet_type, PacketType};
+use crate::estimator::DelayBasedEstimator;
+use crate::http::{ServerCommand, ServerSender};
+use crate::ice_registry::{RoomID, RoomVideo};
+use crate::keyframe::KeyframeMonitor;
+use crate::log_context::LogContext;
+use crate::nack::Reporter;
+use crate::pacer::Pacer;
+use crate::receiver_report::ReceiverReportHistory;
+use crate::rtcp::{
+    create_bye_packet, create_fir_packet, create_pli_packet, create_remb_packet,
+    create_sender_report_packet, get_bye_ssrcs, get_receiver_report_blocks, get_remb_bitrate,
+    get_rtcp_packet_types, RTCPPacketType,
+};
+use crate::rtcp_interval::{RTCPScheduler, RTCPSessionState};
+use crate::rtp::{
+    get_abs_send_time, get_rtp_header_data, get_rtp_payload_offset, get_rtp_sequence_number,
+    get_rtp_timestamp, remap_rtp_header, TimestampRebaser, TransportSequencer,
+};
+use crate::sender_report::{get_clock_rate, OutboundStream};
+use crate::server::SocketIndex;
+use crate::session_state::SessionState;
+use crate::simulcast::{LayerIndex, LayerSelector, SimulcastDemuxer};
+use crate::stun::create_stun_binding_indication;
+use crate::supervisor::Actor;
+use crate::throttle::EgressThrottle;
+use crate::thumbnail::save_thumbnail_to_storage;
+
+/** How often a shard reports peer activity back to the main loop to keep the session alive.
+ */
+const SESSION_REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+/** Upper bound on media packets kept while a peer's DTLS handshake is still exporting the keys.
+ */
+const MAX_EARLY_MEDIA_PACKETS: usize = 64;
+
+pub enum ShardCommand {
+    HandlePacket(Vec<u8>, SocketAddr),
+    AddStreamer(SocketAddr, SocketIndex, RoomID, Box<NegotiatedSession>),
+    AddViewer(
+        SocketAddr,
+        SocketIndex,
+        RoomID,
+        Box<NegotiatedSession>,
+        Box<NegotiatedSession>,
+    ),
+    RemovePeer(SocketAddr),
+    MigratePeer(SocketAddr, SocketAddr, SocketIndex),
+    AdoptPeer(SocketAddr, Box<Peer>),
+    ForwardPacket(RoomID, Option<LayerIndex>, Vec<u8>),
+    RequestKeyframe(RoomID, LayerIndex),
+    UpdateStreamerSession(RoomID, Box<NegotiatedSession>),
+    SendBye(SocketAddr),
+    SendSenderReports,
+    SendPacedPackets,
+    SendKeepalives,
+    Drain(Sender<()>),
+    RunPeriodicChecks,
+}
+
+/** Routes commands to the shard owning a given remote address.
+ */
+#[derive(Clone)]
+pub struct ShardRouter {
+    senders: Vec<Sender<ShardCommand>>,
+}
+
+impl ShardRouter {
+    pub fn new(senders: Vec<Sender<ShardCommand>>) -> Self {
+        assert!(!senders.is_empty(), "Router should have at least one shard");
+        ShardRouter { senders }
+    }
+
+    pub fn shard_count(&self) -> usize {
+        self.senders.len()
+    }
+
+    pub fn get_shard_index(&self, remote: &SocketAddr) -> usize {
+        get_shard_index(remote, self.senders.len())
+    }
+
+    pub fn send(&self, remote: &SocketAddr, command: ShardCommand) {
+        self.send_to_shard(self.get_shard_index(remote), command)
+    }
+
+    pub fn broadcast(&self, get_command: impl Fn() -> ShardCommand) {
+        for index in 0..self.senders.len() {
+            self.send_to_shard(index, get_command())
+        }
+    }
+
+    pub fn send_to_shard(&self, index: usize, command: ShardCommand) {
+        self.senders[index]
+            .send(command)
+            .expect("Shard channel should be open")
+    }
+}
+
+pub fn get_shard_index(remote: &SocketAddr, shard_count: usize) -> usize {
+    let mut hasher = DefaultHasher::new();
+    remote.hash(&mut hasher);
+    (hasher.finish() % shard_count as u64) as usize
+}
+
+/** Owns the DTLS/SRTP state of every peer whose address hashes to this shard.
+ */
+pub struct MediaShard {
+    index: usize,
+    peers: HashMap<SocketAddr, Peer>,
+    room_viewers: HashMap<RoomID, HashSet<SocketAddr>>,
+    room_traffic: HashMap<RoomID, RoomTraffic>,
+    shard_router: ShardRouter,
+    server_sender: ServerSender,
+    inbound_buffer: Vec<u8>,
+    outbound_buffer: Vec<u8>,
+    sockets: Vec<UdpSocket>,
+}
+
+pub struct Peer {
+    client: Client,
+    socket_index: SocketIndex,
+    media_session: NegotiatedSession,
+    last_refresh: Instant,
+    peer_type: PeerType,
+    capture: Option<PacketCapture>,
+    rtcp_scheduler: RTCPScheduler,
+    // RTP traffic in whichever direction the peer's media flows
+    media_traffic: BitrateCounter,
+    state: SessionState,
+    // SRTP and SRTCP packets that arrived before the handshake finished
+    early_media: VecDeque<Vec<u8>>,
+}
+
+impl Peer {
+    fn log_context(&self) -> LogContext {
+        let room_id = match &self.peer_type {
+            PeerType::Streamer(streamer) => streamer.room_id,
+            PeerType::Viewer(viewer) => viewer.room_id,
+        };
+        LogContext::new(self.client.remote_address, room_id)
+    }
+
+    /** Feeds a DTLS handshake packet to the client. Returns the state the peer moved to, if the
+     * handshake finished or failed.
+     */
+    fn read_handshake_packet(&mut self, packet: &[u8]) -> Option<SessionState> {
+        let log_context = self.log_context();
+        let next_state = match self.client.read_packet(packet) {
+            Ok(()) if matches!(self.client.ssl_state, ClientSslState::Established(_)) => {
+                SessionState::Connected
+            }
+            Ok(()) => return None,
+            Err(err) => {
+                if is_log_enabled(LogLevel::Warn) {
+                    eprintln!(
+                        "{} Failed reading packet mid handshake {}",
+                        log_context, err
+                    );
+                }
+                SessionState::Failed
+            }
+        };
+
+        self.state.transition(next_state, log_context).ok()?;
+        Some(next_state)
+    }
+
+    /** Keeps a media packet that can't be unprotected until the handshake finishes. Once the
+     * buffer is full, further packets are dropped.
+     */
+    fn buffer_early_media(&mut self, packet: &[u8]) {
+        if self.early_media.len() == MAX_EARLY_MEDIA_PACKETS {
+            log_unprotect_error(self.log_context(), SRTPError::KeyNotReady);
+            return;
+        }
+        self.early_media.push_back(Vec::from(packet));
+    }
+
+    /** Forgets the sources named by an RTCP BYE. Browsers also say goodbye to single sources, e.g.
+     * the video SSRC replaced when the camera is toggled, so the peer only left once every source
+     * it sends from is named. Returns whether it left.
+     */
+    fn handle_bye(&mut self, ssrcs: &[u32]) -> bool {
+        let remote_ssrcs = self.media_session.get_remote_ssrcs();
+        if remote_ssrcs.iter().all(|ssrc| ssrcs.contains(ssrc)) {
+            return true;
+        }
+
+        let video_ssrc = self.media_session.video_session.remote_ssrc;
+        for ssrc in ssrcs {
+            self.media_session.forget_remote_ssrc(*ssrc);
+        }
+        // Frames of the replacing video source start from scratch
+        if let PeerType::Streamer(streamer) = &mut self.peer_type {
+            if video_ssrc.is_some_and(|ssrc| ssrcs.contains(&ssrc)) {
+                streamer.thumbnail_extractor = ThumbnailExtractor::new();
+            }
+        }
+        false
+    }
+}
+
+enum PeerType {
+    Streamer(StreamerPeer),
+    Viewer(ViewerPeer),
+}
+
+struct StreamerPeer {
+    room_id: RoomID,
+    thumbnail_extractor: ThumbnailExtractor,
+    image_timestamp: Option<Instant>,
+    keyframe_monitor: KeyframeMonitor,
+    reporter: Reporter,
+    simulcast_demuxer: Option<SimulcastDemuxer>,
+    bandwidth_estimator: Option<Box<DelayBasedEstimator>>,
+    fir_sequence_number: u8,
+}
+
+/** Traffic of a room as seen by this shard.
+ */
+struct RoomTraffic {
+    ingress: BitrateCounter,
+    egress: BitrateCounter,
+    layers: HashMap<LayerIndex, BitrateCounter>,
+}
+
+impl RoomTraffic {
+    fn new() -> Self {
+        RoomTraffic {
+            ingress: BitrateCounter::new(BITRATE_WINDOW),
+            egress: BitrateCounter::new(BITRATE_WINDOW),
+            layers: HashMap::new(),
+        }
+    }
+
+    fn add_layer_packet(&mut self, layer: LayerIndex, bytes: usize, now: Instant) {
+        self.layers
+            .entry(layer)
+            .or_insert_with(|| BitrateCounter::new(BITRATE_WINDOW))
+            .add(bytes, now);
+    }
+
+    fn get_layer_bitrates(&mut self, now: Instant) -> Vec<(LayerIndex, u64)> {
+        self.layers
+            .iter_mut()
+            .map(|(layer, counter)| (*layer, counter.bitrate_bps(now)))
+            .collect()
+    }
+}
+
+struct ViewerPeer {
+    room_id: RoomID,
+    streamer_media_session: Box<NegotiatedSession>,
+    egress_throttle: EgressThrottle,
+    pacer: Pacer,
+    outbound_streams: HashMap<u32, OutboundStream>,
+    receiver_reports: ReceiverReportHistory,
+    layer_selector: LayerSelector,
+    timestamp_rebaser: TimestampRebaser,
+    transport_sequencer: TransportSequencer,
+}
+
+impl MediaShard {
+    pub fn new(
+        index: usize,
+        sockets: Vec<UdpSocket>,
+        shard_router: ShardRouter,
+        server_sender: ServerSender,
+    ) -> Self {
+        MediaShard {
+            index,
+            peers: HashMap::new(),
+            room_viewers: HashMap::new(),
+            room_traffic: HashMap::new(),
+            shard_router,
+            server_sender,
+            inbound_buffer: Vec::with_capacity(2000),
+            outbound_buffer: Vec::with_capacity(2000),
+            sockets,
+        }
+    }
+
+    pub fn process_command(&mut self, command: ShardCommand) {
+        match command {
+            ShardCommand::HandlePacket(packet, remote) => self.handle_packet(&packet, remote),
+            ShardCommand::AddStreamer(remote, socket_index, room_id, media_session) => {
+                let peer_type = PeerType::Streamer(StreamerPeer {
+                    room_id,
+                    thumbnail_extractor: ThumbnailExtractor::new(),
+                    image_timestamp: None,
+                    keyframe_monitor: KeyframeMonitor::new(
+                        Instant::now(),
+                        get_global_config().udp_server_config.keyframe_timeout,
+                    ),
+                    reporter: Reporter::new(get_global_config().udp_server_config.nack_window),
+                    simulcast_demuxer: get_simulcast_demuxer(&media_session),
+                    bandwidth_estimator: get_bandwidth_estimator(&media_session),
+                    fir_sequence_number: 0,
+                });
+                self.add_peer(remote, socket_index, *media_session, peer_type)
+            }
+            ShardCommand::AddViewer(
+                remote,
+                socket_index,
+                room_id,
+                media_session,
+                streamer_media_session,
+            ) => {
+                let peer_type = PeerType::Viewer(ViewerPeer {
+                    room_id,
+                    streamer_media_session,
+                    egress_throttle: EgressThrottle::new(),
+                    pacer: Pacer::new(),
+                    outbound_streams: HashMap::new(),
+                    receiver_reports: ReceiverReportHistory::new(),
+                    layer_selector: LayerSelector::new(),
+                    timestamp_rebaser: TimestampRebaser::new(),
+                    transport_sequencer: TransportSequencer::new(),
+                });
+                self.add_peer(remote, socket_index, *media_session, peer_type);
+                self.room_viewers.entry(room_id).or_default().insert(remote);
+            }
+            ShardCommand::RemovePeer(remote) => {
+                self.remove_peer(&remote);
+            }
+            ShardCommand::MigratePeer(previous, remote, socket_index) => {
+                if let Some(mut peer) = self.remove_peer(&previous) {
+                    let socket = self.sockets[socket_index].try_clone().unwrap();
+                    peer.client.set_remote_address(remote, socket);
+                    peer.socket_index = socket_index;
+                    self.shard_router
+                        .send(&remote, ShardCommand::AdoptPeer(remote, Box::new(peer)));
+                }
+            }
+            ShardCommand::AdoptPeer(remote, peer) => {
+                if let PeerType::Viewer(viewer) = &peer.peer_type {
+                    self.room_viewers
+                        .entry(viewer.room_id)
+                        .or_default()
+                        .insert(remote);
+                }
+                self.peers.insert(remote, *peer);
+            }
+            ShardCommand::ForwardPacket(room_id, layer, packet) => {
+                self.forward_to_viewers(room_id, layer, &packet)
+            }
+            ShardCommand::RequestKeyframe(room_id, layer) => self.request_keyframe(room_id, layer),
+            ShardCommand::UpdateStreamerSession(room_id, media_session) => {
+                self.update_streamer_session(room_id, *media_session)
+            }
+            ShardCommand::SendBye(remote) => self.send_bye(&remote),
+            // REMBs toward streamers are paced like Sender Reports
+            ShardCommand::SendSenderReports => {
+                self.send_sender_reports();
+                self.send_bandwidth_estimates();
+            }
+            ShardCommand::SendPacedPackets => self.send_paced_packets(),
+            ShardCommand::SendKeepalives => self.send_keepalives(),
+            // Commands are processed in order, so every packet queued before this one has been handled
+            ShardCommand::Drain(ack) => {
+                let _ = ack.send(());
+            }
+            ShardCommand::RunPeriodicChecks => {
+                self.save_thumbnails();
+                self.report_room_bitrates();
+                self.report_viewer_stats();
+                self.report_room_video();
+                self.request_stale_keyframes();
+            }
+        }
+    }
+
+    /** Swaps the streamer media session of a room, both on the streamer peer and on its viewers.
+     */
+    fn update_streamer_session(&mut self, room_id: RoomID, media_session: NegotiatedSession) {
+        for peer in self.peers.values_mut() {
+            match &mut peer.peer_type {
+                PeerType::Streamer(streamer) if streamer.room_id == room_id => {
+                    streamer.simulcast_demuxer = get_simulcast_demuxer(&media_session);
+                    // Keep the estimate of an ongoing stream
+                    if media_session
+                        .video_session
+                        .abs_send_time_extension_id
+                        .is_none()
+                    {
+                        streamer.bandwidth_estimator = None;
+                    } else if streamer.bandwidth_estimator.is_none() {
+                        streamer.bandwidth_estimator = get_bandwidth_estimator(&media_session);
+                    }
+                    peer.media_session = media_session.clone();
+                }
+                PeerType::Viewer(viewer) if viewer.room_id == room_id => {
+                    *viewer.streamer_media_session = media_session.clone();
+                }
+                PeerType::Streamer(_) | PeerType::Viewer(_) => {}
+            }
+        }
+    }
+
+    /** Sends Sender Reports to the viewers whose RTCP interval elapsed.
+     */
+    fn send_sender_reports(&mut self) {
+        let now = Instant::now();
+        let wallclock = SystemTime::now();
+        let mut rng = rand::thread_rng();
+
+        for peer in self.peers.values_mut() {
+            let log_context = peer.log_context();
+            let viewer = match &peer.peer_type {
+                PeerType::Viewer(viewer) => viewer,
+                PeerType::Streamer(_) => continue,
+            };
+            if viewer.outbound_streams.is_empty() {
+                continue;
+            }
+            let session =
+                RTCPSessionState::point_to_point(true, peer.media_traffic.bitrate_bps(now));
+            if !peer.rtcp_scheduler.is_report_due(&session, now, &mut rng) {
+                continue;
+            }
+
+            if let ClientSslState::Established(ssl_stream) = &mut peer.client.ssl_state {
+                let mut report_size = 0;
+                for stream in viewer.outbound_streams.values() {
+                    let mut packet =
+                        create_sender_report_packet(&stream.create_report(now, wallclock));
+
+                    if ssl_stream.protect_rtcp(&mut packet).is_ok() {
+                        let socket = &self.sockets[peer.socket_index];
+                        match send_datagram(
+                            socket,
+                            &peer.capture,
+                            &packet,
+                            peer.client.remote_address,
+                        ) {
+                            Ok(bytes_sent) => report_size += bytes_sent,
+                            Err(err) => log_send_error(log_context, "RTCP SR", err),
+                        }
+                    }
+                }
+                peer.rtcp_scheduler
+                    .register_report(report_size, &session, now, &mut rng);
+            }
+        }
+    }
+
+    /** Tells streamers how much bandwidth their video can use, as estimated from the delay of its packets.
+     */
+    fn send_bandwidth_estimates(&mut self) {
+        let now = Instant::now();
+        let mut rng = rand::thread_rng();
+
+        for peer in self.peers.values_mut() {
+            let log_context = peer.log_context();
+            let streamer = match &peer.peer_type {
+                PeerType::Streamer(streamer) => streamer,
+                PeerType::Viewer(_) => continue,
+            };
+            let estimate_bps = match &streamer.bandwidth_estimator {
+                Some(estimator) => estimator.estimate_bps(),
+                None => continue,
+            };
+            let video_session = &peer.media_session.video_session;
+            let media_ssrcs = match &streamer.simulcast_demuxer {
+                Some(demuxer) => demuxer.get_ssrcs(),
+                None => video_session.remote_ssrc.into_iter().collect(),
+            };
+            let session =
+                RTCPSessionState::point_to_point(false, peer.media_traffic.bitrate_bps(now));
+            if !peer.rtcp_scheduler.is_report_due(&session, now, &mut rng) {
+                continue;
+            }
+
+            if let ClientSslState::Established(ssl_stream) = &mut peer.client.ssl_state {
+                let mut packet =
+                    create_remb_packet(video_session.host_ssrc, estimate_bps, &media_ssrcs);
+                if ssl_stream.protect_rtcp(&mut packet).is_ok() {
+                    let socket = &self.sockets[peer.socket_index];
+                    match send_datagram(socket, &peer.capture, &packet, peer.client.remote_address)
+                    {
+                        Ok(bytes_sent) => peer
+                            .rtcp_scheduler
+                            .register_report(bytes_sent, &session, now, &mut rng),
+                        Err(err) => log_send_error(log_context, "RTCP REMB", err),
+                    }
+                }
+            }
+        }
+    }
+
+    /** Peers still in the handshake are left alone, their path isn't confirmed yet.
+     */
+    fn send_keepalives(&self) {
+        let connected_peers = self
+            .peers
+            .values()
+            .filter(|peer| peer.state == SessionState::Connected);
+        for peer in connected_peers {
+            let indication = create_stun_binding_indication(rand::random());
+            let remote = peer.client.remote_address;
+
+            if let Err(err) = send_datagram(
+                &self.sockets[peer.socket_index],
+                &peer.capture,
+                &indication,
+                remote,
+            ) {
+                log_send_error(peer.log_context(), "STUN keepalive", err)
+            }
+        }
+    }
+
+    fn send_bye(&mut self, remote: &SocketAddr) {
+        let peer = match self.peers.get_mut(remote) {
+            Some(peer) => peer,
+            None => return,
+        };
+        let log_context = peer.log_context();
+        let ssrcs = [
+            peer.media_session.audio_session.host_ssrc,
+            peer.media_session.video_session.host_ssrc,
+        ];
+        // Streamers were sent no media to report on
+        let report = match &peer.peer_type {
+            PeerType::Viewer(viewer) => ssrcs
+                .iter()
+                .find_map(|ssrc| viewer.outbound_streams.get(ssrc))
+                .map(|stream| stream.create_report(Instant::now(), SystemTime::now())),
+            PeerType::Streamer(_) => None,
+        };
+
+        if let ClientSslState::Established(ssl_stream) = &mut peer.client.ssl_state {
+            let mut packet = create_bye_packet(report.as_ref(), &ssrcs);
+
+            if ssl_stream.protect_rtcp(&mut packet).is_ok() {
+                if let Err(err) = send_datagram(
+                    &self.sockets[peer.socket_index],
+                    &peer.capture,
+                    &packet,
+                    *remote,
+                ) {
+                    log_send_error(log_context, "RTCP BYE", err)
+                }
+            }
+        }
+    }
+
+    fn add_peer(
+        &mut self,
+        remote: SocketAddr,
+        socket_index: SocketIndex,
+        media_session: NegotiatedSession,
+        peer_type: PeerType,
+    ) {
+        let socket = self.sockets[socket_index].try_clone().unwrap();
+        let capture = self.create_capture(remote, socket_index, &peer_type);
+        let client = Client::new(remote, socket, capture.clone()).expect("Should create a Client");
+
+        self.insert_peer(
+            remote,
+            socket_index,
+            client,
+            capture,
+            media_session,
+            peer_type,
+        );
+    }
+
+    fn insert_peer(
+        &mut self,
+        remote: SocketAddr,
+        socket_index: SocketIndex,
+        client: Client,
+        capture: Option<PacketCapture>,
+        media_session: NegotiatedSession,
+        peer_type: PeerType,
+    ) {
+        let mut peer = Peer {
+            client,
+            socket_index,
+            media_session,
+            last_refresh: Instant::now(),
+            peer_type,
+            capture,
+            rtcp_scheduler: RTCPScheduler::new(Instant::now()),
+            media_traffic: BitrateCounter::new(BITRATE_WINDOW),
+            // Peers are handed over once nominated, right before the handshake starts
+            state: SessionState::DtlsHandshaking,
+            early_media: VecDeque::new(),
+        };
+
+        // Plaintext test clients have no handshake to wait for
+        let log_context = peer.log_context();
+        if matches!(peer.client.ssl_state, ClientSslState::Established(_))
+            && peer
+                .state
+                .transition(SessionState::Connected, log_context)
+                .is_ok()
+        {
+            self.server_sender
+                .send(ServerCommand::UpdateSessionState(
+                    remote,
+                    SessionState::Connected,
+                ))
+                .expect("Server channel should be open");
+        }
+
+        self.peers.insert(remote, peer);
+    }
+
+    /** Opens the packet capture of a new peer, if captures are turned on.
+     */
+    fn create_capture(
+        &self,
+        remote: SocketAddr,
+        socket_index: SocketIndex,
+        peer_type: &PeerType,
+    ) -> Option<PacketCapture> {
+        let capture_dir = get_global_config().udp_server_config.capture_dir.as_ref()?;
+        let room_id = match peer_type {
+            PeerType::Streamer(streamer) => streamer.room_id,
+            PeerType::Viewer(viewer) => viewer.room_id,
+        };
+        let local_address = self.sockets[socket_index].local_addr().ok()?;
+
+        match PacketCapture::create(capture_dir, local_address, remote, room_id) {
+            Ok(capture) => Some(capture),
+            Err(err) => {
+                if is_log_enabled(LogLevel::Warn) {
+                    eprintln!(
+                        "{} Couldn't create packet capture {}",
+                        LogContext::new(remote, room_id),
+                        err
+                    );
+                }
+                None
+            }
+        }
+    }
+
+    fn remove_peer(&mut self, remote: &SocketAddr) -> Option<Peer> {
+        let peer = self.peers.remove(remote)?;
+        if let PeerType::Viewer(viewer) = &peer.peer_type {
+            if let Some(viewers) = self.room_viewers.get_mut(&viewer.room_id) {
+                viewers.remove(remote);
+                if viewers.is_empty() {
+                    self.room_viewers.remove(&viewer.room_id);
+                }
+            }
+        }
+        Some(peer)
+    }
+
+    fn handle_packet(&mut self, data: &[u8], remote: SocketAddr) {
+        let peer = match self.peers.get(&remote) {
+            Some(peer) => peer,
+            // Peer has not yet been nominated
+            None => return,
+        };
+        if let Some(capture) = &peer.capture {
+            capture.record(Direction::Inbound, remote, data);
+        }
+        self.read_packet(data, remote)
+    }
+
+    /** Handles a packet already recorded to the peer's capture.
+     */
+    fn read_packet(&mut self, data: &[u8], remote: SocketAddr) {
+        let peer = match self.peers.get_mut(&remote) {
+            Some(peer) => peer,
+            None => return,
+        };
+        let log_context = peer.log_context();
+
+        self.inbound_buffer.clear();
+        self.inbound_buffer
+            .write_all(data)
+            .expect("Failed to write to internal buffer");
+
+        // Report activity to keep the session alive
+        if peer.last_refresh.elapsed() > SESSION_REFRESH_INTERVAL {
+            peer.last_refresh = Instant::now();
+            self.server_sender
+                .send(ServerCommand::RefreshSession(remote))
+                .expect("Server channel should be open");
+        }
+
+        let mut forward_room = None;
+        let mut keyframe_request = None;
+        let mut bye_ssrcs = None;
+        // The DTLS association is gone, e.g. after a close_notify or a failed handshake setup
+        let mut is_shut_down = false;
+        let mut state_update = None;
+
+        match &mut peer.peer_type {
+            PeerType::Viewer(viewer) => match &mut peer.client.ssl_state {
+                ClientSslState::Handshake(_) => match get_packet_type(&self.inbound_buffer) {
+                    PacketType::Dtls => {
+                        state_update = peer.read_handshake_packet(&self.inbound_buffer)
+                    }
+                    PacketType::Rtp | PacketType::Rtcp(_) => peer.buffer_early_media(data),
+                    PacketType::Stun | PacketType::Unknown => {}
+                },
+                ClientSslState::Established(ssl_stream) => {
+                    // Viewers only send RTCP feedback
+                    if let PacketType::Rtcp(_) = get_packet_type(&self.inbound_buffer) {
+                        if ssl_stream
+                            .unprotect_rtcp(&mut self.inbound_buffer)
+                            .map_err(|err| log_unprotect_error(log_context, err))
+                            .is_ok()
+                        {
+                            if let Some(bitrate) = get_remb_bitrate(&self.inbound_buffer) {
+                                viewer.egress_throttle.set_remb(bitrate);
+                                viewer.pacer.set_target_bitrate(bitrate);
+                                if let Some(traffic) = self.room_traffic.get_mut(&viewer.room_id) {
+                                    let layer_bitrates = traffic.get_layer_bitrates(Instant::now());
+                                    keyframe_request = viewer
+                                        .layer_selector
+                                        .select_layer(&layer_bitrates, bitrate)
+                                        .map(|layer| (viewer.room_id, layer));
+                                }
+                            }
+                            for report in get_receiver_report_blocks(&self.inbound_buffer) {
+                                viewer.receiver_reports.add_report(report);
+                            }
+                            if get_rtcp_packet_types(&self.inbound_buffer)
+                                .contains(&RTCPPacketType::Bye)
+                            {
+                                bye_ssrcs = Some(get_bye_ssrcs(&self.inbound_buffer));
+                            }
+                        }
+                    }
+                }
+                ClientSslState::Shutdown => is_shut_down = true,
+            },
+            PeerType::Streamer(streamer) => match &mut peer.client.ssl_state {
+                ClientSslState::Handshake(_) => match get_packet_type(&self.inbound_buffer) {
+                    PacketType::Dtls => {
+                        state_update = peer.read_handshake_packet(&self.inbound_buffer)
+                    }
+                    PacketType::Rtp | PacketType::Rtcp(_) => peer.buffer_early_media(data),
+                    PacketType::Stun | PacketType::Unknown => {}
+                },
+                ClientSslState::Established(ssl_stream) => {
+                    match get_packet_type(&self.inbound_buffer) {
+                        PacketType::Rtp => {
+                            let header = ssl_stream
+                                .unprotect(&mut self.inbound_buffer)
+                                .map_err(|err| log_unprotect_error(log_context, err))
+                                .ok()
+                                .and_then(|_| get_rtp_header_data(&self.inbound_buffer));
+                            if let Some(header) = header {
+                                peer.media_traffic.add(data.len(), Instant::now());
+                                // Extra video tracks, e.g. a screen share, are only forwarded
+                                let is_extra_video_packet = peer
+                                    .media_session
+                                    .get_extra_video_index(header.ssrc)
+                                    .is_some();
+                                let is_video_packet = !is_extra_video_packet
+                                    && header.payload_type
+                                        == peer.media_session.video_session.payload_number as u8;
+                                // Simulcast layers are told apart by their RID rather than their SSRC
+                                if is_video_packet && streamer.simulcast_demuxer.is_none() {
+                                    let ssrc = header.ssrc;
+                                    if let Some(previous_ssrc) =
+                                        streamer.reporter.update_media_ssrc(
+                                            &mut peer.media_session.video_session,
+                                            ssrc,
+                                        )
+                                    {
+                                        if is_log_enabled(LogLevel::Info) {
+                                            eprintln!(
+                                                "{} Video SSRC changed from {} to {}",
+                                                log_context, previous_ssrc, ssrc
+                                            );
+                                        }
+                                        streamer.thumbnail_extractor = ThumbnailExtractor::new();
+                                    }
+                                }
+                                let layer = match &mut streamer.simulcast_demuxer {
+                                    Some(demuxer) if is_video_packet => {
+                                        demuxer.get_layer(&self.inbound_buffer)
+                                    }
+                                    Some(_) | None => None,
+                                };
+                                let video_session = &peer.media_session.video_session;
+                                if let (Some(estimator), Some(id)) = (
+                                    &mut streamer.bandwidth_estimator,
+                                    video_session.abs_send_time_extension_id,
+                                ) {
+                                    if let Some(abs_send_time) =
+                                        get_abs_send_time(&self.inbound_buffer, id)
+                                    {
+                                        estimator.register_packet(
+                                            abs_send_time,
+                                            data.len(),
+                                            Instant::now(),
+                                        );
+                                    }
+                                }
+                                // Simulcast video can't be forwarded until its layer is known
+                                let is_forwarded = layer.is_some()
+                                    || !is_video_packet
+                                    || streamer.simulcast_demuxer.is_none();
+
+                                // Thumbnails and NACKs follow a single stream, the first simulcast layer
+                                if is_video_packet && is_forwarded && layer.unwrap_or(0) == 0 {
+                                    streamer
+                                        .thumbnail_extractor
+                                        .try_extract_thumbnail(&self.inbound_buffer);
+                                    streamer.keyframe_monitor.register_packet(
+                                        &self.inbound_buffer,
+                                        Instant::now(),
+                                        SystemTime::now(),
+                                    );
+
+                                    streamer.reporter.register_packet(get_rtp_sequence_number(
+                                        &self.inbound_buffer,
+                                    ));
+                                    let video_session = &peer.media_session.video_session;
+                                    if let Some(mut packet) =
+                                        streamer.reporter.check_packet_integrity(
+                                            video_session.host_ssrc,
+                                            video_session,
+                                            Instant::now(),
+                                        )
+                                    {
+                                        if ssl_stream.protect_rtcp(&mut packet).is_ok() {
+                                            let socket = &self.sockets[peer.socket_index];
+                                            if let Err(err) = send_datagram(
+                                                socket,
+                                                &peer.capture,
+                                                &packet,
+                                                remote,
+                                            ) {
+                                                log_send_error(log_context, "RTCP NACK", err)
+                                            }
+                                        }
+                                    }
+                                }
+
+                                if is_forwarded {
+                                    forward_room = Some((streamer.room_id, layer));
+                                }
+                            }
+                        }
+                        // Streamer SR/RR only keep the session alive, viewers get Sender Reports from the server
+                        PacketType::Rtcp(_) => {
+                            if ssl_stream
+                                .unprotect_rtcp(&mut self.inbound_buffer)
+                                .map_err(|err| log_unprotect_error(log_context, err))
+                                .is_ok()
+                                && get_rtcp_packet_types(&self.inbound_buffer)
+                                    .contains(&RTCPPacketType::Bye)
+                            {
+                                bye_ssrcs = Some(get_bye_ssrcs(&self.inbound_buffer));
+                            }
+                        }
+                        // Late DTLS records after the handshake carry no media
+                        PacketType::Stun | PacketType::Dtls | PacketType::Unknown => {}
+                    }
+                }
+                ClientSslState::Shutdown => is_shut_down = true,
+            },
+        }
+        let is_bye_received = bye_ssrcs.is_some_and(|ssrcs| peer.handle_bye(&ssrcs));
+
+        if let Some(state) = state_update {
+            self.server_sender
+                .send(ServerCommand::UpdateSessionState(remote, state))
+                .expect("Server channel should be open");
+            if state == SessionState::Connected {
+                self.read_early_media(remote);
+            }
+        }
+
+        if let Some((room_id, layer)) = keyframe_request {
+            // The streamer may live on any shard
+            self.shard_router
+                .broadcast(|| ShardCommand::RequestKeyframe(room_id, layer));
+        }
+
+        if is_shut_down {
+            self.terminate_session(remote);
+            return;
+        }
+
+        if is_bye_received {
+            self.server_sender
+                .send(ServerCommand::RemovePeerSession(remote))
+                .expect("Server channel should be open");
+            return;
+        }
+
+        if let Some((room_id, layer)) = forward_room {
+            self.room_traffic
+                .entry(room_id)
+                .or_insert_with(RoomTraffic::new)
+                .ingress
+                .add(data.len(), Instant::now());
+
+            // Viewers of this room may live on any shard
+            for index in 0..self.shard_router.shard_count() {
+                if index != self.index {
+                    self.shard_router.send_to_shard(
+                        index,
+                        ShardCommand::ForwardPacket(room_id, layer, self.inbound_buffer.clone()),
+                    );
+                }
+            }
+
+            let packet = std::mem::take(&mut self.inbound_buffer);
+            self.forward_to_viewers(room_id, layer, &packet);
+            self.inbound_buffer = packet;
+        }
+    }
+
+    /** Handles the media that arrived mid handshake, now that the keys are exported.
+     */
+    fn read_early_media(&mut self, remote: SocketAddr) {
+        let early_media = match self.peers.get_mut(&remote) {
+            Some(peer) => std::mem::take(&mut peer.early_media),
+            None => return,
+        };
+        for packet in early_media {
+            self.read_packet(&packet, remote);
+        }
+    }
+
+    fn forward_to_viewers(&mut self, room_id: RoomID, layer: Option<LayerIndex>, packet: &[u8]) {
+        let viewers = match self.room_viewers.get(&room_id) {
+            Some(viewers) => viewers,
+            None => return,
+        };
+        let header = match get_rtp_header_data(packet) {
+            Some(header) => header,
+            None => return,
+        };
+        // Viewers pick simulcast layers by their bitrate
+        if let Some(layer) = layer {
+            self.room_traffic
+                .entry(room_id)
+                .or_insert_with(RoomTraffic::new)
+                .add_layer_packet(layer, packet.len(), Instant::now());
+        }
+
+        let mut sent_bytes = 0;
+        for remote in viewers {
+            let viewer = self
+                .peers
+                .get_mut(remote)
+                .expect("Viewer peer should be present if it belongs to a room");
+            // Half-open viewers get nothing until their handshake finishes
+            if !viewer.state.is_connected() {
+                continue;
+            }
+            let viewer_peer = match &mut viewer.peer_type {
+                PeerType::Viewer(viewer_peer) => viewer_peer,
+                PeerType::Streamer(_) => continue,
+            };
+            let streamer_media_session = &viewer_peer.streamer_media_session;
+
+            // The throttle drops whole frames of the primary track, extra tracks are left alone
+            let is_extra_video_packet = streamer_media_session
+                .get_extra_video_index(header.ssrc)
+                .is_some();
+            let is_video_packet = is_extra_video_packet
+                || header
+                    .payload_type
+                    .eq(&(streamer_media_session.video_session.payload_number as u8));
+            // Video goes through the pacer, audio is sent right away
+            let packets = if is_video_packet {
+                let mut packet = packet.to_vec();
+                if let Some(layer) = layer {
+                    if !viewer_peer.layer_selector.forward(layer, &mut packet) {
+                        continue;
+                    }
+                }
+                let now = Instant::now();
+                if !is_extra_video_packet
+                    && !viewer_peer.egress_throttle.should_forward(&packet, now)
+                {
+                    continue;
+                }
+                viewer_peer.pacer.enqueue(packet);
+                viewer_peer.pacer.release(now)
+            } else {
+                vec![packet.to_vec()]
+            };
+
+            for packet in packets {
+                let socket = &self.sockets[viewer.socket_index];
+                sent_bytes += send_to_viewer(socket, &mut self.outbound_buffer, viewer, &packet);
+            }
+        }
+
+        if sent_bytes > 0 {
+            self.room_traffic
+                .entry(room_id)
+                .or_insert_with(RoomTraffic::new)
+                .egress
+                .add(sent_bytes, Instant::now());
+        }
+    }
+
+    /** Asks the streamer of a room for a keyframe of a simulcast layer, if this shard handles the streamer.
+     */
+    fn request_keyframe(&mut self, room_id: RoomID, layer: LayerIndex) {
+        for peer in self.peers.values_mut() {
+            let log_context = peer.log_context();
+            let media_ssrc = match &peer.peer_type {
+                PeerType::Streamer(streamer) if streamer.room_id == room_id => streamer
+                    .simulcast_demuxer
+                    .as_ref()
+                    .and_then(|demuxer| demuxer.get_ssrc(layer)),
+                PeerType::Streamer(_) | PeerType::Viewer(_) => None,
+            };
+            if let Some(media_ssrc) = media_ssrc {
+                send_keyframe_request(
+                    &self.sockets[peer.socket_index],
+                    peer,
+                    media_ssrc,
+                    log_context,
+                );
+            }
+        }
+    }
+
+    /** Asks streamers whose latest keyframe is too old for a new one.
+     */
+    fn request_stale_keyframes(&mut self) {
+        let now = Instant::now();
+        for peer in self.peers.values_mut() {
+            let log_context = peer.log_context();
+            let media_ssrc = match &mut peer.peer_type {
+                PeerType::Streamer(streamer) => {
+                    get_stale_keyframe_ssrc(streamer, &peer.media_session.video_session, now)
+                }
+                PeerType::Viewer(_) => continue,
+            };
+
+            if let Some(media_ssrc) = media_ssrc {
+                if is_log_enabled(LogLevel::Info) {
+                    eprintln!(
+                        "{} Requesting keyframe, the latest one is stale",
+                        log_context
+                    );
+                }
+                send_keyframe_request(
+                    &self.sockets[peer.socket_index],
+                    peer,
+                    media_ssrc,
+                    log_context,
+                );
+            }
+        }
+    }
+
+    fn send_paced_packets(&mut self) {
+        let now = Instant::now();
+
+        for viewer in self.peers.values_mut() {
+            let (room_id, packets) = match &mut viewer.peer_type {
+                PeerType::Viewer(viewer_peer) => {
+                    (viewer_peer.room_id, viewer_peer.pacer.release(now))
+                }
+                PeerType::Streamer(_) => continue,
+            };
+
+            let mut sent_bytes = 0;
+            for packet in packets {
+                let socket = &self.sockets[viewer.socket_index];
+                sent_bytes += send_to_viewer(socket, &mut self.outbound_buffer, viewer, &packet);
+            }
+
+            if sent_bytes > 0 {
+                self.room_traffic
+                    .entry(room_id)
+                    .or_insert_with(RoomTraffic::new)
+                    .egress
+                    .add(sent_bytes, now);
+            }
+        }
+    }
+
+    fn report_viewer_stats(&self) {
+        let viewer_stats = self
+            .peers
+            .iter()
+            .filter_map(|(remote, peer)| match &peer.peer_type {
+                PeerType::Viewer(viewer) => {
+                    let mut stats = viewer.receiver_reports.get_loss_stats()?;
+                    stats.delivery_ratio = viewer
+                        .receiver_reports
+                        .get_delivery_ratio(&viewer.outbound_streams);
+                    Some((*remote, stats))
+                }
+                PeerType::Streamer(_) => None,
+            })
+            .collect::<Vec<_>>();
+
+        if !viewer_stats.is_empty() {
+            self.server_sender
+                .send(ServerCommand::ReportViewerStats(viewer_stats))
+                .expect("Server channel should be open");
+        }
+    }
+
+    fn report_room_video(&self) {
+        let room_videos = self
+            .peers
+            .values()
+            .filter_map(|peer| match &peer.peer_type {
+                PeerType::Streamer(streamer) => {
+                    let video = RoomVideo {
+                        resolution: streamer.thumbnail_extractor.resolution,
+                        is_keyframe_seen: streamer.keyframe_monitor.is_keyframe_seen(),
+                        last_keyframe_time: streamer.keyframe_monitor.last_keyframe_time(),
+                    };
+                    Some((streamer.room_id, video))
+                }
+                PeerType::Viewer(_) => None,
+            })
+            .collect::<Vec<_>>();
+
+        if !room_videos.is_empty() {
+            self.server_sender
+                .send(ServerCommand::ReportRoomVideo(room_videos))
+                .expect("Server channel should be open");
+        }
+    }
+
+    fn report_room_bitrates(&mut self) {
+        let now = Instant::now();
+        let bitrates = self
+            .room_traffic
+            .iter_mut()
+            .map(|(room_id, traffic)| {
+                let bitrate = RoomBitrate {
+                    ingress_bps: traffic.ingress.bitrate_bps(now),
+                    egress_bps: traffic.egress.bitrate_bps(now),
+                };
+                (*room_id, bitrate)
+            })
+            .collect::<Vec<_>>();
+
+        // Idle rooms are reported once more with zero bitrate, then forgotten
+        self.room_traffic.retain(|room_id, _| {
+            bitrates
+                .iter()
+                .any(|(id, bitrate)| id == room_id && *bitrate != RoomBitrate::default())
+        });
+
+        if !bitrates.is_empty() {
+            self.server_sender
+                .send(ServerCommand::ReportRoomBitrates(self.index, bitrates))
+                .expect("Server channel should be open");
+        }
+    }
+
+    fn save_thumbnails(&mut self) {
+        // Get all ImageData of streamers that:
+        // - Have an ImageData ready
+        // - Have no thumbnail or enough time has passed for the thumbnail to be updated
+        let refresh_interval = get_reloadable_config().thumbnail_refresh_interval;
+        let thumbnails_to_update = self
+            .peers
+            .values_mut()
+            .filter_map(|peer| match &mut peer.peer_type {
+                PeerType::Viewer(_) => None,
+                PeerType::Streamer(streamer) => {
+                    let should_update_thumbnail = streamer
+                        .image_timestamp
+                        .is_none_or(|timestamp| timestamp.elapsed().gt(&refresh_interval));
+                    let last_picture = streamer.thumbnail_extractor.last_picture.as_ref()?;
+
+                    if should_update_thumbnail {
+                        // Update new thumbnail timestamp
+                        streamer.image_timestamp = Some(Instant::now());
+                        return Some((streamer.room_id, last_picture.clone()));
+                    }
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+
+        for (thumbnail_id, thumbnail_data) in thumbnails_to_update {
+            thread::spawn(move || save_thumbnail_to_storage(thumbnail_id, thumbnail_data));
+        }
+    }
+}
+
+impl Actor for MediaShard {
+    type Command = ShardCommand;
+
+    fn process_command(&mut self, command: ShardCommand) {
+        MediaShard::process_command(self, command)
+    }
+
+    fn get_session(command: &ShardCommand) -> Option<SocketAddr> {
+        match command {
+            ShardCommand::HandlePacket(_, remote)
+            | ShardCommand::AddStreamer(remote, ..)
+            | ShardCommand::AddViewer(remote, ..)
+            | ShardCommand::RemovePeer(remote)
+            | ShardCommand::MigratePeer(_, remote, _)
+            | ShardCommand::AdoptPeer(remote, _)
+            | ShardCommand::SendBye(remote) => Some(*remote),
+            ShardCommand::ForwardPacket(..)
+            | ShardCommand::RequestKeyframe(..)
+            | ShardCommand::UpdateStreamerSession(..)
+            | ShardCommand::SendSenderReports
+            | ShardCommand::SendPacedPackets
+            | ShardCommand::SendKeepalives
+            | ShardCommand::Drain(_)
+            | ShardCommand::RunPeriodicChecks => None,
+        }
+    }
+
+    fn terminate_session(&mut self, remote: SocketAddr) {
+        self.remove_peer(&remote);
+        self.server_sender
+            .send(ServerCommand::RemovePeerSession(remote))
+            .expect("Server channel should be open");
+    }
+
+    fn terminate_all_sessions(&mut self) {
+        let remotes = self.peers.keys().copied().collect::<Vec<_>>();
+        for remote in remotes {
+            self.terminate_session(remote);
+        }
+    }
+}
+
+/** Sends a datagram to a peer, recording it in the peer's packet capture.
+ */
+fn send_datagram(
+    socket: &UdpSocket,
+    capture: &Option<PacketCapture>,
+    packet: &[u8],
+    remote: SocketAddr,
+) -> io::Result<usize> {
+    if let Some(capture) = capture {
+        capture.record(Direction::Outbound, remote, packet);
+    }
+    socket.send_to(packet, remote)
+}
+
+/** SSRC to request a keyframe of, if the streamer's latest keyframe is stale at `now`. Thumbnails
+ * and stalled viewers follow the first simulcast layer, so that's the one requested.
+ */
+fn get_stale_keyframe_ssrc(
+    streamer: &mut StreamerPeer,
+    video_session: &VideoSession,
+    now: Instant,
+) -> Option<u32> {
+    if !streamer.keyframe_monitor.is_keyframe_request_due(now) {
+        return None;
+    }
+    match &streamer.simulcast_demuxer {
+        Some(demuxer) => demuxer.get_ssrc(0),
+        None => video_session.remote_ssrc,
+    }
+}
+
+/** Keyframe request for a streamer, a FIR if it negotiated one and a PLI otherwise.
+ */
+fn create_keyframe_request(
+    streamer: &mut StreamerPeer,
+    video_session: &VideoSession,
+    media_ssrc: u32,
+) -> Vec<u8> {
+    if video_session.fir_supported {
+        streamer.fir_sequence_number = streamer.fir_sequence_number.wrapping_add(1);
+        create_fir_packet(
+            video_session.host_ssrc,
+            media_ssrc,
+            streamer.fir_sequence_number,
+        )
+    } else {
+        create_pli_packet(video_session.host_ssrc, media_ssrc)
+    }
+}
+
+fn send_keyframe_request(
+    socket: &UdpSocket,
+    peer: &mut Peer,
+    media_ssrc: u32,
+    log_context: LogContext,
+) {
+    let streamer = match &mut peer.peer_type {
+        PeerType::Streamer(streamer) => streamer,
+        PeerType::Viewer(_) => return,
+    };
+    if let ClientSslState::Established(ssl_stream) = &mut peer.client.ssl_state {
+        let mut packet =
+            create_keyframe_request(streamer, &peer.media_session.video_session, media_ssrc);
+        if ssl_stream.protect_rtcp(&mut packet).is_ok() {
+            if let Err(err) =
+                send_datagram(socket, &peer.capture, &packet, peer.client.remote_address)
+            {
+                log_send_error(log_context, "RTCP keyframe request", err)
+            }
+        }
+    }
+}
+
+/** Remaps a streamer RTP packet to the viewer's negotiated session and sends it over SRTP.
+ * Returns the number of bytes sent.
+ */
+fn send_to_viewer(
+    socket: &UdpSocket,
+    outbound_buffer: &mut Vec<u8>,
+    viewer: &mut Peer,
+    packet: &[u8],
+) -> usize {
+    if !viewer.state.is_connected() {
+        return 0;
+    }
+    let log_context = viewer.log_context();
+    let viewer_peer = match &mut viewer.peer_type {
+        PeerType::Viewer(viewer_peer) => viewer_peer,
+        PeerType::Streamer(_) => return 0,
+    };
+    let ssl_stream = match &mut viewer.client.ssl_state {
+        ClientSslState::Established(ssl_stream) => ssl_stream,
+        ClientSslState::Handshake(_) | ClientSslState::Shutdown => return 0,
+    };
+
+    // Write to temp buffer
+    outbound_buffer.clear();
+    outbound_buffer
+        .write_all(packet)
+        .expect("Should write to outbound buffer");
+
+    // Remap Payload Type and SSRC to match negotiated values, skipping tracks the viewer lacks
+    if !remap_rtp_header(
+        outbound_buffer,
+        &viewer_peer.streamer_media_session,
+        &viewer.media_session,
+    ) {
+        return 0;
+    }
+
+    let ssrc = match get_rtp_header_data(outbound_buffer) {
+        Some(header) => header.ssrc,
+        None => return 0,
+    };
+    let clock_rate = get_clock_rate(&viewer.media_session, ssrc);
+    let now = Instant::now();
+
+    // Start the viewer's audio and video timelines at a common origin
+    let rtp_timestamp = clock_rate
+        .and_then(|clock_rate| {
+            viewer_peer
+                .timestamp_rebaser
+                .rebase(outbound_buffer, clock_rate, now)
+        })
+        .unwrap_or_else(|| get_rtp_timestamp(outbound_buffer));
+
+    // Number video packets in send order, so that retransmissions get fresh numbers
+    let transport_cc_extension_id = viewer
+        .media_session
+        .get_video_sessions()
+        .find(|video_session| video_session.host_ssrc == ssrc)
+        .and_then(|video_session| video_session.transport_cc_extension_id);
+    if let Some(extension_id) = transport_cc_extension_id {
+        viewer_peer
+            .transport_sequencer
+            .stamp(outbound_buffer, extension_id);
+    }
+    let payload_length =
+        get_rtp_payload_offset(outbound_buffer).map_or(0, |offset| outbound_buffer.len() - offset);
+
+    // Convert RTP to SRTP and send to remote
+    if ssl_stream.protect(outbound_buffer).is_err() {
+        return 0;
+    }
+    let bytes_sent = match send_datagram(
+        socket,
+        &viewer.capture,
+        outbound_buffer,
+        viewer.client.remote_address,
+    ) {
+        Ok(bytes_sent) => bytes_sent,
+        Err(err) => {
+            log_send_error(log_context, "RTP data", err);
+            return 0;
+        }
+    };
+
+    // Track sent media for Sender Reports
+    viewer.media_traffic.add(bytes_sent, now);
+    if let Some(clock_rate) = clock_rate {
+        viewer_peer
+            .outbound_streams
+            .entry(ssrc)
+            .or_insert_with(|| OutboundStream::new(ssrc, clock_rate, rtp_timestamp, now))
+            .register_packet(rtp_timestamp, payload_length, now);
+    }
+
+    bytes_sent
+}
+
+fn get_bandwidth_estimator(media_session: &NegotiatedSession) -> Option<Box<DelayBasedEstimator>> {
+    media_session
+        .video_session
+        .abs_send_time_extension_id
+        .map(|_| Box::new(DelayBasedEstimator::new()))
+}
+
+fn get_simulcast_demuxer(media_session: &NegotiatedSession) -> Option<SimulcastDemuxer> {
+    media_session
+        .video_session
+        .simulcast
+        .as_ref()
+        .map(SimulcastDemuxer::new)
+}
+
+/** Replayed packets are duplicates or retransmissions that already arrived, those are dropped silently.
+ */
+fn log_unprotect_error(log_context: LogContext, err: SRTPError) {
+    if err != SRTPError::ReplayDetected && is_log_enabled(LogLevel::Warn) {
+        eprintln!("{} Couldn't unprotect packet {}", log_context, err)
+    }
+}
+
+fn log_send_error(log_context: LogContext, packet_kind: &str, err: io::Error) {
+    if is_log_enabled(LogLevel::Warn) {
+        eprintln!("{} Couldn't send {} {}", log_context, packet_kind, err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use thumbnail_image_extractor::ThumbnailExtractor;
+
+    use crate::keyframe::KeyframeMonitor;
+    use crate::nack::Reporter;
+    use crate::shard::{PeerType, StreamerPeer};
+
+    fn get_streamer() -> PeerType {
+        PeerType::Streamer(StreamerPeer {
+            room_id: 1,
+            thumbnail_extractor: ThumbnailExtractor::new(),
+            image_timestamp: None,
+            keyframe_monitor: KeyframeMonitor::new(Instant::now(), None),
+            reporter: Reporter::new(528),
+            simulcast_demuxer: None,
+            bandwidth_estimator: None,
+            fir_sequence_number: 0,
+        })
+    }
+
+    mod shard_router {
+        use std::net::SocketAddr;
+        use std::sync::mpsc::channel;
+
+        use crate::shard::{get_shard_index, ShardCommand, ShardRouter};
+
+        #[test]
+        fn assigns_same_address_to_same_shard() {
+            let remote: SocketAddr = "192.168.0.10:52000".parse().unwrap();
+
+            let first = get_shard_index(&remote, 8);
+            let second = get_shard_index(&remote, 8);
+
+            assert_eq!(first, second);
+            assert!(first < 8);
+        }
+
+        #[test]
+        fn routes_packets_to_owning_shards() {
+            let (first_tx, first_rx) = channel::<ShardCommand>();
+            let (second_tx, second_rx) = channel::<ShardCommand>();
+            let router = ShardRouter::new(vec![first_tx, second_tx]);
+
+            let addresses = (50000..50100)
+                .map(|port| SocketAddr::from(([10, 0, 0, 1], port)))
+                .collect::<Vec<_>>();
+            let first_remote = addresses
+                .iter()
+                .find(|remote| router.get_shard_index(remote) == 0)
+                .expect("Some address should hash to first shard");
+            let second_remote = addresses
+                .iter()
+                .find(|remote| router.get_shard_index(remote) == 1)
+                .expect("Some address should hash to second shard");
+
+            router.send(
+                first_remote,
+                ShardCommand::HandlePacket(vec![1], *first_remote),
+            );
+            router.send(
+                second_remote,
+                ShardCommand::HandlePacket(vec![2], *second_remote),
+            );
+
+            match first_rx.try_recv() {
+                Ok(ShardCommand::HandlePacket(packet, remote)) => {
+                    assert_eq!(packet, vec![1]);
+                    assert_eq!(&remote, first_remote);
+                }
+                _ => panic!("First shard should receive its packet"),
+            }
+            match second_rx.try_recv() {
+                Ok(ShardCommand::HandlePacket(packet, remote)) => {
+                    assert_eq!(packet, vec![2]);
+                    assert_eq!(&remote, second_remote);
+                }
+                _ => panic!("Second shard should receive its packet"),
+            }
+            assert!(first_rx.try_recv().is_err());
+            assert!(second_rx.try_recv().is_err());
+        }
+    }
+
+    mod get_stale_keyframe_ssrc {
+        use std::time::{Duration, Instant, SystemTime};
+
+        use thumbnail_image_extractor::ThumbnailExtractor;
+
+        use crate::keyframe::KeyframeMonitor;
+        use crate::nack::Reporter;
+        use crate::shard::{get_stale_keyframe_ssrc, StreamerPeer};
+        use crate::test_utils::{get_simulcast_packet, get_streamer_session};
+
+        const KEYFRAME_TIMEOUT: Duration = Duration::from_secs(10);
+
+        fn get_streamer(now: Instant) -> StreamerPeer {
+            StreamerPeer {
+                room_id: 1,
+                thumbnail_extractor: ThumbnailExtractor::new(),
+                image_timestamp: None,
+                keyframe_monitor: KeyframeMonitor::new(now, Some(KEYFRAME_TIMEOUT)),
+                reporter: Reporter::new(528),
+                simulcast_demuxer: None,
+                bandwidth_estimator: None,
+                fir_sequence_number: 0,
+            }
+        }
+
+        #[test]
+        fn requests_keyframe_of_stale_stream_on_tick() {
+            let now = Instant::now();
+            let video_session = get_streamer_session().video_session;
+            let mut streamer = get_streamer(now);
+            let keyframe = get_simulcast_packet(1349455990, 1, 0, None, true);
+            streamer
+                .keyframe_monitor
+                .register_packet(&keyframe, now, SystemTime::now());
+
+            let fresh_tick = now + Duration::from_secs(3);
+            let stale_tick = now + KEYFRAME_TIMEOUT;
+
+            assert_eq!(
+                get_stale_keyframe_ssrc(&mut streamer, &video_session, fresh_tick),
+                None
+            );
+            assert_eq!(
+                get_stale_keyframe_ssrc(&mut streamer, &video_session, stale_tick),
+                Some(1349455990)
+            );
+            // Backs off until the streamer responds
+            assert_eq!(
+                get_stale_keyframe_ssrc(&mut streamer, &video_session, stale_tick),
+                None
+            );
+        }
+    }
+
+    mod create_keyframe_request {
+        use std::time::Instant;
+
+        use thumbnail_image_extractor::ThumbnailExtractor;
+
+        use crate::keyframe::KeyframeMonitor;
+        use crate::nack::Reporter;
+        use crate::rtcp::{create_fir_packet, create_pli_packet};
+        use crate::shard::{create_keyframe_request, StreamerPeer};
+        use crate::test_utils::get_streamer_session;
+
+        fn get_streamer() -> StreamerPeer {
+            StreamerPeer {
+                room_id: 1,
+                thumbnail_extractor: ThumbnailExtractor::new(),
+                image_timestamp: None,
+                keyframe_monitor: KeyframeMonitor::new(Instant::now(), None),
+                reporter: Reporter::new(528),
+                simulcast_demuxer: None,
+                bandwidth_estimator: None,
+                fir_sequence_number: 0,
+            }
+        }
+
+        #[test]
+        fn sends_fir_only_when_negotiated() {
+            let mut video_session = get_streamer_session().video_session;
+            let host_ssrc = video_session.host_ssrc;
+            let mut streamer = get_streamer();
+
+            assert_eq!(
+                create_keyframe_request(&mut streamer, &video_session, 2),
+                create_pli_packet(host_ssrc, 2)
+            );
+
+            video_session.fir_supported = true;
+            assert_eq!(
+                create_keyframe_request(&mut streamer, &video_session, 2),
+                create_fir_packet(host_ssrc, 2, 1)
+            );
+            // Each request is a new one
+            assert_eq!(
+                create_keyframe_request(&mut streamer, &video_session, 2),
+                create_fir_packet(host_ssrc, 2, 2)
+            );
+        }
+    }
+
+    mod forward_to_viewers {
+        use std::collections::HashMap;
+        use std::net::UdpSocket;
+        use std::sync::mpsc::channel;
+        use std::time::{Instant, SystemTime};
+
+        use crate::client::Client;
+        use crate::http::create_server_channel;
+        use crate::pacer::Pacer;
+        use crate::receiver_report::ReceiverReportHistory;
+        use crate::rtp::{TimestampRebaser, TransportSequencer};
+        use crate::shard::{MediaShard, PeerType, ShardRouter, ViewerPeer};
+        use crate::simulcast::LayerSelector;
+        use crate::test_utils::get_streamer_session;
+        use crate::throttle::EgressThrottle;
+
+        fn get_audio_packet(sequence_number: u16, payload: &[u8]) -> Vec<u8> {
+            let mut packet = vec![0x80, 111];
+            packet.extend_from_slice(&sequence_number.to_be_bytes());
+            packet.extend_from_slice(&[0, 0, 0, 0]);
+            packet.extend_from_slice(&1349455989u32.to_be_bytes());
+            packet.extend_from_slice(payload);
+            packet
+        }
+
+        #[test]
+        fn counts_packets_and_octets_sent_to_viewer() {
+            let shard_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+            let viewer_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+            let viewer_remote = viewer_socket.local_addr().unwrap();
+            let (shard_sender, _shard_receiver) = channel();
+            let (server_sender, _server_receiver) = create_server_channel(16);
+            let mut shard = MediaShard::new(
+                0,
+                vec![shard_socket.try_clone().unwrap()],
+                ShardRouter::new(vec![shard_sender]),
+                server_sender,
+            );
+            let viewer_session = get_streamer_session();
+            let audio_ssrc = viewer_session.audio_session.host_ssrc;
+            let viewer = PeerType::Viewer(ViewerPeer {
+                room_id: 1,
+                streamer_media_session: Box::new(get_streamer_session()),
+                egress_throttle: EgressThrottle::new(),
+                pacer: Pacer::new(),
+                outbound_streams: HashMap::new(),
+                receiver_reports: ReceiverReportHistory::new(),
+                layer_selector: LayerSelector::new(),
+                timestamp_rebaser: TimestampRebaser::new(),
+                transport_sequencer: TransportSequencer::new(),
+            });
+            let client = Client::new_plaintext(viewer_remote, shard_socket, None);
+            shard.insert_peer(viewer_remote, 0, client, None, viewer_session, viewer);
+            shard
+                .room_viewers
+                .entry(1)
+                .or_default()
+                .insert(viewer_remote);
+
+            shard.forward_to_viewers(1, None, &get_audio_packet(1, &[0xAA; 100]));
+            shard.forward_to_viewers(1, None, &get_audio_packet(2, &[0xBB; 60]));
+
+            let report = match &shard.peers[&viewer_remote].peer_type {
+                PeerType::Viewer(viewer) => viewer.outbound_streams[&audio_ssrc]
+                    .create_report(Instant::now(), SystemTime::now()),
+                PeerType::Streamer(_) => panic!("Peer should be a viewer"),
+            };
+            // Octets count the payload only
+            assert_eq!(report.packet_count, 2);
+            assert_eq!(report.octet_count, 160);
+        }
+    }
+
+    mod handle_packet {
+        use std::net::{SocketAddr, UdpSocket};
+        use std::sync::mpsc::channel;
+
+        use openssl::ssl::HandshakeError;
+
+        use crate::acceptor::{CertificateSource, SSLConfig};
+        use crate::client::{Client, ClientSslState, UDPPeerStream};
+        use crate::http::{create_server_channel, ServerCommand};
+        use crate::rtcp::create_bye_packet;
+        use crate::shard::tests::get_streamer;
+        use crate::shard::{MediaShard, ShardRouter, MAX_EARLY_MEDIA_PACKETS};
+        use crate::test_utils::get_streamer_session;
+
+        #[test]
+        fn removes_peer_after_dtls_shutdown() {
+            let shard_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+            let remote: SocketAddr = "127.0.0.1:40000".parse().unwrap();
+            let (shard_sender, _shard_receiver) = channel();
+            let (server_sender, server_receiver) = create_server_channel(16);
+            let mut shard = MediaShard::new(
+                0,
+                vec![shard_socket.try_clone().unwrap()],
+                ShardRouter::new(vec![shard_sender]),
+                server_sender,
+            );
+            let client = Client::new_plaintext(remote, shard_socket, None);
+            let session = get_streamer_session();
+            shard.insert_peer(remote, 0, client, None, session, get_streamer());
+            // Skips the state update of inserting a plaintext peer
+            server_receiver.try_recv().unwrap();
+            shard.peers.get_mut(&remote).unwrap().client.ssl_state = ClientSslState::Shutdown;
+
+            // DTLS alert record
+            shard.handle_packet(&[21, 254, 253, 0, 0, 0, 0, 0, 0, 0, 1, 0, 2, 1, 0], remote);
+
+            assert!(!shard.peers.contains_key(&remote));
+            assert!(matches!(
+                server_receiver.try_recv(),
+                Ok(ServerCommand::RemovePeerSession(removed_remote)) if removed_remote == remote
+            ));
+        }
+
+        #[test]
+        fn ends_session_once_every_source_left() {
+            let shard_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+            let remote: SocketAddr = "127.0.0.1:40000".parse().unwrap();
+            let (shard_sender, _shard_receiver) = channel();
+            let (server_sender, server_receiver) = create_server_channel(16);
+            let mut shard = MediaShard::new(
+                0,
+                vec![shard_socket.try_clone().unwrap()],
+                ShardRouter::new(vec![shard_sender]),
+                server_sender,
+            );
+            let client = Client::new_plaintext(remote, shard_socket, None);
+            let session = get_streamer_session();
+            shard.insert_peer(remote, 0, client, None, session, get_streamer());
+            server_receiver.try_recv().unwrap();
+
+            // The camera is toggled, replacing the video source
+            shard.handle_packet(&create_bye_packet(None, &[1349455990]), remote);
+            assert!(server_receiver.try_recv().is_err());
+            let media_session = &shard.peers[&remote].media_session;
+            assert_eq!(media_session.video_session.remote_ssrc, None);
+            assert_eq!(media_session.audio_session.remote_ssrc, Some(1349455989));
+
+            shard.handle_packet(&create_bye_packet(None, &[1349455989]), remote);
+            assert!(matches!(
+                server_receiver.try_recv(),
+                Ok(ServerCommand::RemovePeerSession(removed_remote)) if removed_remote == remote
+            ));
+        }
+
+        #[test]
+        fn buffers_media_until_handshake_finishes() {
+            let shard_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+            let remote: SocketAddr = "127.0.0.1:40000".parse().unwrap();
+            let (shard_sender, _shard_receiver) = channel();
+            let (server_sender, server_receiver) = create_server_channel(16);
+            let mut shard = MediaShard::new(
+                0,
+                vec![shard_socket.try_clone().unwrap()],
+                ShardRouter::new(vec![shard_sender]),
+                server_sender,
+            );
+            let client = Client::new_plaintext(remote, shard_socket.try_clone().unwrap(), None);
+            let session = get_streamer_session();
+            shard.insert_peer(remote, 0, client, None, session, get_streamer());
+            server_receiver.try_recv().unwrap();
+            let acceptor = SSLConfig::new(CertificateSource::Generated).acceptor;
+            let udp_stream = UDPPeerStream::new(shard_socket, remote, None);
+            let mid_handshake = match acceptor.accept(udp_stream) {
+                Err(HandshakeError::WouldBlock(mid_handshake)) => mid_handshake,
+                _ => panic!("Handshake should wait for the remote"),
+            };
+            let plaintext_state = std::mem::replace(
+                &mut shard.peers.get_mut(&remote).unwrap().client.ssl_state,
+                ClientSslState::Handshake(mid_handshake),
+            );
+
+            // The streamer leaves before its keys are ready
+            shard.handle_packet(&create_bye_packet(None, &[1349455990, 1349455989]), remote);
+            for _ in 0..MAX_EARLY_MEDIA_PACKETS {
+                shard.handle_packet(&create_bye_packet(None, &[1]), remote);
+            }
+            assert!(server_receiver.try_recv().is_err());
+            let early_media = &shard.peers[&remote].early_media;
+            assert_eq!(early_media.len(), MAX_EARLY_MEDIA_PACKETS);
+
+            shard.peers.get_mut(&remote).unwrap().client.ssl_state = plaintext_state;
+            shard.read_early_media(remote);
+            assert!(matches!(
+                server_receiver.try_recv(),
+                Ok(ServerCommand::RemovePeerSession(removed_remote)) if removed_remote == remote
+            ));
+        }
+    }
+
+    mod send_keepalives {
+        use std::io::ErrorKind;
+        use std::net::UdpSocket;
+        use std::sync::mpsc::channel;
+        use std::time::Duration;
+
+        use crate::client::Client;
+        use crate::http::create_server_channel;
+        use crate::session_state::SessionState;
+        use crate::shard::tests::get_streamer;
+        use crate::shard::{MediaShard, ShardRouter};
+        use crate::stun::{get_stun_packet, ICEStunMessageType};
+        use crate::test_utils::get_streamer_session;
+
+        #[test]
+        fn sends_indications_to_connected_peers_only() {
+            let shard_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+            let connected_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+            let handshaking_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+            let (shard_sender, _shard_receiver) = channel();
+            let (server_sender, _server_receiver) = create_server_channel(16);
+            let mut shard = MediaShard::new(
+                0,
+                vec![shard_socket.try_clone().unwrap()],
+                ShardRouter::new(vec![shard_sender]),
+                server_sender,
+            );
+            for socket in [&connected_socket, &handshaking_socket] {
+                let remote = socket.local_addr().unwrap();
+                let client = Client::new_plaintext(remote, shard_socket.try_clone().unwrap(), None);
+                let session = get_streamer_session();
+                shard.insert_peer(remote, 0, client, None, session, get_streamer());
+            }
+            let handshaking_remote = handshaking_socket.local_addr().unwrap();
+            shard.peers.get_mut(&handshaking_remote).unwrap().state = SessionState::DtlsHandshaking;
+
+            shard.send_keepalives();
+
+            let mut buffer = [0u8; 1500];
+            connected_socket
+                .set_read_timeout(Some(Duration::from_secs(1)))
+                .unwrap();
+            let len = connected_socket.recv(&mut buffer).unwrap();
+            let indication = get_stun_packet(&buffer[..len]);
+            assert!(matches!(indication, Some(ICEStunMessageType::Indication)));
+            handshaking_socket.set_nonblocking(true).unwrap();
+            let err = handshaking_socket.recv(&mut buffer).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::WouldBlock);
+        }
+    }
+
+    mod plaintext_media {
+        use std::collections::HashMap;
+        use std::fs::File;
+        use std::io::BufReader;
+        use std::net::{SocketAddr, UdpSocket};
+        use std::path::Path;
+        use std::sync::mpsc::channel;
+        use std::time::{Duration, Instant};
+
+        use thumbnail_image_extractor::{read_rtp_packets_raw, ThumbnailExtractor};
+
+        use crate::client::Client;
+        use crate::http::{create_server_channel, ServerCommand};
+        use crate::keyframe::KeyframeMonitor;
+        use crate::nack::Reporter;
+        use crate::pacer::Pacer;
+        use crate::receiver_report::ReceiverReportHistory;
+        use crate::rtp::{get_rtp_header_data, TimestampRebaser, TransportSequencer};
+        use crate::session_state::SessionState;
+        use crate::shard::{MediaShard, PeerType, ShardRouter, StreamerPeer, ViewerPeer};
+        use crate::simulcast::LayerSelector;
+        use crate::test_utils::get_streamer_session;
+        use crate::throttle::EgressThrottle;
+
+        const RTP_DUMP_PATH: &str =
+            "crates/thumbnail_image_extractor/assets/wireshark-dump-test.rtp";
+
+        fn bind_socket() -> UdpSocket {
+            let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+            socket
+                .set_read_timeout(Some(Duration::from_secs(1)))
+                .unwrap();
+            socket
+        }
+
+        #[test]
+        fn forwards_plain_rtp_fixture_to_viewer() {
+            let shard_socket = bind_socket();
+            let streamer_socket = bind_socket();
+            let viewer_socket = bind_socket();
+            let streamer_remote = streamer_socket.local_addr().unwrap();
+            let viewer_remote = viewer_socket.local_addr().unwrap();
+            let (shard_sender, _shard_receiver) = channel();
+            let (server_sender, server_receiver) = create_server_channel(16);
+            let mut shard = MediaShard::new(
+                0,
+                vec![shard_socket.try_clone().unwrap()],
+                ShardRouter::new(vec![shard_sender]),
+                server_sender,
+            );
+            let streamer_session = get_streamer_session();
+            // Viewer negotiating the streamer's own payload types
+            let viewer_session = get_streamer_session();
+            let viewer_ssrc = viewer_session.video_session.host_ssrc;
+
+            let add_peer = |shard: &mut MediaShard, remote: SocketAddr, peer_type, session| {
+                let client = Client::new_plaintext(remote, shard_socket.try_clone().unwrap(), None);
+                shard.insert_peer(remote, 0, client, None, session, peer_type);
+            };
+            let streamer = PeerType::Streamer(StreamerPeer {
+                room_id: 1,
+                thumbnail_extractor: ThumbnailExtractor::new(),
+                image_timestamp: None,
+                keyframe_monitor: KeyframeMonitor::new(Instant::now(), None),
+                reporter: Reporter::new(528),
+                simulcast_demuxer: None,
+                bandwidth_estimator: None,
+                fir_sequence_number: 0,
+            });
+            add_peer(
+                &mut shard,
+                streamer_remote,
+                streamer,
+                streamer_session.clone(),
+            );
+            let viewer = PeerType::Viewer(ViewerPeer {
+                room_id: 1,
+                streamer_media_session: Box::new(streamer_session),
+                egress_throttle: EgressThrottle::new(),
+                pacer: Pacer::new(),
+                outbound_streams: HashMap::new(),
+                receiver_reports: ReceiverReportHistory::new(),
+                layer_selector: LayerSelector::new(),
+                timestamp_rebaser: TimestampRebaser::new(),
+                transport_sequencer: TransportSequencer::new(),
+            });
+            add_peer(&mut shard, viewer_remote, viewer, viewer_session);
+            shard
+                .room_viewers
+                .entry(1)
+                .or_default()
+                .insert(viewer_remote);
+
+            // Both peers skip the handshake
+            for _ in 0..2 {
+                match server_receiver.try_recv() {
+                    Ok(ServerCommand::UpdateSessionState(_, state)) => {
+                        assert_eq!(state, SessionState::Connected)
+                    }
+                    _ => panic!("Peers should be reported connected"),
+                }
+            }
+
+            let rtp_dump_path = Path::new(env!("CARGO_MANIFEST_DIR")).join(RTP_DUMP_PATH);
+            let rtp_dump = File::open(rtp_dump_path).expect("RTP dump fixture should exist");
+            for packet in read_rtp_packets_raw(BufReader::new(rtp_dump)) {
+                shard.handle_packet(&packet, streamer_remote);
+            }
+
+            let mut buffer = [0u8; 2000];
+            let (len, _) = viewer_socket
+                .recv_from(&mut buffer)
+                .expect("Viewer should receive forwarded RTP");
+            let header = get_rtp_header_data(&buffer[..len]).expect("Should forward RTP header");
+            assert_eq!(header.ssrc, viewer_ssrc);
+
+            match &shard.peers[&streamer_remote].peer_type {
+                PeerType::Streamer(streamer) => {
+                    assert!(streamer.thumbnail_extractor.last_picture.is_some())
+                }
+                PeerType::Viewer(_) => panic!("Peer should be a streamer"),
+            }
+        }
+    }
+}
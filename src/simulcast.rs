@@ -0,0 +1,261 @@
+use std::collections::HashMap;
+
+use byteorder::{ByteOrder, NetworkEndian};
+use sdp::SimulcastSession;
+
+use crate::rtp::{
+    get_rtp_header_data, get_rtp_header_extension, get_rtp_sequence_number, get_rtp_timestamp,
+    is_keyframe_packet,
+};
+
+/** Index of a simulcast layer, in the order of the streamer's simulcast attribute.
+ */
+pub type LayerIndex = usize;
+
+/** Sorts the video packets of a simulcast streamer into layers. Each layer has its own SSRC, which packets
+ * tie to a layer with the RID header extension. Streamers stop sending the extension once the SSRC is known,
+ * so the mapping is remembered.
+ */
+pub struct SimulcastDemuxer {
+    rids: Vec<String>,
+    rid_extension_id: u8,
+    layers: HashMap<u32, LayerIndex>,
+}
+
+impl SimulcastDemuxer {
+    pub fn new(simulcast_session: &SimulcastSession) -> Self {
+        SimulcastDemuxer {
+            rids: simulcast_session.rids.clone(),
+            rid_extension_id: simulcast_session.rid_extension_id,
+            layers: HashMap::new(),
+        }
+    }
+
+    /** Finds the layer of a video packet. Packets of SSRCs not yet tied to a known RID have no layer.
+     */
+    pub fn get_layer(&mut self, packet: &[u8]) -> Option<LayerIndex> {
+        let ssrc = get_rtp_header_data(packet)?.ssrc;
+        if let Some(layer) = self.layers.get(&ssrc) {
+            return Some(*layer);
+        }
+
+        let rid = get_rtp_header_extension(packet, self.rid_extension_id)?;
+        let layer = self.rids.iter().position(|known| known.as_bytes() == rid)?;
+        // A layer moving to a new SSRC is no longer sent on the previous one
+        self.layers.retain(|_, known| *known != layer);
+        self.layers.insert(ssrc, layer);
+        Some(layer)
+    }
+
+    pub fn get_ssrcs(&self) -> Vec<u32> {
+        self.layers.keys().copied().collect()
+    }
+
+    pub fn get_ssrc(&self, layer: LayerIndex) -> Option<u32> {
+        self.layers
+            .iter()
+            .find_map(|(ssrc, known)| (*known == layer).then_some(*ssrc))
+    }
+}
+
+/** Chooses the simulcast layer forwarded to a single viewer, starting with the first offered one. Switching
+ * to another layer waits for its keyframe, as its delta frames can't be decoded on top of another layer.
+ * Sequence numbers and timestamps are rewritten, so that the viewer sees one continuous stream.
+ */
+pub struct LayerSelector {
+    current_layer: Option<LayerIndex>,
+    target_layer: LayerIndex,
+    sequence_number_offset: u16,
+    timestamp_offset: u32,
+    last_forwarded: Option<(u16, u32)>,
+}
+
+impl LayerSelector {
+    pub fn new() -> Self {
+        LayerSelector {
+            current_layer: None,
+            target_layer: 0,
+            sequence_number_offset: 0,
+            timestamp_offset: 0,
+            last_forwarded: None,
+        }
+    }
+
+    /** Targets the layer with the highest bitrate fitting the viewer's estimate, or the lowest one if none
+     * fits. Returns the new target layer if it changed, a keyframe of it should be requested from the streamer.
+     */
+    pub fn select_layer(
+        &mut self,
+        layer_bitrates: &[(LayerIndex, u64)],
+        estimate_bps: u64,
+    ) -> Option<LayerIndex> {
+        // Ties go to the layer offered first
+        let fitting_layer = layer_bitrates
+            .iter()
+            .filter(|(_, bitrate)| *bitrate <= estimate_bps)
+            .max_by_key(|(layer, bitrate)| (*bitrate, std::cmp::Reverse(*layer)));
+        let lowest_layer = layer_bitrates
+            .iter()
+            .min_by_key(|(layer, bitrate)| (*bitrate, *layer));
+        let (layer, _) = fitting_layer.or(lowest_layer)?;
+
+        if *layer == self.target_layer {
+            return None;
+        }
+        self.target_layer = *layer;
+        Some(*layer)
+    }
+
+    /** Whether a packet of the given layer goes to the viewer, rewriting it if so.
+     */
+    pub fn forward(&mut self, layer: LayerIndex, packet: &mut [u8]) -> bool {
+        let is_switching = self.current_layer != Some(self.target_layer);
+        if is_switching && layer == self.target_layer && is_keyframe_packet(packet) {
+            // Continue right after the last forwarded packet
+            if let Some((sequence_number, timestamp)) = self.last_forwarded {
+                self.sequence_number_offset = sequence_number
+                    .wrapping_add(1)
+                    .wrapping_sub(get_rtp_sequence_number(packet));
+                self.timestamp_offset = timestamp
+                    .wrapping_add(1)
+                    .wrapping_sub(get_rtp_timestamp(packet));
+            }
+            self.current_layer = Some(layer);
+        }
+        if self.current_layer != Some(layer) {
+            return false;
+        }
+
+        let sequence_number =
+            get_rtp_sequence_number(packet).wrapping_add(self.sequence_number_offset);
+        let timestamp = get_rtp_timestamp(packet).wrapping_add(self.timestamp_offset);
+        NetworkEndian::write_u16(&mut packet[2..4], sequence_number);
+        NetworkEndian::write_u32(&mut packet[4..8], timestamp);
+        self.last_forwarded = Some((sequence_number, timestamp));
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod simulcast_demuxer {
+        use sdp::SimulcastSession;
+
+        use crate::simulcast::SimulcastDemuxer;
+        use crate::test_utils::get_simulcast_packet;
+
+        const RID_EXTENSION_ID: u8 = 4;
+
+        fn get_demuxer() -> SimulcastDemuxer {
+            SimulcastDemuxer::new(&SimulcastSession {
+                rids: vec!["h".to_string(), "l".to_string()],
+                rid_extension_id: RID_EXTENSION_ID,
+            })
+        }
+
+        fn get_video_packet(ssrc: u32, rid: Option<&str>) -> Vec<u8> {
+            get_simulcast_packet(ssrc, 1, RID_EXTENSION_ID, rid, false)
+        }
+
+        #[test]
+        fn sorts_packets_by_rid() {
+            let mut demuxer = get_demuxer();
+
+            assert_eq!(demuxer.get_layer(&get_video_packet(10, Some("l"))), Some(1));
+            assert_eq!(demuxer.get_layer(&get_video_packet(20, Some("h"))), Some(0));
+        }
+
+        #[test]
+        fn remembers_layer_of_ssrc() {
+            let mut demuxer = get_demuxer();
+
+            assert_eq!(demuxer.get_layer(&get_video_packet(10, None)), None);
+            assert_eq!(demuxer.get_layer(&get_video_packet(10, Some("l"))), Some(1));
+            assert_eq!(demuxer.get_layer(&get_video_packet(10, None)), Some(1));
+        }
+
+        #[test]
+        fn moves_layer_to_new_ssrc() {
+            let mut demuxer = get_demuxer();
+
+            demuxer.get_layer(&get_video_packet(10, Some("l")));
+            demuxer.get_layer(&get_video_packet(30, Some("l")));
+
+            assert_eq!(demuxer.get_ssrc(1), Some(30));
+            assert_eq!(demuxer.get_layer(&get_video_packet(10, None)), None);
+        }
+
+        #[test]
+        fn skips_unknown_rid() {
+            let mut demuxer = get_demuxer();
+
+            assert_eq!(demuxer.get_layer(&get_video_packet(10, Some("m"))), None);
+        }
+    }
+
+    mod layer_selector {
+        use byteorder::{ByteOrder, NetworkEndian};
+
+        use crate::simulcast::LayerSelector;
+        use crate::test_utils::get_simulcast_packet;
+
+        const LAYER_BITRATES: [(usize, u64); 2] = [(0, 2_000_000), (1, 300_000)];
+
+        fn forward(
+            layer_selector: &mut LayerSelector,
+            layer: usize,
+            sequence_number: u16,
+            is_keyframe: bool,
+        ) -> Option<u16> {
+            let mut packet =
+                get_simulcast_packet(layer as u32, sequence_number, 4, None, is_keyframe);
+            layer_selector
+                .forward(layer, &mut packet)
+                .then(|| NetworkEndian::read_u16(&packet[2..4]))
+        }
+
+        #[test]
+        fn forwards_low_layer_to_low_estimate_viewer() {
+            let mut layer_selector = LayerSelector::new();
+
+            assert_eq!(
+                layer_selector.select_layer(&LAYER_BITRATES, 500_000),
+                Some(1)
+            );
+            assert_eq!(layer_selector.select_layer(&LAYER_BITRATES, 600_000), None);
+
+            assert_eq!(forward(&mut layer_selector, 0, 10, true), None);
+            assert_eq!(forward(&mut layer_selector, 1, 20, true), Some(20));
+            assert_eq!(forward(&mut layer_selector, 1, 21, false), Some(21));
+            assert_eq!(forward(&mut layer_selector, 0, 11, false), None);
+        }
+
+        #[test]
+        fn falls_back_to_lowest_layer() {
+            let mut layer_selector = LayerSelector::new();
+
+            assert_eq!(
+                layer_selector.select_layer(&LAYER_BITRATES, 100_000),
+                Some(1)
+            );
+        }
+
+        #[test]
+        fn switches_layer_on_keyframe() {
+            let mut layer_selector = LayerSelector::new();
+            layer_selector.select_layer(&LAYER_BITRATES, 500_000);
+            forward(&mut layer_selector, 1, 20, true);
+
+            assert_eq!(
+                layer_selector.select_layer(&LAYER_BITRATES, 3_000_000),
+                Some(0)
+            );
+            assert_eq!(forward(&mut layer_selector, 0, 500, false), None);
+            assert_eq!(forward(&mut layer_selector, 1, 21, false), Some(21));
+            // Sequence numbers continue after the last packet of the previous layer
+            assert_eq!(forward(&mut layer_selector, 0, 501, true), Some(22));
+            assert_eq!(forward(&mut layer_selector, 1, 22, false), None);
+            assert_eq!(forward(&mut layer_selector, 0, 502, false), Some(23));
+        }
+    }
+}
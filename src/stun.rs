@@ -1,5 +1,5 @@
 use std::io::{BufReader, BufWriter, Error, Read, Write};
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 
 use byteorder::{BigEndian, ByteOrder, ReadBytesExt, WriteBytesExt};
 use openssl::hash::MessageDigest;
@@ -17,10 +17,12 @@ fn parse_stun_packet(packet: &[u8]) -> Option<StunBindingRequest> {
         return None;
     }
     let mut reader = BufReader::new(packet);
-    let message_type = reader.read_u16::<BigEndian>().ok()?;
-    if message_type != StunType::BindingRequest as u16 {
-        return None;
-    }
+    // Indications, e.g. keepalives of the remote, expect no response
+    let is_indication = match reader.read_u16::<BigEndian>().ok()? {
+        message_type if message_type == StunType::BindingRequest as u16 => false,
+        message_type if message_type == StunType::BindingIndication as u16 => true,
+        _ => return None,
+    };
 
     let length = reader.read_u16::<BigEndian>().ok()?;
     if length % 4 != 0 || STUN_HEADER_LEN + length as usize > packet.len() {
@@ -53,12 +55,11 @@ fn parse_stun_packet(packet: &[u8]) -> Option<StunBindingRequest> {
 
         match attribute_type {
             StunAttributeType::Username => {
-                let username_string = String::from_utf8(value_buffer).unwrap();
-                let (host_username, remote_username) = username_string.split_once(":").unwrap();
-                attributes.push(StunAttribute::Username(SessionUsername {
-                    host: host_username.trim_end_matches(char::from(0)).to_owned(), // Remove null chars
-                    remote: remote_username.trim_end_matches(char::from(0)).to_owned(),
-                }))
+                let username_string = String::from_utf8(value_buffer).ok()?;
+                // Remove null chars of the padding
+                let username_string = username_string.trim_end_matches(char::from(0));
+                let session_username = SessionUsername::try_from(username_string).ok()?;
+                attributes.push(StunAttribute::Username(session_username))
             }
             StunAttributeType::MessageIntegrity => {
                 let mut buffer: [u8; STUN_MESSAGE_INTEGRITY_LEN] = [0; STUN_MESSAGE_INTEGRITY_LEN];
@@ -74,10 +75,15 @@ fn parse_stun_packet(packet: &[u8]) -> Option<StunBindingRequest> {
     return Some(StunBindingRequest {
         transaction_id,
         attributes,
+        is_indication,
     });
 }
 
 fn parse_binding_request(stun_message: StunBindingRequest) -> Option<ICEStunMessageType> {
+    if stun_message.is_indication {
+        return Some(ICEStunMessageType::Indication);
+    }
+
     let message_integrity = stun_message.attributes.iter().find_map(|attr| match attr {
         StunAttribute::MessageIntegrity(integrity) => Some(*integrity),
         _ => None,
@@ -113,6 +119,88 @@ pub fn get_stun_packet(data: &[u8]) -> Option<ICEStunMessageType> {
     parse_stun_packet(data).and_then(parse_binding_request)
 }
 
+/** Binding request without attributes, as sent to a public STUN server to learn our mapped address.
+ */
+pub fn create_stun_binding_request(
+    transaction_id: [u8; STUN_TRANSACTION_ID_LEN],
+) -> [u8; STUN_HEADER_LEN] {
+    create_attributeless_message(StunType::BindingRequest, transaction_id)
+}
+
+/** Binding indication sent to nominated peers to keep NAT mappings open. It expects no response.
+https://datatracker.ietf.org/doc/html/rfc5389#section-10
+ */
+pub fn create_stun_binding_indication(
+    transaction_id: [u8; STUN_TRANSACTION_ID_LEN],
+) -> [u8; STUN_HEADER_LEN] {
+    create_attributeless_message(StunType::BindingIndication, transaction_id)
+}
+
+fn create_attributeless_message(
+    message_type: StunType,
+    transaction_id: [u8; STUN_TRANSACTION_ID_LEN],
+) -> [u8; STUN_HEADER_LEN] {
+    let mut buffer = [0u8; STUN_HEADER_LEN];
+    BigEndian::write_u16(&mut buffer[..2], message_type as u16);
+    BigEndian::write_u16(&mut buffer[2..4], 0);
+    BigEndian::write_u32(&mut buffer[4..8], STUN_COOKIE);
+    buffer[8..20].copy_from_slice(&transaction_id);
+    buffer
+}
+
+/** Reads the XOR-MAPPED-ADDRESS of a binding success response to the given transaction.
+ */
+pub fn get_xor_mapped_address(
+    data: &[u8],
+    transaction_id: [u8; STUN_TRANSACTION_ID_LEN],
+) -> Option<SocketAddr> {
+    let header = data.get(..STUN_HEADER_LEN)?;
+    if BigEndian::read_u16(&header[..2]) != StunType::SuccessResponse as u16
+        || BigEndian::read_u32(&header[4..8]) != STUN_COOKIE
+        || header[8..20] != transaction_id
+    {
+        return None;
+    }
+
+    let mut offset = STUN_HEADER_LEN;
+    while let Some(attribute_header) = data.get(offset..offset + 4) {
+        let attribute_type = BigEndian::read_u16(&attribute_header[..2]);
+        let length = BigEndian::read_u16(&attribute_header[2..4]);
+        let value = data.get(offset + 4..offset + 4 + length as usize)?;
+
+        if attribute_type == StunAttributeType::XORMappedAddress as u16 {
+            return read_xor_mapped_address(value, transaction_id);
+        }
+
+        offset += 4 + pad_to_4bytes(length) as usize;
+    }
+    None
+}
+
+fn read_xor_mapped_address(
+    value: &[u8],
+    transaction_id: [u8; STUN_TRANSACTION_ID_LEN],
+) -> Option<SocketAddr> {
+    let port = BigEndian::read_u16(value.get(2..4)?) ^ (STUN_COOKIE >> 16) as u16;
+
+    match value.get(1)? {
+        0x01 => {
+            let mut address: [u8; 4] = value.get(4..8)?.try_into().ok()?;
+            xor_range(&mut address, &STUN_COOKIE.to_be_bytes());
+            Some(SocketAddr::new(IpAddr::from(address), port))
+        }
+        0x02 => {
+            let mut address: [u8; 16] = value.get(4..20)?.try_into().ok()?;
+            let mut mask = [0; 16];
+            mask[0..4].copy_from_slice(&STUN_COOKIE.to_be_bytes());
+            mask[4..].copy_from_slice(&transaction_id);
+            xor_range(&mut address, &mask);
+            Some(SocketAddr::new(IpAddr::from(address), port))
+        }
+        _ => None,
+    }
+}
+
 pub fn create_stun_success(
     credentials: &ICECredentials,
     transaction_id: [u8; STUN_TRANSACTION_ID_LEN],
@@ -307,12 +395,16 @@ fn pad_to_4bytes(value: u16) -> u16 {
 pub struct StunBindingRequest {
     pub attributes: Vec<StunAttribute>,
     pub transaction_id: [u8; STUN_TRANSACTION_ID_LEN],
+    pub is_indication: bool,
 }
 
 #[derive(Debug)]
 pub enum ICEStunMessageType {
     LiveCheck(ICEStunPacket),
     Nomination(ICEStunPacket),
+    /** Binding indication, carrying no credentials and expecting no response.
+     */
+    Indication,
 }
 
 #[derive(Debug)]
@@ -336,6 +428,7 @@ enum StunAttributeType {
 
 enum StunType {
     BindingRequest = 0x0001,
+    BindingIndication = 0x0011,
     SuccessResponse = 0x0101,
 }
 
@@ -351,6 +444,101 @@ pub enum StunAttribute {
 const STUN_MESSAGE_INTEGRITY_LEN: usize = 20;
 const STUN_MESSAGE_INTEGRITY_ATTRIBUTE_LEN: usize = 24;
 
-const STUN_TRANSACTION_ID_LEN: usize = 12;
-const STUN_HEADER_LEN: usize = 20;
-const STUN_COOKIE: u32 = 0x2112a442;
+pub const STUN_TRANSACTION_ID_LEN: usize = 12;
+pub const STUN_HEADER_LEN: usize = 20;
+pub const STUN_COOKIE: u32 = 0x2112a442;
+
+#[cfg(test)]
+mod tests {
+    mod create_stun_binding_indication {
+        use byteorder::{BigEndian, ByteOrder};
+
+        use crate::demux::{get_packet_type, PacketType};
+        use crate::stun::{create_stun_binding_indication, StunType, STUN_COOKIE};
+
+        #[test]
+        fn creates_well_formed_indication() {
+            let indication = create_stun_binding_indication([7; 12]);
+
+            assert_eq!(get_packet_type(&indication), PacketType::Stun);
+            assert_eq!(
+                BigEndian::read_u16(&indication[..2]),
+                StunType::BindingIndication as u16
+            );
+            assert_eq!(BigEndian::read_u16(&indication[2..4]), 0);
+            assert_eq!(BigEndian::read_u32(&indication[4..8]), STUN_COOKIE);
+            assert_eq!(indication[8..], [7; 12]);
+        }
+    }
+
+    mod get_stun_packet {
+        use crate::stun::{
+            create_stun_binding_indication, create_stun_binding_request, get_stun_packet,
+            ICEStunMessageType,
+        };
+
+        #[test]
+        fn classifies_binding_indication() {
+            let indication = create_stun_binding_indication([7; 12]);
+
+            assert!(matches!(
+                get_stun_packet(&indication),
+                Some(ICEStunMessageType::Indication)
+            ));
+            // Requests without credentials aren't ICE checks
+            assert!(get_stun_packet(&create_stun_binding_request([7; 12])).is_none());
+        }
+    }
+
+    mod get_xor_mapped_address {
+        use std::net::SocketAddr;
+
+        use byteorder::{BigEndian, ByteOrder};
+
+        use crate::stun::{
+            compute_xor_mapped_address, create_stun_binding_request, get_xor_mapped_address,
+            StunType,
+        };
+
+        fn get_success_response(mapped_address: &SocketAddr, transaction_id: [u8; 12]) -> Vec<u8> {
+            let attribute = compute_xor_mapped_address(mapped_address, transaction_id).unwrap();
+            let mut response = create_stun_binding_request(transaction_id).to_vec();
+            BigEndian::write_u16(&mut response[..2], StunType::SuccessResponse as u16);
+            BigEndian::write_u16(&mut response[2..4], attribute.len() as u16);
+            response.extend_from_slice(&attribute);
+            response
+        }
+
+        #[test]
+        fn reads_ipv4_address() {
+            let transaction_id = [7; 12];
+            let mapped_address: SocketAddr = "203.0.113.7:41000".parse().unwrap();
+            let response = get_success_response(&mapped_address, transaction_id);
+
+            assert_eq!(
+                get_xor_mapped_address(&response, transaction_id),
+                Some(mapped_address)
+            );
+        }
+
+        #[test]
+        fn reads_ipv6_address() {
+            let transaction_id = [7; 12];
+            let mapped_address: SocketAddr = "[2001:db8::7]:41000".parse().unwrap();
+            let response = get_success_response(&mapped_address, transaction_id);
+
+            assert_eq!(
+                get_xor_mapped_address(&response, transaction_id),
+                Some(mapped_address)
+            );
+        }
+
+        #[test]
+        fn ignores_response_to_other_transaction() {
+            let mapped_address: SocketAddr = "203.0.113.7:41000".parse().unwrap();
+            let response = get_success_response(&mapped_address, [7; 12]);
+
+            assert_eq!(get_xor_mapped_address(&response, [8; 12]), None);
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use std::any::Any;
+use std::net::SocketAddr;
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+use std::sync::mpsc::Receiver;
+
+/** Restarts granted to an actor after a panic it couldn't pin on a single session.
+ */
+const MAX_ACTOR_RESTARTS: usize = 1;
+
+/** Thread owning the state of many sessions, driven by commands from a channel.
+ */
+pub trait Actor {
+    type Command;
+
+    fn process_command(&mut self, command: Self::Command);
+
+    /** Remote address of the only session a command touches, if any.
+     */
+    fn get_session(command: &Self::Command) -> Option<SocketAddr>;
+
+    /** Drops the state of a session and has the main loop tear it down.
+     */
+    fn terminate_session(&mut self, remote: SocketAddr);
+
+    fn terminate_all_sessions(&mut self);
+}
+
+/** Runs an actor until its channel closes, isolating panics of single commands.
+ * - A panic of a command touching a single session terminates that session, the actor carries on
+ * - Any other panic may have left the actor inconsistent, all its sessions are terminated and the actor is
+ *   restarted from scratch. Once out of restarts, the panic is propagated
+ */
+pub fn supervise<A: Actor>(
+    name: &str,
+    create_actor: impl Fn() -> A,
+    receiver: Receiver<A::Command>,
+) {
+    let mut actor = create_actor();
+    let mut restart_count = 0;
+
+    while let Ok(command) = receiver.recv() {
+        let session = A::get_session(&command);
+        let cause = match catch_unwind(AssertUnwindSafe(|| actor.process_command(command))) {
+            Ok(()) => continue,
+            Err(cause) => cause,
+        };
+
+        match session {
+            Some(remote) => {
+                eprintln!(
+                    "{} panicked handling {}, terminating session: {}",
+                    name,
+                    remote,
+                    get_panic_message(&cause)
+                );
+                actor.terminate_session(remote);
+            }
+            None => {
+                eprintln!(
+                    "{} panicked, terminating all sessions: {}",
+                    name,
+                    get_panic_message(&cause)
+                );
+                actor.terminate_all_sessions();
+                if restart_count == MAX_ACTOR_RESTARTS {
+                    resume_unwind(cause);
+                }
+                restart_count += 1;
+                actor = create_actor();
+            }
+        }
+    }
+}
+
+fn get_panic_message(cause: &Box<dyn Any + Send>) -> &str {
+    if let Some(message) = cause.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = cause.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown cause"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod supervise {
+        use std::net::SocketAddr;
+        use std::sync::mpsc::{channel, Sender};
+        use std::thread;
+
+        use crate::supervisor::{supervise, Actor};
+
+        enum TestCommand {
+            Process(SocketAddr),
+            PanicOn(SocketAddr),
+            Panic,
+        }
+
+        #[derive(Debug, PartialEq)]
+        enum Event {
+            Started,
+            Processed(SocketAddr),
+            Terminated(SocketAddr),
+            TerminatedAll,
+        }
+
+        struct TestActor {
+            events: Sender<Event>,
+        }
+
+        impl Actor for TestActor {
+            type Command = TestCommand;
+
+            fn process_command(&mut self, command: TestCommand) {
+                match command {
+                    TestCommand::Process(remote) => {
+                        self.events.send(Event::Processed(remote)).unwrap()
+                    }
+                    TestCommand::PanicOn(_) | TestCommand::Panic => panic!("Forced panic"),
+                }
+            }
+
+            fn get_session(command: &TestCommand) -> Option<SocketAddr> {
+                match command {
+                    TestCommand::Process(remote) | TestCommand::PanicOn(remote) => Some(*remote),
+                    TestCommand::Panic => None,
+                }
+            }
+
+            fn terminate_session(&mut self, remote: SocketAddr) {
+                self.events.send(Event::Terminated(remote)).unwrap()
+            }
+
+            fn terminate_all_sessions(&mut self) {
+                self.events.send(Event::TerminatedAll).unwrap()
+            }
+        }
+
+        fn start_actor(commands: Vec<TestCommand>) -> (thread::Result<()>, Vec<Event>) {
+            let (command_tx, command_rx) = channel();
+            let (event_tx, event_rx) = channel();
+            for command in commands {
+                command_tx.send(command).unwrap();
+            }
+            drop(command_tx);
+
+            let result = thread::spawn(move || {
+                let create_actor = || {
+                    event_tx.send(Event::Started).unwrap();
+                    TestActor {
+                        events: event_tx.clone(),
+                    }
+                };
+                supervise("Test actor", create_actor, command_rx)
+            })
+            .join();
+            (result, event_rx.try_iter().collect())
+        }
+
+        #[test]
+        fn terminates_session_of_panicking_command() {
+            let first: SocketAddr = "127.0.0.1:52000".parse().unwrap();
+            let second: SocketAddr = "127.0.0.1:52001".parse().unwrap();
+
+            let (result, events) = start_actor(vec![
+                TestCommand::PanicOn(first),
+                TestCommand::Process(second),
+            ]);
+
+            assert!(result.is_ok());
+            assert_eq!(
+                events,
+                vec![
+                    Event::Started,
+                    Event::Terminated(first),
+                    Event::Processed(second)
+                ]
+            );
+        }
+
+        #[test]
+        fn restarts_once_before_giving_up() {
+            let remote: SocketAddr = "127.0.0.1:52000".parse().unwrap();
+
+            let (result, events) = start_actor(vec![
+                TestCommand::Panic,
+                TestCommand::Process(remote),
+                TestCommand::Panic,
+                TestCommand::Process(remote),
+            ]);
+
+            assert!(result.is_err());
+            assert_eq!(
+                events,
+                vec![
+                    Event::Started,
+                    Event::TerminatedAll,
+                    Event::Started,
+                    Event::Processed(remote),
+                    Event::TerminatedAll
+                ]
+            );
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use sdp::{NegotiatedSession, SDPResolver};
+
+const FINGERPRINT: &str = "sha-256 EF:53:C9:F2:E0:A0:4F:1D:5E:99:4C:20:B8:D7:DE:21:3B:58:15:C4:E5:88:87:46:65:27:F7:3B:C6:DC:EF:3B";
+pub const STREAMER_OFFER: &str = "v=0\r\no=rtc 3767197920 0 IN IP4 127.0.0.1\r\ns=-\r\nt=0 0\r\na=group:BUNDLE 0 1\r\na=group:LS 0 1\r\na=msid-semantic:WMS *\r\na=setup:actpass\r\na=ice-ufrag:E2Fr\r\na=ice-pwd:OpQzg1PAwUdeOB244chlgd\r\na=ice-options:trickle\r\na=fingerprint:sha-256 EF:53:C9:F2:E0:A0:4F:1D:5E:99:4C:20:B8:D7:DE:21:3B:58:15:C4:E5:88:87:46:65:27:F7:3B:C6:DC:EF:3B\r\nm=audio 4557 UDP/TLS/RTP/SAVPF 111\r\nc=IN IP4 192.168.0.198\r\na=mid:0\r\na=sendonly\r\na=ssrc:1349455989 cname:0X2NGAsK9XcmnsuZ\r\na=ssrc:1349455989 msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-audio\r\na=msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-audio\r\na=rtcp-mux\r\na=rtpmap:111 opus/48000/2\r\na=fmtp:111 minptime=10;maxaveragebitrate=96000;stereo=1;sprop-stereo=1;useinbandfec=1\r\na=candidate:1 1 UDP 2015363327 192.168.0.198 4557 typ host\r\na=candidate:2 1 UDP 2015363583 fe80::6c3d:5b42:1532:2f9a 10007 typ host\r\na=end-of-candidates\r\nm=video 4557 UDP/TLS/RTP/SAVPF 96\r\nc=IN IP4 192.168.0.198\r\na=mid:1\r\na=sendonly\r\na=ssrc:1349455990 cname:0X2NGAsK9XcmnsuZ\r\na=ssrc:1349455990 msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-video\r\na=msid:qUVEoh7TF9nLCrk4 qUVEoh7TF9nLCrk4-video\r\na=rtcp-mux\r\na=rtpmap:96 H264/90000\r\na=rtcp-fb:96 nack\r\na=rtcp-fb:96 nack pli\r\na=rtcp-fb:96 goog-remb\r\na=fmtp:96 profile-level-id=42e01f;packetization-mode=1;level-asymmetry-allowed=1\r\n";
+
+pub fn get_sdp_resolver() -> SDPResolver {
+    let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 52000);
+    SDPResolver::new(FINGERPRINT, &[socket_addr])
+}
+
+pub fn get_streamer_session() -> NegotiatedSession {
+    get_sdp_resolver()
+        .accept_stream_offer(STREAMER_OFFER)
+        .expect("Should resolve streamer offer")
+}
+
+/** H264 video packet of a simulcast layer, naming the layer with a one-byte RID header extension if given.
+ */
+pub fn get_simulcast_packet(
+    ssrc: u32,
+    sequence_number: u16,
+    rid_extension_id: u8,
+    rid: Option<&str>,
+    is_keyframe: bool,
+) -> Vec<u8> {
+    let mut packet = vec![0x80, 96];
+    packet.extend_from_slice(&sequence_number.to_be_bytes());
+    packet.extend_from_slice(&[0, 0, 0, 0]);
+    packet.extend_from_slice(&ssrc.to_be_bytes());
+    if let Some(rid) = rid {
+        // Elements are padded to 32-bit words
+        let mut elements = vec![(rid_extension_id << 4) | (rid.len() as u8 - 1)];
+        elements.extend_from_slice(rid.as_bytes());
+        elements.resize(elements.len().div_ceil(4) * 4, 0);
+
+        packet[0] |= 0b0001_0000;
+        packet.extend_from_slice(&[0xBE, 0xDE, 0, (elements.len() / 4) as u8]);
+        packet.extend_from_slice(&elements);
+    }
+    // IDR or non-IDR slice NAL header
+    packet.push(if is_keyframe { 0x65 } else { 0x41 });
+    packet.extend_from_slice(&[0, 0]);
+    packet
+}
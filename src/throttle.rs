@@ -0,0 +1,135 @@
+use std::time::Instant;
+
+use crate::bitrate::{BitrateCounter, BITRATE_WINDOW};
+use crate::rtp::{get_rtp_timestamp, is_keyframe_packet};
+
+/** Keeps viewer video egress under the bitrate reported by its latest REMB.
+ * Whole delta frames are dropped in proportion to the excess, keyframes are always forwarded.
+ */
+pub struct EgressThrottle {
+    remb_bps: Option<u64>,
+    offered: BitrateCounter,
+    frame_timestamp: Option<u32>,
+    is_frame_dropped: bool,
+    drop_credit: f64,
+}
+
+impl EgressThrottle {
+    pub fn new() -> Self {
+        EgressThrottle {
+            remb_bps: None,
+            offered: BitrateCounter::new(BITRATE_WINDOW),
+            frame_timestamp: None,
+            is_frame_dropped: false,
+            drop_credit: 0.0,
+        }
+    }
+
+    pub fn set_remb(&mut self, bitrate_bps: u64) {
+        self.remb_bps = Some(bitrate_bps);
+    }
+
+    /** Fraction of video traffic exceeding the REMB budget.
+     */
+    pub fn drop_ratio(&mut self, now: Instant) -> f64 {
+        let offered_bps = self.offered.bitrate_bps(now);
+        match self.remb_bps {
+            Some(remb_bps) if offered_bps > 0 => {
+                (1.0 - remb_bps as f64 / offered_bps as f64).clamp(0.0, 1.0)
+            }
+            _ => 0.0,
+        }
+    }
+
+    /** Decides whether a video RTP packet should be forwarded to the viewer.
+     */
+    pub fn should_forward(&mut self, packet: &[u8], now: Instant) -> bool {
+        self.offered.add(packet.len(), now);
+
+        if is_keyframe_packet(packet) {
+            return true;
+        }
+
+        // Drop decision is made once per frame, so that frames are never forwarded partially
+        let timestamp = get_rtp_timestamp(packet);
+        if self.frame_timestamp != Some(timestamp) {
+            self.frame_timestamp = Some(timestamp);
+            self.drop_credit += self.drop_ratio(now);
+            self.is_frame_dropped = self.drop_credit >= 1.0;
+            if self.is_frame_dropped {
+                self.drop_credit -= 1.0;
+            }
+        }
+
+        !self.is_frame_dropped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod egress_throttle {
+        use std::time::{Duration, Instant};
+
+        use crate::throttle::EgressThrottle;
+
+        const IDR_NAL_HEADER: u8 = 0x65;
+        const SLICE_NAL_HEADER: u8 = 0x41;
+
+        fn get_video_packet(timestamp: u32, nal_header: u8) -> Vec<u8> {
+            let mut packet = vec![0x80, 96, 0, 0];
+            packet.extend_from_slice(&timestamp.to_be_bytes());
+            packet.extend_from_slice(&[0, 0, 0, 1]);
+            packet.push(nal_header);
+            packet.resize(1000, 0);
+            packet
+        }
+
+        // Streams 30 frames per second, keyframe every 10th frame. Returns forwarded (IDR, delta) frame counts.
+        fn stream_frames(throttle: &mut EgressThrottle) -> (usize, usize) {
+            let start = Instant::now();
+            let mut forwarded_idr = 0;
+            let mut forwarded_delta = 0;
+
+            for frame in 0..90u32 {
+                let now = start + Duration::from_millis(frame as u64 * 33);
+                let is_keyframe = frame % 10 == 0;
+                let nal_header = if is_keyframe {
+                    IDR_NAL_HEADER
+                } else {
+                    SLICE_NAL_HEADER
+                };
+
+                if throttle.should_forward(&get_video_packet(frame * 3000, nal_header), now) {
+                    match is_keyframe {
+                        true => forwarded_idr += 1,
+                        false => forwarded_delta += 1,
+                    }
+                }
+            }
+
+            (forwarded_idr, forwarded_delta)
+        }
+
+        #[test]
+        fn forwards_everything_without_remb() {
+            let mut throttle = EgressThrottle::new();
+
+            assert_eq!(stream_frames(&mut throttle), (9, 81));
+        }
+
+        #[test]
+        fn drops_delta_frames_under_low_remb() {
+            let mut throttle = EgressThrottle::new();
+            // Roughly a quarter of the offered 240kbps
+            throttle.set_remb(60_000);
+
+            let (forwarded_idr, forwarded_delta) = stream_frames(&mut throttle);
+
+            assert_eq!(forwarded_idr, 9);
+            assert!(
+                forwarded_delta < 40,
+                "forwarded {forwarded_delta} delta frames"
+            );
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use webp::PixelLayout;
 
@@ -7,6 +7,30 @@ use thumbnail_image_extractor::ImageData;
 
 use crate::config::get_global_config;
 
+pub const THUMBNAIL_CONTENT_TYPE: &str = "image/webp";
+
+pub fn get_thumbnail_path(storage_dir: &Path, id: u32) -> PathBuf {
+    storage_dir.join(format!("{}.webp", id))
+}
+
+/** ETag file is stored next to the thumbnail, so it's computed once per stored thumbnail.
+ */
+pub fn get_thumbnail_etag_path(storage_dir: &Path, id: u32) -> PathBuf {
+    storage_dir.join(format!("{}.etag", id))
+}
+
+pub fn get_thumbnail_etag(thumbnail: &[u8]) -> String {
+    format!("\"{:08x}\"", crc32fast::hash(thumbnail))
+}
+
+pub fn store_thumbnail(storage_dir: &Path, id: u32, thumbnail: &[u8]) -> std::io::Result<()> {
+    fs::write(get_thumbnail_path(storage_dir, id), thumbnail)?;
+    fs::write(
+        get_thumbnail_etag_path(storage_dir, id),
+        get_thumbnail_etag(thumbnail),
+    )
+}
+
 pub fn save_thumbnail_to_storage(id: u32, image_data: ImageData) {
     let encoder = webp::Encoder::new(
         &image_data.data_buffer,
@@ -16,9 +40,7 @@ pub fn save_thumbnail_to_storage(id: u32, image_data: ImageData) {
     );
 
     let encoded = encoder.encode(75.0);
-    let path = PathBuf::from(get_global_config().storage_dir.as_path());
-    let path = path.join(format!("{}.webp", id));
-    if let Err(e) = fs::write(&path, encoded.as_ref()) {
+    if let Err(e) = store_thumbnail(&get_global_config().storage_dir, id, encoded.as_ref()) {
         eprintln!("Error writing thumbnail to folder {}", e)
     }
 }
@@ -0,0 +1,78 @@
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+
+use crate::server::SocketIndex;
+
+/** Outgoing side of the server's UDP sockets, as used by the control plane. Lets ICE handling be
+ * driven without binding real sockets.
+ */
+pub trait MediaTransport {
+    /** Sends a datagram to `remote` through the socket at `socket_index`.
+     */
+    fn send_to(
+        &self,
+        socket_index: SocketIndex,
+        packet: &[u8],
+        remote: SocketAddr,
+    ) -> io::Result<usize>;
+}
+
+/** Transport over the bound UDP sockets, in the order of their socket indexes.
+ */
+pub struct UdpTransport {
+    sockets: Vec<UdpSocket>,
+}
+
+impl UdpTransport {
+    pub fn new(sockets: Vec<UdpSocket>) -> Self {
+        UdpTransport { sockets }
+    }
+}
+
+impl MediaTransport for UdpTransport {
+    fn send_to(
+        &self,
+        socket_index: SocketIndex,
+        packet: &[u8],
+        remote: SocketAddr,
+    ) -> io::Result<usize> {
+        self.sockets[socket_index].send_to(packet, remote)
+    }
+}
+
+/** Datagram sent through a mock transport, along with its socket index and destination.
+ */
+#[cfg(test)]
+pub type SentDatagram = (SocketIndex, SocketAddr, Vec<u8>);
+
+/** In-memory transport keeping every datagram sent through it. Clones share the sent datagrams.
+ */
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct MockTransport {
+    sent: std::rc::Rc<std::cell::RefCell<Vec<SentDatagram>>>,
+}
+
+#[cfg(test)]
+impl MockTransport {
+    /** Takes the datagrams sent so far, oldest first.
+     */
+    pub fn take_sent(&self) -> Vec<SentDatagram> {
+        self.sent.take()
+    }
+}
+
+#[cfg(test)]
+impl MediaTransport for MockTransport {
+    fn send_to(
+        &self,
+        socket_index: SocketIndex,
+        packet: &[u8],
+        remote: SocketAddr,
+    ) -> io::Result<usize> {
+        self.sent
+            .borrow_mut()
+            .push((socket_index, remote, packet.to_vec()));
+        Ok(packet.len())
+    }
+}